@@ -2,11 +2,15 @@
 //! 
 //use std::convert::TryFrom;
 use anyhow::Result;
-use der::{Any, Tag};
+use der::{asn1::ContextSpecific, Any, Decodable, Decoder, Tag, TagNumber};
 use pkcs1::ObjectIdentifier;
 use pkcs8::der::Encodable;
 use pkcs8::AlgorithmIdentifier;
+use std::fmt;
+use std::str::FromStr;
 
+use crate::errors::Error;
+use crate::key_info::{Alg, KeyInfo};
 use crate::oids::*;
 
 /// Create an AlgorithmIdentifier with NULL parameters
@@ -19,6 +23,17 @@ pub fn alg_id_any<'a>(oid: ObjectIdentifier) -> Result<AlgorithmIdentifier<'a>>
 
 }
 
+/// Create an AlgorithmIdentifier with absent parameters
+/// RFC 8410 requires the parameters to be absent for the X25519, X448,
+/// Ed25519 and Ed448 algorithms.
+pub fn alg_id_no_params<'a>(oid: ObjectIdentifier) -> Result<AlgorithmIdentifier<'a>> {
+    let alg_id = AlgorithmIdentifier {
+        oid,
+        parameters: None,
+    };
+    Ok(alg_id)
+}
+
 /// Create an AlgorithmIdentifier with an ObjectIdentifier as a parameter
 /// Most commonly used for Elliptic Curve key formats, where the curve is
 /// represented with an ObjectIdentifier
@@ -35,14 +50,76 @@ pub fn rsa_encryption<'a>() -> Result<AlgorithmIdentifier<'a>> {
     alg_id_any(RSA_ENCRYPTION)
 }
 
-pub fn rsapss_encryption<'a>() -> Result<AlgorithmIdentifier<'a>> {
-    alg_id_any(RSASSA_PSS)
+/// AlgorithmIdentifier of RSASSA-PSS, with the DER RSASSA-PSS-params that
+/// restrict the key, or with absent parameters for a key that is not
+/// restricted.  RFC 4055 does not allow NULL parameters.
+pub fn rsapss_encryption(params: Option<&'_ [u8]>) -> Result<AlgorithmIdentifier<'_>> {
+    let alg_id = AlgorithmIdentifier {
+        oid: RSASSA_PSS,
+        parameters: params.map(Any::from_der).transpose()?,
+    };
+    Ok(alg_id)
 }
 
 pub fn ec_encryption(curve: &'_ [u8]) -> Result<AlgorithmIdentifier<'_>> {
     alg_id_with_oid_param(ECDSA, curve)
 }
 
+/// AlgorithmIdentifier of DSA, with the Dss-Parms of the key
+pub fn dsa_encryption(params: &'_ [u8]) -> Result<AlgorithmIdentifier<'_>> {
+    let alg_id = AlgorithmIdentifier {
+        oid: DSA,
+        parameters: Some(Any::from_der(params)?),
+    };
+    Ok(alg_id)
+}
+
+/// AlgorithmIdentifier of PKCS#3 DH, with the DHParameter of the key
+pub fn dh_encryption(params: &'_ [u8]) -> Result<AlgorithmIdentifier<'_>> {
+    let alg_id = AlgorithmIdentifier {
+        oid: DH,
+        parameters: Some(Any::from_der(params)?),
+    };
+    Ok(alg_id)
+}
+
+/// AlgorithmIdentifier for the Octet Key Pair (RFC 8037) algorithms
+pub fn okp_encryption<'a>(alg: Alg) -> Result<AlgorithmIdentifier<'a>> {
+    let oid = match alg {
+        Alg::X25519 => X25519,
+        Alg::X448 => X448,
+        Alg::EdDsa25519 => ED_DSA25519,
+        Alg::EdDsa448 => ED_DSA448,
+        _ => return Err(Error::UnknownAlg.into()),
+    };
+    alg_id_no_params(oid)
+}
+
+/// AlgorithmIdentifier of ML-DSA, with absent parameters.  The draft OID of a
+/// key read with one is kept, and keys are otherwise written with the OID of
+/// FIPS 204.
+pub fn ml_dsa_encryption<'a>(alg: Alg, oid: Option<ObjectIdentifier>) -> Result<AlgorithmIdentifier<'a>> {
+    let (final_oid, draft_oid) = match alg {
+        Alg::MlDsa44 => (ML_DSA_44, ML_DSA_44_IPD),
+        Alg::MlDsa65 => (ML_DSA_65, ML_DSA_65_IPD),
+        Alg::MlDsa87 => (ML_DSA_87, ML_DSA_87_IPD),
+        _ => return Err(Error::UnknownAlg.into()),
+    };
+    if oid == Some(draft_oid) {
+        return alg_id_no_params(draft_oid);
+    }
+    alg_id_no_params(final_oid)
+}
+
+/// AlgorithmIdentifier of a composite key, with absent parameters.  The OID
+/// names both components, so it is that of the key.
+pub fn composite_encryption<'a>(oid: Option<ObjectIdentifier>) -> Result<AlgorithmIdentifier<'a>> {
+    match oid {
+        Some(oid) => alg_id_no_params(oid),
+        None => Err(Error::MissingInput("composite OID".to_owned()).into()),
+    }
+}
+
 /// Get the parameter bits from an AlgorithmIdentifier
 pub fn alg_params(alg_id: &AlgorithmIdentifier) -> Option<Vec<u8>> {
     if let Some(params) = alg_id.parameters {
@@ -52,3 +129,273 @@ pub fn alg_params(alg_id: &AlgorithmIdentifier) -> Option<Vec<u8>> {
     }
     None
 }
+
+/// The RSASSA-PSS-params of a key of the RSASSA-PSS OID, if it has them
+pub fn rsa_pss_params(key_info: &KeyInfo) -> Option<&[u8]> {
+    match key_info.oid {
+        Some(RSASSA_PSS) => key_info.params.as_deref(),
+        _ => None,
+    }
+}
+
+/// Hash algorithms of RSASSA-PSS-params that kt writes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PssHash {
+    Sha256,
+    Sha384,
+    Sha512,
+}
+
+impl PssHash {
+    pub fn all() -> Vec<&'static str> {
+        vec!["sha256", "sha384", "sha512"]
+    }
+
+    pub fn oid(&self) -> ObjectIdentifier {
+        match self {
+            PssHash::Sha256 => SHA256,
+            PssHash::Sha384 => SHA384,
+            PssHash::Sha512 => SHA512,
+        }
+    }
+
+    /// Length of the hash in bytes, which is the usual salt length
+    pub fn size(&self) -> u32 {
+        match self {
+            PssHash::Sha256 => 32,
+            PssHash::Sha384 => 48,
+            PssHash::Sha512 => 64,
+        }
+    }
+}
+
+impl FromStr for PssHash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<PssHash> {
+        match s.to_lowercase().replace('-', "").as_str() {
+            "sha256" => Ok(PssHash::Sha256),
+            "sha384" => Ok(PssHash::Sha384),
+            "sha512" => Ok(PssHash::Sha512),
+            _ => Err(Error::UnknownPssHash.into()),
+        }
+    }
+}
+
+/// RSASSA-PSS-params from [RFC 4055](https://www.rfc-editor.org/rfc/rfc4055#section-3.1)
+///
+/// Absent fields take the defaults from the RFC: SHA-1, MGF1 with SHA-1,
+/// a salt length of 20, and a trailer field of 1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RsaPssParams {
+    /// Hash algorithm
+    pub hash: ObjectIdentifier,
+    /// Mask generation function.  Always MGF1 in practice
+    pub mask_gen: ObjectIdentifier,
+    /// Hash algorithm used by the mask generation function
+    pub mask_gen_hash: ObjectIdentifier,
+    /// Salt length in bytes
+    pub salt_length: u32,
+    /// Trailer field
+    pub trailer_field: u32,
+}
+
+impl Default for RsaPssParams {
+    fn default() -> Self {
+        Self {
+            hash: SHA1,
+            mask_gen: MGF1,
+            mask_gen_hash: SHA1,
+            salt_length: 20,
+            trailer_field: 1,
+        }
+    }
+}
+
+impl RsaPssParams {
+    /// The parameters of the hash, with MGF1 of the same hash and a trailer
+    /// field of 1.  The salt is the length of the hash, unless given.
+    pub fn new(hash: PssHash, salt_length: Option<u32>) -> Self {
+        Self {
+            hash: hash.oid(),
+            mask_gen: MGF1,
+            mask_gen_hash: hash.oid(),
+            salt_length: salt_length.unwrap_or(hash.size()),
+            trailer_field: 1,
+        }
+    }
+
+    /// Encode the parameters as DER, without the fields that have their
+    /// default values
+    /// ```
+    /// use kt::alg_id::{PssHash, RsaPssParams};
+    /// let params = RsaPssParams::new(PssHash::Sha384, None);
+    /// let der = params.to_der().unwrap();
+    /// assert_eq!(RsaPssParams::from_der(&der).unwrap(), params);
+    /// assert_eq!(params.salt_length, 48);
+    /// // The defaults are all absent
+    /// assert_eq!(RsaPssParams::default().to_der().unwrap(), [0x30, 0x00]);
+    /// ```
+    pub fn to_der(&self) -> Result<Vec<u8>> {
+        let defaults = Self::default();
+        let hash = alg_id_any(self.hash)?.to_vec()?;
+        let mask_gen_hash = alg_id_any(self.mask_gen_hash)?.to_vec()?;
+        Ok(yasna::construct_der(|writer| {
+            writer.write_sequence(|writer| {
+                if self.hash != defaults.hash {
+                    writer
+                        .next()
+                        .write_tagged(yasna::Tag::context(0), |writer| writer.write_der(&hash));
+                }
+                if (self.mask_gen, self.mask_gen_hash) != (defaults.mask_gen, defaults.mask_gen_hash) {
+                    let mask_gen = yasna::models::ObjectIdentifier::from_slice(
+                        &self.mask_gen.arcs().map(u64::from).collect::<Vec<u64>>(),
+                    );
+                    writer.next().write_tagged(yasna::Tag::context(1), |writer| {
+                        writer.write_sequence(|writer| {
+                            writer.next().write_oid(&mask_gen);
+                            writer.next().write_der(&mask_gen_hash);
+                        })
+                    });
+                }
+                if self.salt_length != defaults.salt_length {
+                    writer
+                        .next()
+                        .write_tagged(yasna::Tag::context(2), |writer| writer.write_u32(self.salt_length));
+                }
+                if self.trailer_field != defaults.trailer_field {
+                    writer
+                        .next()
+                        .write_tagged(yasna::Tag::context(3), |writer| writer.write_u32(self.trailer_field));
+                }
+            })
+        }))
+    }
+
+    /// Decode the DER encoded parameters of an RSASSA-PSS AlgorithmIdentifier
+    /// ```
+    /// use kt::alg_id::RsaPssParams;
+    /// use kt::oids::{SHA1, SHA256};
+    /// // SHA-256, default mask generation, and a 32 byte salt
+    /// let der = [
+    ///     0x30, 0x16, 0xa0, 0x0f, 0x30, 0x0d, 0x06, 0x09, 0x60, 0x86, 0x48, 0x01, 0x65, 0x03,
+    ///     0x04, 0x02, 0x01, 0x05, 0x00, 0xa2, 0x03, 0x02, 0x01, 0x20,
+    /// ];
+    /// let params = RsaPssParams::from_der(&der).unwrap();
+    /// assert_eq!(params.hash, SHA256);
+    /// assert_eq!(params.mask_gen_hash, SHA1);
+    /// assert_eq!(params.salt_length, 32);
+    /// ```
+    pub fn from_der(bytes: &[u8]) -> Result<Self> {
+        let mut params = Self::default();
+        let mut decoder = Decoder::new(bytes)?;
+        decoder.sequence(|decoder| {
+            if let Some(hash) = ContextSpecific::<AlgorithmIdentifier>::decode_explicit(decoder, TagNumber::N0)? {
+                params.hash = hash.value.oid;
+            }
+            if let Some(mask_gen) = ContextSpecific::<AlgorithmIdentifier>::decode_explicit(decoder, TagNumber::N1)? {
+                params.mask_gen = mask_gen.value.oid;
+                if let Some(any) = mask_gen.value.parameters {
+                    params.mask_gen_hash = AlgorithmIdentifier::from_der(&any.to_vec()?)?.oid;
+                }
+            }
+            if let Some(salt_length) = ContextSpecific::<u32>::decode_explicit(decoder, TagNumber::N2)? {
+                params.salt_length = salt_length.value;
+            }
+            if let Some(trailer_field) = ContextSpecific::<u32>::decode_explicit(decoder, TagNumber::N3)? {
+                params.trailer_field = trailer_field.value;
+            }
+            Ok(())
+        })?;
+        decoder.finish(params).map_err(|e| e.into())
+    }
+}
+
+impl fmt::Display for RsaPssParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "\t\tHash Algorithm: {}\n\t\tMask Generation: {} with {}\n\t\tSalt Length: {}\n\t\tTrailer Field: {}\n",
+            oid_to_str(&self.hash),
+            oid_to_str(&self.mask_gen),
+            oid_to_str(&self.mask_gen_hash),
+            self.salt_length,
+            self.trailer_field
+        )
+    }
+}
+
+/// GostR3410-2012-PublicKeyParameters from [RFC 9215](https://www.rfc-editor.org/rfc/rfc9215#section-4)
+///
+/// Keys of the CryptoPro parameter sets may also name the encryption
+/// parameter set of GOST 28147-89, which is kept but not shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GostParams {
+    /// The curve, such as id-tc26-gost-3410-12-256-paramSetA
+    pub parameter_set: ObjectIdentifier,
+    /// GOST R 34.11-2012 digest, absent for most parameter sets of RFC 9215
+    pub digest: Option<ObjectIdentifier>,
+    /// GOST 28147-89 encryption parameter set
+    pub encryption: Option<ObjectIdentifier>,
+}
+
+impl GostParams {
+    /// Decode the DER encoded parameters of a GOST R 34.10-2012
+    /// AlgorithmIdentifier
+    /// ```
+    /// use kt::alg_id::GostParams;
+    /// use kt::oids::{GOST2012_256_A, STREEBOG256};
+    /// // paramSetA, with the 256 bit digest
+    /// let der = [
+    ///     0x30, 0x15, 0x06, 0x09, 0x2a, 0x85, 0x03, 0x07, 0x01, 0x02, 0x01, 0x01, 0x01, 0x06,
+    ///     0x08, 0x2a, 0x85, 0x03, 0x07, 0x01, 0x01, 0x02, 0x02,
+    /// ];
+    /// let params = GostParams::from_der(&der).unwrap();
+    /// assert_eq!(params.parameter_set, GOST2012_256_A);
+    /// assert_eq!(params.digest, Some(STREEBOG256));
+    /// ```
+    pub fn from_der(bytes: &[u8]) -> Result<Self> {
+        let mut decoder = Decoder::new(bytes)?;
+        let params = decoder.sequence(|decoder| {
+            Ok(Self {
+                parameter_set: decoder.decode()?,
+                digest: decoder.decode()?,
+                encryption: decoder.decode()?,
+            })
+        })?;
+        decoder.finish(params).map_err(|e| e.into())
+    }
+}
+
+impl fmt::Display for GostParams {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "\t\tParameter Set: {}", oid_to_str(&self.parameter_set))?;
+        if let Some(digest) = &self.digest {
+            writeln!(f, "\t\tDigest: {}", oid_to_str(digest))?;
+        }
+        Ok(())
+    }
+}
+
+/// The hash algorithm named by a signature algorithm
+pub fn signature_hash(oid: &ObjectIdentifier) -> Option<ObjectIdentifier> {
+    match *oid {
+        SHA1_WITH_RSA | ECDSA_WITH_SHA1 => Some(SHA1),
+        SHA256_WITH_RSA | ECDSA_WITH_SHA256 => Some(SHA256),
+        SHA384_WITH_RSA | ECDSA_WITH_SHA384 => Some(SHA384),
+        SHA512_WITH_RSA | ECDSA_WITH_SHA512 => Some(SHA512),
+        _ => None,
+    }
+}
+
+/// The key algorithm that a signature algorithm is used with
+pub fn signature_key_alg(oid: &ObjectIdentifier) -> Option<Alg> {
+    match *oid {
+        SHA1_WITH_RSA | SHA256_WITH_RSA | SHA384_WITH_RSA | SHA512_WITH_RSA => Some(Alg::Rsa),
+        RSASSA_PSS => Some(Alg::RsaSsaPss),
+        ECDSA_WITH_SHA1 | ECDSA_WITH_SHA256 | ECDSA_WITH_SHA384 | ECDSA_WITH_SHA512 => Some(Alg::Ecdsa),
+        ED_DSA25519 => Some(Alg::EdDsa25519),
+        ED_DSA448 => Some(Alg::EdDsa448),
+        _ => None,
+    }
+}
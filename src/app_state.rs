@@ -6,9 +6,32 @@
 //!  
 use crate::errors::Error;
 use anyhow::Result;
-use std::io::{Read, Write};
+use std::io::{IsTerminal, Read, Write};
+use std::path::Path;
+use std::cell::RefCell;
+use std::rc::Rc;
+use zeroize::Zeroizing;
 
-use crate::key_info::{Alg, Encoding, Format, KeyType};
+use crate::alg_id::PssHash;
+use crate::consumer::TargetConsumer;
+use crate::batch::FailureKind;
+use crate::document::composite_docs::Component;
+use crate::document::sec1_docs::PointFormat;
+use crate::document::jwk_docs::KeyUse;
+use crate::document::pkcs8_docs::Pkcs8Version;
+use crate::events::{self, Event};
+use crate::export::ExportTarget;
+use crate::fingerprint::{FingerprintHash, FingerprintStyle};
+use crate::detached::SignatureFormat;
+use crate::generate::DEFAULT_EXPONENT;
+use crate::hex_text::HexStyle;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyType, SecretEncoding};
+use crate::migrate::MigrationTarget;
+use crate::output::OutputPolicy;
+use crate::password::{PasswordProvider, PromptPassword};
+use crate::pbe::PbeParams;
+use crate::selfsign::{KeyUsage, DEFAULT_DAYS};
+use crate::shred::DEFAULT_PASSES;
 
 
 /// The behavior the app should perform.
@@ -18,6 +41,64 @@ pub enum Command {
     Show,
     /// Convert the provided key, based on the input parameters
     Convert,
+    /// Build a JWKS from a directory of keys
+    JwksBuild,
+    /// Explode a JWKS into a directory of keys
+    JwksExplode,
+    /// Serve a JWKS of the provided keys on localhost
+    JwksServe,
+    /// Add the provided key to a JWKS
+    JwksAdd,
+    /// Sign a JWT with the provided private key
+    JwtSign,
+    /// Verify a JWT with the provided public key or JWKS
+    JwtVerify,
+    /// Compare the provided key with the output of another tool
+    CompareImpl,
+    /// Export the provided key, and certificate, for a server
+    Export,
+    /// Replace the provided key with a new key of another algorithm
+    Migrate,
+    /// Change or remove the password of the provided private key
+    Passwd,
+    /// Overwrite and remove the provided key files
+    Shred,
+    /// Write the fingerprint of the provided key
+    Fingerprint,
+    /// Write a certificate signing request for the provided private key
+    Csr,
+    /// Write a self-signed certificate for the provided private key
+    SelfSign,
+    /// Write the SPKI pin of the provided key
+    Pin,
+    /// Write a dump of the ASN.1 elements of the input
+    Asn1,
+    /// Write a field by field breakdown of the document of the provided key
+    Explain,
+    /// Write the provided key in its canonical encoding
+    Normalize,
+    /// Write why the provided key can not be read
+    Doctor,
+    /// Generate a new RSA private key
+    GenerateRsa,
+    /// Generate a new elliptic curve private key
+    GenerateEc,
+    /// Generate a new Ed25519 private key
+    GenerateEd25519,
+    /// Generate a new X25519 private key
+    GenerateX25519,
+    /// Check that a public key belongs to the provided private key
+    Match,
+    /// Validate the provided key
+    Check,
+    /// Write a detached signature of data with the provided private key
+    Sign,
+    /// Verify a detached signature of data with the provided key
+    Verify,
+    /// Encrypt a small payload to the provided key
+    Encrypt,
+    /// Decrypt a small payload with the provided private key
+    Decrypt,
 }
 
 /// Program state.
@@ -25,12 +106,77 @@ pub enum Command {
 pub struct AppState {
     /// Name of file to read from.  If not provided, stdin is used
     pub in_file: Option<String>,
+    /// Name of a directory to read keys from, for commands that process
+    /// more than one key
+    pub in_dir: Option<String>,
+    /// Names of files, or directories, to read keys from, for commands that
+    /// accept a list of inputs
+    pub in_files: Vec<String>,
+    /// Local port to listen on
+    pub port: Option<u16>,
     /// Name of file to write to.  If not provided stdout is used.
     pub out_file: Option<String>,
+    /// Name of a directory to write keys to, for commands that produce
+    /// more than one key
+    pub out_dir: Option<String>,
+    /// Name of a file holding a JWT payload.  If not provided, stdin is used
+    pub payload_file: Option<String>,
+    /// Name of a file holding a verification key, or a JWKS
+    pub key_file: Option<String>,
+    /// Name of a file holding a JWKS to update
+    pub set_file: Option<String>,
+    /// External command to compare output with
+    pub with_command: Option<String>,
+    /// Name of a file holding a certificate, or certificate chain
+    pub cert_file: Option<String>,
+    /// Names of files holding CA certificates
+    pub ca_files: Vec<String>,
+    /// Server to export for
+    pub export_target: Option<ExportTarget>,
+    /// Base name of exported files
+    pub export_name: Option<String>,
+    /// Algorithm to migrate the key to
+    pub migration_target: Option<MigrationTarget>,
+    /// Modulus length in bits of a generated RSA key
+    pub rsa_bits: usize,
+    /// Public exponent of a generated RSA key
+    pub rsa_exponent: u64,
+    /// Name of the file to write the SPKI public key of a generated key to
+    pub pub_out_file: Option<String>,
+    /// Hex seed to generate a key deterministically from.  Unsafe for
+    /// production
+    pub seed: Option<String>,
+    /// Name of a file of seed bytes to generate a key deterministically from
+    pub seed_file: Option<String>,
+    /// Name of the public key file to match with the provided private key
+    pub public_file: Option<String>,
+    /// Name of a file of data to sign, verify, encrypt or decrypt.  If not
+    /// provided, stdin is used
+    pub data_file: Option<String>,
+    /// Name of a file holding a detached signature to verify
+    pub signature_file: Option<String>,
+    /// How a detached signature is written
+    pub signature_format: SignatureFormat,
+    /// Sign with RSASSA-PSS, rather than the scheme of the key
+    pub pss: bool,
+    /// Write, or read, the ciphertext as base64
+    pub base64: bool,
     /// Password, if the input fie is encrypted.
     pub in_password: Option<String>,
+    /// Asked for the input password, if the input is encrypted and no
+    /// password was provided
+    pub password_provider: Option<Rc<dyn PasswordProvider>>,
+    /// The input password that decrypted the input
+    pub decrypted_password: RefCell<Option<Zeroizing<String>>>,
+    /// Times to ask for the input password again at the terminal, if it is
+    /// incorrect
+    pub password_retries: u32,
     /// Password, if the output file should be encrypted.
     pub out_password: Option<String>,
+    /// How password protected PKCS8 and PKCS12 output is encrypted
+    pub pbe: PbeParams,
+    /// Version of written PKCS8 private keys
+    pub pkcs8_version: Pkcs8Version,
     /// Input stream to read from.  Either a file, or stdin.
     pub in_stream: Box<dyn Read>,
     /// Output stream to write to.  Either a file or stdout.
@@ -44,8 +190,85 @@ pub struct AppState {
     pub key_type: Option<KeyType>,
     /// Encoding style to output
     pub encoding: Encoding,
+    /// Named curve of a bare elliptic curve point input, which carries no
+    /// curve information of its own, or of a generated EC key
+    pub curve: Option<Curve>,
+    /// Form of a symmetric secret key input, which has no structure of its
+    /// own to be discovered
+    pub secret: Option<SecretEncoding>,
+    /// Form of the input, when it can not be discovered, such as RAW key
+    /// bytes
+    pub in_format: Option<Encoding>,
+    /// How HEX and RAW_HEX output is written
+    pub hex_style: HexStyle,
+    /// Label of PEM output, instead of the label of the format
+    pub pem_label: Option<String>,
+    /// Component of a composite key input to write, instead of the
+    /// composite key
+    pub component: Option<Component>,
+    /// Hash of the RSASSA-PSS-params of `--alg RSASSA_PSS` output
+    pub pss_hash: Option<PssHash>,
+    /// Salt length of the RSASSA-PSS-params of `--alg RSASSA_PSS` output
+    pub pss_salt_len: Option<u32>,
+    /// How the public point of EC output is written.  Points are read as
+    /// uncompressed, and written so unless asked.
+    pub point_format: Option<PointFormat>,
+    /// Certificate of a chain input to read the key of.  0 is the leaf
+    pub cert_index: usize,
+    /// Friendly name of the bags to read of a PKCS12 input
+    pub friendly_name: Option<String>,
+    /// Read a certificate of a PKCS12 input, rather than its private key
+    pub p12_cert: bool,
     /// File format to use
     pub format: Option<Format>,
+    /// Software the output is intended for.  Used to choose a format, and to
+    /// warn about output the consumer is known to reject
+    pub target_consumer: Option<TargetConsumer>,
+    /// Directory to write redacted diagnostics of the input to
+    pub debug_dump: Option<String>,
+    /// Show a hex dump of the input, marked where each ASN.1 element starts
+    pub hexdump: bool,
+    /// Repair common damage to PEM input before it is read
+    pub lenient: bool,
+    /// JOSE algorithm to set as the JWK `alg` member
+    pub jwk_alg: Option<String>,
+    /// JWK `use` member
+    pub key_use: Option<KeyUse>,
+    /// JWK `key_ops` member
+    pub key_ops: Vec<String>,
+    /// Comment of an OpenSSH public key
+    pub comment: Option<String>,
+    /// Host to show the keys of, from a known_hosts input
+    pub host: Option<String>,
+    /// Subject of a certificate signing request
+    pub subject: Option<String>,
+    /// Subject alternative names of a certificate signing request
+    pub sans: Vec<String>,
+    /// Days a self-signed certificate is valid for
+    pub days: u32,
+    /// Key usage of a self-signed certificate.  If empty, it depends on the
+    /// key
+    pub key_usage: Vec<KeyUsage>,
+    /// Make a self-signed certificate a CA certificate
+    pub ca: bool,
+    /// Passes of random bytes to overwrite shredded files with
+    pub passes: u32,
+    /// Shred the input key once it is replaced
+    pub shred: bool,
+    /// Shred files that are not recognized as keys
+    pub force: bool,
+    /// Hash of fingerprints
+    pub fingerprint_hash: FingerprintHash,
+    /// What fingerprints are a hash of
+    pub fingerprint_style: FingerprintStyle,
+    /// Failures that fail a batch command.  Others are only reported
+    pub fail_on: Vec<FailureKind>,
+    /// Write timestamps in local time, instead of UTC
+    pub local_time: bool,
+    /// Umask and owner of created files
+    pub output: OutputPolicy,
+    /// Indent JSON output, instead of writing it compactly
+    pub pretty: bool,
     /// Automatically set if an output password is provided
     pub encrypted: bool,
     /// What behavior to perform.  Defaults to "CONVERT"
@@ -57,15 +280,79 @@ impl AppState {
         Self {
             key_type: None,
             encoding: Encoding::PEM,
+            curve: None,
+            secret: None,
+            in_format: None,
+            hex_style: HexStyle::Plain,
+            pem_label: None,
+            component: None,
+            pss_hash: None,
+            pss_salt_len: None,
+            point_format: None,
+            cert_index: 0,
+            friendly_name: None,
+            p12_cert: false,
             format: None,
+            target_consumer: None,
+            debug_dump: None,
+            hexdump: false,
+            lenient: false,
             key_id: None,
             alg: None,
             in_file: None,
+            in_dir: None,
+            in_files: Vec::new(),
+            port: None,
+            payload_file: None,
+            key_file: None,
+            set_file: None,
+            with_command: None,
+            cert_file: None,
+            ca_files: Vec::new(),
+            export_target: None,
+            export_name: None,
+            migration_target: None,
+            rsa_bits: 2048,
+            rsa_exponent: DEFAULT_EXPONENT,
+            pub_out_file: None,
+            seed: None,
+            seed_file: None,
+            public_file: None,
+            data_file: None,
+            signature_file: None,
+            signature_format: SignatureFormat::Base64,
+            pss: false,
+            base64: false,
             in_password: None,
+            password_provider: None,
+            decrypted_password: RefCell::new(None),
+            password_retries: 0,
             in_stream: Box::new(std::io::stdin()),
             out_file: None,
+            out_dir: None,
             out_password: None,
+            pbe: PbeParams::default(),
+            pkcs8_version: Pkcs8Version::V1,
             out_stream: Box::new(std::io::stdout()),
+            jwk_alg: None,
+            key_use: None,
+            key_ops: Vec::new(),
+            comment: None,
+            host: None,
+            subject: None,
+            sans: Vec::new(),
+            days: DEFAULT_DAYS,
+            key_usage: Vec::new(),
+            ca: false,
+            passes: DEFAULT_PASSES,
+            shred: false,
+            force: false,
+            fingerprint_hash: FingerprintHash::Sha256,
+            fingerprint_style: FingerprintStyle::Ssh,
+            fail_on: FailureKind::default_fail_on(),
+            local_time: false,
+            output: OutputPolicy::default(),
+            pretty: false,
             encrypted: false,
             command: Command::Convert,
         }
@@ -103,6 +390,50 @@ impl AppState {
         Ok(())
     }
 
+    /// Create, or truncate, an output file, and make it the output stream.
+    /// A file for a private key is readable only by its owner.
+    pub fn create_out_file(&mut self, filename: &str, private: bool) -> Result<()> {
+        self.out_file = Some(filename.to_owned());
+        self.out_stream = Box::new(self.output.create_file(Path::new(filename), private)?);
+        Ok(())
+    }
+
+    /// Return the input password, from the password provider if one was not
+    /// provided, or Error::MissingInput.  Once the input is decrypted, the
+    /// password that decrypted it is returned.
+    pub fn input_password(&self) -> Result<Zeroizing<String>> {
+        if let Some(password) = &self.in_password {
+            return Ok(Zeroizing::new(password.clone()));
+        }
+        if let Some(password) = self.decrypted_password.borrow().as_ref() {
+            return Ok(password.clone());
+        }
+        match &self.password_provider {
+            Some(provider) => provider.password(self.in_file.as_deref().unwrap_or("stdin")),
+            None => Err(Error::MissingInput("password".to_owned()).into()),
+        }
+    }
+
+    /// Decrypt the input with the input password.
+    ///
+    /// If the password is incorrect, and there is a terminal, the user is
+    /// asked for the password again, up to `password_retries` times.
+    pub fn decrypt_input<T>(&self, decrypt: impl Fn(&str) -> Result<T>) -> Result<T> {
+        let mut password = self.input_password()?;
+        let mut result = decrypt(&password);
+        let mut retries = self.password_retries;
+        while retries > 0 && is_bad_password(&result) && std::io::stderr().is_terminal() {
+            events::emit(Event::warning("bad-password", "Incorrect password").with_text("Incorrect password"));
+            password = PromptPassword.password(self.in_file.as_deref().unwrap_or("stdin"))?;
+            result = decrypt(&password);
+            retries -= 1;
+        }
+        if result.is_ok() {
+            self.decrypted_password.replace(Some(password));
+        }
+        result
+    }
+
     /// Return the alg or Error::MissingAlg
     pub fn alg(&self) -> Result<Alg> {
         self.alg.ok_or_else(||Error::MissingAlg.into())
@@ -120,3 +451,10 @@ impl AppState {
     }
 
 }
+
+fn is_bad_password<T>(result: &Result<T>) -> bool {
+    match result {
+        Err(e) => matches!(e.downcast_ref::<Error>(), Some(Error::BadPassword)),
+        Ok(_) => false,
+    }
+}
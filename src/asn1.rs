@@ -0,0 +1,419 @@
+//! A dump of the ASN.1 elements of any input, as `openssl asn1parse` shows
+//! them
+//!
+//! Each element is written on a line with its offset, its depth, the length
+//! of its header and of its content, whether it is constructed or primitive,
+//! and its tag.  OIDs, integers, booleans and strings are shown with their
+//! values, and an OCTET STRING or BIT STRING that holds a DER SEQUENCE, such
+//! as the private key of a PKCS8 document, is dumped one level deeper.
+//! Indefinite lengths, as BER documents such as PKCS7 use, are followed to
+//! their end-of-contents.
+//!
+//! PEM, hex and single line base64 inputs are decoded first, and the offsets
+//! are those of the decoded DER.
+//!
+//! ```rust
+//! use kt::asn1::dump_der;
+//!
+//! let der = [0x30, 0x05, 0x02, 0x01, 0x05, 0x05, 0x00];
+//! let dump = dump_der(&der).unwrap();
+//! assert_eq!(dump.lines().next(), Some("    0:d=0  hl=2 l=   5 cons: SEQUENCE"));
+//! assert!(dump.contains(":05"));
+//! assert!(dump_der(&der[..5]).is_err());
+//! ```
+use anyhow::{bail, Result};
+use pkcs8::ObjectIdentifier;
+use std::fmt::Write as _;
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::hex_text;
+use crate::oids::oid_to_str;
+use crate::pem;
+
+/// Deepest nesting that is dumped
+const MAX_DEPTH: usize = 64;
+
+/// The tag and length of an element
+struct Header {
+    class: u8,
+    constructed: bool,
+    number: u32,
+    header_len: usize,
+    /// None for an indefinite length
+    len: Option<usize>,
+}
+
+fn bad_asn1(offset: usize, message: &str) -> anyhow::Error {
+    Error::BadAsn1(format!("{} at offset {}", message, offset)).into()
+}
+
+fn read_header(der: &[u8], offset: usize) -> Result<Header> {
+    let first = der[0];
+    let mut at = 1;
+    let mut number = (first & 0x1f) as u32;
+    if number == 0x1f {
+        number = 0;
+        loop {
+            let byte = *der
+                .get(at)
+                .ok_or_else(|| bad_asn1(offset, "truncated tag"))?;
+            at += 1;
+            if number > u32::MAX >> 7 {
+                bail!(bad_asn1(offset, "tag number too large"));
+            }
+            number = (number << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+    let len_byte = *der
+        .get(at)
+        .ok_or_else(|| bad_asn1(offset, "truncated length"))?;
+    at += 1;
+    let len = match len_byte {
+        len if len < 0x80 => Some(len as usize),
+        0x80 => None,
+        len => {
+            let count = (len & 0x7f) as usize;
+            if count > std::mem::size_of::<usize>() {
+                bail!(bad_asn1(offset, "length too large"));
+            }
+            let bytes = der
+                .get(at..at + count)
+                .ok_or_else(|| bad_asn1(offset, "truncated length"))?;
+            at += count;
+            Some(bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize))
+        }
+    };
+    Ok(Header {
+        class: first >> 6,
+        constructed: first & 0x20 != 0,
+        number,
+        header_len: at,
+        len,
+    })
+}
+
+fn tag_name(header: &Header) -> String {
+    let name = match (header.class, header.number) {
+        (0, 0) => "EOC",
+        (0, 1) => "BOOLEAN",
+        (0, 2) => "INTEGER",
+        (0, 3) => "BIT STRING",
+        (0, 4) => "OCTET STRING",
+        (0, 5) => "NULL",
+        (0, 6) => "OBJECT",
+        (0, 10) => "ENUMERATED",
+        (0, 12) => "UTF8STRING",
+        (0, 16) => "SEQUENCE",
+        (0, 17) => "SET",
+        (0, 18) => "NUMERICSTRING",
+        (0, 19) => "PRINTABLESTRING",
+        (0, 20) => "T61STRING",
+        (0, 22) => "IA5STRING",
+        (0, 23) => "UTCTIME",
+        (0, 24) => "GENERALIZEDTIME",
+        (0, 26) => "VISIBLESTRING",
+        (0, 28) => "UNIVERSALSTRING",
+        (0, 30) => "BMPSTRING",
+        (0, number) => return format!("UNIVERSAL {}", number),
+        (1, number) => return format!("appl [ {} ]", number),
+        (2, number) => return format!("cont [ {} ]", number),
+        (_, number) => return format!("priv [ {} ]", number),
+    };
+    name.to_owned()
+}
+
+/// The value of an INTEGER, in hex, as openssl shows it
+fn integer_value(content: &[u8]) -> String {
+    if content.first().is_some_and(|b| b & 0x80 != 0) {
+        // The magnitude of a negative number is its two's complement
+        let mut magnitude: Vec<u8> = content.iter().map(|b| !b).collect();
+        for byte in magnitude.iter_mut().rev() {
+            *byte = byte.wrapping_add(1);
+            if *byte != 0 {
+                break;
+            }
+        }
+        format!("-{}", hex::encode_upper(magnitude))
+    } else {
+        hex::encode_upper(content)
+    }
+}
+
+/// The value of a primitive universal element, if it is shown
+fn primitive_value(number: u32, content: &[u8]) -> Option<String> {
+    if number == 4 {
+        return Some(format!("[HEX DUMP]:{}", hex::encode_upper(content)));
+    }
+    let value = match number {
+        1 => Some(
+            if content.iter().any(|b| *b != 0) {
+                "TRUE"
+            } else {
+                "FALSE"
+            }
+            .to_owned(),
+        ),
+        2 | 10 => Some(integer_value(content)),
+        6 => Some(match ObjectIdentifier::from_bytes(content) {
+            Ok(oid) => oid_to_str(&oid),
+            Err(_) => "BAD OBJECT".to_owned(),
+        }),
+        12 | 18 | 19 | 20 | 22 | 23 | 24 | 26 => {
+            Some(String::from_utf8_lossy(content).into_owned())
+        }
+        30 => {
+            let units: Vec<u16> = content
+                .chunks(2)
+                .map(|pair| u16::from_be_bytes([pair[0], *pair.get(1).unwrap_or(&0)]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    };
+    value.map(|value| format!(":{}", value))
+}
+
+/// The DER SEQUENCE held by an OCTET STRING or BIT STRING, if it holds one
+fn encapsulated(number: u32, content: &[u8]) -> Option<&[u8]> {
+    let inner = match number {
+        3 => content.strip_prefix(&[0])?,
+        4 => content,
+        _ => return None,
+    };
+    if inner.first() != Some(&0x30) {
+        return None;
+    }
+    match elements(inner, 0, 0, false, &mut Vec::new()) {
+        Ok(len) if len == inner.len() => Some(inner),
+        _ => None,
+    }
+}
+
+/// An element that was read, and where it starts
+struct Element {
+    offset: usize,
+    depth: usize,
+    header_len: usize,
+    len: Option<usize>,
+    constructed: bool,
+    name: String,
+    value: Option<String>,
+}
+
+impl Element {
+    /// The line of the element, as openssl writes it
+    fn line(&self) -> String {
+        let len = match self.len {
+            Some(len) => format!("{:4} ", len),
+            None => "inf  ".to_owned(),
+        };
+        let kind = match self.constructed {
+            true => "cons",
+            false => "prim",
+        };
+        match &self.value {
+            Some(value) => format!(
+                "{:5}:d={:<2} hl={} l={}{}: {:<18}{}\n",
+                self.offset, self.depth, self.header_len, len, kind, self.name, value
+            ),
+            None => format!(
+                "{:5}:d={:<2} hl={} l={}{}: {}\n",
+                self.offset, self.depth, self.header_len, len, kind, self.name
+            ),
+        }
+    }
+}
+
+/// Read each element of the bytes, and the elements they hold.  Stops at the
+/// end of the bytes or, if `until_eoc`, after an end-of-contents.  Returns
+/// the number of bytes read.  The elements before an error are kept.
+fn elements(
+    der: &[u8],
+    base: usize,
+    depth: usize,
+    until_eoc: bool,
+    out: &mut Vec<Element>,
+) -> Result<usize> {
+    if depth > MAX_DEPTH {
+        bail!(bad_asn1(base, "too deeply nested"));
+    }
+    let mut at = 0;
+    while at < der.len() {
+        let offset = base + at;
+        let header = read_header(&der[at..], offset)?;
+        let mut element = Element {
+            offset,
+            depth,
+            header_len: header.header_len,
+            len: header.len,
+            constructed: header.constructed,
+            name: tag_name(&header),
+            value: None,
+        };
+        let content_start = at + header.header_len;
+        if header.constructed {
+            out.push(element);
+            at = match header.len {
+                Some(len) => {
+                    // The elements of truncated content are read as far as
+                    // they go, to find where the document was cut
+                    let content = match der.get(content_start..content_start + len) {
+                        Some(content) => content,
+                        None => &der[content_start..],
+                    };
+                    elements(content, base + content_start, depth + 1, false, out)?;
+                    if content.len() < len {
+                        bail!(bad_asn1(offset, "truncated content"));
+                    }
+                    content_start + len
+                }
+                None => {
+                    content_start
+                        + elements(
+                            &der[content_start..],
+                            base + content_start,
+                            depth + 1,
+                            true,
+                            out,
+                        )?
+                }
+            };
+            continue;
+        }
+        let len = header
+            .len
+            .ok_or_else(|| bad_asn1(offset, "indefinite length of a primitive"))?;
+        let content = der
+            .get(content_start..content_start + len)
+            .ok_or_else(|| bad_asn1(offset, "truncated content"))?;
+        at = content_start + len;
+        let encapsulated = match header.class {
+            0 => encapsulated(header.number, content),
+            _ => None,
+        };
+        if header.class == 0 && encapsulated.is_none() {
+            element.value = primitive_value(header.number, content);
+        }
+        out.push(element);
+        if let Some(inner) = encapsulated {
+            let inner_base = base + at - inner.len();
+            elements(inner, inner_base, depth + 1, false, out)?;
+        }
+        if until_eoc && header.class == 0 && header.number == 0 {
+            return Ok(at);
+        }
+    }
+    if until_eoc {
+        bail!(bad_asn1(base + at, "missing end-of-contents"));
+    }
+    Ok(at)
+}
+
+/// The length of the first element of the bytes, with its header, if it has
+/// a definite length
+pub fn element_len(der: &[u8]) -> Option<usize> {
+    if der.is_empty() {
+        return None;
+    }
+    let header = read_header(der, 0).ok()?;
+    Some(header.header_len + header.len?)
+}
+
+/// The dump of a DER, or BER, document
+pub fn dump_der(der: &[u8]) -> Result<String> {
+    let mut found = Vec::new();
+    elements(der, 0, 0, false, &mut found)?;
+    Ok(found.iter().map(Element::line).collect())
+}
+
+/// A hex dump of the document, 16 bytes a line.  The first byte of each
+/// element is marked with a `>`, and the elements that start on a line are
+/// named after it, with their offsets.  A document that is not well formed is
+/// marked up to the element in error, and the error ends the dump.
+///
+/// ```rust
+/// use kt::asn1::hexdump;
+///
+/// let dump = hexdump(&[0x30, 0x03, 0x02, 0x01, 0x05]);
+/// assert!(dump.starts_with("     0 >30 03>02 01 05"));
+/// assert!(dump.contains("0:SEQUENCE 2:INTEGER"));
+/// ```
+pub fn hexdump(der: &[u8]) -> String {
+    let mut found = Vec::new();
+    let result = elements(der, 0, 0, false, &mut found);
+    let mut out = String::new();
+    for (row, bytes) in der.chunks(16).enumerate() {
+        let start = row * 16;
+        let _ = write!(out, "{:6} ", start);
+        for (at, byte) in bytes.iter().enumerate() {
+            let marker = match found.iter().any(|element| element.offset == start + at) {
+                true => '>',
+                false => ' ',
+            };
+            let _ = write!(out, "{}{:02x}", marker, byte);
+        }
+        let ascii: String = bytes
+            .iter()
+            .map(|b| match b.is_ascii_graphic() || *b == b' ' {
+                true => *b as char,
+                false => '.',
+            })
+            .collect();
+        let _ = write!(out, "{}  |{}|", "   ".repeat(16 - bytes.len()), ascii);
+        let names: Vec<String> = found
+            .iter()
+            .filter(|element| (start..start + bytes.len()).contains(&element.offset))
+            .map(|element| format!("{}:{}", element.offset, element.name))
+            .collect();
+        if !names.is_empty() {
+            let padding = " ".repeat(16 - bytes.len());
+            let _ = write!(out, "{}  {}", padding, names.join(" "));
+        }
+        let _ = writeln!(out);
+    }
+    if let Err(e) = result {
+        let _ = writeln!(out, "Error: {}", e);
+    }
+    out
+}
+
+/// The documents of the input, with their PEM labels
+pub fn documents(bytes: &[u8]) -> Result<Vec<(Option<String>, Vec<u8>)>> {
+    let text = std::str::from_utf8(bytes).ok().map(str::trim_start);
+    if text.is_some_and(|text| text.starts_with("-----BEGIN ")) {
+        return Ok(pem::unwrap_all(bytes)?
+            .into_iter()
+            .map(|(label, der)| (Some(label), der))
+            .collect());
+    }
+    let der = hex_text::from_hex_der(bytes)
+        .or_else(|| pem::from_base64_line(bytes, None).map(|(_, der)| der))
+        .unwrap_or_else(|| bytes.to_vec());
+    Ok(vec![(None, der)])
+}
+
+/// Write the dump of the input.  A document that is not well formed is
+/// dumped up to the element in error.
+pub fn asn1(app_state: &mut AppState) -> Result<()> {
+    let bytes = app_state.read_stream()?;
+    if bytes.is_empty() {
+        bail!(Error::MissingInput("ASN.1 document".to_owned()));
+    }
+    for (label, der) in documents(&bytes)? {
+        let mut out = String::new();
+        if let Some(label) = label {
+            let _ = writeln!(out, "Label: {}", label);
+        }
+        // The elements before the error are written, then the error
+        let mut found = Vec::new();
+        let result = elements(&der, 0, 0, false, &mut found);
+        out.extend(found.iter().map(Element::line));
+        app_state.write_stream(out.as_bytes())?;
+        result?;
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Error reporting for commands that process many files
+//!
+//! Batch commands, such as `jwks build`, record the failure of each file in a
+//! [BatchReport] and carry on with the rest, rather than stopping at the first
+//! failure.  At the end of the run, a summary grouped by [FailureKind] is
+//! written to stderr, and the command only fails if a failure of a kind given
+//! to `--fail-on` occurred.
+//!
+//! ```rust
+//! use kt::batch::{BatchReport, FailureKind};
+//! use kt::errors::Error;
+//!
+//! let mut report = BatchReport::default();
+//! report.record("notes.txt", &Error::UnknownKeyType.into());
+//! assert!(report.finish(&[FailureKind::Io]).is_ok());
+//! assert!(report.finish(&[FailureKind::UnknownFormat]).is_err());
+//! ```
+use anyhow::{bail, Result};
+use log::warn;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::errors::Error;
+use crate::events::{self, Event};
+
+/// The kinds of failure that a batch summary groups by
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FailureKind {
+    /// The file is not a key kt can read, such as a README
+    UnknownFormat,
+    /// The password was missing or incorrect
+    BadPassword,
+    /// The file could not be read or written
+    Io,
+    /// Anything else
+    Other,
+}
+
+impl FailureKind {
+    pub fn all() -> Vec<&'static str> {
+        vec!["unknown-format", "bad-password", "io", "other"]
+    }
+
+    /// The name of the kind, as given to `--fail-on`
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureKind::UnknownFormat => "unknown-format",
+            FailureKind::BadPassword => "bad-password",
+            FailureKind::Io => "io",
+            FailureKind::Other => "other",
+        }
+    }
+
+    /// The kinds that fail a batch command if `--fail-on` is not given.
+    ///
+    /// Unknown formats are expected in a directory of keys, so they only
+    /// appear in the summary.
+    pub fn default_fail_on() -> Vec<FailureKind> {
+        vec![FailureKind::BadPassword, FailureKind::Io]
+    }
+
+    /// Classify an error
+    pub fn of(e: &anyhow::Error) -> Self {
+        match e.downcast_ref::<Error>() {
+            Some(
+                Error::UnknownKeyType
+                | Error::UnknownFormat
+                | Error::UnknownAlg
+                | Error::UnknownCurve
+                | Error::NotSupported
+                | Error::BadPKCS8File(_)
+                | Error::BadPKCS8DER(_)
+                | Error::BadPem(_)
+                | Error::BadJwk(_),
+            ) => FailureKind::UnknownFormat,
+            Some(Error::BadPassword | Error::BadJwe(_)) => FailureKind::BadPassword,
+            Some(Error::MissingInput(what)) if what == "password" => FailureKind::BadPassword,
+            Some(
+                Error::ReadFileError(_)
+                | Error::WriteFileError(_)
+                | Error::IOEReadError(_)
+                | Error::IOEWriteError(_)
+                | Error::Fetch(_),
+            ) => FailureKind::Io,
+            _ => FailureKind::Other,
+        }
+    }
+}
+
+impl FromStr for FailureKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<FailureKind> {
+        match s.to_lowercase().as_str() {
+            "unknown-format" => Ok(FailureKind::UnknownFormat),
+            "bad-password" => Ok(FailureKind::BadPassword),
+            "io" => Ok(FailureKind::Io),
+            "other" => Ok(FailureKind::Other),
+            _ => Err(Error::UnknownFailureKind.into()),
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txt = match self {
+            FailureKind::UnknownFormat => "unknown format",
+            FailureKind::BadPassword => "bad password",
+            FailureKind::Io => "I/O",
+            FailureKind::Other => "other",
+        };
+        write!(f, "{}", txt)
+    }
+}
+
+/// Parse the values of `--fail-on`.  `all` and `none` stand for every kind
+/// and no kind.
+pub fn parse_fail_on<'a>(values: impl IntoIterator<Item = &'a str>) -> Result<Vec<FailureKind>> {
+    let mut kinds = Vec::new();
+    for value in values {
+        match value.to_lowercase().as_str() {
+            "all" => kinds.extend(FailureKind::all().into_iter().map(FailureKind::from_str)),
+            "none" => (),
+            value => kinds.push(FailureKind::from_str(value)),
+        }
+    }
+    kinds.into_iter().collect()
+}
+
+/// The failure of one item of a batch
+#[derive(Debug)]
+pub struct Failure {
+    /// The file, or key, that failed
+    pub item: String,
+    pub kind: FailureKind,
+    pub message: String,
+}
+
+/// The failures of a batch command
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    pub failures: Vec<Failure>,
+}
+
+impl BatchReport {
+    /// Record the failure of an item, and carry on
+    pub fn record(&mut self, item: &str, e: &anyhow::Error) {
+        warn!("Skipping {}: {}", item, e);
+        let kind = FailureKind::of(e);
+        events::emit(
+            Event::warning(kind.name(), &e.to_string())
+                .with("item", item)
+                .silent(),
+        );
+        self.failures.push(Failure {
+            item: item.to_owned(),
+            kind,
+            message: e.to_string(),
+        });
+    }
+
+    /// The failures, grouped by kind
+    pub fn summary(&self) -> String {
+        let mut kinds: Vec<FailureKind> = self.failures.iter().map(|f| f.kind).collect();
+        kinds.sort();
+        kinds.dedup();
+
+        let mut summary = format!("{} failures:\n", self.failures.len());
+        for kind in kinds {
+            let failures: Vec<&Failure> = self.failures.iter().filter(|f| f.kind == kind).collect();
+            summary.push_str(&format!("  {} {}\n", failures.len(), kind));
+            for failure in failures {
+                summary.push_str(&format!("    {}: {}\n", failure.item, failure.message));
+            }
+        }
+        summary
+    }
+
+    /// Write the summary to stderr, if anything failed.  Fails if any failure
+    /// is of a kind in `fail_on`.
+    pub fn finish(&self, fail_on: &[FailureKind]) -> Result<()> {
+        if self.failures.is_empty() {
+            return Ok(());
+        }
+        let fatal = self
+            .failures
+            .iter()
+            .filter(|f| fail_on.contains(&f.kind))
+            .count();
+        let summary = self.summary();
+        events::emit(
+            Event::progress(&format!("{} failures", self.failures.len()))
+                .with("failures", self.failures.len())
+                .with("fatal", fatal)
+                .with_text(summary.trim_end()),
+        );
+        if fatal > 0 {
+            bail!(Error::BatchFailed(fatal));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,293 @@
+//! Validate a key, structurally and mathematically
+//!
+//! `kt check` reads a key, in any format kt reads, and checks that its
+//! components are consistent with each other, which reading the key does not
+//! do:
+//!
+//! - RSA private keys: n = p * q, e * d = 1 mod p - 1 and mod q - 1, and the
+//!   CRT exponents and coefficient.  The additional primes of multi-prime
+//!   keys are checked as well.
+//! - EC private keys: the scalar is from 1 to n - 1, and the public point the
+//!   key carries is on the curve, and is the public point of the scalar.
+//! - Ed25519 and X25519 private keys: the public key the key carries is the
+//!   public key of the private key.
+//! - Public keys: RSA moduli and exponents are odd, and EC and Ed25519 points
+//!   are on the curve.
+//!
+//! Each finding is written, and the key is invalid if any check fails.
+//!
+//! ```rust
+//! use kt::check::check_key;
+//! use kt::generate::{generate_ed25519, SeededRng};
+//!
+//! let mut key_info = generate_ed25519(&mut SeededRng::new(b"check")).unwrap();
+//! assert!(check_key(&key_info).unwrap().iter().all(|finding| finding.passed()));
+//!
+//! // A public key that is not the public key of the seed
+//! key_info.public_key = Some(vec![0x58; 32]);
+//! assert!(!check_key(&key_info).unwrap().iter().all(|finding| finding.passed()));
+//! ```
+use anyhow::{bail, Result};
+use pkcs8::der::{asn1::OctetString, Decodable};
+use rsa::BigUint;
+use sec1::EcPrivateKey;
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::document::sec1_docs::uncompressed_point;
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, KeyInfo, KeyType};
+use crate::secp256k1;
+use crate::signature::{ec_public_point, ed25519_signing_key};
+use crate::sm2;
+use crate::xdh::x25519_public_key;
+
+/// The result of one check of a key
+pub struct Finding {
+    /// What was checked
+    pub name: String,
+    /// Why the check failed, or None if it passed
+    pub failure: Option<String>,
+}
+
+impl Finding {
+    fn new(name: &str, passed: bool, failure: &str) -> Self {
+        Finding {
+            name: name.to_owned(),
+            failure: (!passed).then(|| failure.to_owned()),
+        }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+fn key_bytes(key_info: &KeyInfo) -> Result<&[u8]> {
+    key_info
+        .bytes
+        .as_deref()
+        .map(Vec::as_slice)
+        .ok_or_else(|| Error::MissingInput("key".to_owned()).into())
+}
+
+fn uint(bytes: &[u8]) -> BigUint {
+    BigUint::from_bytes_be(bytes)
+}
+
+/// The checks of an RSAPrivateKey, including the additional primes of a
+/// multi-prime key
+fn check_rsa_private(key_info: &KeyInfo) -> Result<Vec<Finding>> {
+    let key = pkcs1::RsaPrivateKey::from_der(key_bytes(key_info)?).map_err(|_| Error::BadCrypto)?;
+    let one = BigUint::from(1u32);
+    let n = uint(key.modulus.as_bytes());
+    let e = uint(key.public_exponent.as_bytes());
+    let d = uint(key.private_exponent.as_bytes());
+    let p = uint(key.prime1.as_bytes());
+    let q = uint(key.prime2.as_bytes());
+    let mut findings = Vec::new();
+
+    let mut primes = vec![p.clone(), q.clone()];
+    if let Some(other_prime_infos) = &key.other_prime_infos {
+        primes.extend(other_prime_infos.iter().map(|info| uint(info.prime.as_bytes())));
+    }
+    if primes.iter().any(|prime| *prime <= one) {
+        bail!(Error::BadCrypto);
+    }
+    let product = primes.iter().fold(one.clone(), |product, prime| product * prime);
+    let name = match primes.len() {
+        2 => "n = p * q".to_owned(),
+        count => format!("n = product of {} primes", count),
+    };
+    findings.push(Finding::new(&name, product == n, "the primes are not the factors of n"));
+    findings.push(Finding::new(
+        "e * d = 1 mod each prime - 1",
+        primes.iter().all(|prime| (&e * &d) % (prime - &one) == one),
+        "d is not the inverse of e",
+    ));
+    findings.push(Finding::new(
+        "dP = d mod (p - 1)",
+        uint(key.exponent1.as_bytes()) == &d % (&p - &one),
+        "exponent1 is not d mod (p - 1)",
+    ));
+    findings.push(Finding::new(
+        "dQ = d mod (q - 1)",
+        uint(key.exponent2.as_bytes()) == &d % (&q - &one),
+        "exponent2 is not d mod (q - 1)",
+    ));
+    findings.push(Finding::new(
+        "qInv * q = 1 mod p",
+        (uint(key.coefficient.as_bytes()) * &q) % &p == one,
+        "coefficient is not the inverse of q mod p",
+    ));
+    // Each additional prime r has its exponent d mod (r - 1), and the
+    // inverse mod r of the product of the primes before it
+    if let Some(other_prime_infos) = &key.other_prime_infos {
+        let mut before = &p * &q;
+        for (i, info) in other_prime_infos.iter().enumerate() {
+            let r = uint(info.prime.as_bytes());
+            let exponent = uint(info.exponent.as_bytes()) == &d % (&r - &one);
+            let coefficient = (uint(info.coefficient.as_bytes()) * &before) % &r == one;
+            findings.push(Finding::new(
+                &format!("Prime {} CRT parameters", i + 3),
+                exponent && coefficient,
+                "the exponent or coefficient of the prime is wrong",
+            ));
+            before *= r;
+        }
+    }
+    Ok(findings)
+}
+
+/// The checks of an RSAPublicKey
+fn check_rsa_public(key_info: &KeyInfo) -> Result<Vec<Finding>> {
+    let key = pkcs1::RsaPublicKey::from_der(key_bytes(key_info)?).map_err(|_| Error::BadCrypto)?;
+    let odd = |bytes: &[u8]| bytes.last().is_some_and(|byte| byte & 1 == 1);
+    let e = uint(key.public_exponent.as_bytes());
+    Ok(vec![
+        Finding::new("n is odd", odd(key.modulus.as_bytes()), "n is even"),
+        Finding::new(
+            "e is odd and at least 3",
+            odd(key.public_exponent.as_bytes()) && e >= BigUint::from(3u32),
+            "e can not be a public exponent",
+        ),
+    ])
+}
+
+/// Test whether the big endian scalar is from 1 to n - 1
+fn valid_scalar(scalar: &[u8], curve: Curve) -> bool {
+    match curve {
+        Curve::P256 => p256::SecretKey::from_slice(scalar).is_ok(),
+        Curve::P384 => p384::SecretKey::from_slice(scalar).is_ok(),
+        Curve::P521 => p521::SecretKey::from_slice(scalar).is_ok(),
+        Curve::Secp256k1 => secp256k1::is_valid_scalar(scalar),
+        Curve::Sm2 => sm2::is_valid_scalar(scalar),
+    }
+}
+
+/// The checks of an ECPrivateKey, and of the public point it carries
+fn check_ec_private(key_info: &KeyInfo) -> Result<Vec<Finding>> {
+    let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+    let key = EcPrivateKey::from_der(key_bytes(key_info)?).map_err(|_| Error::BadCrypto)?;
+    let in_range = valid_scalar(key.private_key, curve);
+    let mut findings = vec![Finding::new(
+        "Scalar is from 1 to n - 1",
+        in_range,
+        "the scalar is not a private key of the curve",
+    )];
+
+    let public_key = key.public_key.or(key_info.public_key.as_deref());
+    if let Some(point) = public_key {
+        let on_curve = uncompressed_point(point, curve).ok();
+        findings.push(Finding::new(
+            "Public point is on the curve",
+            on_curve.is_some(),
+            "the public point is not a point of the curve",
+        ));
+        if let (true, Some(point)) = (in_range, on_curve) {
+            findings.push(Finding::new(
+                "Public point is the scalar's",
+                ec_public_point(key_info).ok() == Some(point),
+                "the public point is not the public point of the scalar",
+            ));
+        }
+    }
+    Ok(findings)
+}
+
+/// The checks of an Ed25519 or X25519 private key, and of the public key it
+/// carries
+fn check_okp_private(key_info: &KeyInfo) -> Result<Vec<Finding>> {
+    let private_key = OctetString::from_der(key_bytes(key_info)?).map_err(|_| Error::BadCrypto)?;
+    let length = private_key.as_bytes().len() == 32;
+    let mut findings = vec![Finding::new(
+        "Private key is 32 bytes",
+        length,
+        "the private key is the wrong length",
+    )];
+    if let (true, Some(public_key)) = (length, &key_info.public_key) {
+        let derived = match key_info.alg {
+            Alg::EdDsa25519 => ed25519_signing_key(key_info)?.verifying_key().to_bytes().to_vec(),
+            _ => x25519_public_key(private_key.as_bytes())?,
+        };
+        findings.push(Finding::new(
+            "Public key is the private key's",
+            *public_key == derived,
+            "the public key is not the public key of the private key",
+        ));
+    }
+    Ok(findings)
+}
+
+/// The checks of the key
+pub fn check_key(key_info: &KeyInfo) -> Result<Vec<Finding>> {
+    match (key_info.alg, key_info.key_type) {
+        (Alg::Rsa | Alg::RsaSsaPss, KeyType::Private) => check_rsa_private(key_info),
+        (Alg::Rsa | Alg::RsaSsaPss, KeyType::Public) => check_rsa_public(key_info),
+        (Alg::Ecdsa, KeyType::Private) => check_ec_private(key_info),
+        (Alg::Ecdsa, KeyType::Public) => {
+            let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+            Ok(vec![Finding::new(
+                "Point is on the curve",
+                uncompressed_point(key_bytes(key_info)?, curve).is_ok(),
+                "the point is not a point of the curve",
+            )])
+        }
+        (Alg::EdDsa25519 | Alg::X25519, KeyType::Private) => check_okp_private(key_info),
+        (Alg::EdDsa25519, KeyType::Public) => {
+            let point = key_bytes(key_info)?
+                .try_into()
+                .ok()
+                .and_then(|bytes| ed25519_dalek::VerifyingKey::from_bytes(bytes).ok());
+            let mut findings = vec![Finding::new(
+                "Point is on the curve",
+                point.is_some(),
+                "the public key is not a point of the curve",
+            )];
+            if let Some(point) = point {
+                findings.push(Finding::new(
+                    "Point is not of small order",
+                    !point.is_weak(),
+                    "the public key is a weak key",
+                ));
+            }
+            Ok(findings)
+        }
+        (Alg::X25519, KeyType::Public) => Ok(vec![Finding::new(
+            "Public key is 32 bytes",
+            key_bytes(key_info)?.len() == 32,
+            "the public key is the wrong length",
+        )]),
+        _ => bail!(Error::NotSupported),
+    }
+}
+
+/// Check the provided key, and write each finding.  A key that fails any
+/// check is returned as Error::InvalidKey.
+pub fn check(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    let findings = check_key(&key_info)?;
+    let alg = match (key_info.alg, key_info.curve()) {
+        (Alg::Ecdsa, Some(curve)) => format!("{} {}", key_info.alg, curve),
+        _ => key_info.alg.to_string(),
+    };
+    let mut report = format!("{:<32} {} {:?} key\n", "Key:", alg, key_info.key_type);
+    for finding in &findings {
+        let result = match &finding.failure {
+            None => "ok".to_owned(),
+            Some(failure) => format!("FAILED: {}", failure),
+        };
+        report.push_str(&format!("{:<32} {}\n", finding.name, result));
+    }
+    let failures = findings.iter().filter(|finding| !finding.passed()).count();
+    report.push_str(&format!(
+        "{:<32} {}\n",
+        "Result:",
+        if failures == 0 { "valid" } else { "invalid" }
+    ));
+    app_state.write_stream(report.as_bytes())?;
+    if failures > 0 {
+        bail!(Error::InvalidKey(failures));
+    }
+    Ok(())
+}
@@ -4,73 +4,300 @@
 //! requested sub command.
 //!
 use std::fs::File;
-use std::io::Read;
+use std::io::{Cursor, Read};
+use std::rc::Rc;
 use std::str::FromStr;
 
-use anyhow::{bail, Result};
+use anyhow::Result;
 use clap::ArgMatches;
 
+use crate::alg_id::PssHash;
 use crate::app_state::*;
+use crate::asn1::{self, asn1};
+use crate::batch::{parse_fail_on, FailureKind};
+use crate::consumer::TargetConsumer;
+use crate::check::check;
+use crate::compare::compare_impl;
 use crate::conversion::convert;
+use crate::csr::csr;
+use crate::detached::{self, SignatureFormat};
 use crate::discover::discover;
+use crate::doctor::doctor;
+use crate::encrypt::{decrypt, encrypt};
+use crate::document::composite_docs::Component;
+use crate::document::sec1_docs::PointFormat;
+use crate::document::jwk_docs::{jwk_to_key_info, Jwks, KeyUse};
+use crate::document::known_hosts_docs::{host_name, known_hosts_for, KnownHost};
+use crate::document::openpgp_docs::{
+    is_openpgp, openpgp_encoding, openpgp_keys, pgp_key_to_key_info, select_pgp_key,
+};
+use crate::document::pkcs8_docs::{pk8_encrypted_key_info, Pkcs8Version};
+use crate::document::tink_docs::{is_tink_keyset, tink_key_to_key_info, Keyset};
+use crate::document::tss2_docs::{is_tss2, tss2_key};
 use crate::errors::Error;
-use crate::key_info::{Alg, Encoding, Format, KeyType};
+use crate::events::{self, EventFormat};
+use crate::explain::explain;
+use crate::export::{export, ExportTarget};
+use crate::fingerprint::{
+    fingerprint, key_fingerprint, key_size, pin, FingerprintHash, FingerprintStyle,
+};
+use crate::generate::generate;
+use crate::hex_text::HexStyle;
+use crate::jwks;
+use crate::jwt;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyType, SecretEncoding};
+use crate::matching::match_keys;
+use crate::migrate::{check_out_file, migrate, MigrationTarget};
+use crate::normalize::normalize;
+use crate::selfsign::{selfsign, KeyUsage};
+use crate::output::{parse_owner, parse_umask, OutputPolicy};
+use crate::passwd::passwd;
+use crate::password::{parse_provider, PasswordProvider};
+use crate::pbe::{Cipher, Kdf};
+use crate::shred::shred;
 
-/// Read a password from a local file
+/// Open an input file.
 ///
-/// If the arg to `process_password` is `FILE:<filename>` this method is called
-/// to retrieve the password from `<filename>`.
-fn read_password_from_file(filename: &str) -> Result<Option<String>> {
-    let mut file = File::open(filename).map_err(Error::ReadFileError)?;
-    let mut buf = String::new();
-    let _cnt = file.read_to_string(&mut buf).map_err(Error::IOEReadError);
+/// With the `http` feature, the input may also be an https URL, which is
+/// fetched.
+fn open_input(filename: &str) -> Result<Box<dyn Read>> {
+    if filename.starts_with("https://") || filename.starts_with("http://") {
+        return fetch_input(filename);
+    }
+    Ok(Box::new(File::open(filename).map_err(Error::ReadFileError)?))
+}
 
-    Ok(Some(buf))
+#[cfg(feature = "http")]
+fn fetch_input(url: &str) -> Result<Box<dyn Read>> {
+    Ok(Box::new(Cursor::new(crate::http::fetch(url)?)))
 }
 
-/// Handle password input options similar to openssl
-///
-/// The password may be of 2 forms:
-/// 1. "pass:<value>": The value after the colon represents the actual password
-/// 2. "file:<value>": The value after the colon represents a file that contains the password
+#[cfg(not(feature = "http"))]
+fn fetch_input(_url: &str) -> Result<Box<dyn Read>> {
+    Err(Error::HttpDisabled.into())
+}
+
+/// Open the JWKS of an OpenID Connect issuer as the input
+#[cfg(feature = "http")]
+fn open_issuer(issuer: &str) -> Result<Box<dyn Read>> {
+    Ok(Box::new(Cursor::new(crate::http::fetch_issuer_jwks(issuer)?)))
+}
+
+#[cfg(not(feature = "http"))]
+fn open_issuer(_issuer: &str) -> Result<Box<dyn Read>> {
+    Err(Error::HttpDisabled.into())
+}
+
+/// Display a host key of a known_hosts file, with its fingerprints
+/// Hashed hosts are displayed as the host they matched.
+fn show_known_host(host: &str, known_host: &KnownHost) -> Result<()> {
+    let key_info = &known_host.key_info;
+    match known_host.is_hashed() {
+        true => println!("Host: {} (hashed, line {})", host_name(host), known_host.line),
+        false => println!("Host: {} (line {})", known_host.hosts, known_host.line),
+    }
+    if let Some(marker) = &known_host.marker {
+        println!("Marker: {}", marker);
+    }
+    print!("{:}", key_info);
+    println!("Bits: {}", key_size(key_info)?);
+    for hash in [FingerprintHash::Sha256, FingerprintHash::Md5] {
+        println!("Fingerprint: {}", key_fingerprint(key_info, hash, FingerprintStyle::Ssh)?);
+    }
+    println!();
+    Ok(())
+}
+
+/// Display the input key.
 ///
-fn process_password(input: Option<&str>) -> Result<Option<String>> {
-    match input {
-        None => Ok(None),
-        Some(s) => {
-            let parts = s.split(':').collect::<Vec<&str>>();
-            // If there's not enough args, bail
-            if parts.len() < 2 {
-                bail!(Error::BadPasswordArg);
+/// A JWKS, Tink keyset, or OpenPGP key and its subkeys, is displayed one key
+/// at a time, unless a kid selects one key.  With a host, the input is read
+/// as known_hosts, and each key of the host is displayed.  With `--hexdump`,
+/// a hex dump of the input comes first.
+fn show(app_state: &mut AppState) -> Result<()> {
+    let bytes = app_state.read_stream()?;
+    // The dump is shown first, so that it is there when the key can't be read
+    if app_state.hexdump {
+        for (label, der) in asn1::documents(&bytes)? {
+            if let Some(label) = label {
+                println!("Label: {}", label);
             }
-            let mode = parts[0].to_owned();
-            let target;
-
-            // If the password contains a ':', join them
-            if parts.len() > 2 {
-                match parts.split_first() {
-                    Some((_, remainder)) => {
-                        target = remainder.join("");
-                    }
-                    _ => bail!(Error::BadPasswordArg),
+            println!("{}", asn1::hexdump(&der));
+        }
+    }
+    if let Some(host) = &app_state.host {
+        let text = std::str::from_utf8(&bytes)
+            .map_err(|_| Error::BadKnownHosts("not UTF-8".to_owned()))?;
+        for known_host in known_hosts_for(text, host)? {
+            show_known_host(host, &known_host)?;
+        }
+        return Ok(());
+    }
+    if app_state.key_id.is_none() {
+        let jwks = std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|text| Jwks::from_json(text).ok());
+        if let Some(jwks) = jwks {
+            for jwk in &jwks.keys {
+                println!("Key ID: {}", jwk.kid.as_deref().unwrap_or("none"));
+                match jwk_to_key_info(jwk) {
+                    Ok(key_info) => println!("{:}", key_info),
+                    Err(e) => println!("{}\n", e),
                 }
-            } else {
-                target = parts[1].to_owned();
             }
-            match mode.to_lowercase().as_str() {
-                "pass" => Ok(Some(target)),
-                "file" => read_password_from_file(&target),
-                _ => bail!(Error::BadPasswordArg),
+            return Ok(());
+        }
+    }
+    if is_openpgp(&bytes) {
+        return show_openpgp(app_state, &bytes);
+    }
+    if app_state.key_id.is_none() && is_tink_keyset(&bytes) {
+        let text =
+            std::str::from_utf8(&bytes).map_err(|_| Error::BadTink("not UTF-8".to_owned()))?;
+        let keyset = Keyset::from_json(text)?;
+        for key in &keyset.key {
+            match keyset.primary_key_id == Some(key.key_id) {
+                true => println!("Key ID: {} (primary)", key.key_id),
+                false => println!("Key ID: {}", key.key_id),
+            }
+            println!("Tink Key Type: {}", key.type_name());
+            println!("Status: {}", key.status);
+            println!("Output Prefix: {}", key.output_prefix_type);
+            match tink_key_to_key_info(key) {
+                Ok(key_info) => println!("{:}", key_info),
+                Err(e) => println!("{}\n", e),
             }
         }
+        return Ok(());
     }
+    if is_tss2(&bytes) {
+        println!("{:}", tss2_key(&bytes)?);
+        return Ok(());
+    }
+    // How an encrypted PKCS8 key is protected is shown without the password
+    if app_state.in_password.is_none() && app_state.password_provider.is_none() {
+        if let Some(key_info) = pk8_encrypted_key_info(&bytes) {
+            println!("{:}", key_info);
+            return Ok(());
+        }
+    }
+    app_state.in_stream = Box::new(Cursor::new(bytes));
+    let key_info = discover(app_state)?;
+    println!("{:}", key_info);
+    Ok(())
 }
 
-/// Processes all CLI arguments into an instance of AppState
+/// Display each key of an OpenPGP key, or the key the kid selects
+fn show_openpgp(app_state: &AppState, bytes: &[u8]) -> Result<()> {
+    let keys = openpgp_keys(bytes)?;
+    let keys = match &app_state.key_id {
+        Some(key_id) => vec![select_pgp_key(&keys, Some(key_id))?],
+        None => keys.iter().collect(),
+    };
+    for key in keys {
+        match key.subkey {
+            true => println!("Key ID: {} (subkey)", key.key_id()),
+            false => println!("Key ID: {}", key.key_id()),
+        }
+        println!("Fingerprint: {}", key.fingerprint_hex());
+        println!("Created: {}", key.created_time());
+        println!("OpenPGP Algorithm: {}", key.algorithm_name());
+        match pgp_key_to_key_info(app_state, key, openpgp_encoding(bytes)) {
+            Ok(key_info) => println!("{:}", key_info),
+            Err(e) if matches!(e.downcast_ref::<Error>(), Some(Error::BadOpenPgp(_))) => {
+                println!("{}\n", e)
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+/// The failure kinds given to `--fail-on`, or the default kinds
+fn process_fail_on(matches: &ArgMatches) -> Result<Vec<FailureKind>> {
+    match matches.get_many::<String>("fail-on") {
+        Some(values) => parse_fail_on(values.map(|s| s.as_str())),
+        None => Ok(FailureKind::default_fail_on()),
+    }
+}
+
+/// The password provider for `--inpass`, which is only asked for a password
+/// if the input is encrypted.  See [parse_provider] for the forms of the arg.
+fn process_inpass(matches: &ArgMatches) -> Result<Option<Rc<dyn PasswordProvider>>> {
+    matches
+        .get_one::<String>("inpass")
+        .map(|s| parse_provider(s))
+        .transpose()
+}
+
+/// The password for `--outpass`.  See [parse_provider] for the forms of the
+/// arg.
+fn process_password(input: Option<&str>) -> Result<Option<String>> {
+    match input.map(parse_provider).transpose()? {
+        Some(provider) => Ok(Some(provider.password("output")?.to_string())),
+        None => Ok(None),
+    }
+}
+
+/// The PBES2 parameters of `--kdf`, `--iterations`, `--salt-len` and
+/// `--cipher`
+fn process_pbe(app_state: &mut AppState, matches: &ArgMatches) -> Result<()> {
+    if let Some(kdf) = matches.get_one::<String>("kdf") {
+        app_state.pbe.kdf = Kdf::from_str(kdf)?;
+    }
+    app_state.pbe.iterations = matches.get_one::<u32>("iterations").copied();
+    if let Some(salt_len) = matches.get_one::<usize>("salt-len") {
+        app_state.pbe.salt_len = *salt_len;
+    }
+    if let Some(cipher) = matches.get_one::<String>("cipher") {
+        app_state.pbe.cipher = Cipher::from_str(cipher)?;
+    }
+    Ok(())
+}
+
+/// The `--seed` or `--seed-file` of deterministic key generation
+fn process_seed(app_state: &mut AppState, matches: &ArgMatches) {
+    app_state.seed = matches.get_one::<String>("seed").cloned();
+    app_state.seed_file = matches.get_one::<String>("seed-file").cloned();
+}
+
+/// The name of the subcommand, and of its subcommand, such as `jwks build`
+fn command_name(matches: &ArgMatches) -> String {
+    match matches.subcommand() {
+        Some((name, matches)) => match matches.subcommand_name() {
+            Some(sub) => format!("{} {}", name, sub),
+            None => name.to_owned(),
+        },
+        None => "convert".to_owned(),
+    }
+}
+
+/// Processes all CLI arguments, and runs the requested sub command.  With
+/// `--events`, the run is framed by start and result events.
 pub fn process(matches: &ArgMatches) -> Result<()> {
-    let mut app_state: AppState = Default::default();
+    if let Some(format) = matches.get_one::<String>("events") {
+        let format = EventFormat::from_str(format)?;
+        events::init(format, &command_name(matches), matches.get_flag("local-time"));
+    }
+    events::start();
+    let result = run(matches);
+    events::finish(&result);
+    result
+}
 
+/// Processes all CLI arguments into an instance of AppState
+fn run(matches: &ArgMatches) -> Result<()> {
     // Process the top level inputs
+    let mut app_state = AppState {
+        local_time: matches.get_flag("local-time"),
+        password_retries: matches.get_one::<u32>("password-retries").copied().unwrap_or(3),
+        ..Default::default()
+    };
+    app_state.output = OutputPolicy {
+        umask: matches.get_one::<String>("umask").map(|s| parse_umask(s)).transpose()?,
+        owner: matches.get_one::<String>("owner").map(|s| parse_owner(s)).transpose()?,
+    };
+    app_state.output.apply_umask()?;
 
     // Open the input reader.  Bail on error
 
@@ -79,33 +306,81 @@ pub fn process(matches: &ArgMatches) -> Result<()> {
             app_state.command = Command::Show;
             if let Some(filename) = matches.get_one::<String>("in") {
                 app_state.in_file = Some(filename.to_string());
-                app_state.in_stream =
-                    Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+                app_state.in_stream = open_input(filename)?;
                 //TODO IF no from arg is provided, see if we can determine from the filename.
                 if !matches.contains_id("in") {}
             }
-            app_state.in_password =
-                process_password(matches.get_one::<String>("inpass").map(|s| s.as_str()))?;
+            if let Some(issuer) = matches.get_one::<String>("issuer") {
+                app_state.in_file = Some(issuer.to_string());
+                app_state.in_stream = open_issuer(issuer)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+
+            if let Some(curve) = matches.get_one::<String>("curve") {
+                app_state.curve = Some(Curve::from_str(curve)?);
+            }
+
+            if let Some(secret) = matches.get_one::<String>("secret") {
+                app_state.secret = Some(SecretEncoding::from_str(secret)?);
+            }
+            if let Some(in_format) = matches.get_one::<String>("in-format") {
+                app_state.in_format = Some(Encoding::from_str(in_format)?);
+            }
+            if let Some(alg) = matches.get_one::<String>("alg") {
+                app_state.alg = Some(Alg::from_str(alg)?);
+            }
+            app_state.cert_index = matches.get_one::<usize>("cert-index").copied().unwrap_or(0);
+            app_state.friendly_name = matches.get_one::<String>("friendly-name").cloned();
+            app_state.p12_cert = matches.get_flag("p12-cert");
+
+            if let Some(kid) = matches.get_one::<String>("kid") {
+                app_state.key_id = Some(kid.to_owned());
+            }
+            app_state.debug_dump = matches.get_one::<String>("debug-dump").cloned();
+            app_state.lenient = matches.get_flag("lenient");
+            app_state.host = matches.get_one::<String>("host").cloned();
+            app_state.hexdump = matches.get_flag("hexdump");
         }
 
         Some(("convert", matches)) => {
             app_state.command = Command::Convert;
             if let Some(filename) = matches.get_one::<String>("in") {
                 app_state.in_file = Some(filename.to_string());
-                app_state.in_stream =
-                    Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+                app_state.in_stream = open_input(filename)?;
                 //TODO IF no from arg is provided, see if we can determine from the filename.
                 if !matches.contains_id("in") {}
             }
+            if let Some(issuer) = matches.get_one::<String>("issuer") {
+                app_state.in_file = Some(issuer.to_string());
+                app_state.in_stream = open_issuer(issuer)?;
+            }
+
+            app_state.password_provider = process_inpass(matches)?;
 
-            app_state.in_password =
-                process_password(matches.get_one::<String>("inpass").map(|s| s.as_str()))?;
+            if let Some(curve) = matches.get_one::<String>("curve") {
+                app_state.curve = Some(Curve::from_str(curve)?);
+            }
 
-            // Open the output writer.  Bail on error
+            if let Some(secret) = matches.get_one::<String>("secret") {
+                app_state.secret = Some(SecretEncoding::from_str(secret)?);
+            }
+            if let Some(in_format) = matches.get_one::<String>("in-format") {
+                app_state.in_format = Some(Encoding::from_str(in_format)?);
+            }
+            app_state.cert_index = matches.get_one::<usize>("cert-index").copied().unwrap_or(0);
+            app_state.friendly_name = matches.get_one::<String>("friendly-name").cloned();
+            app_state.p12_cert = matches.get_flag("p12-cert");
+
+            // Open the output writer.  Bail on error.  Only a public key is
+            // written with the usual permissions.
             if let Some(filename) = matches.get_one::<String>("out") {
-                app_state.out_file = Some(filename.to_string());
-                app_state.out_stream =
-                    Box::new(std::fs::File::create(filename).map_err(Error::ReadFileError)?);
+                let private = !matches!(
+                    matches
+                        .get_one::<String>("keytype")
+                        .map(|key_type| KeyType::from_str(key_type)),
+                    Some(Ok(KeyType::Public))
+                );
+                app_state.create_out_file(filename, private)?;
                 //TODO IF no from arg is provided, see if we can determine from the filename.
                 if !matches.contains_id("in") {}
             }
@@ -120,6 +395,25 @@ pub fn process(matches: &ArgMatches) -> Result<()> {
             if let Some(encoding) = matches.get_one::<String>("encoding") {
                 app_state.encoding = Encoding::from_str(encoding)?;
             }
+            if let Some(hex_style) = matches.get_one::<String>("hex-style") {
+                app_state.hex_style = HexStyle::from_str(hex_style)?;
+            }
+            app_state.pem_label = matches.get_one::<String>("pem-label").cloned();
+            if let Some(component) = matches.get_one::<String>("component") {
+                app_state.component = Some(Component::from_str(component)?);
+            }
+            if let Some(hash) = matches.get_one::<String>("pss-hash") {
+                app_state.pss_hash = Some(PssHash::from_str(hash)?);
+            }
+            app_state.pss_salt_len = matches.get_one::<u32>("pss-salt-len").copied();
+            if let Some(point_format) = matches.get_one::<String>("point-format") {
+                app_state.point_format = Some(PointFormat::from_str(point_format)?);
+            }
+
+            process_pbe(&mut app_state, matches)?;
+            if let Some(version) = matches.get_one::<String>("pkcs8-version") {
+                app_state.pkcs8_version = Pkcs8Version::from_str(version)?;
+            }
 
             if let Some(keytype) = matches.get_one::<String>("keytype") {
                 app_state.key_type = Some(KeyType::from_str(keytype)?);
@@ -132,19 +426,522 @@ pub fn process(matches: &ArgMatches) -> Result<()> {
             if let Some(kid) = matches.get_one::<String>("kid") {
                 app_state.key_id = Some(kid.to_owned());
             }
+
+            if let Some(consumer) = matches.get_one::<String>("target-consumer") {
+                app_state.target_consumer = Some(TargetConsumer::from_str(consumer)?);
+            }
+            app_state.debug_dump = matches.get_one::<String>("debug-dump").cloned();
+            app_state.lenient = matches.get_flag("lenient");
+            app_state.pretty = matches.get_flag("pretty");
+            app_state.jwk_alg = matches.get_one::<String>("jwk-alg").cloned();
+            if let Some(key_use) = matches.get_one::<String>("use") {
+                app_state.key_use = Some(KeyUse::from_str(key_use)?);
+            }
+            app_state.key_ops = matches
+                .get_many::<String>("key-ops")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            app_state.cert_file = matches.get_one::<String>("cert").cloned();
+            app_state.ca_files = matches
+                .get_many::<String>("ca")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            app_state.comment = matches.get_one::<String>("comment").cloned();
+        }
+
+        Some(("jwks", matches)) => match matches.subcommand() {
+            Some(("build", matches)) => {
+                app_state.command = Command::JwksBuild;
+                app_state.in_dir = matches.get_one::<String>("dir").cloned();
+
+                app_state.password_provider = process_inpass(matches)?;
+
+                if let Some(filename) = matches.get_one::<String>("out") {
+                    app_state.create_out_file(filename, false)?;
+                }
+                app_state.pretty = matches.get_flag("pretty");
+                app_state.fail_on = process_fail_on(matches)?;
+            }
+            Some(("explode", matches)) => {
+                app_state.command = Command::JwksExplode;
+                if let Some(filename) = matches.get_one::<String>("in") {
+                    app_state.in_file = Some(filename.to_string());
+                    app_state.in_stream =
+                        Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+                }
+                app_state.out_dir = matches.get_one::<String>("out-dir").cloned();
+                app_state.fail_on = process_fail_on(matches)?;
+            }
+            Some(("add", matches)) => {
+                app_state.command = Command::JwksAdd;
+                app_state.set_file = matches.get_one::<String>("set").cloned();
+                if let Some(filename) = matches.get_one::<String>("in") {
+                    app_state.in_file = Some(filename.to_string());
+                    app_state.in_stream = open_input(filename)?;
+                }
+                app_state.password_provider = process_inpass(matches)?;
+                app_state.key_id = matches.get_one::<String>("kid").cloned();
+                app_state.out_file = matches.get_one::<String>("out").cloned();
+                app_state.pretty = matches.get_flag("pretty");
+            }
+            Some(("serve", matches)) => {
+                app_state.command = Command::JwksServe;
+                app_state.in_files = matches
+                    .get_many::<String>("in")
+                    .map(|values| values.cloned().collect())
+                    .unwrap_or_default();
+                app_state.password_provider = process_inpass(matches)?;
+                app_state.port = matches.get_one::<u16>("port").copied();
+                app_state.fail_on = process_fail_on(matches)?;
+            }
+            _ => {}
+        },
+        Some(("jwt", matches)) => match matches.subcommand() {
+            Some(("sign", matches)) => {
+                app_state.command = Command::JwtSign;
+                if let Some(filename) = matches.get_one::<String>("in") {
+                    app_state.in_file = Some(filename.to_string());
+                    app_state.in_stream =
+                        Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+                }
+                app_state.password_provider = process_inpass(matches)?;
+                app_state.payload_file = matches.get_one::<String>("payload").cloned();
+                app_state.key_id = matches.get_one::<String>("kid").cloned();
+
+                if let Some(filename) = matches.get_one::<String>("out") {
+                    app_state.create_out_file(filename, false)?;
+                }
+            }
+            Some(("verify", matches)) => {
+                app_state.command = Command::JwtVerify;
+                if let Some(filename) = matches.get_one::<String>("in") {
+                    app_state.in_file = Some(filename.to_string());
+                    app_state.in_stream =
+                        Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+                }
+                app_state.key_file = matches.get_one::<String>("key").cloned();
+                app_state.password_provider = process_inpass(matches)?;
+
+                if let Some(filename) = matches.get_one::<String>("out") {
+                    app_state.create_out_file(filename, false)?;
+                }
+            }
+            _ => {}
+        },
+        Some(("compare-impl", matches)) => {
+            app_state.command = Command::CompareImpl;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.with_command = matches.get_one::<String>("with-command").cloned();
+
+            if let Some(filename) = matches.get_one::<String>("out") {
+                app_state.create_out_file(filename, false)?;
+            }
+        }
+        Some(("export", matches)) => {
+            app_state.command = Command::Export;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.out_password =
+                process_password(matches.get_one::<String>("outpass").map(|s| s.as_str()))?;
+            app_state.cert_file = matches.get_one::<String>("cert").cloned();
+            app_state.out_dir = matches.get_one::<String>("out-dir").cloned();
+            app_state.export_name = matches.get_one::<String>("name").cloned();
+            if let Some(target) = matches.get_one::<String>("for") {
+                app_state.export_target = Some(ExportTarget::from_str(target)?);
+            }
+        }
+        Some(("migrate", matches)) => {
+            app_state.command = Command::Migrate;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            if let Some(target) = matches.get_one::<String>("to") {
+                app_state.migration_target = Some(MigrationTarget::from_str(target)?);
+            }
+            if let Some(filename) = matches.get_one::<String>("out") {
+                check_out_file(app_state.in_file.as_deref(), filename)?;
+                app_state.create_out_file(filename, true)?;
+            }
+            app_state.out_password =
+                process_password(matches.get_one::<String>("outpass").map(|s| s.as_str()))?;
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            app_state.shred = matches.get_flag("shred");
+        }
+        Some(("passwd", matches)) => {
+            app_state.command = Command::Passwd;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            // The output file is created once the input is read, so that it
+            // can be the input file
+            app_state.out_file = matches.get_one::<String>("out").cloned();
+            app_state.out_password =
+                process_password(matches.get_one::<String>("outpass").map(|s| s.as_str()))?;
+            process_pbe(&mut app_state, matches)?;
+        }
+        Some(("shred", matches)) => {
+            app_state.command = Command::Shred;
+            app_state.in_files = matches
+                .get_many::<String>("in")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            if let Some(passes) = matches.get_one::<u32>("passes") {
+                app_state.passes = *passes;
+            }
+            app_state.force = matches.get_flag("force");
+        }
+        Some(("fingerprint", matches)) => {
+            app_state.command = Command::Fingerprint;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            app_state.cert_index = matches.get_one::<usize>("cert-index").copied().unwrap_or(0);
+            app_state.friendly_name = matches.get_one::<String>("friendly-name").cloned();
+            app_state.p12_cert = matches.get_flag("p12-cert");
+            if let Some(hash) = matches.get_one::<String>("hash") {
+                app_state.fingerprint_hash = FingerprintHash::from_str(hash)?;
+            }
+            if let Some(style) = matches.get_one::<String>("style") {
+                app_state.fingerprint_style = FingerprintStyle::from_str(style)?;
+            }
+        }
+        Some(("pin", matches)) => {
+            app_state.command = Command::Pin;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            app_state.cert_index = matches.get_one::<usize>("cert-index").copied().unwrap_or(0);
+            app_state.friendly_name = matches.get_one::<String>("friendly-name").cloned();
+            app_state.p12_cert = matches.get_flag("p12-cert");
+        }
+        Some(("asn1", matches)) => {
+            app_state.command = Command::Asn1;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+        }
+        Some(("explain", matches)) => {
+            app_state.command = Command::Explain;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            app_state.cert_index = matches.get_one::<usize>("cert-index").copied().unwrap_or(0);
+            app_state.friendly_name = matches.get_one::<String>("friendly-name").cloned();
+            app_state.p12_cert = matches.get_flag("p12-cert");
+        }
+        Some(("normalize", matches)) => {
+            app_state.command = Command::Normalize;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            // The output file is created once the input is read, so that it
+            // can be the input file
+            app_state.out_file = matches.get_one::<String>("out").cloned();
+            app_state.encoding = match matches.get_one::<String>("encoding") {
+                Some(encoding) => Encoding::from_str(encoding)?,
+                None => Encoding::PEM,
+            };
+        }
+        Some(("generate", matches)) => match matches.subcommand() {
+            Some(("rsa", matches)) => {
+                app_state.command = Command::GenerateRsa;
+                if let Some(bits) = matches.get_one::<String>("bits") {
+                    app_state.rsa_bits = bits.parse()?;
+                }
+                if let Some(exponent) = matches.get_one::<u64>("exponent") {
+                    app_state.rsa_exponent = *exponent;
+                }
+                if let Some(format) = matches.get_one::<String>("format") {
+                    app_state.format = Some(Format::from_str(format)?);
+                }
+                if let Some(encoding) = matches.get_one::<String>("encoding") {
+                    app_state.encoding = Encoding::from_str(encoding)?;
+                }
+                if let Some(filename) = matches.get_one::<String>("out") {
+                    app_state.create_out_file(filename, true)?;
+                }
+                process_seed(&mut app_state, matches);
+                app_state.out_password =
+                    process_password(matches.get_one::<String>("outpass").map(|s| s.as_str()))?;
+            }
+            Some(("ec", matches)) => {
+                app_state.command = Command::GenerateEc;
+                if let Some(curve) = matches.get_one::<String>("curve") {
+                    app_state.curve = Some(Curve::from_str(curve)?);
+                }
+                if let Some(format) = matches.get_one::<String>("format") {
+                    app_state.format = Some(Format::from_str(format)?);
+                }
+                if let Some(encoding) = matches.get_one::<String>("encoding") {
+                    app_state.encoding = Encoding::from_str(encoding)?;
+                }
+                if let Some(filename) = matches.get_one::<String>("out") {
+                    app_state.create_out_file(filename, true)?;
+                }
+                app_state.pub_out_file = matches.get_one::<String>("pubout").cloned();
+                process_seed(&mut app_state, matches);
+                app_state.out_password =
+                    process_password(matches.get_one::<String>("outpass").map(|s| s.as_str()))?;
+            }
+            Some((name @ ("ed25519" | "x25519"), matches)) => {
+                app_state.command = match name {
+                    "ed25519" => Command::GenerateEd25519,
+                    _ => Command::GenerateX25519,
+                };
+                app_state.format = Some(Format::PKCS8);
+                if let Some(encoding) = matches.get_one::<String>("encoding") {
+                    app_state.encoding = Encoding::from_str(encoding)?;
+                }
+                app_state.key_id = matches.get_one::<String>("kid").cloned();
+                if let Some(filename) = matches.get_one::<String>("out") {
+                    app_state.create_out_file(filename, true)?;
+                }
+                process_seed(&mut app_state, matches);
+                app_state.out_password =
+                    process_password(matches.get_one::<String>("outpass").map(|s| s.as_str()))?;
+            }
+            _ => {}
+        },
+        Some(("match", matches)) => {
+            app_state.command = Command::Match;
+            if let Some(filename) = matches.get_one::<String>("private") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.public_file = matches.get_one::<String>("public").cloned();
+        }
+        Some(("check", matches)) => {
+            app_state.command = Command::Check;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+        }
+        Some(("sign", matches)) => {
+            app_state.command = Command::Sign;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream =
+                    Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.data_file = matches.get_one::<String>("data").cloned();
+            if let Some(format) = matches.get_one::<String>("format") {
+                app_state.signature_format = SignatureFormat::from_str(format)?;
+            }
+            app_state.pss = matches.get_flag("pss");
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            if let Some(filename) = matches.get_one::<String>("out") {
+                app_state.create_out_file(filename, false)?;
+            }
+        }
+        Some(("verify", matches)) => {
+            app_state.command = Command::Verify;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream =
+                    Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.data_file = matches.get_one::<String>("data").cloned();
+            app_state.signature_file = matches.get_one::<String>("signature").cloned();
+            if let Some(format) = matches.get_one::<String>("format") {
+                app_state.signature_format = SignatureFormat::from_str(format)?;
+            }
+            app_state.pss = matches.get_flag("pss");
+        }
+        Some((name @ ("encrypt" | "decrypt"), matches)) => {
+            app_state.command = match name {
+                "encrypt" => Command::Encrypt,
+                _ => Command::Decrypt,
+            };
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream =
+                    Box::new(std::fs::File::open(filename).map_err(Error::ReadFileError)?);
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.data_file = matches.get_one::<String>("data").cloned();
+            app_state.base64 = matches.get_flag("base64");
+            if let Some(filename) = matches.get_one::<String>("out") {
+                app_state.create_out_file(filename, false)?;
+            }
+        }
+        Some(("doctor", matches)) => {
+            app_state.command = Command::Doctor;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+        }
+        Some(("selfsign", matches)) => {
+            app_state.command = Command::SelfSign;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            app_state.subject = matches.get_one::<String>("subject").cloned();
+            app_state.sans = matches
+                .get_many::<String>("san")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            if let Some(days) = matches.get_one::<u32>("days") {
+                app_state.days = *days;
+            }
+            app_state.key_usage = matches
+                .get_many::<String>("key-usage")
+                .map(|values| values.map(|usage| KeyUsage::from_str(usage)).collect())
+                .transpose()?
+                .unwrap_or_default();
+            app_state.ca = matches.get_flag("ca");
+            if let Some(encoding) = matches.get_one::<String>("encoding") {
+                app_state.encoding = Encoding::from_str(encoding)?;
+            }
+            if let Some(filename) = matches.get_one::<String>("out") {
+                app_state.create_out_file(filename, false)?;
+            }
+        }
+        Some(("csr", matches)) => {
+            app_state.command = Command::Csr;
+            if let Some(filename) = matches.get_one::<String>("in") {
+                app_state.in_file = Some(filename.to_string());
+                app_state.in_stream = open_input(filename)?;
+            }
+            app_state.password_provider = process_inpass(matches)?;
+            app_state.key_id = matches.get_one::<String>("kid").cloned();
+            app_state.subject = matches.get_one::<String>("subject").cloned();
+            app_state.sans = matches
+                .get_many::<String>("san")
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default();
+            if let Some(encoding) = matches.get_one::<String>("encoding") {
+                app_state.encoding = Encoding::from_str(encoding)?;
+            }
+            if let Some(filename) = matches.get_one::<String>("out") {
+                app_state.create_out_file(filename, false)?;
+            }
         }
         _ => {}
     };
 
     match app_state.command {
         Command::Show => {
-            let key_info = discover(&mut app_state)?;
-            println!("{:}", key_info);
+            show(&mut app_state)?;
         }
         Command::Convert => {
             let key_info = discover(&mut app_state)?;
             convert(&mut app_state, &key_info)?;
         }
+        Command::JwksBuild => {
+            jwks::build(&mut app_state)?;
+        }
+        Command::JwksExplode => {
+            jwks::explode(&mut app_state)?;
+        }
+        Command::JwksAdd => {
+            jwks::add(&mut app_state)?;
+        }
+        Command::JwksServe => {
+            jwks::serve(&mut app_state)?;
+        }
+        Command::JwtSign => {
+            jwt::sign(&mut app_state)?;
+        }
+        Command::JwtVerify => {
+            jwt::verify(&mut app_state)?;
+        }
+        Command::CompareImpl => {
+            compare_impl(&mut app_state)?;
+        }
+        Command::Export => {
+            export(&mut app_state)?;
+        }
+        Command::Migrate => {
+            migrate(&mut app_state)?;
+        }
+        Command::Passwd => {
+            passwd(&mut app_state)?;
+        }
+        Command::Shred => {
+            shred(&mut app_state)?;
+        }
+        Command::Fingerprint => {
+            fingerprint(&mut app_state)?;
+        }
+        Command::Csr => {
+            csr(&mut app_state)?;
+        }
+        Command::SelfSign => {
+            selfsign(&mut app_state)?;
+        }
+        Command::Pin => {
+            pin(&mut app_state)?;
+        }
+        Command::Asn1 => {
+            asn1(&mut app_state)?;
+        }
+        Command::Explain => {
+            explain(&mut app_state)?;
+        }
+        Command::Normalize => {
+            normalize(&mut app_state)?;
+        }
+        Command::Doctor => {
+            doctor(&mut app_state)?;
+        }
+        Command::GenerateRsa
+        | Command::GenerateEc
+        | Command::GenerateEd25519
+        | Command::GenerateX25519 => {
+            generate(&mut app_state)?;
+        }
+        Command::Match => {
+            match_keys(&mut app_state)?;
+        }
+        Command::Check => {
+            check(&mut app_state)?;
+        }
+        Command::Sign => {
+            detached::sign(&mut app_state)?;
+        }
+        Command::Verify => {
+            detached::verify(&mut app_state)?;
+        }
+        Command::Encrypt => {
+            encrypt(&mut app_state)?;
+        }
+        Command::Decrypt => {
+            decrypt(&mut app_state)?;
+        }
     }
     Ok(())
 }
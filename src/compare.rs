@@ -0,0 +1,255 @@
+//! Compare kt with other implementations
+//!
+//! Runs an external command, such as `openssl pkey -pubout`, with the input
+//! key on stdin, reads its output as a key, and reports whether the two keys
+//! are semantically the same.  Keys are compared by their key material, so
+//! differences in format or encoding don't matter, and a public key matches
+//! the private key it was derived from.
+use anyhow::{bail, Result};
+use log::{debug, info};
+use rsa::pkcs1::EncodeRsaPublicKey;
+use rsa::traits::PrivateKeyParts;
+use sec1::{der::Decodable, EcPrivateKey};
+use sha2::{Digest, Sha256};
+use std::io::{Cursor, Write};
+use std::process::{Command, Stdio};
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::document::openssh_docs::with_public_key;
+use crate::document::sec1_docs::uncompressed_point;
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, KeyInfo, KeyType};
+use crate::signature::{
+    ec_public_point, ed25519_signing_key, ed25519_verifying_key, rsa_private_key, rsa_public_key,
+};
+
+/// Run a command through the shell, with the input on stdin
+fn run_command(command: &str, input: &[u8]) -> Result<Vec<u8>> {
+    #[cfg(windows)]
+    let mut child = Command::new("cmd");
+    #[cfg(windows)]
+    child.arg("/C");
+    #[cfg(not(windows))]
+    let mut child = Command::new("sh");
+    #[cfg(not(windows))]
+    child.arg("-c");
+
+    info!("Running {}", command);
+    let mut child = child
+        .arg(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::Command(format!("{}: {}", command, e)))?;
+    // Write from another thread, so that a command that produces a lot of
+    // output before reading its input can't deadlock.  A command that
+    // doesn't read its input at all closes the pipe, which is not an error.
+    if let Some(mut stdin) = child.stdin.take() {
+        let input = input.to_vec();
+        std::thread::spawn(move || {
+            if let Err(e) = stdin.write_all(&input) {
+                debug!("Command did not read its input: {}", e);
+            }
+        });
+    }
+    let output = child.wait_with_output().map_err(Error::IOEReadError)?;
+    if !output.status.success() {
+        bail!(Error::Command(format!("{}: {}", command, output.status)));
+    }
+    Ok(output.stdout)
+}
+
+/// Read a key with kt
+fn discover_bytes(app_state: &AppState, name: &str, bytes: Vec<u8>) -> Result<KeyInfo> {
+    let mut key_state = AppState {
+        in_file: Some(name.to_owned()),
+        in_stream: Box::new(Cursor::new(bytes)),
+        in_password: app_state.in_password.clone(),
+        password_provider: app_state.password_provider.clone(),
+        password_retries: app_state.password_retries,
+        curve: app_state.curve,
+        secret: app_state.secret,
+        ..Default::default()
+    };
+    discover(&mut key_state)
+}
+
+/// The public key material, in a canonical form.
+///
+/// RSA keys are RSAPublicKey DER, EC keys are uncompressed points, and OKP
+/// keys are the raw public key.  Keys without a public key return None.
+pub fn public_material(key_info: &KeyInfo) -> Result<Option<Vec<u8>>> {
+    let material = match key_info.alg {
+        Alg::Rsa | Alg::RsaSsaPss => Some(
+            rsa_public_key(key_info)?
+                .to_pkcs1_der()
+                .map_err(|_| Error::BadCrypto)?
+                .as_bytes()
+                .to_vec(),
+        ),
+        Alg::Ecdsa => {
+            let point = ec_public_point(key_info)?;
+            Some(uncompressed_point(&point, key_info.curve().ok_or(Error::UnknownCurve)?)?)
+        }
+        Alg::EdDsa25519 => Some(ed25519_verifying_key(key_info)?.to_bytes().to_vec()),
+        Alg::Symmetric => None,
+        _ => match key_info.key_type {
+            KeyType::Private => with_public_key(key_info)?.public_key,
+            _ => key_info.bytes.as_ref().map(|bytes| bytes.to_vec()),
+        },
+    };
+    Ok(material)
+}
+
+/// The private key material, in a canonical form
+fn private_material(key_info: &KeyInfo) -> Result<Option<Vec<u8>>> {
+    if !matches!(key_info.key_type, KeyType::Private | KeyType::Secret) {
+        return Ok(None);
+    }
+    let bytes = key_info
+        .bytes
+        .as_ref()
+        .ok_or_else(|| Error::MissingInput("key".to_owned()))?;
+    let material = match key_info.alg {
+        Alg::Rsa | Alg::RsaSsaPss => rsa_private_key(key_info)?.d().to_bytes_be(),
+        Alg::Ecdsa => {
+            let scalar = match key_info.curve().ok_or(Error::UnknownCurve)? {
+                Curve::P256 => {
+                    p256::SecretKey::from_sec1_der(bytes).map(|key| key.to_bytes().to_vec())
+                }
+                Curve::P384 => {
+                    p384::SecretKey::from_sec1_der(bytes).map(|key| key.to_bytes().to_vec())
+                }
+                Curve::P521 => {
+                    p521::SecretKey::from_sec1_der(bytes).map(|key| key.to_bytes().to_vec())
+                }
+                Curve::Secp256k1 | Curve::Sm2 => {
+                    let key = EcPrivateKey::from_der(bytes).map_err(|_| Error::BadCrypto)?;
+                    Ok(key.private_key.to_vec())
+                }
+            };
+            scalar.map_err(|_| Error::BadCrypto)?
+        }
+        Alg::EdDsa25519 => ed25519_signing_key(key_info)?.to_bytes().to_vec(),
+        _ => bytes.to_vec(),
+    };
+    Ok(Some(material))
+}
+
+/// One line of the comparison report
+fn report_line(report: &mut String, name: &str, ours: String, theirs: String) -> bool {
+    let same = ours == theirs;
+    let line = match same {
+        true => format!("{:<12} match ({})\n", name, ours),
+        false => format!("{:<12} differs (kt: {}, command: {})\n", name, ours, theirs),
+    };
+    report.push_str(&line);
+    same
+}
+
+/// Compare kt's reading of the input key with the output of a command.
+///
+/// The keys are equivalent if the algorithm and the public key material
+/// match, and the private key material matches when both are private.  A
+/// report is written either way, and an error is returned if the keys are
+/// not equivalent.
+pub fn compare_impl(app_state: &mut AppState) -> Result<()> {
+    let command = app_state
+        .with_command
+        .clone()
+        .ok_or_else(|| Error::MissingInput("command".to_owned()))?;
+    let input = app_state.read_stream()?;
+    let name = app_state
+        .in_file
+        .clone()
+        .unwrap_or_else(|| "stdin".to_owned());
+
+    let ours = discover_bytes(app_state, &name, input.clone())?;
+    let output = run_command(&command, &input)?;
+    let theirs = discover_bytes(app_state, &command, output)?;
+
+    let mut report = String::new();
+    let mut equivalent = report_line(
+        &mut report,
+        "Algorithm:",
+        ours.alg.to_string(),
+        theirs.alg.to_string(),
+    );
+    report_line(
+        &mut report,
+        "Key Type:",
+        format!("{:?}", ours.key_type),
+        format!("{:?}", theirs.key_type),
+    );
+    report_line(
+        &mut report,
+        "Format:",
+        format!("{:?}", ours.format),
+        format!("{:?}", theirs.format),
+    );
+    report_line(
+        &mut report,
+        "Encoding:",
+        format!("{:?}", ours.encoding),
+        format!("{:?}", theirs.encoding),
+    );
+    if let (Some(our_length), Some(their_length)) = (ours.key_length, theirs.key_length) {
+        equivalent &= report_line(
+            &mut report,
+            "Key Length:",
+            our_length.to_string(),
+            their_length.to_string(),
+        );
+    }
+    if ours.alg == Alg::Ecdsa && theirs.alg == Alg::Ecdsa {
+        let curve =
+            |key_info: &KeyInfo| key_info.curve().map(|c| c.to_string()).unwrap_or_default();
+        equivalent &= report_line(&mut report, "Curve:", curve(&ours), curve(&theirs));
+    }
+
+    if equivalent {
+        let (our_public, their_public) = (public_material(&ours)?, public_material(&theirs)?);
+        if our_public.is_some() && their_public.is_some() {
+            equivalent &= report_line(
+                &mut report,
+                "Public Key:",
+                digest(&our_public),
+                digest(&their_public),
+            );
+        }
+        let (our_private, their_private) = (private_material(&ours)?, private_material(&theirs)?);
+        if our_private.is_some() && their_private.is_some() {
+            let same = our_private == their_private;
+            report.push_str(&format!(
+                "{:<12} {}\n",
+                "Private Key:",
+                if same { "match" } else { "differs" }
+            ));
+            equivalent &= same;
+        }
+    }
+
+    report.push_str(&format!(
+        "Result:      {}\n",
+        if equivalent {
+            "equivalent"
+        } else {
+            "different"
+        }
+    ));
+    app_state.write_stream(report.as_bytes())?;
+    if !equivalent {
+        bail!(Error::KeyMismatch);
+    }
+    Ok(())
+}
+
+/// Identify key material in the report by a short SHA-256 prefix, so that it
+/// can be compared at a glance without printing the key
+fn digest(material: &Option<Vec<u8>>) -> String {
+    match material {
+        Some(material) => format!("sha256:{}", hex::encode(&Sha256::digest(material)[..8])),
+        None => "none".to_owned(),
+    }
+}
@@ -0,0 +1,334 @@
+//! Compatibility with the software that will consume a converted key
+//!
+//! Each [TargetConsumer] has known quirks, such as Java only reading DER, or
+//! older OpenSSH rejecting PKCS8 EC keys.  When a target consumer is given,
+//! kt picks an output format that the consumer accepts if none was requested,
+//! and warns about any output the consumer is known to reject.
+//!
+//! Warnings are written to stderr, one per line, as
+//! `warning: [<consumer>/<code>] <message>`, so that scripts can check for them.
+//!
+//! ```rust
+//! use kt::app_state::AppState;
+//! use kt::consumer::{check, TargetConsumer};
+//! use kt::key_info::{Alg, Encoding, Format, KeyInfo, KeyType};
+//!
+//! // OpenSSH 8 reads an Ed25519 key written as an OpenSSH key as it is
+//! let key_info = KeyInfo::new()
+//!     .with_alg(Alg::EdDsa25519)
+//!     .with_key_type(KeyType::Private)
+//!     .with_encoding(Encoding::OPENSSH)
+//!     .with_format(Format::OPENSSH);
+//! let mut app_state = AppState {
+//!     target_consumer: Some(TargetConsumer::OpenSsh8),
+//!     encoding: Encoding::OPENSSH,
+//!     format: Some(Format::OPENSSH),
+//!     ..Default::default()
+//! };
+//! assert!(check(&app_state, &key_info).is_empty());
+//!
+//! // and its public key, written as an OpenSSH public key
+//! app_state.key_type = Some(KeyType::Public);
+//! assert!(check(&app_state, &key_info).is_empty());
+//!
+//! // but not the same key written as PKCS8 PEM
+//! app_state.key_type = None;
+//! app_state.encoding = Encoding::PEM;
+//! app_state.format = Some(Format::PKCS8);
+//! let codes: Vec<&str> = check(&app_state, &key_info).iter().map(|warning| warning.code).collect();
+//! assert_eq!(codes, vec!["openssh-format-required"]);
+//!
+//! // A P-384 key written as an OpenSSH key is read, whatever its input format
+//! let key_info = KeyInfo::new()
+//!     .with_alg(Alg::Ecdsa)
+//!     .with_key_type(KeyType::Private)
+//!     .with_params(&[0x06, 0x05, 0x2b, 0x81, 0x04, 0x00, 0x22]);
+//! app_state.encoding = Encoding::OPENSSH;
+//! assert!(check(&app_state, &key_info).is_empty());
+//!
+//! // but X25519 keys can't be used at all
+//! let key_info = KeyInfo::new()
+//!     .with_alg(Alg::X25519)
+//!     .with_key_type(KeyType::Private);
+//! let codes: Vec<&str> = check(&app_state, &key_info).iter().map(|warning| warning.code).collect();
+//! assert_eq!(codes, vec!["alg-unsupported"]);
+//! ```
+use anyhow::Result;
+use log::info;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::oids;
+use crate::pbe::Kdf;
+
+/// Software that a converted key is intended for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TargetConsumer {
+    /// OpenSSL 1.1.x
+    OpenSsl11,
+    /// The Go standard library crypto/x509 package
+    Go,
+    /// Java 11 KeyFactory
+    Java11,
+    /// Windows Cryptography API: Next Generation
+    WindowsCng,
+    /// OpenSSH 8.x
+    OpenSsh8,
+}
+
+impl TargetConsumer {
+    pub fn all() -> Vec<&'static str> {
+        vec!["openssl1.1", "go", "java11", "windows-cng", "openssh8"]
+    }
+
+    /// The format this consumer prefers for the output key type
+    fn preferred_format(&self, alg: Alg, key_type: KeyType) -> Option<Format> {
+        match (self, alg, key_type) {
+            (_, _, KeyType::Secret) => None,
+            (TargetConsumer::OpenSsh8, Alg::Rsa, KeyType::Private) => Some(Format::PKCS1),
+            (TargetConsumer::OpenSsh8, Alg::Ecdsa, KeyType::Private) => Some(Format::SEC1),
+            (_, _, KeyType::Public) => Some(Format::SPKI),
+            _ => Some(Format::PKCS8),
+        }
+    }
+}
+
+impl FromStr for TargetConsumer {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<TargetConsumer> {
+        match s.to_lowercase().as_str() {
+            "openssl1.1" => Ok(TargetConsumer::OpenSsl11),
+            "go" => Ok(TargetConsumer::Go),
+            "java11" => Ok(TargetConsumer::Java11),
+            "windows-cng" => Ok(TargetConsumer::WindowsCng),
+            "openssh8" => Ok(TargetConsumer::OpenSsh8),
+            _ => Err(Error::UnknownConsumer.into()),
+        }
+    }
+}
+
+impl fmt::Display for TargetConsumer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txt = match self {
+            TargetConsumer::OpenSsl11 => "openssl1.1",
+            TargetConsumer::Go => "go",
+            TargetConsumer::Java11 => "java11",
+            TargetConsumer::WindowsCng => "windows-cng",
+            TargetConsumer::OpenSsh8 => "openssh8",
+        };
+        write!(f, "{}", txt)
+    }
+}
+
+/// An output that a consumer is known to reject
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConsumerWarning {
+    pub consumer: TargetConsumer,
+    /// Stable identifier of the quirk, such as `pem-unsupported`
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for ConsumerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}/{}] {}", self.consumer, self.code, self.message)
+    }
+}
+
+/// The key type that will be written
+fn output_key_type(app_state: &AppState, key_info: &KeyInfo) -> KeyType {
+    match app_state.key_type {
+        Some(KeyType::Public) => KeyType::Public,
+        _ => key_info.key_type,
+    }
+}
+
+fn is_okp(alg: Alg) -> bool {
+    matches!(
+        alg,
+        Alg::X25519
+            | Alg::X448
+            | Alg::EdDsa25519
+            | Alg::EdDsa448
+            | Alg::EdDsa25519Ph
+            | Alg::EdDsa448Ph
+    )
+}
+
+fn is_pss(key_info: &KeyInfo) -> bool {
+    key_info.alg == Alg::RsaSsaPss || key_info.oid == Some(oids::RSASSA_PSS)
+}
+
+/// Choose an output format the consumer accepts, if none was requested
+pub fn apply_quirks(app_state: &mut AppState, key_info: &KeyInfo) {
+    let consumer = match app_state.target_consumer {
+        Some(consumer) => consumer,
+        None => return,
+    };
+    if app_state.format.is_none() {
+        let key_type = output_key_type(app_state, key_info);
+        app_state.format = consumer.preferred_format(key_info.alg, key_type);
+        if let Some(format) = app_state.format {
+            info!("Using {:?} for {}", format, consumer);
+        }
+    }
+}
+
+/// List the known problems the consumer will have with the requested output
+pub fn check(app_state: &AppState, key_info: &KeyInfo) -> Vec<ConsumerWarning> {
+    let consumer = match app_state.target_consumer {
+        Some(consumer) => consumer,
+        None => return Vec::new(),
+    };
+    let key_type = output_key_type(app_state, key_info);
+    let alg = key_info.alg;
+    let format = app_state.format;
+    let encoding = app_state.encoding;
+    let encrypted = app_state.out_password.is_some();
+
+    let mut warnings = Vec::new();
+    let mut warn = |code: &'static str, message: &str| {
+        warnings.push(ConsumerWarning {
+            consumer,
+            code,
+            message: message.to_owned(),
+        })
+    };
+
+    match encoding {
+        Encoding::JWK => warn("jwk-unsupported", "JWK keys cannot be read natively"),
+        Encoding::RAW => warn("raw-unsupported", "raw keys cannot be read natively"),
+        Encoding::COSE => warn("cose-unsupported", "COSE_Key keys cannot be read natively"),
+        Encoding::OPENSSH if consumer != TargetConsumer::OpenSsh8 => {
+            warn("openssh-unsupported", "OpenSSH keys cannot be read natively")
+        }
+        Encoding::PPK => warn("ppk-unsupported", "PuTTY keys cannot be read natively"),
+        Encoding::PEM_JSON => warn(
+            "pem-json-unsupported",
+            "the PEM must be taken out of the JSON envelope",
+        ),
+        Encoding::B64 | Encoding::B64URL => {
+            warn("base64-unsupported", "the DER must be decoded from base64")
+        }
+        Encoding::HEX | Encoding::RAW_HEX => {
+            warn("hex-unsupported", "the key must be decoded from hex")
+        }
+        Encoding::C_ARRAY | Encoding::RUST_ARRAY => {
+            warn("array-unsupported", "source arrays must be compiled in")
+        }
+        _ => {}
+    }
+
+    match consumer {
+        TargetConsumer::OpenSsl11 => {}
+        TargetConsumer::Go => {
+            if is_pss(key_info) {
+                warn(
+                    "rsassa-pss-unsupported",
+                    "crypto/x509 does not parse RSASSA-PSS keys",
+                );
+            }
+            if matches!(alg, Alg::X448 | Alg::EdDsa448 | Alg::EdDsa448Ph) {
+                warn("alg-unsupported", "X448 and Ed448 are not supported");
+            }
+            if encrypted {
+                warn(
+                    "encrypted-unsupported",
+                    "encrypted PKCS8 cannot be decrypted by crypto/x509",
+                );
+            }
+        }
+        TargetConsumer::Java11 => {
+            if matches!(format, Some(Format::PKCS1 | Format::SEC1)) {
+                warn(
+                    "format-unsupported",
+                    "KeyFactory only reads PKCS8 and SPKI keys",
+                );
+            }
+            if encoding == Encoding::PEM {
+                warn("pem-unsupported", "KeyFactory only reads DER");
+            }
+            if matches!(
+                alg,
+                Alg::EdDsa25519 | Alg::EdDsa448 | Alg::EdDsa25519Ph | Alg::EdDsa448Ph
+            ) {
+                warn("alg-unsupported", "EdDSA requires Java 15");
+            }
+            if is_pss(key_info) && key_info.params.is_none() {
+                warn(
+                    "rsassa-pss-missing-params",
+                    "RSASSA-PSS keys without parameters are rejected",
+                );
+            }
+            if encrypted && app_state.pbe.kdf == Kdf::Scrypt {
+                warn(
+                    "scrypt-unsupported",
+                    "EncryptedPrivateKeyInfo does not decrypt scrypt keys",
+                );
+            }
+        }
+        TargetConsumer::WindowsCng => {
+            if is_okp(alg) {
+                warn(
+                    "alg-unsupported",
+                    "X25519, X448, Ed25519 and Ed448 keys cannot be imported",
+                );
+            }
+            if is_pss(key_info) {
+                warn(
+                    "rsassa-pss-unsupported",
+                    "RSASSA-PSS keys cannot be imported",
+                );
+            }
+            if encoding == Encoding::PEM {
+                warn("pem-unsupported", "key import only accepts DER");
+            }
+        }
+        TargetConsumer::OpenSsh8 => {
+            if encoding == Encoding::DER {
+                warn("der-unsupported", "only PEM keys can be read");
+            }
+            if is_pss(key_info) {
+                warn(
+                    "rsassa-pss-unsupported",
+                    "RSASSA-PSS keys are not supported",
+                );
+            }
+            if matches!(alg, Alg::X25519 | Alg::X448 | Alg::EdDsa448 | Alg::EdDsa448Ph)
+                || matches!(key_info.curve(), Some(Curve::Secp256k1 | Curve::Sm2))
+            {
+                warn(
+                    "alg-unsupported",
+                    "X25519, X448, Ed448, secp256k1 and SM2 keys are not supported",
+                );
+            }
+            if key_type == KeyType::Private
+                && alg == Alg::Ecdsa
+                && format == Some(Format::PKCS8)
+                && encoding != Encoding::OPENSSH
+            {
+                warn("pkcs8-ec-unsupported", "EC private keys must be SEC1");
+            }
+            if key_type == KeyType::Private
+                && matches!(alg, Alg::EdDsa25519 | Alg::EdDsa25519Ph)
+                && encoding != Encoding::OPENSSH
+            {
+                warn(
+                    "openssh-format-required",
+                    "Ed25519 private keys must use the openssh-key-v1 format",
+                );
+            }
+            if key_type == KeyType::Public && encoding != Encoding::OPENSSH {
+                warn(
+                    "public-key-import",
+                    "public keys must be imported with ssh-keygen -i -m PKCS8",
+                );
+            }
+        }
+    }
+    warnings
+}
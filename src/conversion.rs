@@ -5,23 +5,58 @@
 //! is determined by the [discover](crate::discover) functionality.
 use anyhow::Result;
 use log::{debug, info, trace};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use zeroize::Zeroizing;
 
+use crate::alg_id::{rsa_pss_params, PssHash, RsaPssParams};
 use crate::app_state::AppState;
+use crate::compare::public_material;
+use crate::consumer;
 use crate::document::{
-    pkcs1_docs::{rsa_private_key_to_pk1, rsa_public_key_to_pk1},
+    cng_docs::key_info_to_cng,
+    composite_docs::component_key_info,
+    dh_docs::dh_parameters_to_stream,
+    cose_docs::key_info_to_cose,
+    jwk_docs::key_info_to_jwk,
+    openpgp_docs::key_info_to_openpgp,
+    openssh_docs::key_info_to_openssh,
+    ppk_docs::key_info_to_ppk,
+    raw_docs::key_info_to_raw,
+    pem_json_docs::PemJson,
+    pkcs12_docs::key_info_to_pkcs12,
+    pkcs1_docs::{rsa_private_key_to_pk1, rsa_public_key_info, rsa_public_key_to_pk1},
     pkcs8_docs::private_key_info_to_pk8,
-    sec1_docs::private_key_info_to_sec1,
+    sec1_docs::{ec_public_key, ec_public_key_to_sec1, private_key_info_to_sec1, with_point_format},
+    secret_docs::secret_key_to_raw,
     spki_docs::key_info_to_spki,
+    tink_docs::key_info_to_tink,
+    xml_docs::key_info_to_xml,
 };
 use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::hex_text;
+use crate::oids;
 use crate::key_info::KeyInfo;
-use crate::key_info::{Alg, Format, KeyType};
+use crate::key_info::{Alg, Encoding, Format, KeyType};
 
+// The public key of an RSA private key is written as PKCS1 if asked for, and
+// otherwise as SPKI, as openssl -pubout does
 fn convert_rsa_private(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
     let format = app_state.format.ok_or(Error::MissingFormat)?;
+    if format == Format::SPKI || app_state.key_type == Some(KeyType::Public) {
+        let public_info = rsa_public_key_info(key_info)?;
+        return match format {
+            Format::PKCS1 => rsa_public_key_to_pk1(app_state, &public_info),
+            _ => key_info_to_spki(app_state, &public_info),
+        };
+    }
     match format {
         Format::PKCS1 => Ok(rsa_private_key_to_pk1(app_state, key_info)?),
-        Format::PKCS8 => Ok(private_key_info_to_pk8(app_state, key_info)?),
+        Format::PKCS8 | Format::PKCS12 | Format::OPENPGP | Format::TINK | Format::CNG | Format::XML => {
+            Ok(private_key_info_to_pk8(app_state, key_info)?)
+        }
         _ => {
             trace!("Unsupported format: {:?}", format);
             Err(Error::NotSupported.into())
@@ -33,7 +68,18 @@ fn convert_rsa_public(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()
     let format = app_state.format.ok_or(Error::MissingFormat)?;
     match format {
         Format::PKCS1 => Ok(rsa_public_key_to_pk1(app_state, key_info)?),
-        Format::PKCS8 | Format::SPKI => Ok(key_info_to_spki(app_state, key_info)?),
+        Format::PKCS8
+        | Format::SPKI
+        | Format::X509
+        | Format::PKCS10
+        | Format::PKCS12
+        | Format::PKCS7
+        | Format::OPENPGP
+        | Format::TINK
+        | Format::CNG
+        | Format::XML => {
+            Ok(key_info_to_spki(app_state, key_info)?)
+        }
         _ => {
             trace!("Unsupported format: {:?}", format);
             Err(Error::NotSupported.into())
@@ -41,6 +87,76 @@ fn convert_rsa_public(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()
     }
 }
 
+fn convert_ec_public(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let format = app_state.format.ok_or(Error::MissingFormat)?;
+    match format {
+        Format::SEC1 => Ok(ec_public_key_to_sec1(app_state, key_info)?),
+        Format::PKCS8
+        | Format::SPKI
+        | Format::X509
+        | Format::PKCS10
+        | Format::PKCS12
+        | Format::PKCS7
+        | Format::OPENPGP
+        | Format::TINK
+        | Format::ETHEREUM
+        | Format::CNG
+        | Format::XML => {
+            Ok(key_info_to_spki(app_state, key_info)?)
+        }
+        _ => {
+            trace!("Unsupported format: {:?}", format);
+            Err(Error::NotSupported.into())
+        }
+    }
+}
+
+/// The public KeyInfo of a private key, with the bytes of the public key of
+/// an SPKI document
+fn public_key_info(key_info: &KeyInfo) -> Result<KeyInfo> {
+    let public_key = public_material(key_info)?
+        .ok_or_else(|| Error::MissingInput("public key".to_owned()))?;
+    Ok(key_info
+        .clone()
+        .with_key_type(KeyType::Public)
+        .with_bytes(&public_key))
+}
+
+// EC private keys are written as SEC1, unless PKCS8 is asked for.  The public
+// key, derived from the private key, is written as SPKI, as SEC1 has no
+// document of a public key.
+fn convert_ec_private(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    if app_state.format == Some(Format::SPKI) || app_state.key_type == Some(KeyType::Public) {
+        let public_info = key_info
+            .clone()
+            .with_key_type(KeyType::Public)
+            .with_bytes(&ec_public_key(key_info, app_state.point_format)?);
+        return key_info_to_spki(app_state, &public_info);
+    }
+    match app_state.format {
+        Some(Format::PKCS8 | Format::PKCS12 | Format::OPENPGP | Format::TINK) => {
+            Ok(private_key_info_to_pk8(app_state, key_info)?)
+        }
+        _ => Ok(private_key_info_to_sec1(app_state, key_info)?),
+    }
+}
+
+// OKP, DSA, DH, ML-DSA and composite private keys are written as PKCS8, as kt writes no
+// other form of them, so other formats, such as that of a JWK input, become
+// PKCS8.  The public key, derived from the private key, is written as SPKI.
+fn convert_pkcs8_private(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    if app_state.format == Some(Format::SPKI) || app_state.key_type == Some(KeyType::Public) {
+        return key_info_to_spki(app_state, &public_key_info(key_info)?);
+    }
+    match app_state.format {
+        Some(format @ (Format::PKCS1 | Format::SEC1)) => {
+            trace!("Unsupported format: {:?}", format);
+            Err(Error::NotSupported.into())
+        }
+        _ => Ok(private_key_info_to_pk8(app_state, key_info)?),
+    }
+}
+
 // Make sure the type of key provided can be converted to the type of key
 // requested
 fn verify_key_types(ki_type: KeyType, as_type: KeyType) -> Result<()> {
@@ -48,6 +164,14 @@ fn verify_key_types(ki_type: KeyType, as_type: KeyType) -> Result<()> {
         info!("Cannot convert from public key to private key");
         return Err(Error::TypeMismatch.into());
     }
+    if ki_type == KeyType::Parameters && !matches!(as_type, KeyType::Unknown | KeyType::Parameters) {
+        info!("Cannot convert parameters to a key");
+        return Err(Error::TypeMismatch.into());
+    }
+    if (ki_type == KeyType::Secret) != (as_type == KeyType::Secret) {
+        info!("Cannot convert between secret keys and asymmetric keys");
+        return Err(Error::TypeMismatch.into());
+    }
     Ok(())
 }
 
@@ -64,14 +188,143 @@ fn safe_to_convert<'a>(
     Ok((app_state, key_info))
 }
 
+/// An output stream that can be read back once written
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Convert to PEM, and write the PEM in a JSON envelope
+fn convert_pem_json(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let buffer = SharedBuffer::default();
+    let out_stream = std::mem::replace(&mut app_state.out_stream, Box::new(buffer.clone()));
+    app_state.encoding = Encoding::PEM;
+    let result = convert_key((&mut *app_state, key_info));
+    app_state.out_stream = out_stream;
+    app_state.encoding = Encoding::PEM_JSON;
+    result?;
+
+    let pem = Zeroizing::new(buffer.0.take());
+    let pem = std::str::from_utf8(&pem).map_err(|e| Error::BadPem(e.to_string()))?;
+    let envelope = PemJson::new(key_info.alg, pem.to_owned());
+    let json = Zeroizing::new(envelope.to_json(app_state.pretty)?);
+    app_state.write_stream(json.as_bytes())
+}
+
+/// Convert to DER, or to raw key material, and write the bytes as text: a
+/// single line of base64, hex, a C or Rust array, or PEM with the label the
+/// user gave
+fn convert_text(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let encoding = app_state.encoding;
+    let buffer = SharedBuffer::default();
+    let out_stream = std::mem::replace(&mut app_state.out_stream, Box::new(buffer.clone()));
+    app_state.encoding = match encoding {
+        Encoding::RAW_HEX => Encoding::RAW,
+        _ => Encoding::DER,
+    };
+    let result = convert_key((&mut *app_state, key_info));
+    app_state.out_stream = out_stream;
+    app_state.encoding = encoding;
+    result?;
+
+    let bytes = Zeroizing::new(buffer.0.take());
+    if bytes.is_empty() {
+        return Err(Error::NotSupported.into());
+    }
+    let text = Zeroizing::new(match encoding {
+        Encoding::HEX | Encoding::RAW_HEX => format!("{}\n", hex_text::encode(&bytes, app_state.hex_style)),
+        Encoding::PEM => {
+            let label = app_state.pem_label.as_deref().ok_or(Error::MissingEncoding)?;
+            crate::pem::wrap(label, &bytes)?
+        }
+        Encoding::C_ARRAY => hex_text::c_array(&bytes),
+        Encoding::RUST_ARRAY => hex_text::rust_array(&bytes),
+        _ => crate::pem::to_base64_line(&bytes, encoding)?,
+    });
+    app_state.write_stream(text.as_bytes())
+}
+
 fn convert_key(params: (&mut AppState, &KeyInfo)) -> Result<()> {
     let app_state = params.0;
     let key_info = params.1;
+    if app_state.encoding == Encoding::JWK {
+        return key_info_to_jwk(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::PEM_JSON {
+        return convert_pem_json(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::COSE {
+        return key_info_to_cose(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::OPENSSH {
+        return key_info_to_openssh(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::PPK {
+        return key_info_to_ppk(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::P12 {
+        return key_info_to_pkcs12(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::OPENPGP {
+        return key_info_to_openpgp(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::TINK {
+        return key_info_to_tink(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::CNG {
+        return key_info_to_cng(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::XML {
+        return key_info_to_xml(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::RAW {
+        return key_info_to_raw(app_state, key_info);
+    }
+    if matches!(
+        app_state.encoding,
+        Encoding::B64
+            | Encoding::B64URL
+            | Encoding::HEX
+            | Encoding::RAW_HEX
+            | Encoding::C_ARRAY
+            | Encoding::RUST_ARRAY
+    ) {
+        return convert_text(app_state, key_info);
+    }
+    if app_state.encoding == Encoding::PEM && app_state.pem_label.is_some() {
+        return convert_text(app_state, key_info);
+    }
     match (key_info.alg, key_info.key_type) {
         (Alg::Rsa | Alg::RsaSsaPss, KeyType::Private) => convert_rsa_private(app_state, key_info),
         (Alg::Rsa | Alg::RsaSsaPss, KeyType::Public) => convert_rsa_public(app_state, key_info),
-        (Alg::EdDsa25519 | Alg::Ecdsa, KeyType::Private) => private_key_info_to_sec1(app_state, key_info),
-        (Alg::EdDsa25519 | Alg::Ecdsa, KeyType::Public) => key_info_to_spki(app_state, key_info),
+        (Alg::Ecdsa, KeyType::Private) => convert_ec_private(app_state, key_info),
+        (Alg::Ecdsa, KeyType::Public) => convert_ec_public(app_state, key_info),
+        (Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448, KeyType::Private) => {
+            convert_pkcs8_private(app_state, key_info)
+        }
+        (Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448, KeyType::Public) => {
+            key_info_to_spki(app_state, key_info)
+        }
+        (Alg::Dsa | Alg::Dh, KeyType::Private) => convert_pkcs8_private(app_state, key_info),
+        (Alg::Dsa | Alg::Dh, KeyType::Public) => key_info_to_spki(app_state, key_info),
+        (Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87, KeyType::Private) => {
+            convert_pkcs8_private(app_state, key_info)
+        }
+        (Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87, KeyType::Public) => {
+            key_info_to_spki(app_state, key_info)
+        }
+        (Alg::Composite, KeyType::Private) => convert_pkcs8_private(app_state, key_info),
+        (Alg::Composite, KeyType::Public) => key_info_to_spki(app_state, key_info),
+        (Alg::Dh, KeyType::Parameters) => dh_parameters_to_stream(app_state, key_info),
+        (Alg::Symmetric, KeyType::Secret) => secret_key_to_raw(app_state, key_info),
 
         (a, b) => {
             debug!("{:?} - {:?}", &a, &b);
@@ -80,6 +333,20 @@ fn convert_key(params: (&mut AppState, &KeyInfo)) -> Result<()> {
     }
 }
 
+/// The RSA key as an id-RSASSA-PSS key, restricted by the RSASSA-PSS-params
+/// of `--pss-hash` and `--pss-salt-len`.  Without them, a PSS key keeps its
+/// own parameters, and any other key is restricted to SHA-256.
+fn rsa_pss_key_info(app_state: &AppState, key_info: &KeyInfo) -> Result<KeyInfo> {
+    let params = match (app_state.pss_hash, app_state.pss_salt_len, rsa_pss_params(key_info)) {
+        (None, None, Some(params)) => params.to_vec(),
+        (hash, salt_len, _) => RsaPssParams::new(hash.unwrap_or(PssHash::Sha256), salt_len).to_der()?,
+    };
+    let mut pss_info = key_info.clone();
+    pss_info.set_oid(&oids::RSASSA_PSS);
+    pss_info.params = Some(params);
+    Ok(pss_info)
+}
+
 /// Consume the AppState to convert the input file.
 /// 
 /// This is the main engine of the app. It processes the AppState to queue up
@@ -91,5 +358,38 @@ fn convert_key(params: (&mut AppState, &KeyInfo)) -> Result<()> {
 /// * `app_state` - The target output state  
 /// * `key_info` - The interpreted input file
 pub fn convert(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let component_info;
+    let key_info = match app_state.component {
+        Some(component) => {
+            component_info = component_key_info(key_info, component)?;
+            app_state.alg = Some(component_info.alg);
+            &component_info
+        }
+        None => key_info,
+    };
+    let pss_info;
+    let key_info = match (app_state.alg, key_info.alg) {
+        (Some(Alg::RsaSsaPss), Alg::Rsa | Alg::RsaSsaPss) => {
+            pss_info = rsa_pss_key_info(app_state, key_info)?;
+            &pss_info
+        }
+        _ => key_info,
+    };
+    let point_info;
+    let key_info = match (app_state.point_format, key_info.alg) {
+        (Some(point_format), Alg::Ecdsa) => {
+            point_info = with_point_format(key_info, point_format)?;
+            &point_info
+        }
+        _ => key_info,
+    };
+    consumer::apply_quirks(app_state, key_info);
+    for warning in consumer::check(app_state, key_info) {
+        events::emit(
+            Event::warning(warning.code, &warning.message)
+                .with("consumer", warning.consumer.to_string())
+                .with_text(&format!("warning: {}", warning)),
+        );
+    }
     safe_to_convert(app_state, key_info).and_then(convert_key)
 }
@@ -0,0 +1,370 @@
+//! PKCS#10 certificate signing requests ([RFC 2986](https://www.rfc-editor.org/rfc/rfc2986))
+//!
+//! A CSR is built from a private key, a subject, and optional subject
+//! alternative names, and signed with the scheme the key naturally produces:
+//! PKCS1 v1.5 with SHA-256 for RSA keys, PSS for RSASSA-PSS keys, ECDSA with
+//! the hash that matches the curve, and Ed25519.  SM2 and Ed448 keys are
+//! rejected, as kt does not sign with them.
+//!
+//! The subject is written either as `openssl req -subj` does, such as
+//! `/C=US/O=Example/CN=www.example.com`, or as a comma separated list, such as
+//! `CN=www.example.com,O=Example,C=US`.  Either way the attributes are kept in
+//! the order given.  A `/`, `,` or `\` in a value is escaped with `\`.
+//!
+//! Subject alternative names are `DNS:`, `IP:`, `email:` or `URI:` and a
+//! value.  A bare value is an IP address if it parses as one, and a DNS name
+//! otherwise.  The names are requested with an extensionRequest attribute.
+//!
+//! ```rust
+//! use kt::csr::parse_subject;
+//!
+//! let subject = parse_subject("/C=US/O=Example\\, Inc./CN=www.example.com").unwrap();
+//! assert_eq!(subject.len(), 3);
+//! assert_eq!(subject[1].1, "Example, Inc.");
+//! ```
+use anyhow::{bail, Result};
+use der::asn1::{Any, Ia5String, ObjectIdentifier, PrintableString, UIntBytes, Utf8String};
+use der::{Encodable, Tag, TagNumber};
+use std::net::IpAddr;
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::document::spki_docs::public_spki_der;
+use crate::errors::Error;
+use crate::key_info::{Encoding, KeyInfo, KeyType};
+use crate::oids;
+use crate::pem;
+use crate::signature::{sign, SignatureScheme};
+
+/// X.520 countryName, a PrintableString
+const COUNTRY_NAME: ObjectIdentifier = ObjectIdentifier::new("2.5.4.6");
+/// X.520 serialNumber, a PrintableString
+const SERIAL_NUMBER: ObjectIdentifier = ObjectIdentifier::new("2.5.4.5");
+/// PKCS#9 emailAddress, an IA5String
+const EMAIL_ADDRESS: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.9.1");
+/// RFC 4519 domainComponent, an IA5String
+const DOMAIN_COMPONENT: ObjectIdentifier = ObjectIdentifier::new("0.9.2342.19200300.100.1.25");
+/// PKCS#9 extensionRequest
+const EXTENSION_REQUEST: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.9.14");
+/// X.509 subjectAltName extension
+pub(crate) const SUBJECT_ALT_NAME: ObjectIdentifier = ObjectIdentifier::new("2.5.29.17");
+
+/// Short names of the subject attributes, as openssl writes them
+const SUBJECT_ATTRIBUTES: [(&str, ObjectIdentifier); 9] = [
+    ("CN", ObjectIdentifier::new("2.5.4.3")),
+    ("serialNumber", SERIAL_NUMBER),
+    ("C", COUNTRY_NAME),
+    ("L", ObjectIdentifier::new("2.5.4.7")),
+    ("ST", ObjectIdentifier::new("2.5.4.8")),
+    ("O", ObjectIdentifier::new("2.5.4.10")),
+    ("OU", ObjectIdentifier::new("2.5.4.11")),
+    ("emailAddress", EMAIL_ADDRESS),
+    ("DC", DOMAIN_COMPONENT),
+];
+
+/// The short name of a subject attribute, such as `CN`, or the OID
+pub(crate) fn attribute_name(oid: ObjectIdentifier) -> String {
+    match SUBJECT_ATTRIBUTES.iter().find(|(_, known)| *known == oid) {
+        Some((short, _)) => (*short).to_owned(),
+        None => oid.to_string(),
+    }
+}
+
+/// A subject alternative name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubjectAltName {
+    Dns(String),
+    Ip(IpAddr),
+    Email(String),
+    Uri(String),
+}
+
+impl SubjectAltName {
+    /// The GeneralName of the name.  Each is an IMPLICIT context specific tag
+    fn to_der(&self) -> Result<Vec<u8>> {
+        let (number, value) = match self {
+            SubjectAltName::Email(email) => (TagNumber::N1, email.as_bytes().to_vec()),
+            SubjectAltName::Dns(name) => (TagNumber::N2, name.as_bytes().to_vec()),
+            SubjectAltName::Uri(uri) => (TagNumber::N6, uri.as_bytes().to_vec()),
+            SubjectAltName::Ip(IpAddr::V4(ip)) => (TagNumber::N7, Vec::from(ip.octets())),
+            SubjectAltName::Ip(IpAddr::V6(ip)) => (TagNumber::N7, Vec::from(ip.octets())),
+        };
+        tlv(
+            Tag::ContextSpecific {
+                constructed: false,
+                number,
+            },
+            &value,
+        )
+    }
+}
+
+/// Split the text at the unescaped separators, and remove the escapes
+fn split_escaped(text: &str, separator: char) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                if let (Some(escaped), Some(part)) = (chars.next(), parts.last_mut()) {
+                    part.push(escaped);
+                }
+            }
+            c if c == separator => parts.push(String::new()),
+            c => {
+                if let Some(part) = parts.last_mut() {
+                    part.push(c)
+                }
+            }
+        }
+    }
+    parts
+}
+
+/// Parse a subject into its attributes, in order.
+///
+/// Attributes are named by their short name, such as `CN`, or by a dotted
+/// OID.
+pub fn parse_subject(subject: &str) -> Result<Vec<(ObjectIdentifier, String)>> {
+    let subject = subject.trim();
+    let parts = match subject.strip_prefix('/') {
+        Some(rest) => split_escaped(rest, '/'),
+        None => split_escaped(subject, ','),
+    };
+    let mut attributes = Vec::new();
+    for part in parts.iter().filter(|part| !part.trim().is_empty()) {
+        let (name, value) = match part.split_once('=') {
+            Some((name, value)) => (name.trim(), value.trim()),
+            None => bail!(Error::BadSubject(format!("no value for {}", part.trim()))),
+        };
+        if value.is_empty() {
+            bail!(Error::BadSubject(format!("no value for {}", name)));
+        }
+        let oid = match SUBJECT_ATTRIBUTES
+            .iter()
+            .find(|(short, _)| short.eq_ignore_ascii_case(name))
+        {
+            Some((_, oid)) => *oid,
+            None => name
+                .parse::<ObjectIdentifier>()
+                .map_err(|_| Error::BadSubject(format!("unknown attribute {}", name)))?,
+        };
+        attributes.push((oid, value.to_owned()));
+    }
+    Ok(attributes)
+}
+
+/// Parse a subject alternative name, such as `DNS:www.example.com`
+pub fn parse_san(san: &str) -> Result<SubjectAltName> {
+    let san = san.trim();
+    let name = match san.split_once(':') {
+        Some((kind, value)) if kind.eq_ignore_ascii_case("DNS") => {
+            SubjectAltName::Dns(value.to_owned())
+        }
+        Some((kind, value)) if kind.eq_ignore_ascii_case("IP") => SubjectAltName::Ip(
+            value
+                .parse()
+                .map_err(|_| Error::BadSubjectAltName(format!("bad IP address {}", value)))?,
+        ),
+        Some((kind, value)) if kind.eq_ignore_ascii_case("email") => {
+            SubjectAltName::Email(value.to_owned())
+        }
+        Some((kind, value)) if kind.eq_ignore_ascii_case("URI") => {
+            SubjectAltName::Uri(value.to_owned())
+        }
+        _ => match san.parse::<IpAddr>() {
+            Ok(ip) => SubjectAltName::Ip(ip),
+            Err(_) if san.contains(':') => {
+                bail!(Error::BadSubjectAltName(format!("unknown name {}", san)))
+            }
+            Err(_) => SubjectAltName::Dns(san.to_owned()),
+        },
+    };
+    match &name {
+        SubjectAltName::Dns(value) | SubjectAltName::Email(value) | SubjectAltName::Uri(value)
+            if value.is_empty() || !value.is_ascii() =>
+        {
+            bail!(Error::BadSubjectAltName(format!("bad name {}", san)))
+        }
+        _ => Ok(name),
+    }
+}
+
+/// Encode the content with the tag
+pub(crate) fn tlv(tag: Tag, content: &[u8]) -> Result<Vec<u8>> {
+    Ok(Any::new(tag, content)?.to_vec()?)
+}
+
+/// The DER Name of the subject, one attribute to each RelativeDistinguishedName
+pub(crate) fn name_der(attributes: &[(ObjectIdentifier, String)]) -> Result<Vec<u8>> {
+    let mut rdns = Vec::new();
+    for (oid, value) in attributes {
+        let value = match *oid {
+            COUNTRY_NAME | SERIAL_NUMBER => PrintableString::new(value)
+                .and_then(|value| value.to_vec())
+                .map_err(|_| Error::BadSubject(format!("{} is not printable", value)))?,
+            EMAIL_ADDRESS | DOMAIN_COMPONENT => Ia5String::new(value)
+                .and_then(|value| value.to_vec())
+                .map_err(|_| Error::BadSubject(format!("{} is not ASCII", value)))?,
+            _ => Utf8String::new(value)?.to_vec()?,
+        };
+        let attribute = tlv(Tag::Sequence, &[oid.to_vec()?, value].concat())?;
+        rdns.extend(tlv(Tag::Set, &attribute)?);
+    }
+    tlv(Tag::Sequence, &rdns)
+}
+
+/// The GeneralNames of the subject alternative names
+pub(crate) fn general_names_der(sans: &[SubjectAltName]) -> Result<Vec<u8>> {
+    let mut names = Vec::new();
+    for san in sans {
+        names.extend(san.to_der()?);
+    }
+    tlv(Tag::Sequence, &names)
+}
+
+/// An X.509 Extension.  critical is DEFAULT FALSE, so it is only written when
+/// it is true
+pub(crate) fn extension_der(oid: ObjectIdentifier, critical: bool, value: &[u8]) -> Result<Vec<u8>> {
+    let critical = match critical {
+        true => true.to_vec()?,
+        false => Vec::new(),
+    };
+    tlv(
+        Tag::Sequence,
+        &[oid.to_vec()?, critical, tlv(Tag::OctetString, value)?].concat(),
+    )
+}
+
+/// The `[0]` attributes of the request, with an extensionRequest for the
+/// subject alternative names, if there are any
+fn attributes_der(sans: &[SubjectAltName]) -> Result<Vec<u8>> {
+    let tag = Tag::ContextSpecific {
+        constructed: true,
+        number: TagNumber::N0,
+    };
+    if sans.is_empty() {
+        return tlv(tag, &[]);
+    }
+    let extension = extension_der(SUBJECT_ALT_NAME, false, &general_names_der(sans)?)?;
+    let extensions = tlv(Tag::Sequence, &extension)?;
+    let attribute = tlv(
+        Tag::Sequence,
+        &[EXTENSION_REQUEST.to_vec()?, tlv(Tag::Set, &extensions)?].concat(),
+    )?;
+    tlv(tag, &attribute)
+}
+
+/// An AlgorithmIdentifier with SHA-256 and NULL parameters
+fn sha256_alg_id() -> Result<Vec<u8>> {
+    tlv(Tag::Sequence, &[oids::SHA256.to_vec()?, Any::NULL.to_vec()?].concat())
+}
+
+/// The signature AlgorithmIdentifier of the scheme
+pub(crate) fn signature_alg_id_der(scheme: SignatureScheme) -> Result<Vec<u8>> {
+    let (oid, params) = match scheme {
+        SignatureScheme::RsaPkcs1Sha256 => (oids::SHA256_WITH_RSA, Any::NULL.to_vec()?),
+        SignatureScheme::RsaPssSha256 => {
+            let explicit = |number, content: &[u8]| {
+                tlv(
+                    Tag::ContextSpecific {
+                        constructed: true,
+                        number,
+                    },
+                    content,
+                )
+            };
+            let mgf = tlv(Tag::Sequence, &[oids::MGF1.to_vec()?, sha256_alg_id()?].concat())?;
+            let salt = UIntBytes::new(&[32])?.to_vec()?;
+            let params = [
+                explicit(TagNumber::N0, &sha256_alg_id()?)?,
+                explicit(TagNumber::N1, &mgf)?,
+                explicit(TagNumber::N2, &salt)?,
+            ]
+            .concat();
+            (oids::RSASSA_PSS, tlv(Tag::Sequence, &params)?)
+        }
+        SignatureScheme::EcdsaP256Sha256 => (oids::ECDSA_WITH_SHA256, Vec::new()),
+        SignatureScheme::EcdsaP384Sha384 => (oids::ECDSA_WITH_SHA384, Vec::new()),
+        SignatureScheme::EcdsaP521Sha512 => (oids::ECDSA_WITH_SHA512, Vec::new()),
+        SignatureScheme::EcdsaSecp256k1Sha256 => (oids::ECDSA_WITH_SHA256, Vec::new()),
+        SignatureScheme::Ed25519 => (oids::ED_DSA25519, Vec::new()),
+    };
+    tlv(Tag::Sequence, &[oid.to_vec()?, params].concat())
+}
+
+/// The signature as X.509 carries it.  ECDSA `r || s` signatures become a
+/// DER SEQUENCE of the two INTEGERs.
+pub(crate) fn signature_der(scheme: SignatureScheme, signature: Vec<u8>) -> Result<Vec<u8>> {
+    match scheme {
+        SignatureScheme::EcdsaP256Sha256
+        | SignatureScheme::EcdsaP384Sha384
+        | SignatureScheme::EcdsaP521Sha512
+        | SignatureScheme::EcdsaSecp256k1Sha256 => {
+            let (r, s) = signature.split_at(signature.len() / 2);
+            let r = UIntBytes::new(r)?.to_vec()?;
+            let s = UIntBytes::new(s)?.to_vec()?;
+            tlv(Tag::Sequence, &[r, s].concat())
+        }
+        _ => Ok(signature),
+    }
+}
+
+/// Sign the DER of a CertificationRequestInfo or TBSCertificate, and wrap it
+/// with the signature AlgorithmIdentifier and the signature
+pub(crate) fn signed_der(key_info: &KeyInfo, scheme: SignatureScheme, tbs: Vec<u8>) -> Result<Vec<u8>> {
+    let signature = signature_der(scheme, sign(key_info, scheme, &tbs)?)?;
+    // The BIT STRING of the signature has no unused bits
+    let signature = tlv(Tag::BitString, &[&[0u8][..], &signature].concat())?;
+    tlv(
+        Tag::Sequence,
+        &[tbs, signature_alg_id_der(scheme)?, signature].concat(),
+    )
+}
+
+/// Parse the subject alternative names of the command line.  Each may be a
+/// comma separated list
+pub(crate) fn parse_sans(sans: &[String]) -> Result<Vec<SubjectAltName>> {
+    sans.iter()
+        .flat_map(|sans| sans.split(','))
+        .filter(|san| !san.trim().is_empty())
+        .map(parse_san)
+        .collect()
+}
+
+/// Build the DER CertificationRequest for a private key
+pub fn build_csr(
+    key_info: &KeyInfo,
+    subject: &[(ObjectIdentifier, String)],
+    sans: &[SubjectAltName],
+) -> Result<Vec<u8>> {
+    if key_info.key_type != KeyType::Private {
+        bail!(Error::TypeMismatch);
+    }
+    let scheme = SignatureScheme::for_key(key_info)?;
+    let version = UIntBytes::new(&[0])?.to_vec()?;
+    let info = tlv(
+        Tag::Sequence,
+        &[
+            version,
+            name_der(subject)?,
+            public_spki_der(key_info)?,
+            attributes_der(sans)?,
+        ]
+        .concat(),
+    )?;
+    signed_der(key_info, scheme, info)
+}
+
+/// Write a CSR for the input private key, as PEM or DER
+pub fn csr(app_state: &mut AppState) -> Result<()> {
+    let subject = parse_subject(app_state.subject.as_deref().unwrap_or_default())?;
+    let sans = parse_sans(&app_state.sans)?;
+    let key_info = discover(app_state)?;
+    let der = build_csr(&key_info, &subject, &sans)?;
+    match app_state.encoding {
+        Encoding::DER => app_state.write_stream(&der),
+        Encoding::PEM => app_state.write_stream(pem::wrap(pem::CERTIFICATE_REQUEST, &der)?.as_bytes()),
+        _ => bail!(Error::NotSupported),
+    }
+}
@@ -0,0 +1,232 @@
+//! Debug dumps of discovery, for diagnosing inputs that fail to parse
+//!
+//! With `--debug-dump DIR`, each run writes a `kt-<time>-<pid>` directory
+//! holding:
+//! * `input.txt` - the time, the size and encoding of the input, and the tag
+//!   and length of each DER element, if discovery read it as DER or PEM.  Only
+//!   OIDs are shown with their values.  Secrets and raw keys are only sized.
+//! * `key_info.txt` - the detected format, encoding and AlgorithmIdentifier,
+//!   as `kt show` displays them
+//! * `error.txt` - the error, if discovery failed
+//!
+//! Key bytes are never written, so the dump can be attached to a bug report.
+//! Dumps are kept for [RETENTION], and older dumps are removed by the next run.
+use anyhow::Result;
+use log::{info, warn};
+use pkcs8::ObjectIdentifier;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::app_state::AppState;
+use crate::events::{self, Event};
+use crate::key_info::{Encoding, KeyInfo};
+use crate::oids::oid_to_str;
+use crate::pem;
+use crate::timestamp;
+
+/// How long dumps are kept
+pub const RETENTION: Duration = Duration::from_secs(24 * 60 * 60);
+
+const DUMP_PREFIX: &str = "kt-";
+const MAX_DEPTH: usize = 8;
+const MAX_LINES: usize = 200;
+
+/// Remove dumps older than [RETENTION]
+fn prune(dir: &Path) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let is_dump = entry.file_name().to_string_lossy().starts_with(DUMP_PREFIX);
+        let age = entry
+            .metadata()
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok());
+        if is_dump && age.is_some_and(|age| age > RETENTION) {
+            info!("Removing expired debug dump {}", entry.path().display());
+            if let Err(e) = fs::remove_dir_all(entry.path()) {
+                warn!("Could not remove {}: {}", entry.path().display(), e);
+            }
+        }
+    }
+}
+
+fn tag_name(tag: u8) -> &'static str {
+    match tag {
+        0x01 => "BOOLEAN",
+        0x02 => "INTEGER",
+        0x03 => "BIT STRING",
+        0x04 => "OCTET STRING",
+        0x05 => "NULL",
+        0x06 => "OBJECT IDENTIFIER",
+        0x0c => "UTF8String",
+        0x13 => "PrintableString",
+        0x17 => "UTCTime",
+        0x18 => "GeneralizedTime",
+        0x30 => "SEQUENCE",
+        0x31 => "SET",
+        tag if tag & 0xc0 == 0x80 => "CONTEXT SPECIFIC",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Describe the tag and length of each DER element, without the content of
+/// anything but OIDs
+fn der_headers(der: &[u8], depth: usize, out: &mut String) {
+    let mut rest = der;
+    while !rest.is_empty() && out.lines().count() < MAX_LINES {
+        let tag = rest[0];
+        let (len, header_len) = match rest.get(1) {
+            Some(&len) if len < 0x80 => (len as usize, 2),
+            Some(&len) if (0x81..=0x84).contains(&len) => {
+                let count = (len & 0x7f) as usize;
+                match rest.get(2..2 + count) {
+                    Some(bytes) => (
+                        bytes.iter().fold(0, |acc, b| (acc << 8) | *b as usize),
+                        2 + count,
+                    ),
+                    None => break,
+                }
+            }
+            _ => {
+                let _ = writeln!(out, "{}bad length", "  ".repeat(depth));
+                return;
+            }
+        };
+        let header = &rest[..header_len];
+        let _ = write!(
+            out,
+            "{}{} {} ({} bytes)",
+            "  ".repeat(depth),
+            hex::encode(header),
+            tag_name(tag),
+            len
+        );
+        let content = match rest.get(header_len..header_len + len) {
+            Some(content) => content,
+            None => {
+                let _ = writeln!(out, " truncated");
+                return;
+            }
+        };
+        if tag == 0x06 {
+            if let Ok(oid) = ObjectIdentifier::from_bytes(content) {
+                let _ = write!(out, " {}", oid_to_str(&oid));
+            }
+        }
+        let _ = writeln!(out);
+        // Constructed elements are described, but never the content of a
+        // primitive, which may be key material.
+        if tag & 0x20 != 0 && depth < MAX_DEPTH {
+            der_headers(content, depth + 1, out);
+        }
+        rest = &rest[header_len + len..];
+    }
+}
+
+/// Describe the input, without its key material.  A `raw` input is a secret
+/// or raw key, and only its size is given.  The elements of DER input are only
+/// walked if discovery read it as `der`.
+fn describe_input(name: &str, bytes: &[u8], time: &str, raw: bool, der: bool) -> String {
+    let mut out = format!(
+        "Time: {}\nInput: {}\nSize: {} bytes\n",
+        time,
+        name,
+        bytes.len()
+    );
+    if raw {
+        let _ = writeln!(out, "Encoding: raw");
+        return out;
+    }
+    let text = std::str::from_utf8(bytes).ok();
+    match text.map(str::trim_start) {
+        Some(text) if text.starts_with("-----BEGIN ") => match pem::unwrap_all(bytes) {
+            Ok(documents) => {
+                for (label, der) in documents {
+                    let _ = writeln!(out, "Encoding: PEM\nLabel: {}", label);
+                    der_headers(&der, 0, &mut out);
+                }
+            }
+            Err(e) => {
+                let _ = writeln!(out, "Encoding: PEM\nPEM error: {}", e);
+            }
+        },
+        Some(text) if text.starts_with('{') => {
+            let _ = writeln!(out, "Encoding: JSON");
+            match serde_json::from_str::<serde_json::Value>(text) {
+                Ok(serde_json::Value::Object(members)) => {
+                    let names: Vec<&String> = members.keys().collect();
+                    let _ = writeln!(out, "Members: {:?}", names);
+                }
+                Ok(_) => {
+                    let _ = writeln!(out, "Not a JSON object");
+                }
+                Err(e) => {
+                    let _ = writeln!(out, "JSON error: {}", e);
+                }
+            }
+        }
+        // The headers of anything that is not DER, such as an EC point or a
+        // seed that starts with a SEQUENCE tag, would be key bytes.
+        _ if der => {
+            let _ = writeln!(out, "Encoding: DER");
+            der_headers(bytes, 0, &mut out);
+        }
+        _ => {
+            let _ = writeln!(out, "Encoding: unknown");
+        }
+    }
+    out
+}
+
+/// Write a dump of the discovery of the input.
+///
+/// Failing to write the dump is logged, and does not fail the conversion.
+pub fn dump(app_state: &AppState, dir: &str, name: &str, bytes: &[u8], result: &Result<KeyInfo>) {
+    if let Err(e) = write_dump(app_state, Path::new(dir), name, bytes, result) {
+        warn!("Could not write the debug dump: {}", e);
+    }
+}
+
+fn write_dump(
+    app_state: &AppState,
+    dir: &Path,
+    name: &str,
+    bytes: &[u8],
+    result: &Result<KeyInfo>,
+) -> Result<()> {
+    app_state.output.create_dir_all(dir)?;
+    prune(dir);
+
+    let now = SystemTime::now();
+    let secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let dump_dir: PathBuf = dir.join(format!("{}{}-{}", DUMP_PREFIX, secs, std::process::id()));
+    app_state.output.create_dir_all(&dump_dir)?;
+
+    let raw = app_state.secret.is_some()
+        || matches!(app_state.in_format, Some(Encoding::RAW | Encoding::RAW_HEX));
+    let der = matches!(result, Ok(key_info) if key_info.encoding == Encoding::DER);
+    let time = timestamp::format(now, app_state.local_time);
+    app_state.output.write_file(
+        &dump_dir.join("input.txt"),
+        describe_input(name, bytes, &time, raw, der).as_bytes(),
+        false,
+    )?;
+    match result {
+        Ok(key_info) => app_state
+            .output
+            .write_file(&dump_dir.join("key_info.txt"), key_info.to_string().as_bytes(), false),
+        Err(e) => app_state
+            .output
+            .write_file(&dump_dir.join("error.txt"), format!("{:#}\n", e).as_bytes(), false),
+    }?;
+    events::emit(
+        Event::progress(&format!("Wrote debug dump to {}", dump_dir.display()))
+            .with("dir", dump_dir.display().to_string()),
+    );
+    Ok(())
+}
@@ -0,0 +1,195 @@
+//! Detached signatures
+//!
+//! `kt sign` signs a file, or stdin, with any private key kt can read, and
+//! writes the signature on its own.  `kt verify` verifies such a signature
+//! with the public key, or the private key, in any format kt reads, and
+//! exits with 1 if it does not verify.  The signature scheme is the one the key
+//! naturally produces, as for certificate signing requests: PKCS1 v1.5 with
+//! SHA-256 for RSA keys, RSASSA-PSS for PSS keys, ECDSA with the hash that
+//! matches the curve, or Ed25519.
+//!
+//! The signature is written as the raw signature bytes, as base64, or as a
+//! JWS with a detached payload (RFC 7515, appendix F).  Raw and base64 ECDSA
+//! signatures are the DER SEQUENCE that `openssl dgst` writes, and JWS ECDSA
+//! signatures are `r || s`.
+//!
+//! ```rust
+//! use kt::detached::SignatureFormat;
+//! use std::str::FromStr;
+//!
+//! assert_eq!(SignatureFormat::from_str("JWS").unwrap(), SignatureFormat::Jws);
+//! assert!(SignatureFormat::from_str("pgp").is_err());
+//! ```
+use anyhow::{bail, Result};
+use base64ct::{Base64, Base64UrlUnpadded, Encoding as _};
+use std::fs;
+use std::io::Read;
+use std::str::FromStr;
+
+use crate::app_state::AppState;
+use crate::csr::signature_der;
+use crate::discover::discover;
+use crate::errors::Error;
+use crate::jwt::JwtHeader;
+use crate::key_info::{KeyInfo, KeyType};
+use crate::signature::{sign as sign_message, verify as verify_message, SignatureScheme};
+
+/// How a detached signature is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureFormat {
+    /// The signature bytes
+    Raw,
+    /// The signature bytes as base64
+    Base64,
+    /// A JWS compact serialization with the payload left out
+    Jws,
+}
+
+impl SignatureFormat {
+    pub fn all() -> Vec<&'static str> {
+        vec!["raw", "base64", "jws"]
+    }
+}
+
+impl FromStr for SignatureFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<SignatureFormat> {
+        match s.to_lowercase().as_str() {
+            "raw" => Ok(SignatureFormat::Raw),
+            "base64" => Ok(SignatureFormat::Base64),
+            "jws" => Ok(SignatureFormat::Jws),
+            _ => Err(Error::UnknownSignatureFormat.into()),
+        }
+    }
+}
+
+/// Read the data from the data file, or from stdin if the key was read from
+/// a file
+pub(crate) fn read_data(app_state: &AppState) -> Result<Vec<u8>> {
+    match &app_state.data_file {
+        Some(filename) => fs::read(filename).map_err(|e| Error::ReadFileError(e).into()),
+        None => {
+            let mut bytes = Vec::new();
+            std::io::stdin()
+                .read_to_end(&mut bytes)
+                .map_err(Error::IOEReadError)?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// The scheme the key naturally produces, or RSASSA-PSS for an RSA key if
+/// it was asked for
+fn signature_scheme(app_state: &AppState, key_info: &KeyInfo) -> Result<SignatureScheme> {
+    if app_state.pss {
+        if !SignatureScheme::RsaPssSha256.accepts_key(key_info) {
+            bail!(Error::TypeMismatch);
+        }
+        return Ok(SignatureScheme::RsaPssSha256);
+    }
+    SignatureScheme::for_key(key_info)
+}
+
+/// Sign the data with the input private key, and write the detached
+/// signature in the signature format of the AppState
+pub fn sign(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    if key_info.key_type != KeyType::Private {
+        bail!(Error::TypeMismatch);
+    }
+    let scheme = signature_scheme(app_state, &key_info)?;
+    let data = read_data(app_state)?;
+
+    let output = match app_state.signature_format {
+        SignatureFormat::Jws => {
+            let header = JwtHeader {
+                alg: scheme.jose_alg().to_owned(),
+                typ: "JOSE".to_owned(),
+                kid: app_state.key_id.clone(),
+            };
+            let header = Base64UrlUnpadded::encode_string(serde_json::to_string(&header)?.as_bytes());
+            let signing_input = format!("{}.{}", header, Base64UrlUnpadded::encode_string(&data));
+            let signature = sign_message(&key_info, scheme, signing_input.as_bytes())?;
+            format!("{}..{}\n", header, Base64UrlUnpadded::encode_string(&signature)).into_bytes()
+        }
+        format => {
+            let signature = signature_der(scheme, sign_message(&key_info, scheme, &data)?)?;
+            match format {
+                SignatureFormat::Base64 => format!("{}\n", Base64::encode_string(&signature)).into_bytes(),
+                _ => signature,
+            }
+        }
+    };
+    app_state.write_stream(&output)
+}
+
+/// The `r || s` form that [verify_message] takes of a DER ECDSA signature.
+/// Other signatures are returned unchanged.
+fn signature_from_der(scheme: SignatureScheme, signature: &[u8]) -> Result<Vec<u8>> {
+    let signature = match scheme {
+        SignatureScheme::EcdsaP256Sha256 => p256::ecdsa::Signature::from_der(signature).map(|sig| sig.to_vec()),
+        SignatureScheme::EcdsaP384Sha384 => p384::ecdsa::Signature::from_der(signature).map(|sig| sig.to_vec()),
+        SignatureScheme::EcdsaP521Sha512 => p521::ecdsa::Signature::from_der(signature).map(|sig| sig.to_vec()),
+        SignatureScheme::EcdsaSecp256k1Sha256 => k256::ecdsa::Signature::from_der(signature).map(|sig| sig.to_vec()),
+        _ => return Ok(signature.to_vec()),
+    };
+    signature.map_err(|_| Error::BadSignature.into())
+}
+
+/// The scheme and signing input of a JWS with a detached payload
+fn jws_signing_input(jws: &str, data: &[u8]) -> Result<(SignatureScheme, String, Vec<u8>)> {
+    let parts: Vec<&str> = jws.split('.').collect();
+    if parts.len() != 3 || !parts[1].is_empty() {
+        bail!(Error::BadJwt("not a JWS with a detached payload".to_owned()));
+    }
+    let header: serde_json::Value = Base64UrlUnpadded::decode_vec(parts[0])
+        .ok()
+        .and_then(|header| serde_json::from_slice(&header).ok())
+        .ok_or_else(|| Error::BadJwt("bad header".to_owned()))?;
+    let alg = header["alg"]
+        .as_str()
+        .ok_or_else(|| Error::BadJwt("missing alg".to_owned()))?;
+    let signature = Base64UrlUnpadded::decode_vec(parts[2])
+        .map_err(|_| Error::BadJwt("not base64url".to_owned()))?;
+    let signing_input = format!("{}.{}", parts[0], Base64UrlUnpadded::encode_string(data));
+    Ok((SignatureScheme::from_jose_alg(alg)?, signing_input, signature))
+}
+
+/// Verify the detached signature of the data with the input key.  A
+/// signature that does not verify is returned as Error::BadSignature.
+pub fn verify(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    let signature_file = app_state
+        .signature_file
+        .clone()
+        .ok_or_else(|| Error::MissingInput("signature".to_owned()))?;
+    let signature = fs::read(&signature_file).map_err(Error::ReadFileError)?;
+    let data = read_data(app_state)?;
+
+    let (scheme, data, signature) = match app_state.signature_format {
+        SignatureFormat::Jws => {
+            let jws = std::str::from_utf8(&signature)
+                .map_err(|_| Error::BadJwt("not UTF-8".to_owned()))?;
+            let (scheme, signing_input, signature) = jws_signing_input(jws.trim(), &data)?;
+            if !scheme.accepts_key(&key_info) {
+                bail!(Error::TypeMismatch);
+            }
+            (scheme, signing_input.into_bytes(), signature)
+        }
+        format => {
+            let signature = match format {
+                SignatureFormat::Base64 => {
+                    let text = std::str::from_utf8(&signature).map_err(|_| Error::BadSignature)?;
+                    let text: String = text.split_whitespace().collect();
+                    Base64::decode_vec(&text).map_err(|_| Error::BadSignature)?
+                }
+                _ => signature,
+            };
+            let scheme = signature_scheme(app_state, &key_info)?;
+            (scheme, data, signature_from_der(scheme, &signature)?)
+        }
+    };
+    verify_message(&key_info, scheme, &data, &signature)?;
+    app_state.write_stream(b"Verified OK\n")
+}
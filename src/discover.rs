@@ -5,6 +5,7 @@
 //! * [encoding](crate::key_info::Encoding)
 //! * [Algorithm](crate::key_info::Alg)
 use anyhow::Result;
+use log::warn;
 use pkcs8::der::Document;
 
 use pkcs1::{RsaPrivateKeyDocument, RsaPublicKeyDocument};
@@ -13,20 +14,79 @@ use pkcs8::{DecodePrivateKey, EncryptedPrivateKeyDocument, PrivateKeyDocument, P
 use sec1::{DecodeEcPrivateKey, EcPrivateKeyDocument};
 
 use crate::app_state::AppState;
+use crate::debug_dump;
 use crate::document::{
+    cng_docs::{cng_to_key_info, is_cng_blob},
+    dh_docs::{dh_key_length, dh_parameters_to_key_info, is_dh_parameters_der, is_dh_parameters_pem},
+    dsa_docs::{dsa_key_length, dsa_to_key_info, is_dsa_pem},
+    ethereum_docs::{ethereum_to_key_info, is_ethereum_keystore},
+    jwe_docs::{is_jwe, jwe_decrypt},
+    jwk_docs::{jwk_to_key_info, Jwk, Jwks},
+    openpgp_docs::{is_openpgp, openpgp_to_key_info},
+    openssh_docs::{
+        is_openssh_private_key, is_openssh_public_key, openssh_public_to_key_info,
+        openssh_to_private_key_info,
+    },
+    pem_json_docs::PemJson,
     pkcs1_docs::{pk1_to_rsa_private_key, pk1_to_rsa_public_key},
     pkcs8_docs::{pk8_encrypted_to_private_key_info, pk8_to_private_key_info},
-    sec1_docs::sec1_to_private_key_info,
+    pkcs12_docs::{is_pkcs12, pkcs12_to_key_info},
+    pkcs10_docs::{csr_to_key_info, is_csr_pem},
+    pkcs7_docs::{is_pkcs7, is_pkcs7_pem, pkcs7_to_key_info},
+    ppk_docs::{is_ppk, ppk_to_private_key_info},
+    raw_docs::raw_to_key_info,
+    sec1_docs::{ec_point_to_key_info, sec1_to_private_key_info, with_point_format, PointFormat},
+    secret_docs::secret_to_key_info,
     spki_docs::spki_to_key_info,
+    tink_docs::{is_tink_keyset, tink_to_key_info},
+    tss2_docs::{is_tss2, tss2_error},
+    x509_docs::{certificate_to_key_info, is_certificate_pem, pem_certificates, select_certificate},
+    xml_docs::{is_xml_key, xml_to_key_info},
 };
 use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::hex_text;
+use crate::key_info::Alg;
 use crate::key_info::KeyInfo;
 use crate::key_info::Encoding;
+use crate::key_info::Format;
 
 
+/// Decrypt a PKCS1 or SEC1 PEM key with traditional encryption.  A wrong
+/// password that happens to leave good padding is found by the DER.
+fn discover_encrypted_pem(app_state: &AppState, text: &str) -> Result<KeyInfo> {
+    let encryption = crate::pem::encrypted_info(text)?;
+    let key_info = app_state.decrypt_input(|pwd| {
+        let (label, der) = crate::pem::unwrap_encrypted(text, pwd)?;
+        match crate::pem::label_to_format(&label) {
+            Some(Format::PKCS1) => match RsaPrivateKeyDocument::from_der(&der) {
+                Ok(pk1_doc) => pk1_to_rsa_private_key(&pk1_doc, Encoding::PEM),
+                Err(_) => Err(Error::BadPassword.into()),
+            },
+            Some(Format::SEC1) => match EcPrivateKeyDocument::from_sec1_der(&der) {
+                Ok(sec1_doc) => sec1_to_private_key_info(&sec1_doc, Encoding::PEM),
+                Err(_) => Err(Error::BadPassword.into()),
+            },
+            Some(Format::DSA) => {
+                dsa_to_key_info(&der, Encoding::PEM).map_err(|_| Error::BadPassword.into())
+            }
+            _ => Err(Error::UnknownKeyType.into()),
+        }
+    })?;
+    Ok(key_info.with_encryption(Some(encryption)))
+}
+
 fn discover_private_key(app_state: &AppState, key_bytes: &[u8]) -> Result<KeyInfo> {
     // Test for PEM encoding
     if let Ok(pem) = std::str::from_utf8(key_bytes) {
+        if is_ppk(pem) {
+            return ppk_to_private_key_info(app_state, pem);
+        }
+
+        if is_openpgp(key_bytes) {
+            return openpgp_to_key_info(app_state, key_bytes);
+        }
+
         // Test PKCS8
         if let Ok(pk8_doc) = PrivateKeyDocument::from_pkcs8_pem(pem) {
             return pk8_to_private_key_info(&pk8_doc, Encoding::PEM);
@@ -37,6 +97,15 @@ fn discover_private_key(app_state: &AppState, key_bytes: &[u8]) -> Result<KeyInf
             return pk8_encrypted_to_private_key_info(app_state, &enc_doc, Encoding::PEM);
         }
 
+        if is_openssh_private_key(pem) {
+            return openssh_to_private_key_info(app_state, pem);
+        }
+
+        // Test PKCS1 and SEC1 with traditional encryption
+        if crate::pem::is_encrypted(pem) {
+            return discover_encrypted_pem(app_state, pem);
+        }
+
         // Test PKCS1
         if let Ok(pk1_doc) = RsaPrivateKeyDocument::from_pem(pem) {
             return pk1_to_rsa_private_key(&pk1_doc, Encoding::PEM);
@@ -44,6 +113,26 @@ fn discover_private_key(app_state: &AppState, key_bytes: &[u8]) -> Result<KeyInf
         if let Ok(sec1_doc) = EcPrivateKeyDocument::from_sec1_pem(pem) {
             return sec1_to_private_key_info(&sec1_doc, Encoding::PEM);
         }
+        // OpenSSL labels the SEC1 document of an SM2 key differently
+        if crate::pem::label(pem.trim_start().as_bytes())
+            .is_ok_and(|label| label == crate::pem::SM2_PRIVATE_KEY)
+        {
+            let (_, der) = crate::pem::unwrap(pem.trim_start().as_bytes())?;
+            let sec1_doc = EcPrivateKeyDocument::from_sec1_der(&der)?;
+            return sec1_to_private_key_info(&sec1_doc, Encoding::PEM);
+        }
+        if is_dsa_pem(pem) {
+            let (_, der) = crate::pem::unwrap(pem.trim_start().as_bytes())?;
+            return dsa_to_key_info(&der, Encoding::PEM);
+        }
+    }
+
+    if is_openpgp(key_bytes) {
+        return openpgp_to_key_info(app_state, key_bytes);
+    }
+
+    if is_pkcs12(key_bytes) {
+        return pkcs12_to_key_info(app_state, key_bytes);
     }
 
     // Test for PKCS8 DER
@@ -63,12 +152,78 @@ fn discover_private_key(app_state: &AppState, key_bytes: &[u8]) -> Result<KeyInf
         return sec1_to_private_key_info(&sec1_doc, Encoding::DER);
     }
 
+    if let Ok(key_info) = dsa_to_key_info(key_bytes, Encoding::DER) {
+        return Ok(key_info);
+    }
+
+    Err(Error::UnknownKeyType.into())
+}
+
+fn discover_jwk(app_state: &AppState, key_bytes: &[u8]) -> Result<KeyInfo> {
+    let text = std::str::from_utf8(key_bytes).map_err(|_| Error::UnknownKeyType)?;
+
+    // Try a password protected JWK
+    if is_jwe(text) {
+        let json = app_state.decrypt_input(|pwd| jwe_decrypt(text, pwd))?;
+        let json = std::str::from_utf8(&json).map_err(|_| Error::BadJwk("not UTF-8".to_owned()))?;
+        return Ok(jwk_to_key_info(&Jwk::from_json(json)?)?.with_encrypted(true));
+    }
+
+    if !text.trim_start().starts_with('{') {
+        return Err(Error::UnknownKeyType.into());
+    }
+    if let Ok(jwks) = Jwks::from_json(text) {
+        let jwk = jwks.select(app_state.key_id.as_deref()).map_err(|e| {
+            warn!("{}", e);
+            e
+        })?;
+        return jwk_to_key_info(jwk);
+    }
+    let jwk = Jwk::from_json(text)?;
+    jwk_to_key_info(&jwk)
+}
+
+/// The input was a JWK, or protected JWK, so the error says what is wrong
+/// with it, rather than that the input is not a JWK
+fn is_jwk_error(e: &anyhow::Error) -> bool {
+    match e.downcast_ref::<Error>() {
+        Some(Error::BadJwk(_) | Error::BadJwe(_) | Error::BadPassword) => true,
+        Some(Error::MissingInput(what)) => what == "password",
+        _ => false,
+    }
+}
+
+/// A bare EC point has no structure to identify its curve.  So it is only
+/// considered if the curve was provided.
+fn discover_ec_point(app_state: &AppState, key_bytes: &[u8]) -> Result<KeyInfo> {
+    match app_state.curve {
+        Some(curve) => ec_point_to_key_info(key_bytes, curve),
+        None => Err(Error::UnknownKeyType.into()),
+    }
+}
+
+// A DER DHParameter is only told from a PKCS1 RSAPublicKey by its generator,
+// so it is tried before public keys
+fn discover_dh_parameters(key_bytes: &[u8]) -> Result<KeyInfo> {
+    if let Ok(pem) = std::str::from_utf8(key_bytes) {
+        if is_dh_parameters_pem(pem) {
+            let (_, der) = crate::pem::unwrap(pem.trim_start().as_bytes())?;
+            return dh_parameters_to_key_info(&der, Encoding::PEM);
+        }
+    }
+    if is_dh_parameters_der(key_bytes) {
+        return dh_parameters_to_key_info(key_bytes, Encoding::DER);
+    }
     Err(Error::UnknownKeyType.into())
 }
 
-fn discover_public_key(key_bytes: &[u8]) -> Result<KeyInfo> {
+fn discover_public_key(app_state: &AppState, key_bytes: &[u8]) -> Result<KeyInfo> {
     // Test for PEM encoding
     if let Ok(pem) = std::str::from_utf8(key_bytes) {
+        if is_openssh_public_key(pem) {
+            return openssh_public_to_key_info(pem);
+        }
+
         if let Ok(spki_doc) = PublicKeyDocument::from_pem(pem) {
             return spki_to_key_info(&spki_doc, Encoding::PEM);
         }
@@ -76,6 +231,22 @@ fn discover_public_key(key_bytes: &[u8]) -> Result<KeyInfo> {
         if let Ok(pk1_doc) = RsaPublicKeyDocument::from_pem(pem) {
             return pk1_to_rsa_public_key(&pk1_doc, Encoding::PEM);
         }
+
+        if is_certificate_pem(pem) {
+            let certs = pem_certificates(pem)?;
+            let cert = select_certificate(&certs, app_state.cert_index)?;
+            return certificate_to_key_info(cert, Encoding::PEM);
+        }
+
+        if is_csr_pem(pem) {
+            let (_, der) = crate::pem::unwrap(pem.as_bytes())?;
+            return csr_to_key_info(&der, Encoding::PEM);
+        }
+
+        if is_pkcs7_pem(pem) {
+            let (_, der) = crate::pem::unwrap(pem.as_bytes())?;
+            return pkcs7_to_key_info(&der, Encoding::PEM, app_state.cert_index);
+        }
     }
 
     if let Ok(spki_doc) = PublicKeyDocument::from_der(key_bytes) {
@@ -86,6 +257,20 @@ fn discover_public_key(key_bytes: &[u8]) -> Result<KeyInfo> {
         return pk1_to_rsa_public_key(&pk1_doc, Encoding::DER);
     }
 
+    if is_pkcs7(key_bytes) {
+        return pkcs7_to_key_info(key_bytes, Encoding::DER, app_state.cert_index);
+    }
+
+    // A DER file holds a single certificate
+    if let Ok(key_info) = certificate_to_key_info(key_bytes, Encoding::DER) {
+        select_certificate(&[key_bytes.to_vec()], app_state.cert_index)?;
+        return Ok(key_info);
+    }
+
+    if let Ok(key_info) = csr_to_key_info(key_bytes, Encoding::DER) {
+        return Ok(key_info);
+    }
+
     Err(Error::UnknownKeyType.into())
 }
 
@@ -96,13 +281,138 @@ fn discover_public_key(key_bytes: &[u8]) -> Result<KeyInfo> {
 pub fn discover(app_state: &mut AppState) -> Result<KeyInfo> {
 
     let in_bytes = app_state.read_stream()?;
+    // A PEM in a JSON envelope is read as the PEM it holds
+    let envelope = PemJson::from_bytes(&in_bytes).ok();
+    let in_bytes = match &envelope {
+        Some(envelope) => envelope.pem.as_bytes().to_vec(),
+        None => in_bytes,
+    };
+    let in_bytes = match app_state.lenient.then(|| crate::pem::repair(&in_bytes)).flatten() {
+        Some((repaired, fixes)) => {
+            for fix in fixes {
+                let message = format!("Repaired the input: {}", fix);
+                events::emit(Event::warning("repaired", &message).with("fix", fix));
+            }
+            repaired
+        }
+        None => in_bytes,
+    };
+    // A DER document in hex, as a single line of base64 or with a
+    // nonstandard PEM label, or raw key material in hex, is read as the
+    // bytes it holds
+    let text = match (app_state.secret, app_state.in_format) {
+        (Some(_), _) => None,
+        (None, Some(encoding @ (Encoding::HEX | Encoding::RAW_HEX))) => {
+            let text = std::str::from_utf8(&in_bytes).map_err(|_| Error::BadHex)?;
+            Some((encoding, hex_text::decode(text)?))
+        }
+        (None, in_format) => hex_text::from_hex_der(&in_bytes)
+            .map(|der| (Encoding::HEX, der))
+            .or_else(|| crate::pem::from_base64_line(&in_bytes, in_format))
+            .or_else(|| crate::pem::unwrap_nonstandard(&in_bytes).map(|der| (Encoding::PEM, der))),
+    };
+    let in_bytes = match &text {
+        Some((_, bytes)) => bytes.clone(),
+        None => in_bytes,
+    };
 
-    let unknown_type = |_| -> Result<KeyInfo> { Err(Error::UnknownKeyType.into())}; 
+    // Keep the errors that tell the user what to fix, such as a wrong password
+    let unknown_type = |e: anyhow::Error| -> Result<KeyInfo> {
+        match e.downcast_ref::<Error>() {
+            Some(
+                Error::BadPassword
+                | Error::MissingInput(_)
+                | Error::BadOpenSsh(_)
+                | Error::BadPpk(_)
+                | Error::BadCertificate(_)
+                | Error::BadCsr(_)
+                | Error::BadPkcs12(_)
+                | Error::BadPkcs7(_)
+                | Error::BadOpenPgp(_)
+                | Error::BadTink(_)
+                | Error::BadEthereum(_)
+                | Error::BadTpmKey(_)
+                | Error::BadCng(_)
+                | Error::BadXml(_)
+                | Error::BadDsa(_)
+                | Error::BadDh(_)
+                | Error::TpmKey(_),
+            ) => Err(e),
+            _ => Err(Error::UnknownKeyType.into()),
+        }
+    };
     // Calling discover_private_key with some forms of a public key causes
     // the pkcs8 crate to panic.  Until that's fixed, just call this first.
-    let result = discover_public_key(&in_bytes)
-    .or_else(|_| discover_private_key(app_state, &in_bytes))
-    .or_else(unknown_type)?;
+    let result = match app_state.secret {
+        // A secret is just bytes, so there is nothing to discover.
+        Some(secret_encoding) => secret_to_key_info(&in_bytes, secret_encoding),
+        // Raw key bytes could be mistaken for anything, so they are only read
+        // when the user says so
+        None if matches!(app_state.in_format, Some(Encoding::RAW | Encoding::RAW_HEX)) => {
+            raw_to_key_info(app_state, &in_bytes)
+        }
+        // A keyset is JSON, so it is read before it is mistaken for a JWK
+        None if is_tink_keyset(&in_bytes) => tink_to_key_info(app_state, &in_bytes),
+        None if is_ethereum_keystore(&in_bytes) => ethereum_to_key_info(app_state, &in_bytes),
+        None if is_cng_blob(&in_bytes) => cng_to_key_info(&in_bytes),
+        None if is_xml_key(&in_bytes) => xml_to_key_info(&in_bytes),
+        // Only the TPM that holds the parent can read a TPM key
+        None if is_tss2(&in_bytes) => Err(tss2_error(&in_bytes).into()),
+        None => match discover_jwk(app_state, &in_bytes) {
+            Err(e) if is_jwk_error(&e) => Err(e),
+            result => result
+                .or_else(|_| discover_ec_point(app_state, &in_bytes))
+                .or_else(|_| discover_dh_parameters(&in_bytes))
+                .or_else(|e| match e.downcast_ref::<Error>() {
+                    Some(Error::BadDh(_)) => Err(e),
+                    _ => discover_public_key(app_state, &in_bytes),
+                })
+                // A certificate is never a private key, so say what is wrong with it
+                .or_else(|e| match e.downcast_ref::<Error>() {
+                    Some(Error::BadCertificate(_) | Error::BadCsr(_) | Error::BadPkcs7(_)) => Err(e),
+                    _ => discover_private_key(app_state, &in_bytes),
+                })
+                .or_else(unknown_type),
+        },
+    };
+    if let Some(dir) = &app_state.debug_dump {
+        let name = app_state.in_file.as_deref().unwrap_or("stdin");
+        debug_dump::dump(app_state, dir, name, &in_bytes, &result);
+    }
+    let mut result = result?;
+    if envelope.is_some() {
+        result.encoding = Encoding::PEM_JSON;
+    }
+    if let Some((encoding, _)) = text {
+        result.encoding = encoding;
+    }
+    // A SEC1 ECPrivateKey may leave out its curve, so it is the curve given
+    if let (Alg::Ecdsa, None, Some(curve)) = (result.alg, result.curve(), app_state.curve) {
+        result.set_oid(&curve.oid());
+    }
+    // The length of an EC key is that of its curve, and of a DSA or DH key
+    // that of its p, whatever it was read from
+    if result.key_length.is_none() {
+        if let Some(curve) = result.curve() {
+            result.set_key_length(curve.key_length());
+        } else if let Some(params) = &result.params {
+            let key_length = match result.alg {
+                Alg::Dsa => dsa_key_length(params),
+                Alg::Dh => dh_key_length(params),
+                _ => None,
+            };
+            if let Some(key_length) = key_length {
+                result.set_key_length(key_length);
+            }
+        }
+    }
+    // A compressed EC point is read as the uncompressed point, which every
+    // format can hold.  A point that is not on the curve is left as it is.
+    if result.alg == Alg::Ecdsa {
+        if let Ok(point_info) = with_point_format(&result, PointFormat::Uncompressed) {
+            result = point_info;
+        }
+    }
 
     // Make sure the app_state defaults align correctly
     if app_state.alg.is_none() {
@@ -0,0 +1,293 @@
+//! Diagnose an input that kt can't read
+//!
+//! When no reader takes the input, `discover` only says that the key type is
+//! unknown.  `kt doctor` tries each DER reader on the input, PKCS8,
+//! encrypted PKCS8, PKCS1, SEC1 and SPKI, and shows why each one failed.  The
+//! reader that got furthest into the input is taken as what the input was
+//! meant to be, and its failure is explained, with what to do about it.
+//!
+//! A PEM label narrows the readers to those of its format.  An input that
+//! kt reads is shown as `kt show` shows it.
+//!
+//! ```rust
+//! use kt::doctor::diagnose;
+//!
+//! // A PKCS8 document cut short
+//! let der = [0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70];
+//! let diagnosis = diagnose(&der);
+//! assert!(diagnosis.contains("Most plausible: PKCS8 PrivateKeyInfo"));
+//! assert!(diagnosis.contains("truncated"));
+//! ```
+use anyhow::Result;
+use pkcs1::{RsaPrivateKeyDocument, RsaPublicKeyDocument};
+use pkcs8::der::{Document, Error as DerError, ErrorKind};
+use pkcs8::{EncryptedPrivateKeyDocument, PrivateKeyDocument, PublicKeyDocument};
+use sec1::EcPrivateKeyDocument;
+use std::fmt::Write as _;
+use std::io::Cursor;
+
+use crate::app_state::AppState;
+use crate::asn1;
+use crate::discover::discover;
+use crate::errors::Error;
+use crate::key_info::{Alg, Format};
+use crate::oids::oid_to_str;
+use crate::pem;
+
+/// A DER reader of the input
+struct Reader {
+    name: &'static str,
+    format: Format,
+    /// The algorithm OID of the document, if it has one
+    read: fn(&[u8]) -> Result<Option<pkcs8::ObjectIdentifier>, DerError>,
+}
+
+const READERS: [Reader; 6] = [
+    Reader {
+        name: "PKCS8 PrivateKeyInfo",
+        format: Format::PKCS8,
+        read: |der| {
+            Ok(Some(
+                PrivateKeyDocument::from_der(der)?.decode().algorithm.oid,
+            ))
+        },
+    },
+    Reader {
+        name: "PKCS8 EncryptedPrivateKeyInfo",
+        format: Format::PKCS8,
+        read: |der| EncryptedPrivateKeyDocument::from_der(der).map(|_| None),
+    },
+    Reader {
+        name: "PKCS1 RSAPrivateKey",
+        format: Format::PKCS1,
+        read: |der| RsaPrivateKeyDocument::from_der(der).map(|_| None),
+    },
+    Reader {
+        name: "SEC1 ECPrivateKey",
+        format: Format::SEC1,
+        read: |der| EcPrivateKeyDocument::from_der(der).map(|_| None),
+    },
+    Reader {
+        name: "SPKI SubjectPublicKeyInfo",
+        format: Format::SPKI,
+        read: |der| {
+            Ok(Some(
+                PublicKeyDocument::from_der(der)?.decode().algorithm.oid,
+            ))
+        },
+    },
+    Reader {
+        name: "PKCS1 RSAPublicKey",
+        format: Format::PKCS1,
+        read: |der| RsaPublicKeyDocument::from_der(der).map(|_| None),
+    },
+];
+
+/// How far into the input the reader got
+fn progress(error: &DerError) -> u32 {
+    error.position().map(u32::from).unwrap_or_default()
+}
+
+/// What went wrong, and what to do about it
+fn explain(name: &str, error: &DerError, der: &[u8]) -> (String, String) {
+    let at = progress(error);
+    match error.kind() {
+        ErrorKind::Incomplete {
+            expected_len,
+            actual_len,
+        } => {
+            let mut fix = "The file was truncated, or damaged when it was copied.  Copy it again".to_owned();
+            if der.windows(2).any(|pair| pair == b"\r\n") {
+                fix.push_str(", in binary mode: it holds CR LF pairs, which a text mode transfer adds");
+            }
+            (
+                format!(
+                    "The {} is truncated: {} bytes are needed, but there are {}",
+                    name, expected_len, actual_len
+                ),
+                fix,
+            )
+        }
+        ErrorKind::TrailingData { decoded, remaining } => (
+            format!(
+                "{} bytes follow the {} byte {}",
+                remaining, decoded, name
+            ),
+            "Remove what follows the document.  Files that were joined together must be split".to_owned(),
+        ),
+        ErrorKind::TagUnexpected { expected, actual } => (
+            match expected {
+                Some(expected) => format!(
+                    "At byte {}, the {} has a {} where a {} belongs",
+                    at, name, actual, expected
+                ),
+                None => format!("At byte {}, the {} has an unexpected {}", at, name, actual),
+            },
+            "The input is another kind of document, or was damaged.  `kt asn1` shows its structure".to_owned(),
+        ),
+        ErrorKind::Length { tag } | ErrorKind::Noncanonical { tag } => (
+            format!(
+                "At byte {}, the length of the {} of the {} is not DER",
+                at, tag, name
+            ),
+            "The document is BER, such as with indefinite lengths.  Encode it as DER, such as with `openssl pkey -outform DER`".to_owned(),
+        ),
+        ErrorKind::Value { tag } => (
+            format!("At byte {}, the {} of the {} has a bad value", at, tag, name),
+            "The document was damaged, or written by a tool that does not follow its standard".to_owned(),
+        ),
+        ErrorKind::OidUnknown { oid } => (
+            format!("The {} is of an unknown algorithm, {}", name, oid_to_str(&oid)),
+            "kt does not read keys of this algorithm".to_owned(),
+        ),
+        _ => (
+            format!("The {} can not be read: {}", name, error),
+            "`kt asn1` shows the structure of the input".to_owned(),
+        ),
+    }
+}
+
+/// The diagnosis of one DER document, of a PEM label if it had one
+fn diagnose_der(label: Option<&str>, der: &[u8], out: &mut String) {
+    let format = label.and_then(pem::label_to_format);
+    let readers: Vec<&Reader> = READERS
+        .iter()
+        .filter(|reader| format.is_none_or(|format| reader.format == format))
+        .collect();
+    if readers.is_empty() {
+        let _ = writeln!(
+            out,
+            "The {} document is not a key kt can diagnose",
+            label.unwrap_or_default()
+        );
+        return;
+    }
+    if der.first() != Some(&0x30) {
+        let what = match std::str::from_utf8(der) {
+            Ok(_) => "text, but not PEM, JSON, an OpenSSH or PuTTY key, or DER in hex or base64",
+            Err(_) => "not a DER document, which starts with a SEQUENCE",
+        };
+        let _ = writeln!(out, "The input is {}", what);
+        let _ = writeln!(
+            out,
+            "Fix: Raw key bytes are read with --in-format raw and --curve, and secrets with --secret"
+        );
+        return;
+    }
+
+    // Bytes after the first document are explained on their own, so that
+    // the document is still recognized
+    let (der, trailing) = match asn1::element_len(der) {
+        Some(len) if len < der.len() => (&der[..len], der.len() - len),
+        _ => (der, 0),
+    };
+    let _ = writeln!(out, "Tried:");
+    let mut best: Option<(&Reader, DerError)> = None;
+    let mut read = None;
+    for reader in readers {
+        match (reader.read)(der) {
+            Ok(oid) => {
+                let _ = writeln!(out, "\t{}: well formed", reader.name);
+                read = Some((reader, oid));
+                break;
+            }
+            Err(error) => {
+                let _ = writeln!(out, "\t{}: {}", reader.name, error);
+                if best
+                    .as_ref()
+                    .is_none_or(|(_, best)| progress(&error) > progress(best))
+                {
+                    best = Some((reader, error));
+                }
+            }
+        }
+    }
+    let (name, explanation, fix) = match (read, best) {
+        (Some((reader, _)), _) if trailing > 0 => (
+            reader.name,
+            format!(
+                "{} bytes follow the {} byte {}",
+                trailing,
+                der.len(),
+                reader.name
+            ),
+            "Remove what follows the document.  Files that were joined together must be split"
+                .to_owned(),
+        ),
+        (Some((reader, Some(oid))), _) if Alg::try_from(&oid).is_err() => (
+            reader.name,
+            format!("The key is of an unknown algorithm, {}", oid_to_str(&oid)),
+            "kt does not read keys of this algorithm".to_owned(),
+        ),
+        (Some((reader, _)), _) => (
+            reader.name,
+            "The document is well formed, but its key can not be read".to_owned(),
+            "`kt asn1` shows what it holds".to_owned(),
+        ),
+        (None, Some((reader, error))) => {
+            let (explanation, fix) = explain(reader.name, &error, der);
+            (reader.name, explanation, fix)
+        }
+        (None, None) => return,
+    };
+    let _ = writeln!(out, "Most plausible: {}", name);
+    let _ = writeln!(out, "\t{}", explanation);
+    if trailing > 0 && read.is_none() {
+        let _ = writeln!(out, "\t{} more bytes follow the document", trailing);
+    }
+    let _ = writeln!(out, "Fix: {}", fix);
+}
+
+/// Why each reader failed to read the input, and the most plausible
+/// explanation and fix
+pub fn diagnose(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    if bytes.iter().all(u8::is_ascii_whitespace) {
+        let _ = writeln!(out, "The input is empty");
+        return out;
+    }
+    let documents = match asn1::documents(bytes) {
+        Ok(documents) => documents,
+        Err(e) => {
+            let _ = writeln!(out, "The PEM armor can not be read: {}", e);
+            let _ = writeln!(
+                out,
+                "Fix: The BEGIN and END lines must match, with only base64 between them"
+            );
+            return out;
+        }
+    };
+    for (label, der) in &documents {
+        if let Some(label) = label {
+            let _ = writeln!(out, "Label: {}", label);
+            if pem::label_to_format(label).is_none() && !pem::is_recognizable_label(label) {
+                let _ = writeln!(out, "kt does not read {} documents", label);
+                continue;
+            }
+        }
+        diagnose_der(label.as_deref(), der, &mut out);
+    }
+    out
+}
+
+/// Write why the input can't be read, or the key if it can
+pub fn doctor(app_state: &mut AppState) -> Result<()> {
+    let bytes = app_state.read_stream()?;
+    app_state.in_stream = Box::new(Cursor::new(bytes.clone()));
+    let report = match discover(app_state) {
+        Ok(key_info) if key_info.alg == Alg::Unknown => format!(
+            "The input was read, but its algorithm is unknown\n{}Fix: kt does not read keys of this algorithm\n",
+            key_info
+        ),
+        Ok(key_info) => format!("The input was read\n{}", key_info),
+        // Discovery already says what is wrong with the input
+        Err(e) => match e.downcast_ref::<Error>() {
+            Some(Error::UnknownKeyType) => diagnose(&bytes),
+            Some(Error::BadPassword) => {
+                format!("{}\nFix: Give the password of the key with --inpass\n", e)
+            }
+            _ => format!("{}\n", e),
+        },
+    };
+    app_state.write_stream(report.as_bytes())
+}
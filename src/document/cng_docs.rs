@@ -0,0 +1,325 @@
+//! Windows CNG key blobs, as `BCryptExportKey` and `NCryptExportKey` write
+//! them ([BCRYPT_RSAKEY_BLOB](https://learn.microsoft.com/en-us/windows/win32/api/bcrypt/ns-bcrypt-bcrypt_rsakey_blob),
+//! [BCRYPT_ECCKEY_BLOB](https://learn.microsoft.com/en-us/windows/win32/api/bcrypt/ns-bcrypt-bcrypt_ecckey_blob))
+//!
+//! A blob is a little endian header, of a magic number and the sizes of the
+//! parts of the key, followed by the parts as big endian numbers.
+//!
+//! | Magic | Blob |
+//! |-------|------|
+//! | `RSA1` | BCRYPT_RSAPUBLIC_BLOB |
+//! | `RSA2` | BCRYPT_RSAPRIVATE_BLOB, with the primes and not the private exponent |
+//! | `RSA3` | BCRYPT_RSAFULLPRIVATE_BLOB |
+//! | `ECS1` to `ECS6` | ECDSA public and private keys on P-256, P-384 and P-521 |
+//! | `ECK1` to `ECK6` | ECDH public and private keys on the same curves |
+//!
+//! All of them are read.  RSA private keys are written as
+//! BCRYPT_RSAFULLPRIVATE_BLOB, and EC keys as ECDSA blobs.  Blobs are never
+//! encrypted.
+//!
+//! ```rust
+//! use kt::document::cng_docs::is_cng_blob;
+//!
+//! // ECS1, a 32 byte key, and the X and Y coordinates
+//! let mut blob = b"ECS1".to_vec();
+//! blob.extend_from_slice(&32u32.to_le_bytes());
+//! blob.extend_from_slice(&[0x11; 64]);
+//! assert!(is_cng_blob(&blob));
+//! assert!(!is_cng_blob(&blob[..70]));
+//! ```
+use anyhow::{bail, Result};
+use pkcs1::{RsaPrivateKeyDocument, RsaPublicKeyDocument};
+use pkcs8::der::{Decodable, Document, Encodable};
+use rsa::pkcs1::{EncodeRsaPrivateKey, EncodeRsaPublicKey};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use rsa::BigUint;
+use sec1::{EcParameters, EcPrivateKey, EcPrivateKeyDocument};
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::{
+    pkcs1_docs::{pk1_to_rsa_private_key, pk1_to_rsa_public_key},
+    sec1_docs::{ec_point_to_key_info, sec1_to_private_key_info},
+};
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::signature::{ec_public_point, rsa_private_key, rsa_public_key};
+
+const RSA_PUBLIC: &[u8; 4] = b"RSA1";
+const RSA_PRIVATE: &[u8; 4] = b"RSA2";
+const RSA_FULL_PRIVATE: &[u8; 4] = b"RSA3";
+/// Bytes of the header of an RSA blob: the magic and five sizes
+const RSA_HEADER_LEN: usize = 24;
+/// Bytes of the header of an EC blob: the magic and the key size
+const ECC_HEADER_LEN: usize = 8;
+
+/// The kinds of CNG blob
+enum CngBlob {
+    Rsa(&'static [u8; 4]),
+    /// The curve, and whether the blob holds the private key
+    Ecc(Curve, bool),
+}
+
+/// The blob of a magic number
+fn blob_kind(magic: &[u8]) -> Option<CngBlob> {
+    let kind = match magic {
+        b"RSA1" => CngBlob::Rsa(RSA_PUBLIC),
+        b"RSA2" => CngBlob::Rsa(RSA_PRIVATE),
+        b"RSA3" => CngBlob::Rsa(RSA_FULL_PRIVATE),
+        [b'E', b'C', b'S' | b'K', n @ b'1'..=b'6'] => {
+            let curve = match n {
+                b'1' | b'2' => Curve::P256,
+                b'3' | b'4' => Curve::P384,
+                _ => Curve::P521,
+            };
+            CngBlob::Ecc(curve, n % 2 == 0)
+        }
+        _ => return None,
+    };
+    Some(kind)
+}
+
+/// The ECDSA magic of a curve
+fn ecdsa_magic(curve: Curve, private: bool) -> Result<[u8; 4]> {
+    let n = match curve {
+        Curve::P256 => b'1',
+        Curve::P384 => b'3',
+        Curve::P521 => b'5',
+        Curve::Secp256k1 => bail!(Error::BadCng(
+            "CNG blobs do not hold secp256k1 keys".to_owned()
+        )),
+        Curve::Sm2 => bail!(Error::BadCng("CNG blobs do not hold SM2 keys".to_owned())),
+    };
+    Ok([b'E', b'C', b'S', n + private as u8])
+}
+
+/// The little endian u32 at the offset
+fn header_u32(bytes: &[u8], offset: usize) -> Option<usize> {
+    let field = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(field.try_into().ok()?) as usize)
+}
+
+/// The sizes of the parts of a blob, which must fill it
+fn part_sizes(bytes: &[u8]) -> Option<Vec<usize>> {
+    let (header, sizes) = match blob_kind(bytes.get(..4)?)? {
+        CngBlob::Rsa(magic) => {
+            let exponent = header_u32(bytes, 8)?;
+            let modulus = header_u32(bytes, 12)?;
+            let prime1 = header_u32(bytes, 16)?;
+            let prime2 = header_u32(bytes, 20)?;
+            let sizes = match magic {
+                RSA_PUBLIC => vec![exponent, modulus],
+                RSA_PRIVATE => vec![exponent, modulus, prime1, prime2],
+                _ => vec![
+                    exponent, modulus, prime1, prime2, prime1, prime2, prime1, modulus,
+                ],
+            };
+            (RSA_HEADER_LEN, sizes)
+        }
+        CngBlob::Ecc(curve, private) => {
+            let key = header_u32(bytes, 4)?;
+            if key != curve.field_size() {
+                return None;
+            }
+            match private {
+                true => (ECC_HEADER_LEN, vec![key, key, key]),
+                false => (ECC_HEADER_LEN, vec![key, key]),
+            }
+        }
+    };
+    let len = sizes
+        .iter()
+        .try_fold(header, |len, size| len.checked_add(*size))?;
+    (len == bytes.len()).then_some(sizes)
+}
+
+/// Test whether the bytes are a CNG key blob.  The magic number must be
+/// known, and the sizes of the header must add up to the blob.
+pub fn is_cng_blob(bytes: &[u8]) -> bool {
+    part_sizes(bytes).is_some()
+}
+
+/// The parts of the blob, after its header
+fn blob_parts<'a>(bytes: &'a [u8], header: usize, sizes: &[usize]) -> Vec<&'a [u8]> {
+    let mut rest = &bytes[header..];
+    sizes
+        .iter()
+        .map(|size| {
+            let (part, tail) = rest.split_at(*size);
+            rest = tail;
+            part
+        })
+        .collect()
+}
+
+fn rsa_blob_to_key_info(bytes: &[u8], magic: &[u8; 4], sizes: &[usize]) -> Result<KeyInfo> {
+    let parts = blob_parts(bytes, RSA_HEADER_LEN, sizes);
+    let e = BigUint::from_bytes_be(parts[0]);
+    let n = BigUint::from_bytes_be(parts[1]);
+    let bad_rsa = |e: rsa::Error| Error::BadCng(format!("bad RSA key: {}", e));
+    if magic == RSA_PUBLIC {
+        let pk1_der = rsa::RsaPublicKey::new(n, e)
+            .map_err(bad_rsa)?
+            .to_pkcs1_der()
+            .map_err(|e| Error::BadCng(e.to_string()))?;
+        let pk1_doc = RsaPublicKeyDocument::from_der(pk1_der.as_bytes())?;
+        return pk1_to_rsa_public_key(&pk1_doc, Encoding::CNG);
+    }
+    // The private exponent of a full blob is the one the primes give
+    let p = BigUint::from_bytes_be(parts[2]);
+    let q = BigUint::from_bytes_be(parts[3]);
+    let private_key = rsa::RsaPrivateKey::from_p_q(p, q, e).map_err(bad_rsa)?;
+    if *private_key.n() != n {
+        bail!(Error::BadCng(
+            "the primes are not of the modulus".to_owned()
+        ));
+    }
+    let pk1_der = private_key
+        .to_pkcs1_der()
+        .map_err(|e| Error::BadCng(e.to_string()))?;
+    let pk1_doc = RsaPrivateKeyDocument::from_der(pk1_der.as_bytes())?;
+    pk1_to_rsa_private_key(&pk1_doc, Encoding::CNG)
+}
+
+fn ecc_blob_to_key_info(bytes: &[u8], curve: Curve, sizes: &[usize]) -> Result<KeyInfo> {
+    let parts = blob_parts(bytes, ECC_HEADER_LEN, sizes);
+    let point = [&[0x04][..], parts[0], parts[1]].concat();
+    let d = match parts.get(2) {
+        Some(d) => Zeroizing::new(d.to_vec()),
+        None => {
+            return ec_point_to_key_info(&point, curve)
+                .map_err(|_| Error::BadCng("bad EC point".to_owned()).into())
+        }
+    };
+    let ec_private_key = EcPrivateKey {
+        private_key: &d,
+        parameters: Some(EcParameters::NamedCurve(curve.oid())),
+        public_key: Some(&point),
+    };
+    let sec1_doc = EcPrivateKeyDocument::from_der(&Zeroizing::new(ec_private_key.to_vec()?))?;
+    let key_info = sec1_to_private_key_info(&sec1_doc, Encoding::CNG)?;
+    // Fails if d is not a valid scalar, or is not the private key of the point
+    if ec_public_point(&key_info).ok().as_ref() != Some(&point) {
+        bail!(Error::BadCng(
+            "the private key does not match the point".to_owned()
+        ));
+    }
+    Ok(key_info)
+}
+
+/// Read a CNG key blob
+pub fn cng_to_key_info(bytes: &[u8]) -> Result<KeyInfo> {
+    let sizes = part_sizes(bytes).ok_or_else(|| Error::BadCng("not a key blob".to_owned()))?;
+    let key_info = match blob_kind(&bytes[..4]) {
+        Some(CngBlob::Rsa(magic)) => rsa_blob_to_key_info(bytes, magic, &sizes)?,
+        Some(CngBlob::Ecc(curve, _)) => ecc_blob_to_key_info(bytes, curve, &sizes)?,
+        None => bail!(Error::BadCng("unknown magic".to_owned())),
+    };
+    Ok(key_info.with_format(Format::CNG))
+}
+
+/// The number, big endian, left padded with zeros to the size
+fn pad(n: &BigUint, size: usize) -> Result<Vec<u8>> {
+    let bytes = n.to_bytes_be();
+    if bytes.len() > size {
+        bail!(Error::BadCng("a part is larger than its size".to_owned()));
+    }
+    Ok([vec![0; size - bytes.len()], bytes].concat())
+}
+
+fn rsa_blob(key_info: &KeyInfo, public_only: bool) -> Result<Zeroizing<Vec<u8>>> {
+    let public_key = rsa_public_key(key_info)?;
+    let bits = public_key.n().bits();
+    let modulus_len = bits.div_ceil(8);
+    let exponent = public_key.e().to_bytes_be();
+    let header = |magic: &[u8; 4], prime_len: usize| {
+        [
+            magic.as_slice(),
+            &(bits as u32).to_le_bytes(),
+            &(exponent.len() as u32).to_le_bytes(),
+            &(modulus_len as u32).to_le_bytes(),
+            &(prime_len as u32).to_le_bytes(),
+            &(prime_len as u32).to_le_bytes(),
+        ]
+        .concat()
+    };
+    if key_info.key_type != KeyType::Private || public_only {
+        return Ok(Zeroizing::new(
+            [
+                header(RSA_PUBLIC, 0),
+                exponent.clone(),
+                pad(public_key.n(), modulus_len)?,
+            ]
+            .concat(),
+        ));
+    }
+
+    let private_key = rsa_private_key(key_info)?;
+    let (p, q) = match private_key.primes() {
+        [p, q] => (p, q),
+        _ => bail!(Error::BadCng(
+            "multi-prime RSA keys are not supported".to_owned()
+        )),
+    };
+    let prime_len = modulus_len.div_ceil(2);
+    let one = BigUint::from(1u32);
+    let dp = private_key.d() % (p - &one);
+    let dq = private_key.d() % (q - &one);
+    // The CRT coefficient, the inverse of q mod p
+    let crt = q.modpow(&(p - 2u32), p);
+    let mut blob = Zeroizing::new(header(RSA_FULL_PRIVATE, prime_len));
+    blob.extend_from_slice(&exponent);
+    blob.extend_from_slice(&pad(public_key.n(), modulus_len)?);
+    for part in [p, q, &dp, &dq, &crt] {
+        blob.extend_from_slice(&Zeroizing::new(pad(part, prime_len)?));
+    }
+    blob.extend_from_slice(&Zeroizing::new(pad(private_key.d(), modulus_len)?));
+    Ok(blob)
+}
+
+fn ecc_blob(key_info: &KeyInfo, public_only: bool) -> Result<Zeroizing<Vec<u8>>> {
+    let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+    let size = curve.field_size();
+    let point = ec_public_point(key_info)?;
+    if point.len() != 1 + 2 * size || point[0] != 0x04 {
+        bail!(Error::BadCng(
+            "only uncompressed EC points are supported".to_owned()
+        ));
+    }
+    let private = key_info.key_type == KeyType::Private && !public_only;
+    let mut blob = Zeroizing::new(
+        [
+            ecdsa_magic(curve, private)?.as_slice(),
+            &(size as u32).to_le_bytes(),
+            &point[1..],
+        ]
+        .concat(),
+    );
+    if private {
+        let bytes = key_info
+            .bytes
+            .as_ref()
+            .ok_or_else(|| Error::MissingInput("key".to_owned()))?;
+        let ec_private_key = EcPrivateKey::from_der(bytes)?;
+        let d = BigUint::from_bytes_be(ec_private_key.private_key);
+        blob.extend_from_slice(&Zeroizing::new(pad(&d, size)?));
+    }
+    Ok(blob)
+}
+
+/// Write the key as a CNG key blob.  With `-t public`, the public key of a
+/// private key is written.
+pub fn key_info_to_cng(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    if app_state.out_password.is_some() {
+        bail!(Error::BadCng("key blobs are not encrypted".to_owned()));
+    }
+    let public_only = app_state.key_type == Some(KeyType::Public);
+    let blob = match key_info.alg {
+        Alg::Rsa => rsa_blob(key_info, public_only)?,
+        Alg::Ecdsa => ecc_blob(key_info, public_only)?,
+        _ => bail!(Error::BadCng(
+            "only RSA and EC keys can be written".to_owned()
+        )),
+    };
+    app_state.write_stream(&blob)
+}
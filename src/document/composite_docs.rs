@@ -0,0 +1,319 @@
+//! Composite ML-DSA keys, of the draft
+//! [Composite ML-DSA for use in X.509](https://datatracker.ietf.org/doc/draft-ietf-lamps-pq-composite-sigs/)
+//!
+//! A composite key pairs an ML-DSA key with a traditional RSA, ECDSA or EdDSA
+//! key, under one OID that names both.  PKCS8 and SPKI hold the components
+//! concatenated, the ML-DSA component first:
+//! * a public key is the ML-DSA public key, followed by the RSAPublicKey, the
+//!   uncompressed EC point or the EdDSA public key
+//! * a private key is the ML-DSA seed, followed by the RSAPrivateKey, the
+//!   ECPrivateKey or the EdDSA private key
+//!
+//! The ML-DSA public key is of a fixed length, so the components can be told
+//! apart.  Composite keys are shown, rewritten as PKCS8 or SPKI, and taken
+//! apart with `--component`.  kt does not sign with composite keys.
+use anyhow::{bail, Result};
+use pkcs8::der::{asn1::OctetString, Encodable};
+use pkcs8::ObjectIdentifier;
+use std::str::FromStr;
+
+use crate::compare::public_material;
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, KeyInfo, KeyType};
+use crate::ml_dsa;
+use crate::oids;
+
+/// A component of a composite key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Component {
+    /// The ML-DSA key
+    MlDsa,
+    /// The RSA, ECDSA or EdDSA key
+    Traditional,
+}
+
+impl Component {
+    pub fn all() -> Vec<&'static str> {
+        vec!["ml-dsa", "traditional"]
+    }
+}
+
+impl FromStr for Component {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Component> {
+        match s.to_lowercase().as_str() {
+            "ml-dsa" | "mldsa" | "pq" => Ok(Component::MlDsa),
+            "traditional" | "classical" => Ok(Component::Traditional),
+            _ => Err(Error::UnknownComponent.into()),
+        }
+    }
+}
+
+/// The algorithms of a composite OID
+struct Composite {
+    oid: ObjectIdentifier,
+    ml_dsa: Alg,
+    traditional: Alg,
+    /// The curve of an ECDSA component, if kt supports it
+    curve: Option<Curve>,
+    /// The traditional algorithm, as the draft names it
+    name: &'static str,
+}
+
+const fn composite(
+    oid: ObjectIdentifier,
+    ml_dsa: Alg,
+    traditional: Alg,
+    curve: Option<Curve>,
+    name: &'static str,
+) -> Composite {
+    Composite {
+        oid,
+        ml_dsa,
+        traditional,
+        curve,
+        name,
+    }
+}
+
+const COMPOSITES: [Composite; 18] = [
+    composite(
+        oids::MLDSA44_RSA2048_PSS,
+        Alg::MlDsa44,
+        Alg::Rsa,
+        None,
+        "RSA2048-PSS",
+    ),
+    composite(
+        oids::MLDSA44_RSA2048_PKCS15,
+        Alg::MlDsa44,
+        Alg::Rsa,
+        None,
+        "RSA2048-PKCS15",
+    ),
+    composite(
+        oids::MLDSA44_ED25519,
+        Alg::MlDsa44,
+        Alg::EdDsa25519,
+        None,
+        "Ed25519",
+    ),
+    composite(
+        oids::MLDSA44_ECDSA_P256,
+        Alg::MlDsa44,
+        Alg::Ecdsa,
+        Some(Curve::P256),
+        "ECDSA-P256",
+    ),
+    composite(
+        oids::MLDSA65_RSA3072_PSS,
+        Alg::MlDsa65,
+        Alg::Rsa,
+        None,
+        "RSA3072-PSS",
+    ),
+    composite(
+        oids::MLDSA65_RSA3072_PKCS15,
+        Alg::MlDsa65,
+        Alg::Rsa,
+        None,
+        "RSA3072-PKCS15",
+    ),
+    composite(
+        oids::MLDSA65_RSA4096_PSS,
+        Alg::MlDsa65,
+        Alg::Rsa,
+        None,
+        "RSA4096-PSS",
+    ),
+    composite(
+        oids::MLDSA65_RSA4096_PKCS15,
+        Alg::MlDsa65,
+        Alg::Rsa,
+        None,
+        "RSA4096-PKCS15",
+    ),
+    composite(
+        oids::MLDSA65_ECDSA_P256,
+        Alg::MlDsa65,
+        Alg::Ecdsa,
+        Some(Curve::P256),
+        "ECDSA-P256",
+    ),
+    composite(
+        oids::MLDSA65_ECDSA_P384,
+        Alg::MlDsa65,
+        Alg::Ecdsa,
+        Some(Curve::P384),
+        "ECDSA-P384",
+    ),
+    composite(
+        oids::MLDSA65_ECDSA_BRAINPOOL_P256R1,
+        Alg::MlDsa65,
+        Alg::Ecdsa,
+        None,
+        "ECDSA-brainpoolP256r1",
+    ),
+    composite(
+        oids::MLDSA65_ED25519,
+        Alg::MlDsa65,
+        Alg::EdDsa25519,
+        None,
+        "Ed25519",
+    ),
+    composite(
+        oids::MLDSA87_ECDSA_P384,
+        Alg::MlDsa87,
+        Alg::Ecdsa,
+        Some(Curve::P384),
+        "ECDSA-P384",
+    ),
+    composite(
+        oids::MLDSA87_ECDSA_BRAINPOOL_P384R1,
+        Alg::MlDsa87,
+        Alg::Ecdsa,
+        None,
+        "ECDSA-brainpoolP384r1",
+    ),
+    composite(
+        oids::MLDSA87_ED448,
+        Alg::MlDsa87,
+        Alg::EdDsa448,
+        None,
+        "Ed448",
+    ),
+    composite(
+        oids::MLDSA87_RSA3072_PSS,
+        Alg::MlDsa87,
+        Alg::Rsa,
+        None,
+        "RSA3072-PSS",
+    ),
+    composite(
+        oids::MLDSA87_RSA4096_PSS,
+        Alg::MlDsa87,
+        Alg::Rsa,
+        None,
+        "RSA4096-PSS",
+    ),
+    composite(
+        oids::MLDSA87_ECDSA_P521,
+        Alg::MlDsa87,
+        Alg::Ecdsa,
+        Some(Curve::P521),
+        "ECDSA-P521",
+    ),
+];
+
+fn find(oid: &ObjectIdentifier) -> Option<&'static Composite> {
+    COMPOSITES.iter().find(|composite| composite.oid == *oid)
+}
+
+/// Test whether the OID is of a composite algorithm
+pub fn is_composite(oid: &ObjectIdentifier) -> bool {
+    find(oid).is_some()
+}
+
+/// The components of a composite OID, such as "ML-DSA-65 and ECDSA-P256"
+///
+/// ```rust
+/// use kt::document::composite_docs::components_to_str;
+/// use kt::oids;
+///
+/// assert_eq!(
+///     components_to_str(&oids::MLDSA65_ECDSA_P256).as_deref(),
+///     Some("ML-DSA-65 and ECDSA-P256")
+/// );
+/// assert_eq!(components_to_str(&oids::ML_DSA_65), None);
+/// ```
+pub fn components_to_str(oid: &ObjectIdentifier) -> Option<String> {
+    let composite = find(oid)?;
+    let ml_dsa = match composite.ml_dsa {
+        Alg::MlDsa44 => "ML-DSA-44",
+        Alg::MlDsa65 => "ML-DSA-65",
+        _ => "ML-DSA-87",
+    };
+    Some(format!("{} and {}", ml_dsa, composite.name))
+}
+
+/// The composite of a KeyInfo, and its bytes
+fn composite_of(key_info: &KeyInfo) -> Result<(&'static Composite, &[u8])> {
+    let composite = match (key_info.alg, &key_info.oid) {
+        (Alg::Composite, Some(oid)) => find(oid),
+        _ => None,
+    }
+    .ok_or_else(|| Error::BadComposite("the key is not a composite key".to_owned()))?;
+    let bytes = key_info
+        .bytes
+        .as_deref()
+        .ok_or(Error::MissingInput("key".to_owned()))?;
+    Ok((composite, bytes))
+}
+
+/// The KeyInfo of a component of a composite key, as PKCS8 or SPKI would hold
+/// the component on its own
+pub fn component_key_info(key_info: &KeyInfo, component: Component) -> Result<KeyInfo> {
+    let (composite, bytes) = composite_of(key_info)?;
+    let split = match key_info.key_type {
+        KeyType::Private => ml_dsa::SEED_SIZE,
+        KeyType::Public => ml_dsa::public_key_size(composite.ml_dsa).unwrap_or_default(),
+        _ => bail!(Error::UnknownKeyType),
+    };
+    if bytes.len() <= split {
+        bail!(Error::BadComposite(format!(
+            "bad key length {}",
+            bytes.len()
+        )));
+    }
+    let (ml_dsa_key, traditional_key) = bytes.split_at(split);
+
+    let mut component_info = KeyInfo::new()
+        .with_key_type(key_info.key_type)
+        .with_format(key_info.format)
+        .with_encoding(key_info.encoding);
+    match component {
+        Component::MlDsa => {
+            component_info.set_alg(composite.ml_dsa);
+            match key_info.key_type {
+                KeyType::Private => component_info.set_bytes(&ml_dsa::seed_der(ml_dsa_key)),
+                _ => component_info.set_bytes(ml_dsa_key),
+            };
+        }
+        Component::Traditional => {
+            component_info.set_alg(composite.traditional);
+            match (composite.traditional, key_info.key_type) {
+                (Alg::Rsa, _) => {
+                    component_info.set_oid(&oids::RSA_ENCRYPTION);
+                    component_info.set_bytes(traditional_key);
+                }
+                (Alg::Ecdsa, _) => {
+                    let curve = composite.curve.ok_or(Error::UnknownCurve)?;
+                    component_info.set_oid(&oids::ECDSA);
+                    component_info.params = Some(curve.oid().to_vec()?);
+                    component_info.set_bytes(traditional_key);
+                }
+                (_, KeyType::Private) => {
+                    component_info.set_bytes(&OctetString::new(traditional_key)?.to_vec()?);
+                }
+                _ => {
+                    component_info.set_bytes(traditional_key);
+                }
+            }
+        }
+    }
+    Ok(component_info)
+}
+
+/// The composite public key of a composite private key, of the public keys
+/// of its components
+pub fn composite_public_key(key_info: &KeyInfo) -> Result<Vec<u8>> {
+    let mut public_key = Vec::new();
+    for component in [Component::MlDsa, Component::Traditional] {
+        let component_info = component_key_info(key_info, component)?;
+        let component_key = public_material(&component_info)?
+            .ok_or_else(|| Error::MissingInput("public key".to_owned()))?;
+        public_key.extend(component_key);
+    }
+    Ok(public_key)
+}
@@ -0,0 +1,217 @@
+//! COSE_Key documents
+//!
+//! A [RFC 9052](https://www.rfc-editor.org/rfc/rfc9052#section-7) COSE_Key is
+//! the CBOR counterpart of a JWK, used by WebAuthn and CWT.  Parameters are
+//! labelled by integers rather than names, and hold bytes rather than
+//! base64url.  A COSE_Key is made from the JWK of the key, so the same keys,
+//! and the `alg` and `key_ops` metadata, are supported.
+//!
+//! The labels are written in the deterministic order of RFC 8949: the common
+//! parameters, then the key type parameters.
+//!
+//! ```rust
+//! use ciborium::value::Value;
+//! use kt::app_state::AppState;
+//! use kt::document::cose_docs::jwk_to_cose_key;
+//! use kt::document::jwk_docs::key_info_as_jwk;
+//! use kt::key_info::{Alg, KeyInfo, KeyType};
+//!
+//! // An RSA private key has the RFC 8230 labels n, e, d, p, q, dP, dQ and qInv
+//! let der = std::fs::read("test_data/rsa-2048-private-pk1.der").unwrap();
+//! let key_info = KeyInfo::new()
+//!     .with_alg(Alg::Rsa)
+//!     .with_key_type(KeyType::Private)
+//!     .with_bytes(&der);
+//! let jwk = key_info_as_jwk(&AppState::default(), &key_info).unwrap();
+//! let labels: Vec<Value> = match jwk_to_cose_key(&jwk).unwrap() {
+//!     Value::Map(map) => map.into_iter().map(|(label, _)| label).collect(),
+//!     _ => unreachable!(),
+//! };
+//! let expected: Vec<Value> = [1, -1, -2, -3, -4, -5, -6, -7, -8].into_iter().map(Value::from).collect();
+//! assert_eq!(labels, expected);
+//! ```
+use anyhow::{bail, Result};
+use ciborium::value::Value;
+use log::warn;
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::jwk_docs::{
+    b64_decode, key_info_as_jwk, Jwk, KTY_EC, KTY_OCT, KTY_OKP, KTY_RSA,
+};
+use crate::errors::Error;
+use crate::key_info::KeyInfo;
+
+// Common parameters, RFC 9052 section 7.1
+const LABEL_KTY: i64 = 1;
+const LABEL_KID: i64 = 2;
+const LABEL_ALG: i64 = 3;
+const LABEL_KEY_OPS: i64 = 4;
+// The curve of EC2 and OKP keys, RFC 9053 section 7
+const LABEL_CRV: i64 = -1;
+
+// Key types, RFC 9053 section 7
+const COSE_KTY_OKP: i64 = 1;
+const COSE_KTY_EC2: i64 = 2;
+const COSE_KTY_RSA: i64 = 3;
+const COSE_KTY_SYMMETRIC: i64 = 4;
+
+/// The COSE key type, and the labels of the key type parameters, of a JWK
+/// key type.  EC2 and OKP are defined by RFC 9053 section 7, RSA by RFC 8230
+/// section 4, and Symmetric by RFC 9053 section 7.3.
+fn key_type_labels(kty: &str) -> Result<(i64, &'static [(&'static str, i64)])> {
+    match kty {
+        KTY_OKP => Ok((COSE_KTY_OKP, &[("x", -2), ("d", -4)])),
+        KTY_EC => Ok((COSE_KTY_EC2, &[("x", -2), ("y", -3), ("d", -4)])),
+        KTY_RSA => Ok((
+            COSE_KTY_RSA,
+            &[
+                ("n", -1),
+                ("e", -2),
+                ("d", -3),
+                ("p", -4),
+                ("q", -5),
+                ("dp", -6),
+                ("dq", -7),
+                ("qi", -8),
+            ],
+        )),
+        KTY_OCT => Ok((COSE_KTY_SYMMETRIC, &[("k", -1)])),
+        kty => bail!(Error::BadCose(format!("unsupported kty {}", kty))),
+    }
+}
+
+/// The COSE curve of a JWK curve, RFC 9053 section 7.1
+fn cose_crv(crv: &str) -> Result<i64> {
+    match crv {
+        "P-256" => Ok(1),
+        "P-384" => Ok(2),
+        "P-521" => Ok(3),
+        "X25519" => Ok(4),
+        "X448" => Ok(5),
+        "Ed25519" => Ok(6),
+        "Ed448" => Ok(7),
+        crv => bail!(Error::BadCose(format!("unsupported curve {}", crv))),
+    }
+}
+
+/// The COSE algorithm of a JOSE algorithm.
+///
+/// The JOSE ECDH-ES algorithms derive keys with the Concat KDF, and the COSE
+/// ones with HKDF, so they have no equivalent.
+fn cose_alg(jose_alg: &str) -> Result<i64> {
+    match jose_alg {
+        "ES256" => Ok(-7),
+        "ES384" => Ok(-35),
+        "ES512" => Ok(-36),
+        "EdDSA" => Ok(-8),
+        "PS256" => Ok(-37),
+        "PS384" => Ok(-38),
+        "PS512" => Ok(-39),
+        "RS256" => Ok(-257),
+        "RS384" => Ok(-258),
+        "RS512" => Ok(-259),
+        "RSA-OAEP" => Ok(-40),
+        "RSA-OAEP-256" => Ok(-41),
+        "HS256" => Ok(5),
+        "HS384" => Ok(6),
+        "HS512" => Ok(7),
+        "A128KW" => Ok(-3),
+        "A192KW" => Ok(-4),
+        "A256KW" => Ok(-5),
+        "dir" => Ok(-6),
+        alg => bail!(Error::BadCose(format!(
+            "alg {} has no COSE equivalent",
+            alg
+        ))),
+    }
+}
+
+/// The COSE key operation of a JWK key operation, RFC 9052 section 7.1
+fn cose_key_op(key_op: &str) -> Result<i64> {
+    match key_op {
+        "sign" => Ok(1),
+        "verify" => Ok(2),
+        "encrypt" => Ok(3),
+        "decrypt" => Ok(4),
+        "wrapKey" => Ok(5),
+        "unwrapKey" => Ok(6),
+        "deriveKey" => Ok(7),
+        "deriveBits" => Ok(8),
+        op => bail!(Error::BadCose(format!("unknown key_ops {}", op))),
+    }
+}
+
+fn member<'a>(jwk: &'a Jwk, name: &str) -> Option<&'a String> {
+    match name {
+        "x" => jwk.x.as_ref(),
+        "y" => jwk.y.as_ref(),
+        "n" => jwk.n.as_ref(),
+        "e" => jwk.e.as_ref(),
+        "d" => jwk.d.as_ref(),
+        "p" => jwk.p.as_ref(),
+        "q" => jwk.q.as_ref(),
+        "dp" => jwk.dp.as_ref(),
+        "dq" => jwk.dq.as_ref(),
+        "qi" => jwk.qi.as_ref(),
+        "k" => jwk.k.as_ref(),
+        _ => None,
+    }
+}
+
+/// Turn a JWK into a COSE_Key map
+///
+/// `use` and the `x5` members have no COSE_Key parameter, and are left out.
+pub fn jwk_to_cose_key(jwk: &Jwk) -> Result<Value> {
+    let (kty, labels) = key_type_labels(&jwk.kty)?;
+    let mut map = vec![(Value::from(LABEL_KTY), Value::from(kty))];
+    if let Some(kid) = &jwk.kid {
+        map.push((
+            Value::from(LABEL_KID),
+            Value::Bytes(kid.as_bytes().to_vec()),
+        ));
+    }
+    if let Some(alg) = &jwk.alg {
+        map.push((Value::from(LABEL_ALG), Value::from(cose_alg(alg)?)));
+    }
+    if let Some(key_ops) = &jwk.key_ops {
+        let key_ops = key_ops
+            .iter()
+            .map(|op| cose_key_op(op).map(Value::from))
+            .collect::<Result<Vec<Value>>>()?;
+        map.push((Value::from(LABEL_KEY_OPS), Value::Array(key_ops)));
+    }
+    if let Some(crv) = &jwk.crv {
+        map.push((Value::from(LABEL_CRV), Value::from(cose_crv(crv)?)));
+    }
+    for (name, label) in labels {
+        if let Some(value) = member(jwk, name) {
+            map.push((Value::from(*label), Value::Bytes(b64_decode(name, value)?)));
+        }
+    }
+    if jwk.key_use.is_some() || jwk.x5c.is_some() {
+        warn!("COSE_Key has no use or x5c parameters. Omitting them");
+    }
+    Ok(Value::Map(map))
+}
+
+/// Write KeyInfo bytes as a CBOR COSE_Key
+///
+/// COSE_Key has no password protection, so private and symmetric keys can
+/// not be written with an output password.
+pub fn key_info_to_cose(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let jwk = key_info_as_jwk(app_state, key_info)?;
+    if app_state.out_password.is_some() {
+        if jwk.is_private() {
+            bail!(Error::BadCose(
+                "COSE_Key can not be password protected".to_owned()
+            ));
+        }
+        warn!("Public keys are not protected. Ignoring the output password");
+    }
+    let cose_key = jwk_to_cose_key(&jwk)?;
+    let mut bytes = Zeroizing::new(Vec::new());
+    ciborium::ser::into_writer(&cose_key, &mut *bytes)
+        .map_err(|e| Error::BadCose(e.to_string()))?;
+    app_state.write_stream(&bytes)
+}
@@ -0,0 +1,120 @@
+//! PKCS#3 Diffie-Hellman parameters and keys
+//!
+//! The `DH PARAMETERS` document of `openssl dhparam` is a DHParameter, a
+//! SEQUENCE of the prime p, the generator g and an optional length of the
+//! private value.  PKCS8 and SPKI hold the DHParameter in the
+//! AlgorithmIdentifier of dhKeyAgreement, and the private value x, or the
+//! public value y, as an INTEGER.
+//!
+//! Parameters are shown, and written as PEM or DER.  Keys are shown, and
+//! written as PKCS8 or SPKI.  kt does not agree keys with DH keys.
+use anyhow::{bail, Result};
+use rsa::BigUint;
+use yasna::{ASN1Error, ASN1ErrorKind, ASN1Result, BERReader};
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::key_info::{Alg, Encoding, Format, KeyInfo, KeyType};
+use crate::oids;
+use crate::pem;
+
+/// The p, g and privateValueLength of a DHParameter
+struct DhParameter {
+    p: BigUint,
+    g: BigUint,
+    private_value_length: Option<u64>,
+}
+
+/// Read an INTEGER that must not be negative
+fn read_uint(reader: BERReader) -> ASN1Result<BigUint> {
+    match reader.read_bigint_bytes()? {
+        (bytes, true) => Ok(BigUint::from_bytes_be(&bytes)),
+        (_, false) => Err(ASN1Error::new(ASN1ErrorKind::Invalid)),
+    }
+}
+
+fn read_params(params: &[u8]) -> Result<DhParameter> {
+    yasna::parse_der(params, |reader| {
+        reader.read_sequence(|reader| {
+            Ok(DhParameter {
+                p: read_uint(reader.next())?,
+                g: read_uint(reader.next())?,
+                private_value_length: reader.read_optional(|reader| reader.read_u64())?,
+            })
+        })
+    })
+    .map_err(|e| Error::BadDh(format!("bad DHParameter: {}", e)).into())
+}
+
+/// The length of a DH key, the size of p in bits, of its DHParameter
+pub fn dh_key_length(params: &[u8]) -> Option<u32> {
+    read_params(params)
+        .ok()
+        .map(|params| params.p.bits() as u32)
+}
+
+/// The generator g of a DHParameter, in decimal
+pub fn dh_generator(params: &[u8]) -> Option<String> {
+    read_params(params).ok().map(|params| params.g.to_string())
+}
+
+/// The INTEGER public value y = g^x mod p of the INTEGER private value x and
+/// the DHParameter
+pub fn dh_public_key(params: &[u8], private_key: &[u8]) -> Result<Vec<u8>> {
+    let params = read_params(params)?;
+    let x = yasna::parse_der(private_key, read_uint)
+        .map_err(|e| Error::BadDh(format!("bad private value: {}", e)))?;
+    let y = params.g.modpow(&x, &params.p);
+    Ok(yasna::construct_der(|writer| {
+        writer.write_bigint_bytes(&y.to_bytes_be(), true)
+    }))
+}
+
+/// Test whether DER bytes are a DHParameter, rather than the PKCS1
+/// RSAPublicKey it looks like.  An RSA exponent is odd, so a DHParameter
+/// needs an even generator, such as the usual 2, or a privateValueLength.
+pub fn is_dh_parameters_der(der: &[u8]) -> bool {
+    match read_params(der) {
+        Ok(params) => {
+            let even = params.g.to_bytes_le().first().is_some_and(|b| b & 1 == 0);
+            params.g < params.p && (params.private_value_length.is_some() || even)
+        }
+        Err(_) => false,
+    }
+}
+
+/// Test whether the text is a `DH PARAMETERS` PEM document
+pub fn is_dh_parameters_pem(text: &str) -> bool {
+    pem::label(text.trim_start().as_bytes()).is_ok_and(|label| label == pem::DH_PARAMETERS)
+}
+
+/// Turn a DHParameter document into KeyInfo, whose bytes are the DHParameter
+pub fn dh_parameters_to_key_info(der: &[u8], encoding: Encoding) -> Result<KeyInfo> {
+    let params = read_params(der)?;
+    let mut key_info = KeyInfo::new()
+        .with_key_type(KeyType::Parameters)
+        .with_format(Format::DH)
+        .with_encoding(encoding)
+        .with_alg(Alg::Dh)
+        .with_oid(&oids::DH)
+        .with_key_length(params.p.bits() as u32)
+        .with_bytes(der);
+    key_info.params = Some(der.to_vec());
+    Ok(key_info)
+}
+
+/// Write DH parameters as a `DH PARAMETERS` PEM document, or as DER
+pub fn dh_parameters_to_stream(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let bytes = key_info
+        .bytes
+        .clone()
+        .ok_or(Error::MissingInput("DH parameters".to_owned()))?;
+    match app_state.encoding {
+        Encoding::DER => app_state.write_stream(&bytes),
+        Encoding::PEM => {
+            let text = pem::wrap(pem::DH_PARAMETERS, &bytes)?;
+            app_state.write_stream(text.as_bytes())
+        }
+        _ => bail!(Error::NotSupported),
+    }
+}
@@ -0,0 +1,109 @@
+//! DSA keys ([FIPS 186-4](https://csrc.nist.gov/pubs/fips/186-4/final)), so
+//! that old keys can be identified and moved to PKCS8
+//!
+//! OpenSSL wrote DSA private keys in its traditional `DSA PRIVATE KEY` form,
+//! a SEQUENCE of the version 0, p, q, g, the public key y and the private key
+//! x.  PKCS8 and SPKI ([RFC 3279](https://www.rfc-editor.org/rfc/rfc3279))
+//! hold p, q and g as the Dss-Parms of the AlgorithmIdentifier, and x or y as
+//! an INTEGER.
+//!
+//! DSA keys are read from any of these, and written as PKCS8 or SPKI.  kt
+//! does not sign with DSA keys.
+use anyhow::Result;
+use rsa::BigUint;
+use yasna::{ASN1Error, ASN1ErrorKind, ASN1Result, BERReader, DERWriter};
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+use crate::key_info::{Alg, Encoding, Format, KeyInfo, KeyType};
+use crate::oids;
+use crate::pem;
+
+/// Read an INTEGER that must not be negative
+fn read_uint(reader: BERReader) -> ASN1Result<BigUint> {
+    match reader.read_bigint_bytes()? {
+        (bytes, true) => Ok(BigUint::from_bytes_be(&bytes)),
+        (_, false) => Err(ASN1Error::new(ASN1ErrorKind::Invalid)),
+    }
+}
+
+fn write_uint(writer: DERWriter, n: &BigUint) {
+    writer.write_bigint_bytes(&n.to_bytes_be(), true)
+}
+
+fn uint_der(n: &BigUint) -> Vec<u8> {
+    yasna::construct_der(|writer| write_uint(writer, n))
+}
+
+/// The p, q and g of the Dss-Parms
+fn read_params(params: &[u8]) -> Result<(BigUint, BigUint, BigUint)> {
+    yasna::parse_der(params, |reader| {
+        reader.read_sequence(|reader| {
+            Ok((
+                read_uint(reader.next())?,
+                read_uint(reader.next())?,
+                read_uint(reader.next())?,
+            ))
+        })
+    })
+    .map_err(|e| Error::BadDsa(format!("bad Dss-Parms: {}", e)).into())
+}
+
+/// The length of a DSA key, the size of p in bits, of its Dss-Parms
+pub fn dsa_key_length(params: &[u8]) -> Option<u32> {
+    read_params(params).ok().map(|(p, _, _)| p.bits() as u32)
+}
+
+/// The INTEGER public key y = g^x mod p of the INTEGER private key x and the
+/// Dss-Parms
+pub fn dsa_public_key(params: &[u8], private_key: &[u8]) -> Result<Vec<u8>> {
+    let (p, _, g) = read_params(params)?;
+    let x = yasna::parse_der(private_key, read_uint)
+        .map_err(|e| Error::BadDsa(format!("bad private key: {}", e)))?;
+    Ok(uint_der(&g.modpow(&x, &p)))
+}
+
+/// Test whether the text is a traditional `DSA PRIVATE KEY` PEM document
+pub fn is_dsa_pem(text: &str) -> bool {
+    pem::label(text.trim_start().as_bytes()).is_ok_and(|label| label == pem::DSA_PRIVATE_KEY)
+}
+
+/// Turn a traditional DSAPrivateKey document into KeyInfo bytes, as PKCS8
+/// would hold it
+pub fn dsa_to_key_info(der: &[u8], encoding: Encoding) -> Result<KeyInfo> {
+    let (p, q, g, y, x) = yasna::parse_der(der, |reader| {
+        reader.read_sequence(|reader| {
+            if reader.next().read_u8()? != 0 {
+                return Err(ASN1Error::new(ASN1ErrorKind::Invalid));
+            }
+            Ok((
+                read_uint(reader.next())?,
+                read_uint(reader.next())?,
+                read_uint(reader.next())?,
+                read_uint(reader.next())?,
+                read_uint(reader.next())?,
+            ))
+        })
+    })
+    .map_err(|e| Error::BadDsa(e.to_string()))?;
+    let x = Zeroizing::new(x);
+
+    let params = yasna::construct_der(|writer| {
+        writer.write_sequence(|writer| {
+            write_uint(writer.next(), &p);
+            write_uint(writer.next(), &q);
+            write_uint(writer.next(), &g);
+        })
+    });
+    let mut key_info = KeyInfo::new()
+        .with_key_type(KeyType::Private)
+        .with_format(Format::DSA)
+        .with_encoding(encoding)
+        .with_alg(Alg::Dsa)
+        .with_oid(&oids::DSA)
+        .with_key_length(p.bits() as u32)
+        .with_bytes(&Zeroizing::new(uint_der(&x)))
+        .with_public_key(&uint_der(&y));
+    key_info.params = Some(params);
+    Ok(key_info)
+}
@@ -0,0 +1,241 @@
+//! Ethereum keystore files, version 3 ([Web3 Secret Storage](https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/))
+//!
+//! A keystore holds a secp256k1 private key, encrypted with AES-128-CTR
+//! under a key derived from the password with scrypt or PBKDF2-HMAC-SHA256.
+//! The MAC of the file is the Keccak-256 of the second half of the derived
+//! key and the ciphertext, so a wrong password is reported, rather than
+//! giving a wrong key.
+//!
+//! The address of a key is the last 20 bytes of the Keccak-256 of its public
+//! point, written with the mixed case checksum of
+//! [EIP-55](https://eips.ethereum.org/EIPS/eip-55).
+//!
+//! ```rust
+//! use kt::document::ethereum_docs::checksum_address;
+//!
+//! assert_eq!(
+//!     checksum_address(&hex::decode("5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap()),
+//!     "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+//! );
+//! ```
+use aes::cipher::{KeyIvInit, StreamCipher};
+use anyhow::{bail, Result};
+use log::warn;
+use pbkdf2::pbkdf2_hmac;
+use pkcs8::der::Document;
+use sec1::EcPrivateKeyDocument;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::Sha256;
+use sha3::{Digest, Keccak256};
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::sec1_docs::sec1_to_private_key_info;
+use crate::errors::Error;
+use crate::key_info::{Curve, Encoding, Format, KeyInfo};
+use crate::secp256k1;
+use crate::signature::ec_public_point;
+
+/// Largest scrypt cost, as log2 of N, that is read.  Keystores written by
+/// geth use 18.
+const MAX_SCRYPT_LOG_N: u8 = 22;
+
+fn bad_ethereum(message: &str) -> anyhow::Error {
+    Error::BadEthereum(message.to_owned()).into()
+}
+
+#[derive(Debug, Deserialize)]
+struct CipherParams {
+    iv: String,
+}
+
+/// The encrypted key and how to decrypt it
+#[derive(Debug, Deserialize)]
+struct Crypto {
+    cipher: String,
+    cipherparams: CipherParams,
+    ciphertext: String,
+    kdf: String,
+    kdfparams: Value,
+    mac: String,
+}
+
+/// A version 3 keystore
+#[derive(Debug, Deserialize)]
+pub struct Keystore {
+    version: u32,
+    /// Address of the key, in hex, without 0x
+    address: Option<String>,
+    // Older versions of geth wrote "Crypto"
+    #[serde(alias = "Crypto")]
+    crypto: Crypto,
+}
+
+impl Keystore {
+    pub fn from_json(json: &str) -> Result<Self> {
+        let keystore: Keystore =
+            serde_json::from_str(json).map_err(|e| Error::BadEthereum(e.to_string()))?;
+        if keystore.version != 3 {
+            bail!(Error::BadEthereum(format!(
+                "version {} keystores are not supported",
+                keystore.version
+            )));
+        }
+        Ok(keystore)
+    }
+}
+
+/// Test whether the bytes are a keystore: JSON with a version and crypto
+pub fn is_ethereum_keystore(bytes: &[u8]) -> bool {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(Value::Object(object)) => {
+            object.get("version").is_some_and(Value::is_u64)
+                && (object.contains_key("crypto") || object.contains_key("Crypto"))
+        }
+        _ => false,
+    }
+}
+
+fn hex_field(name: &str, value: &str) -> Result<Vec<u8>> {
+    hex::decode(value.trim_start_matches("0x"))
+        .map_err(|_| Error::BadEthereum(format!("bad hex {}", name)).into())
+}
+
+fn kdf_u32(params: &Value, name: &str) -> Result<u32> {
+    params
+        .get(name)
+        .and_then(Value::as_u64)
+        .and_then(|n| u32::try_from(n).ok())
+        .ok_or_else(|| Error::BadEthereum(format!("missing kdfparams {}", name)).into())
+}
+
+/// The key derived from the password, by the KDF of the keystore
+fn derive_key(crypto: &Crypto, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let params = &crypto.kdfparams;
+    let salt = hex_field(
+        "salt",
+        params
+            .get("salt")
+            .and_then(Value::as_str)
+            .unwrap_or_default(),
+    )?;
+    let dklen = kdf_u32(params, "dklen")? as usize;
+    if dklen < 32 {
+        bail!(Error::BadEthereum(
+            "the derived key is too short".to_owned()
+        ));
+    }
+    let mut key = Zeroizing::new(vec![0u8; dklen]);
+    match crypto.kdf.as_str() {
+        "scrypt" => {
+            let n = kdf_u32(params, "n")?;
+            if !n.is_power_of_two() || n < 2 {
+                bail!(Error::BadEthereum(
+                    "scrypt n is not a power of 2".to_owned()
+                ));
+            }
+            let log_n = n.trailing_zeros() as u8;
+            if log_n > MAX_SCRYPT_LOG_N {
+                bail!(Error::BadEthereum("scrypt n is too large".to_owned()));
+            }
+            let scrypt_params =
+                scrypt::Params::new(log_n, kdf_u32(params, "r")?, kdf_u32(params, "p")?)
+                    .map_err(|_| bad_ethereum("unsupported scrypt parameters"))?;
+            scrypt::scrypt(password.as_bytes(), &salt, &scrypt_params, &mut key)
+                .map_err(|_| bad_ethereum("bad scrypt parameters"))?;
+        }
+        "pbkdf2" => {
+            match params.get("prf").and_then(Value::as_str) {
+                Some("hmac-sha256") => {}
+                _ => bail!(Error::BadEthereum("unsupported PBKDF2 PRF".to_owned())),
+            }
+            pbkdf2_hmac::<Sha256>(password.as_bytes(), &salt, kdf_u32(params, "c")?, &mut key);
+        }
+        kdf => bail!(Error::BadEthereum(format!("unsupported kdf {}", kdf))),
+    }
+    Ok(key)
+}
+
+/// Decrypt the private key of a keystore.  The MAC is checked first, so a
+/// wrong password is Error::BadPassword.
+pub fn decrypt_keystore(keystore: &Keystore, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let crypto = &keystore.crypto;
+    if crypto.cipher != "aes-128-ctr" {
+        bail!(Error::BadEthereum(format!(
+            "unsupported cipher {}",
+            crypto.cipher
+        )));
+    }
+    let ciphertext = hex_field("ciphertext", &crypto.ciphertext)?;
+    let iv = hex_field("iv", &crypto.cipherparams.iv)?;
+    let mac = hex_field("mac", &crypto.mac)?;
+    if iv.len() != 16 {
+        bail!(Error::BadEthereum("bad iv".to_owned()));
+    }
+
+    let key = derive_key(crypto, password)?;
+    if Keccak256::digest([&key[16..32], &ciphertext].concat())[..] != mac[..] {
+        bail!(Error::BadPassword);
+    }
+    let mut private_key = Zeroizing::new(ciphertext);
+    ctr::Ctr128BE::<aes::Aes128>::new(key[..16].into(), iv[..].into())
+        .apply_keystream(&mut private_key);
+    Ok(private_key)
+}
+
+/// The EIP-55 form of an address: hex, with the letters upper case where
+/// the Keccak-256 of the lower case hex has a high nibble
+pub fn checksum_address(address: &[u8]) -> String {
+    let lower = hex::encode(address);
+    let hash = Keccak256::digest(lower.as_bytes());
+    let mixed: String = lower
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (4 * (1 - i % 2))) & 0x0f;
+            match nibble >= 8 {
+                true => c.to_ascii_uppercase(),
+                false => c,
+            }
+        })
+        .collect();
+    format!("0x{}", mixed)
+}
+
+/// The address of a secp256k1 key, public or private
+pub fn ethereum_address(key_info: &KeyInfo) -> Result<String> {
+    if key_info.curve() != Some(Curve::Secp256k1) {
+        bail!(Error::UnknownCurve);
+    }
+    let point = secp256k1::uncompressed_point(&ec_public_point(key_info)?)?;
+    Ok(checksum_address(&Keccak256::digest(&point[1..])[12..]))
+}
+
+/// Read the private key of a keystore, decrypted with the input password
+pub fn ethereum_to_key_info(app_state: &AppState, bytes: &[u8]) -> Result<KeyInfo> {
+    let text = std::str::from_utf8(bytes).map_err(|_| bad_ethereum("not UTF-8"))?;
+    let keystore = Keystore::from_json(text)?;
+    let scalar = app_state.decrypt_input(|pwd| decrypt_keystore(&keystore, pwd))?;
+    if !secp256k1::is_valid_scalar(&scalar) {
+        bail!(Error::BadEthereum(
+            "the private key is not a secp256k1 key".to_owned()
+        ));
+    }
+    let sec1_doc = EcPrivateKeyDocument::from_der(&secp256k1::sec1_der(&scalar)?)?;
+    let key_info = sec1_to_private_key_info(&sec1_doc, Encoding::ETHEREUM)?
+        .with_format(Format::ETHEREUM)
+        .with_encrypted(true);
+
+    // The address is not protected by the MAC, so it is only a hint
+    if let Some(address) = &keystore.address {
+        let derived = ethereum_address(&key_info)?;
+        if !derived[2..].eq_ignore_ascii_case(address.trim_start_matches("0x")) {
+            warn!(
+                "The keystore address {} is not the address of the key, {}",
+                address, derived
+            );
+        }
+    }
+    Ok(key_info)
+}
@@ -0,0 +1,200 @@
+//! Password protected JSON Web Encryption ([RFC 7516](https://www.rfc-editor.org/rfc/rfc7516)) documents
+//!
+//! Private JWKs are protected the way [RFC 7517](https://www.rfc-editor.org/rfc/rfc7517#section-7)
+//! describes, with PBES2 key wrapping ([RFC 7518](https://www.rfc-editor.org/rfc/rfc7518#section-4.8))
+//! and AES GCM content encryption, in the JWE compact serialization.
+use aes_gcm::{
+    aead::{Aead, KeyInit, Payload},
+    aes::{Aes128, Aes192, Aes256},
+    AesGcm, Nonce,
+};
+use aes_kw::{KekAes128, KekAes192, KekAes256};
+use anyhow::{bail, Result};
+use base64ct::{Base64UrlUnpadded, Encoding as _};
+use pbkdf2::pbkdf2_hmac;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Sha256, Sha384, Sha512};
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+
+/// Key management algorithm used when protecting output
+pub const ALG_PBES2: &str = "PBES2-HS512+A256KW";
+/// Content encryption algorithm used when protecting output
+pub const ENC_AES_GCM: &str = "A256GCM";
+/// PBKDF2 iteration count used when protecting output
+pub const PBES2_ITERATIONS: u32 = 210_000;
+/// Content type of a protected JWK
+pub const CTY_JWK: &str = "jwk+json";
+
+const SALT_LEN: usize = 16;
+const IV_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// The protected header of a PBES2 JWE
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct JweHeader {
+    pub alg: String,
+    pub enc: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cty: Option<String>,
+    /// PBES2 salt input
+    pub p2s: String,
+    /// PBES2 iteration count
+    pub p2c: u32,
+}
+
+fn b64_decode(value: &str) -> Result<Vec<u8>> {
+    Base64UrlUnpadded::decode_vec(value).map_err(|_| Error::BadJwe("not base64url".to_owned()).into())
+}
+
+/// Test whether the text looks like a JWE compact serialization
+pub fn is_jwe(text: &str) -> bool {
+    let parts: Vec<&str> = text.trim().split('.').collect();
+    parts.len() == 5 && parts.iter().all(|part| Base64UrlUnpadded::decode_vec(part).is_ok())
+}
+
+/// Derive the key encryption key from the password.
+///
+/// The PBKDF2 salt is the algorithm name, a zero byte, and the salt input.
+fn pbes2_kek(header: &JweHeader, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let mut salt = header.alg.as_bytes().to_vec();
+    salt.push(0);
+    salt.extend_from_slice(&b64_decode(&header.p2s)?);
+
+    let password = password.as_bytes();
+    let rounds = header.p2c;
+    let kek = match header.alg.as_str() {
+        "PBES2-HS256+A128KW" => {
+            let mut kek = Zeroizing::new(vec![0u8; 16]);
+            pbkdf2_hmac::<Sha256>(password, &salt, rounds, &mut kek);
+            kek
+        }
+        "PBES2-HS384+A192KW" => {
+            let mut kek = Zeroizing::new(vec![0u8; 24]);
+            pbkdf2_hmac::<Sha384>(password, &salt, rounds, &mut kek);
+            kek
+        }
+        "PBES2-HS512+A256KW" => {
+            let mut kek = Zeroizing::new(vec![0u8; 32]);
+            pbkdf2_hmac::<Sha512>(password, &salt, rounds, &mut kek);
+            kek
+        }
+        alg => bail!(Error::BadJwe(format!("unsupported alg {}", alg))),
+    };
+    Ok(kek)
+}
+
+fn key_wrap(kek: &[u8], cek: &[u8]) -> Result<Vec<u8>> {
+    let wrapped = match kek.len() {
+        16 => KekAes128::try_from(kek).and_then(|kek| kek.wrap_vec(cek)),
+        24 => KekAes192::try_from(kek).and_then(|kek| kek.wrap_vec(cek)),
+        _ => KekAes256::try_from(kek).and_then(|kek| kek.wrap_vec(cek)),
+    };
+    wrapped.map_err(|_| Error::BadCrypto.into())
+}
+
+fn key_unwrap(kek: &[u8], wrapped: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let cek = match kek.len() {
+        16 => KekAes128::try_from(kek).and_then(|kek| kek.unwrap_vec(wrapped)),
+        24 => KekAes192::try_from(kek).and_then(|kek| kek.unwrap_vec(wrapped)),
+        _ => KekAes256::try_from(kek).and_then(|kek| kek.unwrap_vec(wrapped)),
+    };
+    // A failed integrity check almost always means a wrong password
+    cek.map(Zeroizing::new).map_err(|_| Error::BadPassword.into())
+}
+
+fn cek_len(enc: &str) -> Result<usize> {
+    match enc {
+        "A128GCM" => Ok(16),
+        "A192GCM" => Ok(24),
+        "A256GCM" => Ok(32),
+        enc => bail!(Error::BadJwe(format!("unsupported enc {}", enc))),
+    }
+}
+
+/// AES GCM with the CEK size selecting the AES variant
+fn aes_gcm(cek: &[u8], iv: &[u8], payload: Payload, encrypt: bool) -> Result<Vec<u8>> {
+    fn run<C: Aead + KeyInit>(cek: &[u8], iv: &[u8], payload: Payload, encrypt: bool) -> Result<Vec<u8>> {
+        let cipher = C::new_from_slice(cek).map_err(|_| Error::BadCrypto)?;
+        let nonce = Nonce::from_slice(iv);
+        let result = match encrypt {
+            true => cipher.encrypt(nonce, payload),
+            false => cipher.decrypt(nonce, payload),
+        };
+        result.map_err(|_| Error::BadCrypto.into())
+    }
+    if iv.len() != IV_LEN {
+        bail!(Error::BadJwe("bad iv".to_owned()));
+    }
+    match cek.len() {
+        16 => run::<AesGcm<Aes128, aes_gcm::aead::consts::U12>>(cek, iv, payload, encrypt),
+        24 => run::<AesGcm<Aes192, aes_gcm::aead::consts::U12>>(cek, iv, payload, encrypt),
+        _ => run::<AesGcm<Aes256, aes_gcm::aead::consts::U12>>(cek, iv, payload, encrypt),
+    }
+}
+
+/// Encrypt the plaintext with a password, as a JWE compact serialization
+pub fn jwe_encrypt(plaintext: &[u8], password: &str, cty: &str) -> Result<String> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let header = JweHeader {
+        alg: ALG_PBES2.to_owned(),
+        enc: ENC_AES_GCM.to_owned(),
+        cty: Some(cty.to_owned()),
+        p2s: Base64UrlUnpadded::encode_string(&salt),
+        p2c: PBES2_ITERATIONS,
+    };
+    let protected = Base64UrlUnpadded::encode_string(serde_json::to_string(&header)?.as_bytes());
+
+    let kek = pbes2_kek(&header, password)?;
+    let mut cek = Zeroizing::new(vec![0u8; cek_len(&header.enc)?]);
+    OsRng.fill_bytes(&mut cek);
+    let encrypted_key = key_wrap(&kek, &cek)?;
+
+    let mut iv = [0u8; IV_LEN];
+    OsRng.fill_bytes(&mut iv);
+    let payload = Payload {
+        msg: plaintext,
+        aad: protected.as_bytes(),
+    };
+    let mut ciphertext = aes_gcm(&cek, &iv, payload, true)?;
+    let tag = ciphertext.split_off(ciphertext.len() - TAG_LEN);
+
+    Ok([
+        protected,
+        Base64UrlUnpadded::encode_string(&encrypted_key),
+        Base64UrlUnpadded::encode_string(&iv),
+        Base64UrlUnpadded::encode_string(&ciphertext),
+        Base64UrlUnpadded::encode_string(&tag),
+    ]
+    .join("."))
+}
+
+/// Decrypt a password protected JWE compact serialization
+pub fn jwe_decrypt(jwe: &str, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let parts: Vec<&str> = jwe.trim().split('.').collect();
+    if parts.len() != 5 {
+        bail!(Error::BadJwe("not a compact serialization".to_owned()));
+    }
+    let protected = parts[0];
+    let header: JweHeader = serde_json::from_slice(&b64_decode(protected)?)
+        .map_err(|e| Error::BadJwe(e.to_string()))?;
+    cek_len(&header.enc)?;
+
+    let kek = pbes2_kek(&header, password)?;
+    let cek = key_unwrap(&kek, &b64_decode(parts[1])?)?;
+    if cek.len() != cek_len(&header.enc)? {
+        bail!(Error::BadJwe("bad content encryption key".to_owned()));
+    }
+
+    let mut ciphertext = b64_decode(parts[3])?;
+    ciphertext.extend_from_slice(&b64_decode(parts[4])?);
+    let payload = Payload {
+        msg: &ciphertext,
+        aad: protected.as_bytes(),
+    };
+    let plaintext = aes_gcm(&cek, &b64_decode(parts[2])?, payload, false)?;
+    Ok(Zeroizing::new(plaintext))
+}
@@ -0,0 +1,925 @@
+//! JSON Web Key ([RFC 7517](https://www.rfc-editor.org/rfc/rfc7517)) documents
+//!
+//! RSA and elliptic curve keys follow [RFC 7518](https://www.rfc-editor.org/rfc/rfc7518#section-6.2),
+//! and Octet Key Pair keys (Ed25519, Ed448, X25519, X448) follow
+//! [RFC 8037](https://www.rfc-editor.org/rfc/rfc8037).  Symmetric keys use the
+//! "oct" key type from [RFC 7518](https://www.rfc-editor.org/rfc/rfc7518#section-6.4).
+//! ML-DSA keys use the "AKP" key type of the draft
+//! [ML-DSA for JOSE and COSE](https://datatracker.ietf.org/doc/draft-ietf-cose-dilithium/),
+//! whose private key is the seed.
+//!
+//! ```rust
+//! use kt::app_state::AppState;
+//! use kt::document::jwk_docs::{jwk_to_key_info, key_info_as_jwk};
+//! use kt::key_info::{Alg, KeyInfo, KeyType};
+//!
+//! // An RSA private key round trips through a private JWK
+//! let der = std::fs::read("test_data/rsa-2048-private-pk1.der").unwrap();
+//! let key_info = KeyInfo::new()
+//!     .with_alg(Alg::Rsa)
+//!     .with_key_type(KeyType::Private)
+//!     .with_bytes(&der);
+//! let jwk = key_info_as_jwk(&AppState::default(), &key_info).unwrap();
+//! assert!(jwk.is_private() && jwk.p.is_some() && jwk.qi.is_some());
+//! assert_eq!(jwk_to_key_info(&jwk).unwrap().bytes.as_deref(), Some(&der));
+//! ```
+use anyhow::{bail, Result};
+use base64ct::{Base64, Base64UrlUnpadded, Encoding as _};
+use log::warn;
+use pkcs1::{RsaPrivateKey, RsaPublicKey};
+use pkcs8::der::{
+    asn1::{OctetString, UIntBytes},
+    Decodable, Encodable,
+};
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::BigUint;
+use sec1::{EcParameters, EcPrivateKey};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::compare::public_material;
+use crate::document::jwe_docs::{jwe_encrypt, CTY_JWK};
+use crate::document::sec1_docs::ec_point_to_key_info;
+use crate::document::openssh_docs::with_public_key;
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::ml_dsa;
+use crate::pem;
+use crate::signature::ec_public_point;
+
+/// JWK key type for RSA keys
+pub const KTY_RSA: &str = "RSA";
+/// JWK key type for Elliptic Curve keys
+pub const KTY_EC: &str = "EC";
+/// JWK key type for Octet Key Pairs
+pub const KTY_OKP: &str = "OKP";
+/// JWK key type for symmetric keys
+pub const KTY_OCT: &str = "oct";
+/// JWK key type for Algorithm Key Pairs, such as ML-DSA keys
+pub const KTY_AKP: &str = "AKP";
+
+/// A single JSON Web Key.
+///
+/// Only the members needed for the supported key types are represented.
+/// Members are base64url encoded, without padding.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Jwk {
+    /// Key type, such as "OKP"
+    pub kty: String,
+    /// Key ID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    /// Curve name, such as "Ed25519" or "P-256"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crv: Option<String>,
+    /// Public key, or the X coordinate of an EC public point
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x: Option<String>,
+    /// Y coordinate of an EC public point
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+    /// RSA modulus
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<String>,
+    /// RSA public exponent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub e: Option<String>,
+    /// Private key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub d: Option<String>,
+    /// RSA first prime factor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p: Option<String>,
+    /// RSA second prime factor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    /// RSA first factor CRT exponent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dp: Option<String>,
+    /// RSA second factor CRT exponent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dq: Option<String>,
+    /// RSA first CRT coefficient
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qi: Option<String>,
+    /// Symmetric key value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub k: Option<String>,
+    /// Public key of an Algorithm Key Pair
+    #[serde(rename = "pub", skip_serializing_if = "Option::is_none")]
+    pub public: Option<String>,
+    /// Private key of an Algorithm Key Pair, the seed of an ML-DSA key
+    #[serde(rename = "priv", skip_serializing_if = "Option::is_none")]
+    pub private: Option<String>,
+    /// JOSE algorithm the key is intended for, such as "ES256"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+    /// Public key use, "sig" or "enc"
+    #[serde(rename = "use", skip_serializing_if = "Option::is_none")]
+    pub key_use: Option<String>,
+    /// Operations the key is intended for, such as "verify"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_ops: Option<Vec<String>>,
+    /// Certificate chain, as base64 (not base64url) DER, starting with the
+    /// certificate of the key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x5c: Option<Vec<String>>,
+    /// SHA-1 thumbprint of the certificate of the key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub x5t: Option<String>,
+    /// SHA-256 thumbprint of the certificate of the key
+    #[serde(rename = "x5t#S256", skip_serializing_if = "Option::is_none")]
+    pub x5t_s256: Option<String>,
+}
+
+impl Jwk {
+    /// Parse a JWK from its JSON representation
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| Error::BadJwk(e.to_string()).into())
+    }
+
+    /// Serialize the JWK to canonical JSON.  See [canonical_json].
+    pub fn to_json(&self, pretty: bool) -> Result<String> {
+        canonical_json(self, pretty)
+    }
+
+    /// Test whether the JWK has private or symmetric key members
+    pub fn is_private(&self) -> bool {
+        self.d.is_some() || self.k.is_some() || self.private.is_some()
+    }
+
+    /// A copy of the JWK without any private or symmetric key members
+    pub fn to_public(&self) -> Self {
+        Self {
+            d: None,
+            p: None,
+            q: None,
+            dp: None,
+            dq: None,
+            qi: None,
+            k: None,
+            private: None,
+            ..self.clone()
+        }
+    }
+
+    /// The [RFC 7638](https://www.rfc-editor.org/rfc/rfc7638) SHA-256 thumbprint
+    ///
+    /// The hash input is the required public members only, in lexicographic
+    /// order and without whitespace.
+    pub fn thumbprint(&self) -> Result<String> {
+        let members: BTreeMap<&str, &str> = match self.kty.as_str() {
+            KTY_RSA => BTreeMap::from([
+                ("e", required("e", &self.e)?),
+                ("kty", KTY_RSA),
+                ("n", required("n", &self.n)?),
+            ]),
+            KTY_EC => BTreeMap::from([
+                ("crv", required("crv", &self.crv)?),
+                ("kty", KTY_EC),
+                ("x", required("x", &self.x)?),
+                ("y", required("y", &self.y)?),
+            ]),
+            KTY_OKP => BTreeMap::from([
+                ("crv", required("crv", &self.crv)?),
+                ("kty", KTY_OKP),
+                ("x", required("x", &self.x)?),
+            ]),
+            KTY_OCT => BTreeMap::from([("k", required("k", &self.k)?), ("kty", KTY_OCT)]),
+            KTY_AKP => BTreeMap::from([
+                ("alg", required("alg", &self.alg)?),
+                ("kty", KTY_AKP),
+                ("pub", required("pub", &self.public)?),
+            ]),
+            kty => bail!(Error::BadJwk(format!("unsupported key type {}", kty))),
+        };
+        let json = serde_json::to_string(&members)?;
+        let digest = Sha256::digest(json.as_bytes());
+        Ok(b64_encode(&digest))
+    }
+}
+
+/// Rebuild each object of the value with its members in lexicographic order
+fn sort_members(value: Value) -> Value {
+    match value {
+        Value::Object(members) => {
+            let sorted: BTreeMap<String, Value> = members
+                .into_iter()
+                .map(|(name, value)| (name, sort_members(value)))
+                .collect();
+            Value::Object(sorted.into_iter().collect())
+        }
+        Value::Array(values) => Value::Array(values.into_iter().map(sort_members).collect()),
+        value => value,
+    }
+}
+
+/// Serialize a JWK or JWKS as canonical JSON, so that the same key always
+/// produces the same bytes.
+///
+/// Object members are in lexicographic order, and there is no insignificant
+/// whitespace.  `pretty` keeps the order, but indents the output.
+pub fn canonical_json<T: Serialize>(doc: &T, pretty: bool) -> Result<String> {
+    let value = serde_json::to_value(doc).map_err(|e| Error::BadJwk(e.to_string()))?;
+    let value = sort_members(value);
+    let json = match pretty {
+        true => serde_json::to_string_pretty(&value),
+        false => serde_json::to_string(&value),
+    };
+    json.map_err(|e| Error::BadJwk(e.to_string()).into())
+}
+
+/// A JSON Web Key Set
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Jwks {
+    pub keys: Vec<Jwk>,
+}
+
+impl Jwks {
+    /// Parse a JWKS from its JSON representation
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).map_err(|e| Error::BadJwk(e.to_string()).into())
+    }
+
+    /// Serialize the JWKS to canonical JSON.  See [canonical_json].
+    pub fn to_json(&self, pretty: bool) -> Result<String> {
+        canonical_json(self, pretty)
+    }
+
+    /// Select a key by kid.  Without a kid, the set must hold only one key.
+    pub fn select(&self, kid: Option<&str>) -> Result<&Jwk> {
+        match kid {
+            Some(kid) => self
+                .keys
+                .iter()
+                .find(|jwk| jwk.kid.as_deref() == Some(kid))
+                .ok_or_else(|| Error::BadJwk(format!("no key with kid {}", kid)).into()),
+            None => match self.keys.as_slice() {
+                [jwk] => Ok(jwk),
+                _ => Err(Error::MissingInput("kid to select a key from the JWKS".to_owned()).into()),
+            },
+        }
+    }
+}
+
+/// Public key use ([RFC 7517](https://www.rfc-editor.org/rfc/rfc7517#section-4.2))
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyUse {
+    /// Signatures
+    Sig,
+    /// Encryption, including key wrapping and key agreement
+    Enc,
+}
+
+impl KeyUse {
+    pub fn all() -> Vec<&'static str> {
+        vec!["sig", "enc"]
+    }
+}
+
+impl FromStr for KeyUse {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<KeyUse> {
+        match s.to_lowercase().as_str() {
+            "sig" => Ok(KeyUse::Sig),
+            "enc" => Ok(KeyUse::Enc),
+            _ => Err(Error::BadJwk(format!("unknown use {}", s)).into()),
+        }
+    }
+}
+
+impl fmt::Display for KeyUse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txt = match self {
+            KeyUse::Sig => "sig",
+            KeyUse::Enc => "enc",
+        };
+        write!(f, "{}", txt)
+    }
+}
+
+/// The JWK `key_ops` values ([RFC 7517](https://www.rfc-editor.org/rfc/rfc7517#section-4.3))
+pub fn key_ops_all() -> Vec<&'static str> {
+    vec![
+        "sign",
+        "verify",
+        "encrypt",
+        "decrypt",
+        "wrapKey",
+        "unwrapKey",
+        "deriveKey",
+        "deriveBits",
+    ]
+}
+
+/// The use that a key operation belongs to
+fn key_op_use(key_op: &str) -> Result<KeyUse> {
+    match key_op {
+        "sign" | "verify" => Ok(KeyUse::Sig),
+        "encrypt" | "decrypt" | "wrapKey" | "unwrapKey" | "deriveKey" | "deriveBits" => {
+            Ok(KeyUse::Enc)
+        }
+        _ => Err(Error::BadJwk(format!("unknown key operation {}", key_op)).into()),
+    }
+}
+
+/// The use of a JOSE algorithm
+/// ([RFC 7518](https://www.rfc-editor.org/rfc/rfc7518#section-3.1)), if it
+/// applies to the key
+fn jose_alg_use(jose_alg: &str, key_info: &KeyInfo) -> Result<KeyUse> {
+    let alg = key_info.alg;
+    let curve = key_info.curve();
+    let (key_use, applies) = match jose_alg {
+        "RS256" | "RS384" | "RS512" => (KeyUse::Sig, alg == Alg::Rsa),
+        "RSA-OAEP" | "RSA-OAEP-256" | "RSA1_5" => (KeyUse::Enc, alg == Alg::Rsa),
+        "PS256" | "PS384" | "PS512" => (KeyUse::Sig, matches!(alg, Alg::Rsa | Alg::RsaSsaPss)),
+        "ES256" => (KeyUse::Sig, curve == Some(Curve::P256)),
+        "ES384" => (KeyUse::Sig, curve == Some(Curve::P384)),
+        "ES512" => (KeyUse::Sig, curve == Some(Curve::P521)),
+        "ES256K" => (KeyUse::Sig, curve == Some(Curve::Secp256k1)),
+        "EdDSA" => (KeyUse::Sig, matches!(alg, Alg::EdDsa25519 | Alg::EdDsa448)),
+        "ECDH-ES" | "ECDH-ES+A128KW" | "ECDH-ES+A192KW" | "ECDH-ES+A256KW" => (
+            KeyUse::Enc,
+            matches!(alg, Alg::Ecdsa | Alg::X25519 | Alg::X448),
+        ),
+        "ML-DSA-44" => (KeyUse::Sig, alg == Alg::MlDsa44),
+        "ML-DSA-65" => (KeyUse::Sig, alg == Alg::MlDsa65),
+        "ML-DSA-87" => (KeyUse::Sig, alg == Alg::MlDsa87),
+        "HS256" | "HS384" | "HS512" => (KeyUse::Sig, alg == Alg::Symmetric),
+        "A128KW" | "A192KW" | "A256KW" | "A128GCMKW" | "A192GCMKW" | "A256GCMKW" | "dir" => {
+            (KeyUse::Enc, alg == Alg::Symmetric)
+        }
+        _ => bail!(Error::BadJwk(format!("unknown alg {}", jose_alg))),
+    };
+    if !applies {
+        bail!(Error::BadJwk(format!(
+            "alg {} does not apply to {} keys",
+            jose_alg, alg
+        )));
+    }
+    Ok(key_use)
+}
+
+/// The only use of keys that can either sign or encrypt, but not both
+fn key_alg_use(alg: Alg) -> Option<KeyUse> {
+    match alg {
+        Alg::EdDsa25519 | Alg::EdDsa448 => Some(KeyUse::Sig),
+        Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87 => Some(KeyUse::Sig),
+        Alg::X25519 | Alg::X448 => Some(KeyUse::Enc),
+        _ => None,
+    }
+}
+
+/// Add the `alg`, `use` and `key_ops` members requested in the [AppState].
+///
+/// The members must agree with each other, and with the key.
+fn add_metadata(app_state: &AppState, key_info: &KeyInfo, jwk: &mut Jwk) -> Result<()> {
+    // Each member that is given implies a use, and they must all be the same
+    let mut uses: Vec<(String, KeyUse)> = Vec::new();
+    if let Some(key_use) = key_alg_use(key_info.alg) {
+        uses.push((format!("{} keys", key_info.alg), key_use));
+    }
+    if let Some(jose_alg) = &app_state.jwk_alg {
+        uses.push((format!("alg {}", jose_alg), jose_alg_use(jose_alg, key_info)?));
+        jwk.alg = Some(jose_alg.clone());
+    }
+    if let Some(key_use) = app_state.key_use {
+        uses.push((format!("use {}", key_use), key_use));
+        jwk.key_use = Some(key_use.to_string());
+    }
+    if !app_state.key_ops.is_empty() {
+        for key_op in &app_state.key_ops {
+            uses.push((format!("key operation {}", key_op), key_op_use(key_op)?));
+        }
+        jwk.key_ops = Some(app_state.key_ops.clone());
+    }
+    if let Some((first, first_use)) = uses.first() {
+        let conflict = uses.iter().find(|(_, key_use)| key_use != first_use);
+        if let Some((other, other_use)) = conflict {
+            bail!(Error::BadJwk(format!(
+                "{} ({}) conflicts with {} ({})",
+                first, first_use, other, other_use
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Add the certificate chain as `x5c`, and the thumbprints of the first
+/// certificate as `x5t` and `x5t#S256`.
+///
+/// The first certificate must be for the key.
+fn add_certs(cert_file: &str, key_info: &KeyInfo, jwk: &mut Jwk) -> Result<()> {
+    let certs = pem::read_certs(cert_file)?;
+    // The certificate's SubjectPublicKeyInfo holds the same public key
+    // material, so the certificate must contain it
+    let public_key = public_material(key_info)?
+        .ok_or_else(|| Error::MissingInput("public key".to_owned()))?;
+    if !certs[0]
+        .windows(public_key.len())
+        .any(|window| window == public_key.as_slice())
+    {
+        bail!(Error::BadJwk(format!(
+            "the first certificate of {} is not for this key",
+            cert_file
+        )));
+    }
+
+    jwk.x5c = Some(certs.iter().map(|cert| Base64::encode_string(cert)).collect());
+    jwk.x5t = Some(b64_encode(&Sha1::digest(&certs[0])));
+    jwk.x5t_s256 = Some(b64_encode(&Sha256::digest(&certs[0])));
+    Ok(())
+}
+
+/// Map an OKP algorithm to its JWK curve name
+fn okp_crv(alg: Alg) -> Result<&'static str> {
+    match alg {
+        Alg::EdDsa25519 => Ok("Ed25519"),
+        Alg::EdDsa448 => Ok("Ed448"),
+        Alg::X25519 => Ok("X25519"),
+        Alg::X448 => Ok("X448"),
+        _ => Err(Error::UnknownAlg.into()),
+    }
+}
+
+/// Map a JWK OKP curve name to its algorithm
+fn crv_to_okp(crv: &str) -> Result<Alg> {
+    match crv {
+        "Ed25519" => Ok(Alg::EdDsa25519),
+        "Ed448" => Ok(Alg::EdDsa448),
+        "X25519" => Ok(Alg::X25519),
+        "X448" => Ok(Alg::X448),
+        _ => Err(Error::BadJwk(format!("unsupported OKP curve {}", crv)).into()),
+    }
+}
+
+/// Map an ML-DSA algorithm to the JOSE algorithm of its AKP JWK
+fn akp_alg(alg: Alg) -> Result<&'static str> {
+    match alg {
+        Alg::MlDsa44 => Ok("ML-DSA-44"),
+        Alg::MlDsa65 => Ok("ML-DSA-65"),
+        Alg::MlDsa87 => Ok("ML-DSA-87"),
+        _ => Err(Error::UnknownAlg.into()),
+    }
+}
+
+/// Map the JOSE algorithm of an AKP JWK to its algorithm
+fn alg_to_akp(alg: &str) -> Result<Alg> {
+    match alg {
+        "ML-DSA-44" => Ok(Alg::MlDsa44),
+        "ML-DSA-65" => Ok(Alg::MlDsa65),
+        "ML-DSA-87" => Ok(Alg::MlDsa87),
+        _ => Err(Error::BadJwk(format!("unsupported AKP alg {}", alg)).into()),
+    }
+}
+
+pub fn b64_decode(name: &str, value: &str) -> Result<Vec<u8>> {
+    Base64UrlUnpadded::decode_vec(value)
+        .map_err(|_| Error::BadJwk(format!("member {} is not base64url", name)).into())
+}
+
+pub fn b64_encode(bytes: &[u8]) -> String {
+    Base64UrlUnpadded::encode_string(bytes)
+}
+
+fn required<'a>(name: &str, value: &'a Option<String>) -> Result<&'a str> {
+    value
+        .as_deref()
+        .ok_or_else(|| Error::BadJwk(format!("missing member {}", name)).into())
+}
+
+/// Turn an RSA JWK into KeyInfo bytes
+///
+/// The members are assembled into a PKCS1 RSAPublicKey or RSAPrivateKey, so
+/// that the resulting KeyInfo matches one discovered from PKCS1.  The CRT
+/// members of a private key are computed from the primes, which are recovered
+/// from `d` if the JWK doesn't have them.
+fn rsa_jwk_to_key_info(jwk: &Jwk) -> Result<KeyInfo> {
+    let n = b64_decode("n", required("n", &jwk.n)?)?;
+    let e = b64_decode("e", required("e", &jwk.e)?)?;
+    let key_info = KeyInfo::new()
+        .with_alg(Alg::Rsa)
+        .with_encoding(Encoding::JWK)
+        .with_format(Format::JWK)
+        .with_key_length(rsa_key_length(&n));
+
+    let d = match &jwk.d {
+        Some(d) => Zeroizing::new(b64_decode("d", d)?),
+        None => {
+            let public_key = RsaPublicKey {
+                modulus: UIntBytes::new(&n)?,
+                public_exponent: UIntBytes::new(&e)?,
+            };
+            let pk1_doc = public_key.to_der()?;
+            return Ok(key_info
+                .with_key_type(KeyType::Public)
+                .with_bytes(pk1_doc.as_ref()));
+        }
+    };
+    let primes = match (&jwk.p, &jwk.q) {
+        (Some(p), Some(q)) => vec![
+            BigUint::from_bytes_be(&Zeroizing::new(b64_decode("p", p)?)),
+            BigUint::from_bytes_be(&Zeroizing::new(b64_decode("q", q)?)),
+        ],
+        _ => Vec::new(),
+    };
+    let private_key = rsa::RsaPrivateKey::from_components(
+        BigUint::from_bytes_be(&n),
+        BigUint::from_bytes_be(&e),
+        BigUint::from_bytes_be(&d),
+        primes,
+    )
+    .map_err(|e| Error::BadJwk(format!("bad RSA private key: {}", e)))?;
+    let pk1_doc = private_key
+        .to_pkcs1_der()
+        .map_err(|e| Error::BadJwk(format!("bad RSA private key: {}", e)))?;
+    Ok(key_info
+        .with_key_type(KeyType::Private)
+        .with_bytes(pk1_doc.as_bytes()))
+}
+
+/// The length of an RSA modulus in bits, rounded to whole bytes, as for
+/// keys discovered from PKCS1
+fn rsa_key_length(n: &[u8]) -> u32 {
+    let leading_zeros = n.iter().take_while(|byte| **byte == 0).count();
+    (n.len() - leading_zeros) as u32 * 8
+}
+
+/// Turn an EC JWK into KeyInfo bytes
+///
+/// The coordinates are assembled into an uncompressed SEC1 point, so that the
+/// resulting KeyInfo matches one discovered from SPKI.  A private key is
+/// assembled into a SEC1 ECPrivateKey, so that it matches one discovered from
+/// SEC1, and must match the point.
+fn ec_jwk_to_key_info(jwk: &Jwk) -> Result<KeyInfo> {
+    let curve = Curve::from_str(required("crv", &jwk.crv)?)?;
+    let x = b64_decode("x", required("x", &jwk.x)?)?;
+    let y = b64_decode("y", required("y", &jwk.y)?)?;
+
+    let mut point = vec![0x04];
+    point.extend_from_slice(&x);
+    point.extend_from_slice(&y);
+    let public_key_info = ec_point_to_key_info(&point, curve)?
+        .with_encoding(Encoding::JWK)
+        .with_format(Format::JWK);
+
+    let d = match &jwk.d {
+        Some(d) => Zeroizing::new(b64_decode("d", d)?),
+        None => return Ok(public_key_info),
+    };
+    if d.len() != curve.field_size() {
+        bail!(Error::BadJwk("member d is not the size of the curve".to_owned()));
+    }
+    let curve_oid = curve.oid();
+    let ec_private_key = EcPrivateKey {
+        private_key: &d,
+        parameters: Some(EcParameters::NamedCurve(curve_oid)),
+        public_key: Some(&point),
+    };
+    let sec1_der = Zeroizing::new(ec_private_key.to_vec()?);
+    let mut key_info = KeyInfo::new()
+        .with_alg(Alg::Ecdsa)
+        .with_key_type(KeyType::Private)
+        .with_encoding(Encoding::JWK)
+        .with_format(Format::JWK)
+        .with_bytes(&sec1_der);
+    key_info.set_oid(&curve_oid);
+
+    // Fails if d is not a valid scalar, or is not the private key of the point
+    if ec_public_point(&key_info).ok().as_ref() != Some(&point) {
+        bail!(Error::BadJwk("member d does not match the point".to_owned()));
+    }
+    Ok(key_info)
+}
+
+/// Turn an OKP JWK into KeyInfo bytes
+///
+/// The private key bytes are wrapped as a CurvePrivateKey OCTET STRING, so
+/// that the resulting KeyInfo matches one discovered from PKCS8.
+fn okp_jwk_to_key_info(jwk: &Jwk) -> Result<KeyInfo> {
+    let alg = crv_to_okp(required("crv", &jwk.crv)?)?;
+    let x = jwk
+        .x
+        .as_deref()
+        .map(|x| b64_decode("x", x))
+        .transpose()?;
+
+    let mut key_info = KeyInfo::new()
+        .with_alg(alg)
+        .with_encoding(Encoding::JWK)
+        .with_format(Format::JWK);
+
+    match &jwk.d {
+        Some(d) => {
+            let d = Zeroizing::new(b64_decode("d", d)?);
+            let private_key = Zeroizing::new(OctetString::new(&d)?.to_vec()?);
+            key_info.set_key_type(KeyType::Private);
+            key_info.set_bytes(&private_key);
+            if let Some(x) = x {
+                key_info.set_public_key(&x);
+            }
+        }
+        None => {
+            let x = x.ok_or_else(|| Error::BadJwk("missing member x".to_owned()))?;
+            key_info.set_key_type(KeyType::Public);
+            key_info.set_bytes(&x);
+        }
+    }
+    Ok(key_info)
+}
+
+/// Turn an AKP JWK into KeyInfo bytes
+///
+/// The seed is wrapped in the seed form of the ML-DSA private key, so that
+/// the resulting KeyInfo matches one discovered from PKCS8.
+fn akp_jwk_to_key_info(jwk: &Jwk) -> Result<KeyInfo> {
+    let alg = alg_to_akp(required("alg", &jwk.alg)?)?;
+    let public = jwk
+        .public
+        .as_deref()
+        .map(|public| b64_decode("pub", public))
+        .transpose()?;
+    if let Some(public) = &public {
+        if Some(public.len()) != ml_dsa::public_key_size(alg) {
+            bail!(Error::BadJwk(format!("bad {} public key length {}", alg, public.len())));
+        }
+    }
+
+    let mut key_info = KeyInfo::new()
+        .with_alg(alg)
+        .with_encoding(Encoding::JWK)
+        .with_format(Format::JWK);
+
+    match &jwk.private {
+        Some(private) => {
+            let seed = Zeroizing::new(b64_decode("priv", private)?);
+            if seed.len() != ml_dsa::SEED_SIZE {
+                bail!(Error::BadJwk(format!("bad seed length {}", seed.len())));
+            }
+            key_info.set_key_type(KeyType::Private);
+            key_info.set_bytes(&ml_dsa::seed_der(&seed));
+            if let Some(public) = public {
+                key_info.set_public_key(&public);
+            }
+        }
+        None => {
+            let public = public.ok_or_else(|| Error::BadJwk("missing member pub".to_owned()))?;
+            key_info.set_key_type(KeyType::Public);
+            key_info.set_bytes(&public);
+        }
+    }
+    Ok(key_info)
+}
+
+/// Turn a symmetric JWK into KeyInfo bytes
+fn oct_jwk_to_key_info(jwk: &Jwk) -> Result<KeyInfo> {
+    let k = Zeroizing::new(b64_decode("k", required("k", &jwk.k)?)?);
+    let key_info = KeyInfo::new()
+        .with_alg(Alg::Symmetric)
+        .with_key_type(KeyType::Secret)
+        .with_encoding(Encoding::JWK)
+        .with_format(Format::JWK)
+        .with_key_length(k.len() as u32 * 8)
+        .with_bytes(&k);
+    Ok(key_info)
+}
+
+/// Turn a JWK into KeyInfo bytes
+pub fn jwk_to_key_info(jwk: &Jwk) -> Result<KeyInfo> {
+    match jwk.kty.as_str() {
+        KTY_RSA => rsa_jwk_to_key_info(jwk),
+        KTY_EC => ec_jwk_to_key_info(jwk),
+        KTY_OKP => okp_jwk_to_key_info(jwk),
+        KTY_OCT => oct_jwk_to_key_info(jwk),
+        KTY_AKP => akp_jwk_to_key_info(jwk),
+        kty => bail!(Error::BadJwk(format!("unsupported key type {}", kty))),
+    }
+}
+
+/// Turn RSA KeyInfo bytes into a JWK
+///
+/// For private keys, the members are taken from the PKCS1 document.  If a
+/// public key is requested from a private key, the private members are not
+/// emitted.  Multi-prime keys are not written, as member `oth` is not
+/// supported.
+fn rsa_key_info_to_jwk(app_state: &AppState, key_info: &KeyInfo) -> Result<Jwk> {
+    let bytes = key_info.bytes.clone().ok_or(Error::MissingInput("key".to_owned()))?;
+    let mut jwk = Jwk {
+        kty: KTY_RSA.to_owned(),
+        kid: app_state.key_id.clone(),
+        ..Default::default()
+    };
+    let public_key = match (key_info.key_type, app_state.key_type) {
+        (KeyType::Private, Some(KeyType::Public)) => RsaPrivateKey::from_der(&bytes)?.public_key(),
+        (KeyType::Private, _) => {
+            let private_key = RsaPrivateKey::from_der(&bytes)?;
+            if private_key.other_prime_infos.is_some() {
+                warn!("Multi-prime RSA private keys can not be represented as JWK");
+                bail!(Error::NotSupported);
+            }
+            jwk.d = Some(b64_encode(private_key.private_exponent.as_bytes()));
+            jwk.p = Some(b64_encode(private_key.prime1.as_bytes()));
+            jwk.q = Some(b64_encode(private_key.prime2.as_bytes()));
+            jwk.dp = Some(b64_encode(private_key.exponent1.as_bytes()));
+            jwk.dq = Some(b64_encode(private_key.exponent2.as_bytes()));
+            jwk.qi = Some(b64_encode(private_key.coefficient.as_bytes()));
+            private_key.public_key()
+        }
+        (KeyType::Public, _) => RsaPublicKey::from_der(&bytes)?,
+        _ => bail!(Error::NotSupported),
+    };
+    jwk.n = Some(b64_encode(public_key.modulus.as_bytes()));
+    jwk.e = Some(b64_encode(public_key.public_exponent.as_bytes()));
+    Ok(jwk)
+}
+
+/// Turn EC KeyInfo bytes into a JWK
+///
+/// For private keys, the public point is taken from the SEC1 document.  If a
+/// public key is requested from a private key, member d is not emitted.
+fn ec_key_info_to_jwk(app_state: &AppState, key_info: &KeyInfo) -> Result<Jwk> {
+    let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+    let bytes = key_info.bytes.clone().ok_or(Error::MissingInput("key".to_owned()))?;
+    let (point, d) = match key_info.key_type {
+        KeyType::Private => {
+            let ec_private_key = EcPrivateKey::from_der(&bytes)?;
+            let point = ec_private_key
+                .public_key
+                .ok_or_else(|| Error::MissingInput("public key".to_owned()))?
+                .to_vec();
+            let d = match app_state.key_type {
+                Some(KeyType::Public) => None,
+                _ => Some(b64_encode(ec_private_key.private_key)),
+            };
+            (point, d)
+        }
+        KeyType::Public => (Vec::from(bytes.as_slice()), None),
+        _ => bail!(Error::NotSupported),
+    };
+    let size = curve.field_size();
+    if point.len() != 1 + 2 * size || point[0] != 0x04 {
+        warn!("Only uncompressed EC points can be represented as JWK");
+        bail!(Error::NotSupported);
+    }
+    let jwk = Jwk {
+        kty: KTY_EC.to_owned(),
+        kid: app_state.key_id.clone(),
+        crv: Some(curve.jwk_crv().to_owned()),
+        x: Some(b64_encode(&point[1..=size])),
+        y: Some(b64_encode(&point[1 + size..])),
+        d,
+        ..Default::default()
+    };
+    Ok(jwk)
+}
+
+/// Turn OKP KeyInfo bytes into a JWK
+///
+/// If a public key is requested from a private key, only the public key
+/// member is emitted.  The public key of an Ed25519 private key that does
+/// not carry it is derived from the seed.
+fn okp_key_info_to_jwk(app_state: &AppState, key_info: &KeyInfo) -> Result<Jwk> {
+    let key_info = &with_public_key(key_info)?;
+    let bytes = key_info.bytes.clone().ok_or(Error::MissingInput("key".to_owned()))?;
+    let mut jwk = Jwk {
+        kty: KTY_OKP.to_owned(),
+        kid: app_state.key_id.clone(),
+        crv: Some(okp_crv(key_info.alg)?.to_owned()),
+        ..Default::default()
+    };
+
+    match key_info.key_type {
+        KeyType::Private => {
+            match &key_info.public_key {
+                Some(public_key) => jwk.x = Some(b64_encode(public_key)),
+                None => warn!("Public key is not available. Omitting member x"),
+            }
+            if app_state.key_type != Some(KeyType::Public) {
+                let seed = OctetString::from_der(&bytes)?;
+                jwk.d = Some(b64_encode(seed.as_bytes()));
+            } else if jwk.x.is_none() {
+                bail!(Error::MissingInput("public key".to_owned()));
+            }
+        }
+        KeyType::Public => jwk.x = Some(b64_encode(&bytes)),
+        _ => bail!(Error::UnknownKeyType),
+    }
+    Ok(jwk)
+}
+
+/// Turn ML-DSA KeyInfo bytes into an AKP JWK
+///
+/// The private key member is the seed, so a private key that is only held
+/// expanded can only be written as a public key.  Keys of the draft OIDs are
+/// not written, as the JOSE algorithms are those of FIPS 204.
+fn akp_key_info_to_jwk(app_state: &AppState, key_info: &KeyInfo) -> Result<Jwk> {
+    if key_info.oid.as_ref().is_some_and(ml_dsa::is_draft) {
+        warn!("Keys of the draft ML-DSA OIDs can not be represented as JWK");
+        bail!(Error::NotSupported);
+    }
+    let key_info = &with_public_key(key_info)?;
+    let bytes = key_info.bytes.clone().ok_or(Error::MissingInput("key".to_owned()))?;
+    let mut jwk = Jwk {
+        kty: KTY_AKP.to_owned(),
+        kid: app_state.key_id.clone(),
+        alg: Some(akp_alg(key_info.alg)?.to_owned()),
+        ..Default::default()
+    };
+
+    match key_info.key_type {
+        KeyType::Private => {
+            let public_key = key_info
+                .public_key
+                .as_ref()
+                .ok_or_else(|| Error::MissingInput("public key".to_owned()))?;
+            jwk.public = Some(b64_encode(public_key));
+            if app_state.key_type != Some(KeyType::Public) {
+                let seed = ml_dsa::private_key_seed(&bytes)?.ok_or_else(|| {
+                    warn!("Only ML-DSA private keys with their seed can be represented as JWK");
+                    Error::NotSupported
+                })?;
+                jwk.private = Some(b64_encode(&seed));
+            }
+        }
+        KeyType::Public => jwk.public = Some(b64_encode(&bytes)),
+        _ => bail!(Error::UnknownKeyType),
+    }
+    Ok(jwk)
+}
+
+/// Turn symmetric KeyInfo bytes into a JWK
+///
+/// A secret has no public part, so asking for a public key is an error.
+fn oct_key_info_to_jwk(app_state: &AppState, key_info: &KeyInfo) -> Result<Jwk> {
+    if app_state.key_type == Some(KeyType::Public) {
+        bail!(Error::TypeMismatch);
+    }
+    let bytes = key_info.bytes.clone().ok_or(Error::MissingInput("key".to_owned()))?;
+    let jwk = Jwk {
+        kty: KTY_OCT.to_owned(),
+        kid: app_state.key_id.clone(),
+        k: Some(b64_encode(&bytes)),
+        ..Default::default()
+    };
+    Ok(jwk)
+}
+
+/// Turn KeyInfo bytes into a JWK document
+///
+/// If an output password is provided, private and symmetric keys are
+/// protected as a JWE.
+pub fn key_info_to_jwk(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let jwk = key_info_as_jwk(app_state, key_info)?;
+    let json = Zeroizing::new(jwk.to_json(app_state.pretty)?);
+    match &app_state.out_password {
+        Some(password) if jwk.is_private() => {
+            let jwe = jwe_encrypt(json.as_bytes(), password, CTY_JWK)?;
+            app_state.write_stream(jwe.as_bytes())?;
+        }
+        Some(_) => {
+            warn!("Public keys are not protected. Ignoring the output password");
+            app_state.write_stream(json.as_bytes())?;
+        }
+        None => app_state.write_stream(json.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Turn KeyInfo bytes into a JWK of the public key, without metadata
+pub fn key_info_as_public_jwk(key_info: &KeyInfo) -> Result<Jwk> {
+    let public_state = AppState {
+        key_type: Some(KeyType::Public),
+        ..Default::default()
+    };
+    key_info_as_jwk(&public_state, key_info)
+}
+
+/// Turn KeyInfo bytes into a JWK.
+///
+/// If the [AppState] key type is Public, only the public members are
+/// populated, even if the KeyInfo is a private key.
+pub fn key_info_as_jwk(app_state: &AppState, key_info: &KeyInfo) -> Result<Jwk> {
+    let mut jwk = match key_info.alg {
+        Alg::Rsa | Alg::RsaSsaPss => rsa_key_info_to_jwk(app_state, key_info)?,
+        Alg::Ecdsa => ec_key_info_to_jwk(app_state, key_info)?,
+        Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448 => {
+            okp_key_info_to_jwk(app_state, key_info)?
+        }
+        Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87 => akp_key_info_to_jwk(app_state, key_info)?,
+        Alg::Symmetric => oct_key_info_to_jwk(app_state, key_info)?,
+        _ => bail!(Error::NotSupported),
+    };
+    add_metadata(app_state, key_info, &mut jwk)?;
+    if let Some(cert_file) = &app_state.cert_file {
+        add_certs(cert_file, key_info, &mut jwk)?;
+    }
+    Ok(jwk)
+}
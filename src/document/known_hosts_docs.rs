@@ -0,0 +1,161 @@
+//! OpenSSH known_hosts files
+//!
+//! Each line of a known_hosts file is an optional marker, such as
+//! `@cert-authority`, the hosts the key is for, and the key, as an
+//! `authorized_keys` line.  See the SSH_KNOWN_HOSTS FILE FORMAT section of
+//! sshd(8).
+//!
+//! The hosts are a comma separated list of patterns, which may use the `*`
+//! and `?` wildcards, and be negated with `!`.  Hosts on a port other than 22
+//! are written as `[host]:port`.  Hashed hosts, written by `ssh-keygen -H`,
+//! are `|1|`, the base64 of a salt, `|`, and the base64 of the HMAC-SHA1 of
+//! the host, keyed by the salt.
+//!
+//! ```rust
+//! use kt::document::known_hosts_docs::hosts_match;
+//!
+//! assert!(hosts_match("*.example.com,!bad.example.com", "www.example.com"));
+//! assert!(!hosts_match("*.example.com,!bad.example.com", "bad.example.com"));
+//! assert!(hosts_match("[git.example.com]:2222", "[git.example.com]:2222"));
+//! ```
+use anyhow::Result;
+use base64ct::{Base64, Encoding as _};
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+
+use crate::document::openssh_docs::openssh_public_line_to_key_info;
+use crate::errors::Error;
+use crate::key_info::KeyInfo;
+
+/// Start of a hashed host
+const HASH_MAGIC: &str = "|1|";
+
+/// A key of a known_hosts file
+#[derive(Clone, Debug)]
+pub struct KnownHost {
+    /// Line of the file, from 1
+    pub line: usize,
+    /// Marker of the line, `@cert-authority` or `@revoked`
+    pub marker: Option<String>,
+    /// Hosts of the line, as written
+    pub hosts: String,
+    /// The host key
+    pub key_info: KeyInfo,
+}
+
+impl KnownHost {
+    /// Whether the hosts of the line are hashed
+    pub fn is_hashed(&self) -> bool {
+        self.hosts.starts_with(HASH_MAGIC)
+    }
+}
+
+/// The name of a host in known_hosts, which includes the port if it is not
+/// 22.  Names already written as `[host]:port` are kept.
+pub fn host_name(host: &str) -> String {
+    if host.starts_with('[') {
+        return host.to_owned();
+    }
+    match host.rsplit_once(':') {
+        // More than one colon is an IPv6 address, without a port
+        Some((name, port)) if !name.contains(':') && port.parse::<u16>().is_ok() => {
+            match port {
+                "22" => name.to_owned(),
+                port => format!("[{}]:{}", name, port),
+            }
+        }
+        _ => host.to_owned(),
+    }
+}
+
+/// Match the text against a pattern of `*` and `?` wildcards
+fn wildcard_match(pattern: &[u8], text: &[u8]) -> bool {
+    match (pattern.first(), text.first()) {
+        (None, None) => true,
+        (Some(b'*'), _) => {
+            wildcard_match(&pattern[1..], text)
+                || (!text.is_empty() && wildcard_match(pattern, &text[1..]))
+        }
+        (Some(b'?'), Some(_)) => wildcard_match(&pattern[1..], &text[1..]),
+        (Some(p), Some(t)) if p.eq_ignore_ascii_case(t) => {
+            wildcard_match(&pattern[1..], &text[1..])
+        }
+        _ => false,
+    }
+}
+
+/// Whether a hashed host is the host
+fn hashed_match(hashed: &str, host: &str) -> bool {
+    let (salt, hash) = match hashed.strip_prefix(HASH_MAGIC).and_then(|rest| rest.split_once('|')) {
+        Some(fields) => fields,
+        None => return false,
+    };
+    let (salt, hash) = match (Base64::decode_vec(salt), Base64::decode_vec(hash)) {
+        (Ok(salt), Ok(hash)) => (salt, hash),
+        _ => return false,
+    };
+    match Hmac::<Sha1>::new_from_slice(&salt) {
+        Ok(mac) => mac.chain_update(host.as_bytes()).verify_slice(&hash).is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// Whether the hosts of a line match the host.  A negated pattern that
+/// matches rules the line out, even if another pattern matches.
+pub fn hosts_match(hosts: &str, host: &str) -> bool {
+    if hosts.starts_with(HASH_MAGIC) {
+        return hashed_match(hosts, host);
+    }
+    let mut found = false;
+    for pattern in hosts.split(',') {
+        match pattern.strip_prefix('!') {
+            Some(pattern) if wildcard_match(pattern.as_bytes(), host.as_bytes()) => return false,
+            Some(_) => {}
+            None => found |= wildcard_match(pattern.as_bytes(), host.as_bytes()),
+        }
+    }
+    found
+}
+
+/// The keys of the known_hosts file for the host, in the order of the file
+///
+/// The host may be a name or address, with a port, such as
+/// `example.com:2222`.  Lines of other hosts are not read, so a damaged line
+/// is only reported if it is for the host.
+pub fn known_hosts_for(text: &str, host: &str) -> Result<Vec<KnownHost>> {
+    let name = host_name(host);
+    let mut found = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (marker, rest) = match line.strip_prefix('@') {
+            Some(_) => match line.split_once(char::is_whitespace) {
+                Some((marker, rest)) => (Some(marker.to_owned()), rest.trim_start()),
+                None => continue,
+            },
+            None => (None, line),
+        };
+        let (hosts, key) = match rest.split_once(char::is_whitespace) {
+            Some(fields) => fields,
+            None => continue,
+        };
+        if !hosts_match(hosts, &name) {
+            continue;
+        }
+        let key_info = openssh_public_line_to_key_info(key).map_err(|e| {
+            Error::BadKnownHosts(format!("line {}: {}", index + 1, e))
+        })?;
+        found.push(KnownHost {
+            line: index + 1,
+            marker,
+            hosts: hosts.to_owned(),
+            key_info,
+        });
+    }
+    if found.is_empty() {
+        return Err(Error::HostNotFound(host.to_owned()).into());
+    }
+    Ok(found)
+}
@@ -1,5 +1,27 @@
 //! Wrappers for the [RustCrypto](https://github.com/RustCrypto) [formats](https://github.com/RustCrypto/formats) crates.
+pub mod cng_docs;
+pub mod composite_docs;
+pub mod cose_docs;
+pub mod dh_docs;
+pub mod dsa_docs;
+pub mod ethereum_docs;
+pub mod jwe_docs;
+pub mod jwk_docs;
+pub mod known_hosts_docs;
+pub mod openpgp_docs;
+pub mod openssh_docs;
+pub mod pem_json_docs;
+pub mod pkcs10_docs;
 pub mod pkcs1_docs;
+pub mod pkcs12_docs;
+pub mod pkcs7_docs;
 pub mod pkcs8_docs;
+pub mod ppk_docs;
+pub mod raw_docs;
 pub mod sec1_docs;
-pub mod spki_docs;
\ No newline at end of file
+pub mod secret_docs;
+pub mod spki_docs;
+pub mod tink_docs;
+pub mod tss2_docs;
+pub mod x509_docs;
+pub mod xml_docs;
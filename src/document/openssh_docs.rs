@@ -0,0 +1,733 @@
+//! OpenSSH keys
+//!
+//! Private keys are read and written in the `openssh-key-v1` format of
+//! [PROTOCOL.key](https://github.com/openssh/openssh-portable/blob/master/PROTOCOL.key),
+//! including keys encrypted with bcrypt_pbkdf and AES-CTR.  RSA, ECDSA and
+//! Ed25519 keys are turned into the same KeyInfo as PKCS1, SEC1 and PKCS8
+//! keys, so that they can be shown and converted.
+//!
+//! A public key is read and written as an `authorized_keys` line of
+//! [RFC 4253](https://www.rfc-editor.org/rfc/rfc4253#section-6.6): the key
+//! type, the base64 of the key blob, and an optional comment.  Options at
+//! the start of a line are skipped when reading.  ECDSA keys
+//! follow [RFC 5656](https://www.rfc-editor.org/rfc/rfc5656#section-3.1), and
+//! Ed25519 keys follow [RFC 8709](https://www.rfc-editor.org/rfc/rfc8709).
+//!
+//! The blob is made from the public JWK of the key, so public keys can be
+//! taken from private keys.
+use aes::cipher::{KeyIvInit, StreamCipher};
+use anyhow::{bail, Result};
+use base64ct::{Base64, Encoding as _};
+use bcrypt_pbkdf::bcrypt_pbkdf;
+use log::warn;
+use pkcs8::der::{asn1::OctetString, Decodable, Encodable};
+use rand_core::{OsRng, RngCore};
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use rsa::BigUint;
+use sec1::{EcParameters, EcPrivateKey};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::app_state::AppState;
+use crate::document::composite_docs::composite_public_key;
+use crate::document::dh_docs::dh_public_key;
+use crate::document::dsa_docs::dsa_public_key;
+use crate::document::jwk_docs::{
+    b64_decode, b64_encode, jwk_to_key_info, key_info_as_public_jwk, Jwk, KTY_EC, KTY_OKP, KTY_RSA,
+};
+use crate::ed448;
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::ml_dsa;
+use crate::pem;
+use crate::xdh;
+use crate::signature::{ec_public_point, ed25519_signing_key, rsa_private_key};
+
+/// OpenSSH key type of RSA keys
+pub const SSH_RSA: &str = "ssh-rsa";
+/// OpenSSH key type of Ed25519 keys
+pub const SSH_ED25519: &str = "ssh-ed25519";
+
+/// The magic bytes that start an openssh-key-v1 document
+const AUTH_MAGIC: &[u8] = b"openssh-key-v1\0";
+/// Cipher and KDF name of unencrypted keys
+const NONE: &str = "none";
+/// KDF name of encrypted keys
+const BCRYPT: &str = "bcrypt";
+/// Cipher of keys written with a password, as ssh-keygen
+const AES256_CTR: &str = "aes256-ctr";
+/// bcrypt_pbkdf rounds of keys written with a password, as ssh-keygen
+const BCRYPT_ROUNDS: u32 = 16;
+/// Size of the bcrypt_pbkdf salt of keys written with a password
+const SALT_SIZE: usize = 16;
+/// OpenSSH wraps the base64 of private keys at 70 characters
+const LINE_WIDTH: usize = 70;
+
+/// Reads the SSH wire encoding of keys, RFC 4251 section 5
+pub(crate) struct SshReader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> SshReader<'a> {
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.0.len() < len {
+            bail!(Error::BadOpenSsh("truncated key".to_owned()));
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    /// A `string`: a uint32 length, followed by the bytes
+    pub(crate) fn string(&mut self) -> Result<&'a [u8]> {
+        let len = self.u32()? as usize;
+        self.bytes(len)
+    }
+
+    /// A `string` that must be UTF-8, such as a name
+    pub(crate) fn str(&mut self) -> Result<&'a str> {
+        std::str::from_utf8(self.string()?)
+            .map_err(|_| Error::BadOpenSsh("name is not UTF-8".to_owned()).into())
+    }
+
+    /// An `mpint`, as an unsigned big endian integer
+    pub(crate) fn mpint(&mut self) -> Result<BigUint> {
+        let bytes = self.string()?;
+        if bytes.first().is_some_and(|b| b & 0x80 != 0) {
+            bail!(Error::BadOpenSsh("negative integer".to_owned()));
+        }
+        Ok(BigUint::from_bytes_be(bytes))
+    }
+}
+
+/// The SSH wire encoding of keys, RFC 4251 section 5
+#[derive(Default)]
+pub(crate) struct SshWriter(pub(crate) Vec<u8>);
+
+impl SshWriter {
+    /// A `string`: a uint32 length, followed by the bytes
+    pub(crate) fn string(&mut self, bytes: &[u8]) -> &mut Self {
+        self.0.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        self.0.extend_from_slice(bytes);
+        self
+    }
+
+    /// An `mpint` of an unsigned big endian integer.  Leading zeros are
+    /// removed, and a zero is added if the high bit is set, so that the
+    /// integer is not negative.
+    pub(crate) fn mpint(&mut self, bytes: &[u8]) -> &mut Self {
+        let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+        let bytes = &bytes[start..];
+        match bytes.first() {
+            Some(b) if b & 0x80 != 0 => {
+                let mut padded = vec![0u8];
+                padded.extend_from_slice(bytes);
+                self.string(&padded)
+            }
+            _ => self.string(bytes),
+        }
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) -> &mut Self {
+        self.0.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+}
+
+impl Drop for SshWriter {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// The curve of an OpenSSH curve identifier
+pub(crate) fn ssh_curve(name: &str) -> Result<Curve> {
+    match name {
+        "nistp256" => Ok(Curve::P256),
+        "nistp384" => Ok(Curve::P384),
+        "nistp521" => Ok(Curve::P521),
+        name => bail!(Error::BadOpenSsh(format!("unsupported curve {}", name))),
+    }
+}
+
+/// Test whether the PEM text is an openssh-key-v1 private key
+pub fn is_openssh_private_key(pem: &str) -> bool {
+    pem.trim_start()
+        .starts_with(&format!("-----BEGIN {}-----", pem::OPENSSH_PRIVATE_KEY))
+}
+
+/// Remove the armor of an openssh-key-v1 private key.
+///
+/// OpenSSH wraps the base64 at 70 characters, rather than the 64 of RFC
+/// 7468, so the PEM decoder can't be used.
+fn unwrap_openssh(pem: &str) -> Result<Vec<u8>> {
+    let begin = format!("-----BEGIN {}-----", pem::OPENSSH_PRIVATE_KEY);
+    let end = format!("-----END {}-----", pem::OPENSSH_PRIVATE_KEY);
+    let body = pem
+        .trim_start()
+        .strip_prefix(&begin)
+        .and_then(|rest| rest.split_once(&end))
+        .map(|(body, _)| body)
+        .ok_or_else(|| Error::BadPem("missing end of document".to_owned()))?;
+    let base64: String = body.split_whitespace().collect();
+    Base64::decode_vec(&base64).map_err(|e| Error::BadPem(e.to_string()).into())
+}
+
+/// The key size of an AES-CTR cipher name
+fn cipher_key_len(cipher: &str) -> Result<usize> {
+    match cipher {
+        "aes128-ctr" => Ok(16),
+        "aes192-ctr" => Ok(24),
+        AES256_CTR => Ok(32),
+        cipher => bail!(Error::BadOpenSsh(format!("unsupported cipher {}", cipher))),
+    }
+}
+
+/// Encrypt or decrypt the private section in place with AES-CTR, keyed by
+/// bcrypt_pbkdf of the password
+fn apply_cipher(
+    cipher: &str,
+    salt: &[u8],
+    rounds: u32,
+    password: &str,
+    section: &mut [u8],
+) -> Result<()> {
+    let key_len = cipher_key_len(cipher)?;
+    let mut key_iv = Zeroizing::new(vec![0u8; key_len + 16]);
+    bcrypt_pbkdf(password.as_bytes(), salt, rounds, &mut key_iv)
+        .map_err(|_| Error::BadOpenSsh("bad bcrypt KDF parameters".to_owned()))?;
+    let (key, iv) = key_iv.split_at(key_len);
+    match key_len {
+        16 => ctr::Ctr128BE::<aes::Aes128>::new(key.into(), iv.into()).apply_keystream(section),
+        24 => ctr::Ctr128BE::<aes::Aes192>::new(key.into(), iv.into()).apply_keystream(section),
+        _ => ctr::Ctr128BE::<aes::Aes256>::new(key.into(), iv.into()).apply_keystream(section),
+    }
+    Ok(())
+}
+
+/// Decrypt the private section with bcrypt_pbkdf and AES-CTR
+fn decrypt_private_section(
+    cipher: &str,
+    kdf_options: &[u8],
+    encrypted: &[u8],
+    password: &str,
+) -> Result<Zeroizing<Vec<u8>>> {
+    let mut options = SshReader(kdf_options);
+    let salt = options.string()?;
+    let rounds = options.u32()?;
+    let mut section = Zeroizing::new(encrypted.to_vec());
+    apply_cipher(cipher, salt, rounds, password, &mut section)?;
+    // The check ints only match if the password was correct
+    let mut reader = SshReader(&section);
+    if reader.u32()? != reader.u32()? {
+        bail!(Error::BadPassword);
+    }
+    Ok(section)
+}
+
+/// KeyInfo of the components of an RSA private key, as PKCS1
+pub(crate) fn rsa_key_info(
+    n: BigUint,
+    e: BigUint,
+    d: BigUint,
+    p: BigUint,
+    q: BigUint,
+) -> Result<KeyInfo> {
+    let key_length = n.bits() as u32;
+    let private_key = rsa::RsaPrivateKey::from_components(n, e, d, vec![p, q])
+        .map_err(|e| Error::BadOpenSsh(format!("bad RSA private key: {}", e)))?;
+    let pk1_doc = private_key
+        .to_pkcs1_der()
+        .map_err(|e| Error::BadOpenSsh(format!("bad RSA private key: {}", e)))?;
+    Ok(KeyInfo::new()
+        .with_key_type(KeyType::Private)
+        .with_alg(Alg::Rsa)
+        .with_key_length(key_length)
+        .with_bytes(pk1_doc.as_bytes()))
+}
+
+/// KeyInfo of an ECDSA private key and its public point, as SEC1
+pub(crate) fn ecdsa_key_info(curve: Curve, point: &[u8], d: &BigUint) -> Result<KeyInfo> {
+    let d = Zeroizing::new(d.to_bytes_be());
+    let size = curve.field_size();
+    if d.len() > size {
+        bail!(Error::BadOpenSsh("private key is larger than the curve".to_owned()));
+    }
+    let mut private_key = Zeroizing::new(vec![0u8; size - d.len()]);
+    private_key.extend_from_slice(&d);
+    let curve_oid = curve.oid();
+    let ec_private_key = EcPrivateKey {
+        private_key: &private_key,
+        parameters: Some(EcParameters::NamedCurve(curve_oid)),
+        public_key: Some(point),
+    };
+    let sec1_der = Zeroizing::new(ec_private_key.to_vec()?);
+    Ok(KeyInfo::new()
+        .with_key_type(KeyType::Private)
+        .with_alg(Alg::Ecdsa)
+        .with_oid(&curve_oid)
+        .with_bytes(&sec1_der))
+}
+
+/// KeyInfo of an Ed25519 seed and public key, as the PKCS8 private key
+pub(crate) fn ed25519_key_info(public_key: &[u8], seed: &[u8]) -> Result<KeyInfo> {
+    if public_key.len() != 32 || seed.len() != 32 {
+        bail!(Error::BadOpenSsh("bad Ed25519 key".to_owned()));
+    }
+    let private_key = Zeroizing::new(OctetString::new(seed)?.to_vec()?);
+    Ok(KeyInfo::new()
+        .with_key_type(KeyType::Private)
+        .with_alg(Alg::EdDsa25519)
+        .with_bytes(&private_key)
+        .with_public_key(public_key))
+}
+
+/// Turn the private key of a decrypted private section into KeyInfo bytes,
+/// matching a key discovered from PKCS1, SEC1 or PKCS8.
+fn private_section_to_key_info(section: &[u8]) -> Result<KeyInfo> {
+    let mut reader = SshReader(section);
+    if reader.u32()? != reader.u32()? {
+        bail!(Error::BadOpenSsh("check ints do not match".to_owned()));
+    }
+    let key_type = reader.str()?;
+    let key_info = match key_type {
+        SSH_RSA => {
+            let n = reader.mpint()?;
+            let e = reader.mpint()?;
+            let d = reader.mpint()?;
+            let _iqmp = reader.mpint()?;
+            let p = reader.mpint()?;
+            let q = reader.mpint()?;
+            rsa_key_info(n, e, d, p, q)?
+        }
+        key_type if key_type.starts_with("ecdsa-sha2-") => {
+            let curve = ssh_curve(reader.str()?)?;
+            let point = reader.string()?;
+            ecdsa_key_info(curve, point, &reader.mpint()?)?
+        }
+        SSH_ED25519 => {
+            let public_key = reader.string()?;
+            // The private key is the seed, followed by the public key
+            let keypair = reader.string()?;
+            if keypair.len() != 64 {
+                bail!(Error::BadOpenSsh("bad Ed25519 key".to_owned()));
+            }
+            ed25519_key_info(public_key, &keypair[..32])?
+        }
+        key_type => bail!(Error::BadOpenSsh(format!("unsupported key type {}", key_type))),
+    };
+    let comment = reader.str()?;
+    Ok(key_info
+        .with_encoding(Encoding::OPENSSH)
+        .with_format(Format::OPENSSH)
+        .with_comment(comment))
+}
+
+/// Turn an openssh-key-v1 private key into KeyInfo bytes
+///
+/// Encrypted keys are decrypted with the input password.
+pub fn openssh_to_private_key_info(app_state: &AppState, pem: &str) -> Result<KeyInfo> {
+    let bytes = unwrap_openssh(pem)?;
+    let mut reader = SshReader(
+        bytes
+            .strip_prefix(AUTH_MAGIC)
+            .ok_or_else(|| Error::BadOpenSsh("not an openssh-key-v1 key".to_owned()))?,
+    );
+    let cipher = reader.str()?;
+    let kdf = reader.str()?;
+    let kdf_options = reader.string()?;
+    if reader.u32()? != 1 {
+        bail!(Error::BadOpenSsh("only files of one key are supported".to_owned()));
+    }
+    let _public_key = reader.string()?;
+    let section = reader.string()?;
+
+    match (cipher, kdf) {
+        (NONE, NONE) => private_section_to_key_info(section),
+        (_, BCRYPT) => {
+            let section = app_state
+                .decrypt_input(|pwd| decrypt_private_section(cipher, kdf_options, section, pwd))?;
+            Ok(private_section_to_key_info(&section)?.with_encrypted(true))
+        }
+        (_, kdf) => bail!(Error::BadOpenSsh(format!("unsupported KDF {}", kdf))),
+    }
+}
+
+/// The OpenSSH identifier of a JWK curve, RFC 5656 section 10.1
+fn ecdsa_curve(crv: &str) -> Result<&'static str> {
+    match crv {
+        "P-256" => Ok("nistp256"),
+        "P-384" => Ok("nistp384"),
+        "P-521" => Ok("nistp521"),
+        crv => bail!(Error::BadOpenSsh(format!("unsupported curve {}", crv))),
+    }
+}
+
+fn member<'a>(name: &str, value: &'a Option<String>) -> Result<&'a str> {
+    value
+        .as_deref()
+        .ok_or_else(|| Error::BadOpenSsh(format!("missing member {}", name)).into())
+}
+
+/// The OpenSSH key type and key blob of a public JWK
+pub fn jwk_to_ssh_blob(jwk: &Jwk) -> Result<(String, Vec<u8>)> {
+    let mut blob = SshWriter::default();
+    let key_type = match jwk.kty.as_str() {
+        KTY_RSA => {
+            let e = b64_decode("e", member("e", &jwk.e)?)?;
+            let n = b64_decode("n", member("n", &jwk.n)?)?;
+            blob.string(SSH_RSA.as_bytes()).mpint(&e).mpint(&n);
+            SSH_RSA.to_owned()
+        }
+        KTY_EC => {
+            let curve = ecdsa_curve(member("crv", &jwk.crv)?)?;
+            let mut point = vec![0x04];
+            point.extend(b64_decode("x", member("x", &jwk.x)?)?);
+            point.extend(b64_decode("y", member("y", &jwk.y)?)?);
+            let key_type = format!("ecdsa-sha2-{}", curve);
+            blob.string(key_type.as_bytes())
+                .string(curve.as_bytes())
+                .string(&point);
+            key_type
+        }
+        KTY_OKP if jwk.crv.as_deref() == Some("Ed25519") => {
+            let x = b64_decode("x", member("x", &jwk.x)?)?;
+            blob.string(SSH_ED25519.as_bytes()).string(&x);
+            SSH_ED25519.to_owned()
+        }
+        KTY_OKP => bail!(Error::BadOpenSsh(format!(
+            "unsupported curve {}",
+            jwk.crv.as_deref().unwrap_or("none")
+        ))),
+        kty => bail!(Error::BadOpenSsh(format!("unsupported kty {}", kty))),
+    };
+    Ok((key_type, std::mem::take(&mut blob.0)))
+}
+
+/// The public JWK of an OpenSSH key blob
+fn ssh_blob_to_jwk(blob: &[u8]) -> Result<Jwk> {
+    let mut reader = SshReader(blob);
+    let jwk = match reader.str()? {
+        SSH_RSA => {
+            let e = reader.mpint()?;
+            let n = reader.mpint()?;
+            Jwk {
+                kty: KTY_RSA.to_owned(),
+                n: Some(b64_encode(&n.to_bytes_be())),
+                e: Some(b64_encode(&e.to_bytes_be())),
+                ..Default::default()
+            }
+        }
+        key_type if key_type.starts_with("ecdsa-sha2-") => {
+            let name = reader.str()?;
+            if key_type.strip_prefix("ecdsa-sha2-") != Some(name) {
+                bail!(Error::BadOpenSsh(format!("curve {} does not match {}", name, key_type)));
+            }
+            let curve = ssh_curve(name)?;
+            let point = reader.string()?;
+            let size = curve.field_size();
+            if point.len() != 1 + 2 * size || point[0] != 0x04 {
+                bail!(Error::BadOpenSsh("only uncompressed points are supported".to_owned()));
+            }
+            Jwk {
+                kty: KTY_EC.to_owned(),
+                crv: Some(curve.jwk_crv().to_owned()),
+                x: Some(b64_encode(&point[1..=size])),
+                y: Some(b64_encode(&point[1 + size..])),
+                ..Default::default()
+            }
+        }
+        SSH_ED25519 => {
+            let public_key = reader.string()?;
+            if public_key.len() != 32 {
+                bail!(Error::BadOpenSsh("bad Ed25519 key".to_owned()));
+            }
+            Jwk {
+                kty: KTY_OKP.to_owned(),
+                crv: Some("Ed25519".to_owned()),
+                x: Some(b64_encode(public_key)),
+                ..Default::default()
+            }
+        }
+        key_type => bail!(Error::BadOpenSsh(format!("unsupported key type {}", key_type))),
+    };
+    if !reader.0.is_empty() {
+        bail!(Error::BadOpenSsh("trailing bytes after the key".to_owned()));
+    }
+    Ok(jwk)
+}
+
+/// Turn an OpenSSH key blob into public KeyInfo bytes, matching a key
+/// discovered from SPKI or PKCS1
+pub fn ssh_blob_to_key_info(blob: &[u8]) -> Result<KeyInfo> {
+    Ok(jwk_to_key_info(&ssh_blob_to_jwk(blob)?)?
+        .with_encoding(Encoding::OPENSSH)
+        .with_format(Format::OPENSSH))
+}
+
+/// Whether the name is an OpenSSH key type that can be read
+fn is_ssh_key_type(name: &str) -> bool {
+    name == SSH_RSA || name == SSH_ED25519 || name.starts_with("ecdsa-sha2-")
+}
+
+/// The lines of an `authorized_keys` file, or `.pub` file, that may hold a
+/// key.  Blank lines and comments are skipped.
+pub fn public_key_lines(text: &str) -> impl Iterator<Item = &str> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Test whether the text is an OpenSSH public key, such as a `.pub` file.
+/// The key type must be followed by the base64 of a key blob, which starts
+/// with the length of the key type.
+pub fn is_openssh_public_key(text: &str) -> bool {
+    public_key_lines(text).next().is_some_and(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        fields
+            .windows(2)
+            .any(|pair| is_ssh_key_type(pair[0]) && pair[1].starts_with("AAAA"))
+    })
+}
+
+/// Turn an `authorized_keys` line into public KeyInfo bytes, with the
+/// comment of the line
+pub fn openssh_public_line_to_key_info(line: &str) -> Result<KeyInfo> {
+    let mut rest = line.trim();
+    loop {
+        let (field, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+        let tail = tail.trim_start();
+        if is_ssh_key_type(field) {
+            let (base64, comment) = tail.split_once(char::is_whitespace).unwrap_or((tail, ""));
+            let blob = Base64::decode_vec(base64)
+                .map_err(|_| Error::BadOpenSsh("key is not base64".to_owned()))?;
+            if SshReader(&blob).str()? != field {
+                bail!(Error::BadOpenSsh(format!("key does not match key type {}", field)));
+            }
+            return Ok(ssh_blob_to_key_info(&blob)?.with_comment(comment.trim()));
+        }
+        if tail.is_empty() {
+            bail!(Error::BadOpenSsh("missing key type".to_owned()));
+        }
+        rest = tail;
+    }
+}
+
+/// Turn the first key of an `authorized_keys` file, or `.pub` file, into
+/// public KeyInfo bytes
+pub fn openssh_public_to_key_info(text: &str) -> Result<KeyInfo> {
+    let line = public_key_lines(text)
+        .next()
+        .ok_or_else(|| Error::BadOpenSsh("no public key".to_owned()))?;
+    openssh_public_line_to_key_info(line)
+}
+
+/// The KeyInfo, with the public key of an Ed25519, Ed448, X25519, X448, DSA,
+/// DH, ML-DSA or composite private key derived from the private key if the key doesn't
+/// carry it.  The keys of the draft ML-DSA OIDs can not be derived.
+///
+/// The public key of an OKP seed is cheap to derive, so it is derived even
+/// if the key carries one.  A PKCS8 version 2 key with the wrong publicKey
+/// gives the public key of its seed, as it does with openssl.
+pub(crate) fn with_public_key(key_info: &KeyInfo) -> Result<KeyInfo> {
+    let mut key_info = key_info.clone();
+    let is_okp = matches!(
+        key_info.alg,
+        Alg::EdDsa25519 | Alg::EdDsa448 | Alg::X25519 | Alg::X448
+    );
+    if key_info.key_type != KeyType::Private || (key_info.public_key.is_some() && !is_okp) {
+        return Ok(key_info);
+    }
+    match key_info.alg {
+        Alg::EdDsa25519 => {
+            let signing_key = ed25519_signing_key(&key_info)?;
+            key_info.set_public_key(signing_key.verifying_key().as_bytes());
+        }
+        Alg::EdDsa448 | Alg::X25519 | Alg::X448 => {
+            let bytes = key_info.bytes.as_ref().ok_or(Error::MissingInput("key".to_owned()))?;
+            let seed = OctetString::from_der(bytes)?;
+            let public_key = match key_info.alg {
+                Alg::EdDsa448 => ed448::public_key(seed.as_bytes())?,
+                Alg::X25519 => xdh::x25519_public_key(seed.as_bytes())?,
+                _ => xdh::x448_public_key(seed.as_bytes())?,
+            };
+            key_info.set_public_key(&public_key);
+        }
+        Alg::Dsa | Alg::Dh => {
+            let bytes = key_info.bytes.as_ref().ok_or(Error::MissingInput("key".to_owned()))?;
+            let params = key_info
+                .params
+                .as_ref()
+                .ok_or(Error::MissingInput("parameters".to_owned()))?;
+            let public_key = match key_info.alg {
+                Alg::Dsa => dsa_public_key(params, bytes)?,
+                _ => dh_public_key(params, bytes)?,
+            };
+            key_info.set_public_key(&public_key);
+        }
+        Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87 => {
+            if key_info.oid.as_ref().is_some_and(ml_dsa::is_draft) {
+                bail!(Error::NotSupported);
+            }
+            let bytes = key_info.bytes.as_ref().ok_or(Error::MissingInput("key".to_owned()))?;
+            key_info.set_public_key(&ml_dsa::public_key(key_info.alg, bytes)?);
+        }
+        Alg::Composite => {
+            let public_key = composite_public_key(&key_info)?;
+            key_info.set_public_key(&public_key);
+        }
+        _ => {}
+    }
+    Ok(key_info)
+}
+
+/// The private section of an openssh-key-v1 key, without the check ints
+/// and padding
+fn private_fields(key_info: &KeyInfo, key_type: &str, comment: &str) -> Result<SshWriter> {
+    let mut fields = SshWriter::default();
+    fields.string(key_type.as_bytes());
+    match key_info.alg {
+        Alg::Rsa | Alg::RsaSsaPss => {
+            let private_key = rsa_private_key(key_info)?;
+            let (p, q) = match private_key.primes() {
+                [p, q] => (p, q),
+                _ => bail!(Error::BadOpenSsh("only two prime RSA keys are supported".to_owned())),
+            };
+            let iqmp = private_key
+                .crt_coefficient()
+                .ok_or_else(|| Error::BadOpenSsh("bad RSA private key".to_owned()))?;
+            fields
+                .mpint(&private_key.n().to_bytes_be())
+                .mpint(&private_key.e().to_bytes_be())
+                .mpint(&Zeroizing::new(private_key.d().to_bytes_be()))
+                .mpint(&Zeroizing::new(iqmp.to_bytes_be()))
+                .mpint(&Zeroizing::new(p.to_bytes_be()))
+                .mpint(&Zeroizing::new(q.to_bytes_be()));
+        }
+        Alg::Ecdsa => {
+            let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+            let bytes = key_info.bytes.as_ref().ok_or(Error::MissingInput("key".to_owned()))?;
+            let ec_private_key = EcPrivateKey::from_der(bytes)?;
+            fields
+                .string(ecdsa_curve(curve.jwk_crv())?.as_bytes())
+                .string(&ec_public_point(key_info)?)
+                .mpint(ec_private_key.private_key);
+        }
+        Alg::EdDsa25519 => {
+            let signing_key = ed25519_signing_key(key_info)?;
+            let public_key = signing_key.verifying_key();
+            let mut keypair = Zeroizing::new(Vec::from(signing_key.to_bytes()));
+            keypair.extend_from_slice(public_key.as_bytes());
+            fields.string(public_key.as_bytes()).string(&keypair);
+        }
+        _ => bail!(Error::BadOpenSsh(format!("unsupported algorithm {}", key_info.alg))),
+    }
+    fields.string(comment.as_bytes());
+    Ok(fields)
+}
+
+/// Turn a private KeyInfo into an openssh-key-v1 document, encrypted with
+/// bcrypt_pbkdf and aes256-ctr if a password is provided
+pub fn key_info_as_openssh_private(
+    key_info: &KeyInfo,
+    comment: Option<&str>,
+    password: Option<&str>,
+) -> Result<Zeroizing<String>> {
+    let key_info = with_public_key(key_info)?;
+    let jwk = key_info_as_public_jwk(&key_info)?;
+    let (key_type, public_blob) = jwk_to_ssh_blob(&jwk)?;
+
+    // The check ints are random, so that a wrong password is detected
+    let check = OsRng.next_u32();
+    let mut section = SshWriter::default();
+    section.u32(check).u32(check);
+    section
+        .0
+        .extend_from_slice(&private_fields(&key_info, &key_type, comment.unwrap_or(""))?.0);
+
+    let mut header = SshWriter::default();
+    header.0.extend_from_slice(AUTH_MAGIC);
+    // The section is padded to the cipher block size, which is 8 when
+    // there is no cipher
+    match password {
+        Some(password) => {
+            let mut salt = [0u8; SALT_SIZE];
+            OsRng.fill_bytes(&mut salt);
+            let mut options = SshWriter::default();
+            options.string(&salt).u32(BCRYPT_ROUNDS);
+            header
+                .string(AES256_CTR.as_bytes())
+                .string(BCRYPT.as_bytes())
+                .string(&options.0);
+            pad(&mut section, 16);
+            apply_cipher(AES256_CTR, &salt, BCRYPT_ROUNDS, password, &mut section.0)?;
+        }
+        None => {
+            header
+                .string(NONE.as_bytes())
+                .string(NONE.as_bytes())
+                .string(&[]);
+            pad(&mut section, 8);
+        }
+    }
+    header.u32(1).string(&public_blob).string(&section.0);
+
+    let base64 = Zeroizing::new(Base64::encode_string(&header.0));
+    let mut pem = Zeroizing::new(format!("-----BEGIN {}-----\n", pem::OPENSSH_PRIVATE_KEY));
+    for line in base64.as_bytes().chunks(LINE_WIDTH) {
+        pem.push_str(std::str::from_utf8(line)?);
+        pem.push('\n');
+    }
+    pem.push_str(&format!("-----END {}-----\n", pem::OPENSSH_PRIVATE_KEY));
+    Ok(pem)
+}
+
+/// Pad the private section to the cipher block size with 1, 2, 3...
+fn pad(section: &mut SshWriter, block_size: usize) {
+    let mut i = 1u8;
+    while !section.0.len().is_multiple_of(block_size) {
+        section.0.push(i);
+        i += 1;
+    }
+}
+
+/// The `authorized_keys` line of the public key of the KeyInfo, without the
+/// line ending
+pub fn key_info_as_openssh(key_info: &KeyInfo, comment: Option<&str>) -> Result<String> {
+    let jwk = key_info_as_public_jwk(&with_public_key(key_info)?)?;
+    let (key_type, blob) = jwk_to_ssh_blob(&jwk)?;
+    let line = match comment {
+        Some(comment) => format!("{} {} {}", key_type, Base64::encode_string(&blob), comment),
+        None => format!("{} {}", key_type, Base64::encode_string(&blob)),
+    };
+    Ok(line)
+}
+
+/// Write the KeyInfo as an OpenSSH key
+///
+/// Private keys are written as openssh-key-v1 documents, protected by the
+/// output password if one is provided.  Public keys, and private keys
+/// converted to public keys, are written as an `authorized_keys` line, and
+/// the output password is ignored.
+pub fn key_info_to_openssh(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let comment = app_state.comment.clone();
+    if comment.as_deref().is_some_and(|c| c.contains(['\r', '\n'])) {
+        bail!(Error::BadOpenSsh("the comment must be one line".to_owned()));
+    }
+    if key_info.key_type == KeyType::Private && app_state.key_type != Some(KeyType::Public) {
+        let pem = key_info_as_openssh_private(
+            key_info,
+            comment.as_deref(),
+            app_state.out_password.as_deref(),
+        )?;
+        return app_state.write_stream(pem.as_bytes());
+    }
+    if app_state.out_password.is_some() {
+        warn!("Public keys are not protected. Ignoring the output password");
+    }
+    let line = key_info_as_openssh(key_info, comment.as_deref())?;
+    app_state.write_stream(format!("{}\n", line).as_bytes())
+}
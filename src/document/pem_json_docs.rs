@@ -0,0 +1,63 @@
+//! PEM documents in a JSON envelope
+//!
+//! Some APIs carry keys as PEM text in a JSON string, which is easy to get
+//! wrong when escaping by hand.  The envelope holds the PEM, and a hint of
+//! the key type, using the JWK `kty` names:
+//!
+//! ```json
+//! {"kty-hint":"EC","pem":"-----BEGIN PUBLIC KEY-----\r\nMFkw...\r\n-----END PUBLIC KEY-----\r\n"}
+//! ```
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::document::jwk_docs::{canonical_json, KTY_AKP, KTY_EC, KTY_OCT, KTY_OKP, KTY_RSA};
+use crate::errors::Error;
+use crate::key_info::Alg;
+
+/// A PEM document in a JSON envelope
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct PemJson {
+    /// JWK key type of the key, such as "RSA"
+    #[serde(rename = "kty-hint", skip_serializing_if = "Option::is_none")]
+    pub kty_hint: Option<String>,
+    /// The PEM document
+    pub pem: String,
+}
+
+impl PemJson {
+    /// Wrap a PEM document, with the key type of the algorithm as the hint
+    pub fn new(alg: Alg, pem: String) -> Self {
+        Self {
+            kty_hint: kty_hint(alg).map(str::to_owned),
+            pem,
+        }
+    }
+
+    /// Parse an envelope.  Fails if the input is not JSON, or has no `pem`
+    /// member, such as a JWK.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes).map_err(|e| Error::BadPem(e.to_string()).into())
+    }
+
+    /// Serialize the envelope to canonical JSON
+    pub fn to_json(&self, pretty: bool) -> Result<String> {
+        canonical_json(self, pretty)
+    }
+}
+
+/// The JWK key type of an algorithm
+fn kty_hint(alg: Alg) -> Option<&'static str> {
+    match alg {
+        Alg::Rsa | Alg::RsaSsaPss => Some(KTY_RSA),
+        Alg::Ecdsa => Some(KTY_EC),
+        Alg::X25519
+        | Alg::X448
+        | Alg::EdDsa25519
+        | Alg::EdDsa448
+        | Alg::EdDsa25519Ph
+        | Alg::EdDsa448Ph => Some(KTY_OKP),
+        Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87 => Some(KTY_AKP),
+        Alg::Symmetric => Some(KTY_OCT),
+        _ => None,
+    }
+}
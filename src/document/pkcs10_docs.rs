@@ -0,0 +1,76 @@
+//! PKCS#10 certificate signing requests ([RFC 2986](https://www.rfc-editor.org/rfc/rfc2986))
+//!
+//! A CSR is read for the SubjectPublicKeyInfo it holds, as a certificate is,
+//! so that the public key can be shown and converted like an SPKI document.
+//! The signature algorithm the request was signed with is kept as the
+//! KeyInfo signature algorithm.  The signature is not verified.
+//!
+//! ```rust
+//! use kt::document::pkcs10_docs::csr_to_key_info;
+//! use kt::key_info::{Alg, Encoding, Format};
+//! use kt::oids;
+//!
+//! // A request for CN=test, signed with ecdsa-with-SHA256 by a P-256 key
+//! let der = std::fs::read("test_data/ec-p256-csr.der").unwrap();
+//! let key_info = csr_to_key_info(&der, Encoding::DER).unwrap();
+//! assert_eq!(key_info.format, Format::PKCS10);
+//! assert_eq!(key_info.alg, Alg::Ecdsa);
+//! assert_eq!(key_info.signature_oid, Some(oids::ECDSA_WITH_SHA256));
+//! ```
+use anyhow::Result;
+use pkcs8::der::{asn1::Any, Decoder};
+use spki::{AlgorithmIdentifier, PublicKeyDocument, SubjectPublicKeyInfo};
+
+use crate::document::spki_docs::spki_to_key_info;
+use crate::errors::Error;
+use crate::key_info::{Encoding, Format, KeyInfo};
+use crate::pem;
+
+/// The parts of a CSR that kt reads
+pub struct CertificationRequest<'a> {
+    /// The subject's Name
+    pub subject: Any<'a>,
+    /// The subject's public key
+    pub spki: SubjectPublicKeyInfo<'a>,
+    /// The algorithm the request was signed with
+    pub signature_algorithm: AlgorithmIdentifier<'a>,
+}
+
+/// Parse a DER CertificationRequest.  The version and the attributes are
+/// skipped.
+pub fn parse_csr(der: &[u8]) -> Result<CertificationRequest<'_>> {
+    let mut decoder = Decoder::new(der)?;
+    let csr = decoder.sequence(|csr| {
+        let (subject, spki) = csr.sequence(|info| {
+            info.any()?;
+            let subject = info.any()?;
+            let spki: SubjectPublicKeyInfo = info.decode()?;
+            // The [0] IMPLICIT attributes
+            info.any()?;
+            Ok((subject, spki))
+        })?;
+        let signature_algorithm: AlgorithmIdentifier = csr.decode()?;
+        csr.bit_string()?;
+        Ok(CertificationRequest {
+            subject,
+            spki,
+            signature_algorithm,
+        })
+    })?;
+    Ok(decoder.finish(csr)?)
+}
+
+/// Test whether the text is a PEM CSR
+pub fn is_csr_pem(text: &str) -> bool {
+    pem::label(text.trim_start().as_bytes()).is_ok_and(|label| label == pem::CERTIFICATE_REQUEST)
+}
+
+/// Turn the public key of a DER CSR into KeyInfo bytes, matching a key
+/// discovered from SPKI
+pub fn csr_to_key_info(der: &[u8], encoding: Encoding) -> Result<KeyInfo> {
+    let csr = parse_csr(der).map_err(|e| Error::BadCsr(e.to_string()))?;
+    let spki_doc: PublicKeyDocument = csr.spki.try_into()?;
+    Ok(spki_to_key_info(&spki_doc, encoding)?
+        .with_format(Format::PKCS10)
+        .with_signature_alg_id(&csr.signature_algorithm))
+}
@@ -0,0 +1,502 @@
+//! PKCS#12 keystores, such as `.p12` and `.pfx` files ([RFC 7292](https://www.rfc-editor.org/rfc/rfc7292))
+//!
+//! A keystore is a list of bags, each holding a private key or a certificate,
+//! and named by an optional friendly name.  The bags are read with the input
+//! password, which both protects the integrity of the file, with a MAC, and
+//! encrypts the bags.  Both the PBES2 (PBKDF2 and AES) encryption that
+//! OpenSSL 3 writes, and the legacy PKCS#12 PBE (SHA-1 and 3DES or RC2), are
+//! read.
+//!
+//! The private key is read by default.  Its certificate, or another
+//! certificate of the chain, is read instead if it is asked for, or if the
+//! keystore has no private key.
+//!
+//! Keystores are written as OpenSSL 3 writes them: the certificates are in
+//! encrypted data, and the private key in a shrouded key bag, both with
+//! PBES2, by default PBKDF2-SHA256 and AES-256-CBC (see [crate::pbe]).  The
+//! MAC is HMAC-SHA256.  The key and its certificate share a localKeyId, the
+//! SHA-1 of the certificate.
+use anyhow::{bail, Result};
+use hmac::{Mac, SimpleHmac};
+use p12::{
+    AlgorithmIdentifier as PbeAlgorithm, CertBag, ContentInfo, DigestInfo, EncryptedContentInfo,
+    EncryptedData, EncryptedPrivateKeyInfo, MacData, OtherAlgorithmIdentifier, PKCS12Attribute,
+    SafeBag, SafeBagKind, PFX,
+};
+use pkcs8::der::{Any, Decodable, Document, Encodable};
+use pkcs8::pkcs5::{pbes2, EncryptionScheme};
+use pkcs8::PrivateKeyDocument;
+use rand_core::{OsRng, RngCore};
+use sha1::Sha1;
+use sha2::digest::core_api::BlockSizeUser;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use spki::{AlgorithmIdentifier, ObjectIdentifier};
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::pkcs8_docs::{key_info_to_pk8_document, pk8_to_private_key_info};
+use crate::document::spki_docs::public_spki_der;
+use crate::document::x509_docs::{certificate_to_key_info, parse_certificate, select_certificate};
+use crate::errors::Error;
+use crate::key_info::{Encoding, Format, KeyInfo, KeyType};
+use crate::oids;
+use crate::pbe::PbeParams;
+use crate::pem;
+
+/// The version of a PFX
+const PFX_VERSION: u8 = 3;
+/// An unencrypted PKCS8 key bag
+const KEY_BAG: &str = "1.2.840.113549.1.12.10.1.1";
+/// ID of the PKCS#12 key derivation for MAC keys
+const MAC_KEY_ID: u8 = 3;
+/// MAC iterations of written keystores.  The same as openssl
+const ITERATIONS: u32 = 2048;
+/// Bytes of the MAC salt of written keystores
+const SALT_LEN: usize = 16;
+
+/// What a bag of a keystore holds
+pub enum Pkcs12Content {
+    /// A DER PKCS8 PrivateKeyInfo
+    PrivateKey(Zeroizing<Vec<u8>>),
+    /// A DER X.509 certificate
+    Certificate(Vec<u8>),
+}
+
+/// A bag of a keystore
+pub struct Pkcs12Bag {
+    pub friendly_name: Option<String>,
+    pub content: Pkcs12Content,
+}
+
+/// The password as a BMPString, with the terminating NULL, as the PKCS#12
+/// key derivation and PBE use it
+fn bmp_password(password: &str) -> Zeroizing<Vec<u8>> {
+    let mut bytes: Vec<u8> = password
+        .encode_utf16()
+        .flat_map(|c| c.to_be_bytes())
+        .collect();
+    bytes.extend([0, 0]);
+    Zeroizing::new(bytes)
+}
+
+/// The PKCS#12 key derivation of RFC 7292, Appendix B.2
+fn pkcs12_kdf<D: Digest + BlockSizeUser>(
+    password: &[u8],
+    salt: &[u8],
+    id: u8,
+    iterations: u32,
+    len: usize,
+) -> Zeroizing<Vec<u8>> {
+    let v = D::block_size();
+    let u = <D as Digest>::output_size();
+    // Repeat the data to a whole number of v byte blocks
+    let fill = |data: &[u8]| -> Vec<u8> {
+        (0..v * data.len().div_ceil(v))
+            .map(|i| data[i % data.len()])
+            .collect()
+    };
+    let mut input = Zeroizing::new([fill(salt), fill(password)].concat());
+    let mut output = Zeroizing::new(Vec::new());
+    while output.len() < len {
+        let mut a = D::new()
+            .chain_update(vec![id; v])
+            .chain_update(&*input)
+            .finalize()
+            .to_vec();
+        for _ in 1..iterations {
+            a = D::digest(&a).to_vec();
+        }
+        // Each block of the input becomes block + B + 1
+        let b: Vec<u8> = (0..v).map(|i| a[i % u]).collect();
+        for block in input.chunks_mut(v) {
+            let mut carry = 1u16;
+            for (byte, b) in block.iter_mut().rev().zip(b.iter().rev()) {
+                let sum = *byte as u16 + *b as u16 + carry;
+                *byte = sum as u8;
+                carry = sum >> 8;
+            }
+        }
+        output.extend_from_slice(&a);
+    }
+    output.truncate(len);
+    output
+}
+
+/// Whether the MAC of the data is the MAC of the keystore
+fn mac_matches<D: Digest + BlockSizeUser>(mac_data: &MacData, data: &[u8], password: &str) -> bool {
+    let key = pkcs12_kdf::<D>(
+        &bmp_password(password),
+        &mac_data.salt,
+        MAC_KEY_ID,
+        mac_data.iterations,
+        <D as Digest>::output_size(),
+    );
+    match SimpleHmac::<D>::new_from_slice(&key) {
+        Ok(mac) => mac
+            .chain_update(data)
+            .verify_slice(&mac_data.mac.digest)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+/// The OID of an algorithm that the p12 crate doesn't know
+fn other_oid(alg: &PbeAlgorithm) -> Result<ObjectIdentifier> {
+    match alg {
+        PbeAlgorithm::Sha1 => Ok(oids::SHA1),
+        PbeAlgorithm::OtherAlg(other) => other
+            .algorithm_type
+            .to_string()
+            .parse()
+            .map_err(|_| Error::BadPkcs12("bad algorithm".to_owned()).into()),
+        _ => bail!(Error::BadPkcs12("unexpected algorithm".to_owned())),
+    }
+}
+
+/// Check the MAC of the keystore, which fails if the password is wrong
+fn verify_mac(mac_data: &MacData, data: &[u8], password: &str) -> Result<()> {
+    let matches = match other_oid(&mac_data.mac.digest_algorithm)? {
+        oids::SHA1 => mac_matches::<Sha1>(mac_data, data, password),
+        oids::SHA256 => mac_matches::<Sha256>(mac_data, data, password),
+        oids::SHA384 => mac_matches::<Sha384>(mac_data, data, password),
+        oids::SHA512 => mac_matches::<Sha512>(mac_data, data, password),
+        oid => bail!(Error::BadPkcs12(format!("unsupported MAC {}", oid))),
+    };
+    match matches {
+        true => Ok(()),
+        false => bail!(Error::BadPassword),
+    }
+}
+
+/// Decrypt the content of an encrypted bag, or of encrypted data
+fn decrypt(alg: &PbeAlgorithm, ciphertext: &[u8], password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let plaintext = match alg {
+        PbeAlgorithm::OtherAlg(other) => {
+            let params = other.params.as_deref().unwrap_or_default();
+            let alg_id = AlgorithmIdentifier {
+                oid: other_oid(alg)?,
+                parameters: Some(Any::from_der(params)?),
+            };
+            let scheme = EncryptionScheme::try_from(alg_id)
+                .map_err(|e| Error::BadPkcs12(format!("unsupported encryption: {}", e)))?;
+            scheme.decrypt(password, ciphertext).ok()
+        }
+        alg => alg.decrypt_pbe(ciphertext, &bmp_password(password)),
+    };
+    plaintext
+        .map(Zeroizing::new)
+        .ok_or_else(|| Error::BadPassword.into())
+}
+
+/// Parse a DER, or BER, PFX
+fn parse_pfx(bytes: &[u8]) -> Result<PFX> {
+    let pfx = yasna::parse_ber(bytes, |r| {
+        r.read_sequence(|r| {
+            let version = r.next().read_u8()?;
+            let auth_safe = ContentInfo::parse(r.next())?;
+            let mac_data = r.read_optional(MacData::parse)?;
+            Ok(PFX {
+                version,
+                auth_safe,
+                mac_data,
+            })
+        })
+    })
+    .map_err(|e| Error::BadPkcs12(e.to_string()))?;
+    if pfx.version != PFX_VERSION {
+        bail!(Error::BadPkcs12(format!("unknown version {}", pfx.version)));
+    }
+    Ok(pfx)
+}
+
+/// Test whether the bytes are a PKCS#12 keystore
+pub fn is_pkcs12(bytes: &[u8]) -> bool {
+    parse_pfx(bytes).is_ok()
+}
+
+/// The bags of a keystore, in order, decrypted with the password
+pub fn pkcs12_bags(bytes: &[u8], password: &str) -> Result<Vec<Pkcs12Bag>> {
+    let bad = |e: yasna::ASN1Error| Error::BadPkcs12(e.to_string());
+    let pfx = parse_pfx(bytes)?;
+    let data = match &pfx.auth_safe {
+        ContentInfo::Data(data) => data,
+        _ => bail!(Error::BadPkcs12(
+            "public key integrity is not supported".to_owned()
+        )),
+    };
+    if let Some(mac_data) = &pfx.mac_data {
+        verify_mac(mac_data, data, password)?;
+    }
+
+    let mut bags = Vec::new();
+    let contents =
+        yasna::parse_ber(data, |r| r.collect_sequence_of(ContentInfo::parse)).map_err(bad)?;
+    for content in contents {
+        let safe_contents = match content {
+            ContentInfo::Data(data) => Zeroizing::new(data),
+            ContentInfo::EncryptedData(encrypted) => {
+                let info = encrypted.encrypted_content_info;
+                decrypt(
+                    &info.content_encryption_algorithm,
+                    &info.encrypted_content,
+                    password,
+                )?
+            }
+            ContentInfo::OtherContext(_) => continue,
+        };
+        let safe_bags = yasna::parse_ber(&safe_contents, |r| {
+            r.collect_sequence_of(p12::SafeBag::parse)
+        })
+        .map_err(bad)?;
+        for safe_bag in safe_bags {
+            let content = match safe_bag.bag {
+                SafeBagKind::Pkcs8ShroudedKeyBag(key) => Pkcs12Content::PrivateKey(decrypt(
+                    &key.encryption_algorithm,
+                    &key.encrypted_data,
+                    password,
+                )?),
+                SafeBagKind::CertBag(p12::CertBag::X509(cert)) => Pkcs12Content::Certificate(cert),
+                SafeBagKind::OtherBagKind(other) if other.bag_id.to_string() == KEY_BAG => {
+                    Pkcs12Content::PrivateKey(Zeroizing::new(other.bag_value))
+                }
+                _ => continue,
+            };
+            let friendly_name =
+                safe_bag
+                    .attributes
+                    .into_iter()
+                    .find_map(|attribute| match attribute {
+                        p12::PKCS12Attribute::FriendlyName(name) => Some(name),
+                        _ => None,
+                    });
+            bags.push(Pkcs12Bag {
+                friendly_name,
+                content,
+            });
+        }
+    }
+    Ok(bags)
+}
+
+/// Read the private key, or a certificate, of a keystore.
+///
+/// Only the bags with the friendly name are read, if one is given.  The
+/// certificate is chosen by the certificate index.
+pub fn pkcs12_to_key_info(app_state: &AppState, bytes: &[u8]) -> Result<KeyInfo> {
+    let bags = app_state.decrypt_input(|pwd| pkcs12_bags(bytes, pwd))?;
+    let named = |bag: &&Pkcs12Bag| match &app_state.friendly_name {
+        Some(name) => bag.friendly_name.as_deref() == Some(name.as_str()),
+        None => true,
+    };
+    let bags: Vec<&Pkcs12Bag> = bags.iter().filter(named).collect();
+
+    let mut certs = Vec::new();
+    for bag in &bags {
+        match &bag.content {
+            Pkcs12Content::PrivateKey(der) if !app_state.p12_cert => {
+                let pk8_doc = PrivateKeyDocument::from_der(der)?;
+                return Ok(pk8_to_private_key_info(&pk8_doc, Encoding::DER)?
+                    .with_format(Format::PKCS12)
+                    .with_encrypted(true)
+                    .with_comment(bag.friendly_name.as_deref().unwrap_or_default()));
+            }
+            Pkcs12Content::PrivateKey(_) => {}
+            Pkcs12Content::Certificate(der) => certs.push((der.clone(), &bag.friendly_name)),
+        }
+    }
+
+    if certs.is_empty() {
+        let what = match app_state.p12_cert {
+            true => "certificate",
+            false => "private key or certificate",
+        };
+        bail!(Error::BadPkcs12(match &app_state.friendly_name {
+            Some(name) => format!("no {} named {}", what, name),
+            None => format!("no {}", what),
+        }));
+    }
+    let ders: Vec<Vec<u8>> = certs.iter().map(|(der, _)| der.clone()).collect();
+    let der = select_certificate(&ders, app_state.cert_index)?;
+    let name = certs[app_state.cert_index].1.as_deref().unwrap_or_default();
+    Ok(certificate_to_key_info(der, Encoding::DER)?
+        .with_format(Format::PKCS12)
+        .with_comment(name))
+}
+
+/// The yasna OID, as the p12 crate uses, of an OID
+fn yasna_oid(oid: ObjectIdentifier) -> Result<yasna::models::ObjectIdentifier> {
+    oid.to_string()
+        .parse()
+        .map_err(|_| Error::BadPkcs12("bad algorithm".to_owned()).into())
+}
+
+/// Encrypt the content of a bag, or of encrypted data, with PBES2
+fn encrypt(plaintext: &[u8], password: &str, pbe: &PbeParams) -> Result<(PbeAlgorithm, Vec<u8>)> {
+    pbe.with_parameters(|params| {
+        let ciphertext = params
+            .encrypt(password, plaintext)
+            .map_err(|e| Error::BadPkcs12(e.to_string()))?;
+        let alg = PbeAlgorithm::OtherAlg(OtherAlgorithmIdentifier {
+            algorithm_type: yasna_oid(pbes2::PBES2_OID)?,
+            params: Some(params.to_vec()?),
+        });
+        Ok((alg, ciphertext))
+    })
+}
+
+/// The HMAC-SHA256 MAC of the keystore data
+fn mac_data(data: &[u8], password: &str) -> Result<MacData> {
+    let mut salt = vec![0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let key = pkcs12_kdf::<Sha256>(
+        &bmp_password(password),
+        &salt,
+        MAC_KEY_ID,
+        ITERATIONS,
+        <Sha256 as Digest>::output_size(),
+    );
+    let mac = SimpleHmac::<Sha256>::new_from_slice(&key).map_err(|_| Error::BadCrypto)?;
+    Ok(MacData {
+        mac: DigestInfo {
+            digest_algorithm: PbeAlgorithm::OtherAlg(OtherAlgorithmIdentifier {
+                algorithm_type: yasna_oid(oids::SHA256)?,
+                params: Some(vec![0x05, 0x00]),
+            }),
+            digest: mac.chain_update(data).finalize().into_bytes().to_vec(),
+        },
+        salt,
+        iterations: ITERATIONS,
+    })
+}
+
+/// The SafeContents DER of bags
+fn safe_contents_der(bags: &[SafeBag]) -> Vec<u8> {
+    yasna::construct_der(|w| {
+        w.write_sequence_of(|w| {
+            for bag in bags {
+                bag.write(w.next());
+            }
+        })
+    })
+}
+
+/// Build a DER keystore of a PKCS8 private key and a certificate chain.
+///
+/// The first certificate is the certificate of the key.  The key and that
+/// certificate are given the friendly name, if there is one.  The bags are
+/// encrypted as `pbe` says.
+pub fn build_pkcs12(
+    key_der: &[u8],
+    certs: &[Vec<u8>],
+    friendly_name: Option<&str>,
+    password: &str,
+    pbe: &PbeParams,
+) -> Result<Vec<u8>> {
+    let mut attributes = Vec::new();
+    if let Some(name) = friendly_name {
+        attributes.push(PKCS12Attribute::FriendlyName(name.to_owned()));
+    }
+    if let Some(leaf) = certs.first() {
+        attributes.push(PKCS12Attribute::LocalKeyId(Sha1::digest(leaf).to_vec()));
+    }
+
+    let mut contents = Vec::new();
+    if !certs.is_empty() {
+        let cert_bags: Vec<SafeBag> = certs
+            .iter()
+            .enumerate()
+            .map(|(i, cert)| SafeBag {
+                bag: SafeBagKind::CertBag(CertBag::X509(cert.clone())),
+                attributes: match i {
+                    0 => attributes.clone(),
+                    _ => Vec::new(),
+                },
+            })
+            .collect();
+        let (alg, encrypted_content) = encrypt(&safe_contents_der(&cert_bags), password, pbe)?;
+        contents.push(ContentInfo::EncryptedData(EncryptedData {
+            encrypted_content_info: EncryptedContentInfo {
+                content_encryption_algorithm: alg,
+                encrypted_content,
+            },
+        }));
+    }
+    let (alg, encrypted_data) = encrypt(key_der, password, pbe)?;
+    let key_bag = SafeBag {
+        bag: SafeBagKind::Pkcs8ShroudedKeyBag(EncryptedPrivateKeyInfo {
+            encryption_algorithm: alg,
+            encrypted_data,
+        }),
+        attributes,
+    };
+    contents.push(ContentInfo::Data(safe_contents_der(&[key_bag])));
+
+    let auth_safe = yasna::construct_der(|w| {
+        w.write_sequence_of(|w| {
+            for content in &contents {
+                content.write(w.next());
+            }
+        })
+    });
+    let mac_data = mac_data(&auth_safe, password)?;
+    Ok(PFX {
+        version: PFX_VERSION,
+        auth_safe: ContentInfo::Data(auth_safe),
+        mac_data: Some(mac_data),
+    }
+    .to_der())
+}
+
+/// Write the private key as a keystore, protected by the output password.
+///
+/// The certificate of the key, and the CA certificates of its chain, are
+/// read from the `--cert` and `--ca` files.  The friendly name is the one
+/// given, or else the comment of the key.
+pub fn key_info_to_pkcs12(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    if key_info.key_type != KeyType::Private {
+        bail!(Error::TypeMismatch);
+    }
+    let password = app_state
+        .out_password
+        .clone()
+        .map(Zeroizing::new)
+        .ok_or_else(|| Error::MissingInput("keystore password".to_owned()))?;
+
+    let mut certs = match &app_state.cert_file {
+        Some(cert_file) => pem::read_certs(cert_file)?,
+        None => Vec::new(),
+    };
+    if let Some(leaf) = certs.first() {
+        let certificate =
+            parse_certificate(leaf).map_err(|e| Error::BadCertificate(e.to_string()))?;
+        let spki_der = public_spki_der(key_info)?;
+        let spki = spki::SubjectPublicKeyInfo::from_der(&spki_der)?;
+        if certificate.spki.subject_public_key != spki.subject_public_key {
+            bail!(Error::BadCertificate(
+                "the first certificate is not for this key".to_owned()
+            ));
+        }
+    }
+    for ca_file in &app_state.ca_files {
+        certs.extend(pem::read_certs(ca_file)?);
+    }
+
+    let friendly_name = app_state
+        .friendly_name
+        .clone()
+        .or_else(|| key_info.comment.clone())
+        .filter(|name| !name.is_empty());
+    let key_der = Zeroizing::new(
+        key_info_to_pk8_document(key_info.alg, key_info, app_state.pkcs8_version)?
+            .as_der()
+            .to_vec(),
+    );
+    let der = build_pkcs12(
+        &key_der,
+        &certs,
+        friendly_name.as_deref(),
+        &password,
+        &app_state.pbe,
+    )?;
+    app_state.write_stream(&der)
+}
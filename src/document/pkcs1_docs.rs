@@ -1,16 +1,36 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 
 use pkcs8::{der::Document, LineEnding::CRLF};
 use pkcs1::{RsaPrivateKeyDocument, RsaPublicKeyDocument};
+use zeroize::Zeroizing;
 
 use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::explain::uint_bits;
 use crate::key_info::KeyInfo;
+use crate::pem;
 use crate::key_info::{Alg, Encoding, Format, KeyType};
 
+/// The length of an RSA key, the size of its modulus in bits
+pub(crate) fn rsa_modulus_bits(modulus: &[u8]) -> u32 {
+    uint_bits(modulus) as u32
+}
+
+/// The number of primes of a multi-prime RSAPrivateKey, version 1 with
+/// otherPrimeInfos.  None for a two-prime key.
+pub fn rsa_prime_count(der: &[u8]) -> Option<usize> {
+    let pk1_doc = RsaPrivateKeyDocument::from_der(der).ok()?;
+    let pk1 = pk1_doc.decode();
+    pk1.other_prime_infos.map(|infos| infos.len() + 2)
+}
+
 /// Turns a PKCS1 private key document into KeyInfo bytes
+///
+/// Multi-prime keys, of version 1, are read as well.  Their length is the
+/// size of the modulus, as for any RSA key.
 pub fn pk1_to_rsa_private_key(pk1_doc: &RsaPrivateKeyDocument, encoding: Encoding) -> Result<KeyInfo> {
     let pk1 = pk1_doc.decode();
-    let key_length = u32::from(pk1.private_exponent.len()) * 8;
+    let key_length = rsa_modulus_bits(pk1.modulus.as_bytes());
     let key_info = KeyInfo::new()
         .with_alg(Alg::Rsa)
         .with_format(Format::PKCS1)
@@ -24,7 +44,7 @@ pub fn pk1_to_rsa_private_key(pk1_doc: &RsaPrivateKeyDocument, encoding: Encodin
 /// Turns a PKCS1 public key document into KeyInfo bytes
 pub fn pk1_to_rsa_public_key(pk1_doc: &RsaPublicKeyDocument, encoding: Encoding) -> Result<KeyInfo> {
     let pk1 = pk1_doc.decode();
-    let key_length = u32::from(pk1.modulus.len()) * 8;
+    let key_length = rsa_modulus_bits(pk1.modulus.as_bytes());
     let key_info = KeyInfo::new()
         .with_alg(Alg::Rsa)
         .with_format(Format::PKCS1)
@@ -35,16 +55,37 @@ pub fn pk1_to_rsa_public_key(pk1_doc: &RsaPublicKeyDocument, encoding: Encoding)
     Ok(key_info)
 }
 
+/// The public KeyInfo of an RSA private key, whose bytes are the
+/// RSAPublicKey of the modulus and public exponent of the RSAPrivateKey
+pub fn rsa_public_key_info(key_info: &KeyInfo) -> Result<KeyInfo> {
+    let bytes = key_info
+        .bytes
+        .as_deref()
+        .ok_or_else(|| Error::MissingInput("key".to_owned()))?;
+    let pk1_doc = RsaPrivateKeyDocument::from_der(bytes)?;
+    let public_key = pk1_doc.decode().public_key().to_der()?;
+    Ok(key_info
+        .clone()
+        .with_key_type(KeyType::Public)
+        .with_bytes(public_key.as_der()))
+}
+
 /// Turn a RSA private key bytes into a PKCS1 document
 pub fn rsa_private_key_to_pk1(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
     let bytes = key_info.bytes.clone().unwrap();
     let pkd = RsaPrivateKeyDocument::from_der(&bytes)?;
-    match app_state.encoding {
-        Encoding::DER => {
+    match (app_state.encoding, app_state.out_password.clone()) {
+        // Only PEM has a traditional encryption
+        (Encoding::DER, Some(_)) => bail!(Error::NotSupported),
+        (Encoding::PEM, Some(password)) => {
+            let text = Zeroizing::new(pem::wrap_encrypted(pem::RSA_PRIVATE_KEY, pkd.as_der(), &password)?);
+            app_state.write_stream(text.as_bytes())?;
+        }
+        (Encoding::DER, None) => {
             let bytes = pkd.to_der();
             app_state.write_stream(&bytes)?;
         }
-        Encoding::PEM => {
+        (Encoding::PEM, None) => {
             let bytes = pkd.to_pem(CRLF)?;
             app_state.write_stream(bytes.as_bytes())?;
         }
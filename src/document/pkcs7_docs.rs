@@ -0,0 +1,95 @@
+//! PKCS#7 certificate bundles, such as `.p7b` and `.p7c` files ([RFC 2315](https://www.rfc-editor.org/rfc/rfc2315))
+//!
+//! A "certs-only" bundle is a SignedData, with no content and no signers,
+//! that carries a list of certificates.  It is read like a certificate
+//! chain: the certificate at the certificate index is chosen, and read for
+//! its public key.  Bundles are often BER, as Windows writes them, so they
+//! are parsed as BER.
+//!
+//! Unlike a PEM chain, the certificates of a bundle are a SET, so they are
+//! not in any particular order.  The subjects of the certificates are
+//! logged, at the info level, in the order of the bundle, to help choose one.
+use anyhow::{bail, Result};
+use log::info;
+use yasna::Tag;
+
+use crate::document::x509_docs::{
+    certificate_to_key_info, parse_certificate, select_certificate, subject_to_string,
+};
+use crate::errors::Error;
+use crate::key_info::{Encoding, Format, KeyInfo};
+use crate::pem;
+
+/// PKCS#7 signedData content type
+const SIGNED_DATA: &str = "1.2.840.113549.1.7.2";
+/// The first byte of a certificate, a SEQUENCE.  The other choices of a
+/// CertificateChoices, such as attribute certificates, are tagged.
+const CERTIFICATE_TAG: u8 = 0x30;
+
+/// Test whether the bytes are a PKCS#7 SignedData ContentInfo
+pub fn is_pkcs7(bytes: &[u8]) -> bool {
+    yasna::parse_ber(bytes, |r| {
+        r.read_sequence(|r| {
+            let content_type = r.next().read_oid()?;
+            r.next().read_der()?;
+            Ok(content_type.to_string() == SIGNED_DATA)
+        })
+    })
+    .unwrap_or(false)
+}
+
+/// Test whether the text is a PEM PKCS#7 document
+pub fn is_pkcs7_pem(text: &str) -> bool {
+    pem::label(text.trim_start().as_bytes()).is_ok_and(|label| label == pem::PKCS7)
+}
+
+/// The DER certificates of a PKCS#7 SignedData, in the order of the bundle
+pub fn pkcs7_certificates(bytes: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let choices = yasna::parse_ber(bytes, |r| {
+        r.read_sequence(|r| {
+            r.next().read_oid()?;
+            r.next().read_tagged(Tag::context(0), |r| {
+                r.read_sequence(|r| {
+                    // version, digestAlgorithms and contentInfo
+                    for _ in 0..3 {
+                        r.next().read_der()?;
+                    }
+                    let certificates = r.read_optional(|r| {
+                        r.read_tagged_implicit(Tag::context(0), |r| {
+                            r.collect_set_of(|r| r.read_der())
+                        })
+                    })?;
+                    // crls and signerInfos
+                    while r.read_optional(|r| r.read_der())?.is_some() {}
+                    Ok(certificates.unwrap_or_default())
+                })
+            })
+        })
+    })
+    .map_err(|e| Error::BadPkcs7(e.to_string()))?;
+
+    let certs: Vec<Vec<u8>> = choices
+        .into_iter()
+        .filter(|choice| choice.first() == Some(&CERTIFICATE_TAG))
+        .collect();
+    if certs.is_empty() {
+        bail!(Error::BadPkcs7("no certificates".to_owned()));
+    }
+    Ok(certs)
+}
+
+/// Read the public key of the certificate of a bundle at the index
+pub fn pkcs7_to_key_info(bytes: &[u8], encoding: Encoding, index: usize) -> Result<KeyInfo> {
+    let certs = pkcs7_certificates(bytes)?;
+    for (i, cert) in certs.iter().enumerate() {
+        let certificate =
+            parse_certificate(cert).map_err(|e| Error::BadCertificate(e.to_string()))?;
+        info!(
+            "Certificate {}: {}",
+            i,
+            subject_to_string(&certificate.subject)?
+        );
+    }
+    let cert = select_certificate(&certs, index)?;
+    Ok(certificate_to_key_info(cert, encoding)?.with_format(Format::PKCS7))
+}
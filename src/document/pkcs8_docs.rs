@@ -1,3 +1,5 @@
+use std::str::FromStr;
+
 use anyhow::{bail, Result};
 
 use pkcs1::RsaPrivateKeyDocument;
@@ -5,11 +7,51 @@ use pkcs8::{
     der::Document, EncodePrivateKey, EncryptedPrivateKeyDocument, LineEnding::CRLF,
     PrivateKeyDocument, PrivateKeyInfo,
 };
+use zeroize::Zeroizing;
 
-use crate::alg_id::{rsa_encryption, rsapss_encryption};
+use crate::alg_id::{
+    composite_encryption, dh_encryption, dsa_encryption, ec_encryption, ml_dsa_encryption, okp_encryption, rsa_encryption, rsa_pss_params, rsapss_encryption,
+};
 use crate::app_state::AppState;
+use crate::compare::public_material;
+use crate::document::pkcs1_docs::rsa_modulus_bits;
+use crate::document::sec1_docs::pk8_ec_private_key;
 use crate::errors::Error;
 use crate::key_info::{Alg, Encoding, Format, KeyInfo, KeyType};
+use crate::pbe::PbeInfo;
+
+/// Version of written PKCS8 private keys.
+///
+/// Version 1 ([RFC 5208](https://www.rfc-editor.org/rfc/rfc5208)) holds only
+/// the private key.  Version 2, the OneAsymmetricKey of
+/// [RFC 5958](https://www.rfc-editor.org/rfc/rfc5958), also holds the public
+/// key, which is computed from the private key if it was not read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Pkcs8Version {
+    /// PrivateKeyInfo
+    #[default]
+    V1,
+    /// OneAsymmetricKey, with the public key
+    V2,
+}
+
+impl Pkcs8Version {
+    pub fn all() -> Vec<&'static str> {
+        vec!["1", "2"]
+    }
+}
+
+impl FromStr for Pkcs8Version {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Pkcs8Version> {
+        match s.to_lowercase().as_str() {
+            "1" | "v1" => Ok(Pkcs8Version::V1),
+            "2" | "v2" => Ok(Pkcs8Version::V2),
+            _ => Err(Error::UnknownPkcs8Version.into()),
+        }
+    }
+}
 
 /// Convert a PKCS8 private key document into KeyInfo bytes
 pub fn pk8_to_private_key_info(
@@ -24,9 +66,13 @@ pub fn pk8_to_private_key_info(
         .with_alg_id(&pk8.algorithm)
         .with_bytes(pk8.private_key);
 
+    if let Some(public_key) = pk8.public_key {
+        key_info.set_public_key(public_key);
+    }
+
     if let Ok(pk1_doc) = RsaPrivateKeyDocument::from_der(pk8.private_key) {
         let pk1 = pk1_doc.decode();
-        let key_length = u32::from(pk1.private_exponent.len()) * 8;
+        let key_length = rsa_modulus_bits(pk1.modulus.as_bytes());
         key_info.set_key_length(key_length);
     }
 
@@ -39,25 +85,99 @@ pub fn pk8_encrypted_to_private_key_info(
     enc_pk8_doc: &EncryptedPrivateKeyDocument,
     encoding: Encoding,
 ) -> Result<KeyInfo> {
-    let pwd = app_state.in_password.as_deref();
-    if pwd.is_none() {
-        return Err(Error::MissingInput("password".to_owned()).into());
+    let pk8_doc = app_state.decrypt_input(|pwd| {
+        enc_pk8_doc
+            .decrypt(pwd.as_bytes())
+            .map_err(|_| Error::BadPassword.into())
+    })?;
+    Ok(pk8_to_private_key_info(&pk8_doc, encoding)?
+        .with_encrypted(true)
+        .with_encryption(PbeInfo::from_scheme(&enc_pk8_doc.decode().encryption_algorithm)))
+}
+
+/// The KeyInfo of an encrypted PKCS8 document, PEM or DER, that is not
+/// decrypted.  Only how it is encrypted is known.
+pub fn pk8_encrypted_key_info(bytes: &[u8]) -> Option<KeyInfo> {
+    let (enc_pk8_doc, encoding) = match std::str::from_utf8(bytes) {
+        Ok(pem) => (EncryptedPrivateKeyDocument::from_pem(pem).ok()?, Encoding::PEM),
+        Err(_) => (EncryptedPrivateKeyDocument::from_der(bytes).ok()?, Encoding::DER),
+    };
+    Some(
+        KeyInfo::new()
+            .with_key_type(KeyType::Private)
+            .with_format(Format::PKCS8)
+            .with_encoding(encoding)
+            .with_encrypted(true)
+            .with_encryption(PbeInfo::from_scheme(&enc_pk8_doc.decode().encryption_algorithm)),
+    )
+}
+
+/// The public key of a version 2 document: the public key that was read, or
+/// else the RSAPublicKey, EC point or Ed25519 key of the private key
+fn pk8_public_key(key_info: &KeyInfo) -> Result<Vec<u8>> {
+    if let Some(public_key) = &key_info.public_key {
+        return Ok(public_key.clone());
     }
-    let pk8_doc = enc_pk8_doc.decrypt(pwd.unwrap())?;
-    pk8_to_private_key_info(&pk8_doc, encoding)
+    public_material(key_info)?.ok_or_else(|| {
+        Error::MissingInput(format!("the public key of the {:?} key", key_info.alg)).into()
+    })
 }
 
-/// Turn a PKCS8 PrivateKeyInfo into a document
-pub fn private_key_info_to_pk8(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
-    let alg_id = match app_state.alg()? {
+/// Build a PKCS8 PrivateKeyInfo document for a private key, of the version
+pub fn key_info_to_pk8_document(
+    alg: Alg,
+    key_info: &KeyInfo,
+    version: Pkcs8Version,
+) -> Result<PrivateKeyDocument> {
+    let curve_oid = key_info.curve().map(|curve| curve.oid());
+    let alg_id = match alg {
         Alg::Rsa => rsa_encryption()?,
-        Alg::RsaSsaPss => rsapss_encryption()?,
+        Alg::RsaSsaPss => rsapss_encryption(rsa_pss_params(key_info))?,
+        Alg::Ecdsa => match &curve_oid {
+            Some(curve_oid) => ec_encryption(curve_oid.as_bytes())?,
+            None => bail!(Error::UnknownCurve),
+        },
+        alg @ (Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448) => okp_encryption(alg)?,
+        Alg::Dsa => match &key_info.params {
+            Some(params) => dsa_encryption(params)?,
+            None => bail!(Error::MissingInput("DSA parameters".to_owned())),
+        },
+        Alg::Dh => match &key_info.params {
+            Some(params) => dh_encryption(params)?,
+            None => bail!(Error::MissingInput("DH parameters".to_owned())),
+        },
+        alg @ (Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87) => ml_dsa_encryption(alg, key_info.oid)?,
+        Alg::Composite => composite_encryption(key_info.oid)?,
         _ => bail!(Error::UnknownAlg),
     };
 
-    let bytes = key_info.bytes.clone().unwrap();
-    let pki = PrivateKeyInfo::new(alg_id, &bytes);
-    let pkd: PrivateKeyDocument = pki.try_into()?;
+    let bytes = key_info
+        .bytes
+        .clone()
+        .ok_or_else(|| Error::MissingInput("key".to_owned()))?;
+    // A SEC1 ECPrivateKey names the curve, which is now in alg_id
+    let bytes = match alg {
+        Alg::Ecdsa => pk8_ec_private_key(&bytes)?,
+        _ => bytes,
+    };
+    let public_key = match version {
+        Pkcs8Version::V1 => None,
+        Pkcs8Version::V2 => Some(pk8_public_key(key_info)?),
+    };
+    let mut pki = PrivateKeyInfo::new(alg_id, &bytes);
+    pki.public_key = public_key.as_deref();
+    Ok(pki.try_into()?)
+}
+
+/// Turn a PKCS8 PrivateKeyInfo into a document
+pub fn private_key_info_to_pk8(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let pkd = key_info_to_pk8_document(app_state.alg()?, key_info, app_state.pkcs8_version)?;
+    if let Some(password) = app_state.out_password.clone().map(Zeroizing::new) {
+        let enc_pkd = app_state
+            .pbe
+            .with_parameters(|params| Ok(pkd.encrypt_with_params(params, password.as_bytes())?))?;
+        return encrypted_pk8_to_stream(app_state, &enc_pkd);
+    }
     match app_state.encoding {
         Encoding::DER => {
             let bytes = pkd.to_der();
@@ -71,3 +191,21 @@ pub fn private_key_info_to_pk8(app_state: &mut AppState, key_info: &KeyInfo) ->
     }
     Ok(())
 }
+
+/// Write an EncryptedPrivateKeyInfo document
+fn encrypted_pk8_to_stream(
+    app_state: &mut AppState,
+    enc_pkd: &EncryptedPrivateKeyDocument,
+) -> Result<()> {
+    match app_state.encoding {
+        Encoding::DER => {
+            app_state.write_stream(enc_pkd.as_der())?;
+        }
+        Encoding::PEM => {
+            let bytes = enc_pkd.to_pem(CRLF)?;
+            app_state.write_stream(bytes.as_bytes())?;
+        }
+        _ => {}
+    }
+    Ok(())
+}
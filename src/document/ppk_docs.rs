@@ -0,0 +1,451 @@
+//! PuTTY private keys
+//!
+//! PuTTY keeps keys in its own text format, the `.ppk` file of
+//! [appendix C](https://the.earth.li/~sgtatham/putty/latest/htmldoc/AppendixC.html)
+//! of the PuTTY manual.  Versions 2 and 3 are read.  Encrypted keys use
+//! AES-256-CBC, keyed from the passphrase by SHA-1 in version 2, and by
+//! Argon2 in version 3.  Every file has a MAC over the key, which is checked
+//! before the key is used, so a wrong passphrase is reported as such.
+//!
+//! The public and private blobs are in the SSH wire encoding, and RSA, ECDSA
+//! and Ed25519 keys are turned into the same KeyInfo as OpenSSH keys.
+//!
+//! Keys are written as version 3, which PuTTY has read since 0.75, and are
+//! protected with Argon2id if a password is provided, as `puttygen` does.
+use aes::cipher::{block_padding::NoPadding, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use anyhow::{bail, Result};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64ct::{Base64, Encoding as _};
+use hmac::{Hmac, Mac};
+use pkcs8::der::Decodable;
+use rand_core::{OsRng, RngCore};
+use rsa::traits::PrivateKeyParts;
+use sec1::EcPrivateKey;
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::str::{FromStr, Lines};
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::jwk_docs::key_info_as_public_jwk;
+use crate::document::openssh_docs::{
+    ecdsa_key_info, ed25519_key_info, jwk_to_ssh_blob, rsa_key_info, ssh_curve, with_public_key,
+    SshReader, SshWriter, SSH_ED25519, SSH_RSA,
+};
+use crate::errors::Error;
+use crate::key_info::{Alg, Encoding, Format, KeyInfo, KeyType};
+use crate::signature::{ed25519_signing_key, rsa_private_key};
+
+/// The first line of a PPK file is this, the version, and the key type
+const HEADER: &str = "PuTTY-User-Key-File-";
+/// Encryption of unencrypted keys
+const NONE: &str = "none";
+/// The only encryption PuTTY supports
+const AES256_CBC: &str = "aes256-cbc";
+/// Size of the AES-256-CBC key
+const KEY_SIZE: usize = 32;
+/// Size of the AES-256-CBC IV
+const IV_SIZE: usize = 16;
+/// Size of the version 3 MAC key
+const MAC_KEY_SIZE: usize = 32;
+/// The version 2 MAC key is the SHA-1 of this and the passphrase
+const V2_MAC_KEY: &[u8] = b"putty-private-key-file-mac-key";
+/// Argon2id memory, in KiB, of keys written with a password, as puttygen
+const ARGON2_MEMORY: u32 = 8192;
+/// Argon2id passes of keys written with a password
+const ARGON2_PASSES: u32 = 21;
+/// Size of the Argon2id salt of keys written with a password
+const SALT_SIZE: usize = 16;
+/// PuTTY wraps the base64 of keys at 64 characters
+const LINE_WIDTH: usize = 64;
+
+/// The Argon2 parameters of an encrypted version 3 key
+struct Argon2Params {
+    algorithm: Algorithm,
+    params: Params,
+    salt: Vec<u8>,
+}
+
+/// The fields of a PPK file
+struct PpkFile {
+    version: u32,
+    key_type: String,
+    encryption: String,
+    comment: String,
+    public_blob: Vec<u8>,
+    argon2: Option<Argon2Params>,
+    private_blob: Vec<u8>,
+    mac: Vec<u8>,
+}
+
+/// The keys of a PPK file, derived from the passphrase
+struct PpkKeys {
+    cipher_key: Zeroizing<Vec<u8>>,
+    iv: Zeroizing<Vec<u8>>,
+    mac_key: Zeroizing<Vec<u8>>,
+}
+
+/// Test whether the text is a PPK file
+pub fn is_ppk(text: &str) -> bool {
+    text.trim_start().starts_with(HEADER)
+}
+
+fn bad_ppk(message: &str) -> anyhow::Error {
+    Error::BadPpk(message.to_owned()).into()
+}
+
+/// The value of the next line, which must be the named field
+fn field<'a>(lines: &mut Lines<'a>, name: &str) -> Result<&'a str> {
+    lines
+        .next()
+        .and_then(|line| line.strip_prefix(name))
+        .map(|line| line.trim_end_matches('\r'))
+        // An empty value may have lost its space
+        .and_then(|line| line.strip_prefix(": ").or_else(|| line.strip_prefix(':')))
+        .ok_or_else(|| bad_ppk(&format!("missing {}", name)))
+}
+
+/// A numeric field
+fn number_field<T: FromStr>(lines: &mut Lines, name: &str) -> Result<T> {
+    field(lines, name)?
+        .parse()
+        .map_err(|_| bad_ppk(&format!("bad {}", name)))
+}
+
+/// The base64 lines that follow a line count field
+fn base64_field(lines: &mut Lines, name: &str) -> Result<Vec<u8>> {
+    let count: usize = number_field(lines, name)?;
+    let mut base64 = String::new();
+    for _ in 0..count {
+        let line = lines.next().ok_or_else(|| bad_ppk("truncated key"))?;
+        base64.push_str(line.trim());
+    }
+    Base64::decode_vec(&base64).map_err(|e| bad_ppk(&e.to_string()))
+}
+
+fn parse_ppk(text: &str) -> Result<PpkFile> {
+    let mut lines = text.trim_start().lines();
+    let (version, key_type) = lines
+        .next()
+        .and_then(|line| line.strip_prefix(HEADER))
+        .and_then(|line| line.trim_end_matches('\r').split_once(": "))
+        .ok_or_else(|| bad_ppk("missing header"))?;
+    let version = match version {
+        "2" => 2,
+        "3" => 3,
+        version => bail!(Error::BadPpk(format!("unsupported version {}", version))),
+    };
+    let encryption = field(&mut lines, "Encryption")?;
+    let comment = field(&mut lines, "Comment")?;
+    let public_blob = base64_field(&mut lines, "Public-Lines")?;
+    let argon2 = match (version, encryption) {
+        (3, AES256_CBC) => {
+            let algorithm = match field(&mut lines, "Key-Derivation")? {
+                "Argon2d" => Algorithm::Argon2d,
+                "Argon2i" => Algorithm::Argon2i,
+                "Argon2id" => Algorithm::Argon2id,
+                kdf => bail!(Error::BadPpk(format!("unsupported key derivation {}", kdf))),
+            };
+            let params = Params::new(
+                number_field(&mut lines, "Argon2-Memory")?,
+                number_field(&mut lines, "Argon2-Passes")?,
+                number_field(&mut lines, "Argon2-Parallelism")?,
+                None,
+            )
+            .map_err(|_| bad_ppk("bad Argon2 parameters"))?;
+            let salt = hex::decode(field(&mut lines, "Argon2-Salt")?)
+                .map_err(|_| bad_ppk("bad Argon2-Salt"))?;
+            Some(Argon2Params { algorithm, params, salt })
+        }
+        _ => None,
+    };
+    let private_blob = base64_field(&mut lines, "Private-Lines")?;
+    let mac = hex::decode(field(&mut lines, "Private-MAC")?)
+        .map_err(|_| bad_ppk("bad Private-MAC"))?;
+    Ok(PpkFile {
+        version,
+        key_type: key_type.to_owned(),
+        encryption: encryption.to_owned(),
+        comment: comment.to_owned(),
+        public_blob,
+        argon2,
+        private_blob,
+        mac,
+    })
+}
+
+/// Derive the cipher key, IV and MAC key from the passphrase, which is empty
+/// if the key is not encrypted
+fn derive_keys(ppk: &PpkFile, password: &str) -> Result<PpkKeys> {
+    match (ppk.version, &ppk.argon2) {
+        (2, _) => {
+            let mut cipher_key = Zeroizing::new(Vec::with_capacity(40));
+            for counter in 0u32..2 {
+                let mut sha1 = Sha1::new();
+                sha1.update(counter.to_be_bytes());
+                sha1.update(password.as_bytes());
+                cipher_key.extend_from_slice(&sha1.finalize());
+            }
+            cipher_key.truncate(KEY_SIZE);
+            let mut sha1 = Sha1::new();
+            sha1.update(V2_MAC_KEY);
+            sha1.update(password.as_bytes());
+            Ok(PpkKeys {
+                cipher_key,
+                iv: Zeroizing::new(vec![0u8; IV_SIZE]),
+                mac_key: Zeroizing::new(sha1.finalize().to_vec()),
+            })
+        }
+        (_, Some(argon2_params)) => {
+            let mut bytes = Zeroizing::new(vec![0u8; KEY_SIZE + IV_SIZE + MAC_KEY_SIZE]);
+            Argon2::new(argon2_params.algorithm, Version::V0x13, argon2_params.params.clone())
+                .hash_password_into(password.as_bytes(), &argon2_params.salt, &mut bytes)
+                .map_err(|_| Error::BadCrypto)?;
+            Ok(PpkKeys {
+                cipher_key: Zeroizing::new(bytes[..KEY_SIZE].to_vec()),
+                iv: Zeroizing::new(bytes[KEY_SIZE..KEY_SIZE + IV_SIZE].to_vec()),
+                mac_key: Zeroizing::new(bytes[KEY_SIZE + IV_SIZE..].to_vec()),
+            })
+        }
+        // Unencrypted version 3 keys have an empty MAC key
+        (_, None) => Ok(PpkKeys {
+            cipher_key: Zeroizing::new(Vec::new()),
+            iv: Zeroizing::new(Vec::new()),
+            mac_key: Zeroizing::new(Vec::new()),
+        }),
+    }
+}
+
+/// The MAC of the file, over the fields and the decrypted private blob.
+/// HMAC-SHA-1 in version 2, and HMAC-SHA-256 in version 3.
+fn mac(ppk: &PpkFile, mac_key: &[u8], private_blob: &[u8]) -> Result<Vec<u8>> {
+    let mut data = SshWriter::default();
+    data.string(ppk.key_type.as_bytes())
+        .string(ppk.encryption.as_bytes())
+        .string(ppk.comment.as_bytes())
+        .string(&ppk.public_blob)
+        .string(private_blob);
+    let mac = match ppk.version {
+        2 => Hmac::<Sha1>::new_from_slice(mac_key)
+            .map_err(|_| Error::BadCrypto)?
+            .chain_update(&data.0)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+        _ => Hmac::<Sha256>::new_from_slice(mac_key)
+            .map_err(|_| Error::BadCrypto)?
+            .chain_update(&data.0)
+            .finalize()
+            .into_bytes()
+            .to_vec(),
+    };
+    Ok(mac)
+}
+
+/// Decrypt the private blob, if encrypted, and check the MAC
+fn private_blob(ppk: &PpkFile, password: &str) -> Result<Zeroizing<Vec<u8>>> {
+    let keys = derive_keys(ppk, password)?;
+    let mut private_blob = Zeroizing::new(ppk.private_blob.clone());
+    if ppk.encryption == AES256_CBC {
+        cbc::Decryptor::<aes::Aes256>::new_from_slices(&keys.cipher_key, &keys.iv)
+            .map_err(|_| Error::BadCrypto)?
+            .decrypt_padded_mut::<NoPadding>(&mut private_blob)
+            .map_err(|_| bad_ppk("private key is not a whole number of blocks"))?;
+    }
+    let expected = mac(ppk, &keys.mac_key, &private_blob)?;
+    // Compared without an early exit, as the MAC is of secret data
+    let matches = expected.len() == ppk.mac.len()
+        && expected
+            .iter()
+            .zip(ppk.mac.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    match (matches, ppk.encryption.as_str()) {
+        (true, _) => Ok(private_blob),
+        (false, NONE) => bail!(Error::BadPpk("MAC does not match".to_owned())),
+        (false, _) => bail!(Error::BadPassword),
+    }
+}
+
+/// Turn the public and private blobs into KeyInfo bytes
+fn blobs_to_key_info(key_type: &str, public_blob: &[u8], private_blob: &[u8]) -> Result<KeyInfo> {
+    let mut public = SshReader(public_blob);
+    let mut private = SshReader(private_blob);
+    if public.str()? != key_type {
+        bail!(Error::BadPpk("the public key is not the key type".to_owned()));
+    }
+    match key_type {
+        SSH_RSA => {
+            let e = public.mpint()?;
+            let n = public.mpint()?;
+            let d = private.mpint()?;
+            let p = private.mpint()?;
+            let q = private.mpint()?;
+            rsa_key_info(n, e, d, p, q)
+        }
+        key_type if key_type.starts_with("ecdsa-sha2-") => {
+            let curve = ssh_curve(public.str()?)?;
+            let point = public.string()?;
+            ecdsa_key_info(curve, point, &private.mpint()?)
+        }
+        SSH_ED25519 => {
+            let public_key = public.string()?;
+            // The seed is a little endian integer, so trailing zeros may
+            // have been left out
+            let seed = private.string()?;
+            if seed.len() > 32 {
+                bail!(Error::BadPpk("bad Ed25519 key".to_owned()));
+            }
+            let mut padded = Zeroizing::new(seed.to_vec());
+            padded.resize(32, 0);
+            ed25519_key_info(public_key, &padded)
+        }
+        key_type => bail!(Error::BadPpk(format!("unsupported key type {}", key_type))),
+    }
+}
+
+/// Turn a PPK file into KeyInfo bytes
+///
+/// Encrypted keys are decrypted with the input password.
+pub fn ppk_to_private_key_info(app_state: &AppState, text: &str) -> Result<KeyInfo> {
+    let ppk = parse_ppk(text)?;
+    let encrypted = match ppk.encryption.as_str() {
+        NONE => false,
+        AES256_CBC => true,
+        encryption => bail!(Error::BadPpk(format!("unsupported encryption {}", encryption))),
+    };
+    let private_blob = match encrypted {
+        true => app_state.decrypt_input(|pwd| private_blob(&ppk, pwd))?,
+        false => private_blob(&ppk, "")?,
+    };
+    Ok(blobs_to_key_info(&ppk.key_type, &ppk.public_blob, &private_blob)?
+        .with_encoding(Encoding::PPK)
+        .with_format(Format::PPK)
+        .with_encrypted(encrypted)
+        .with_comment(&ppk.comment))
+}
+
+/// The private blob of the KeyInfo
+fn private_blob_of(key_info: &KeyInfo) -> Result<SshWriter> {
+    let mut blob = SshWriter::default();
+    match key_info.alg {
+        Alg::Rsa | Alg::RsaSsaPss => {
+            let private_key = rsa_private_key(key_info)?;
+            let (p, q) = match private_key.primes() {
+                [p, q] => (p, q),
+                _ => bail!(Error::BadPpk("only two prime RSA keys are supported".to_owned())),
+            };
+            let iqmp = private_key
+                .crt_coefficient()
+                .ok_or_else(|| bad_ppk("bad RSA private key"))?;
+            blob.mpint(&Zeroizing::new(private_key.d().to_bytes_be()))
+                .mpint(&Zeroizing::new(p.to_bytes_be()))
+                .mpint(&Zeroizing::new(q.to_bytes_be()))
+                .mpint(&Zeroizing::new(iqmp.to_bytes_be()));
+        }
+        Alg::Ecdsa => {
+            let bytes = key_info.bytes.as_ref().ok_or(Error::MissingInput("key".to_owned()))?;
+            blob.mpint(EcPrivateKey::from_der(bytes)?.private_key);
+        }
+        Alg::EdDsa25519 => {
+            let signing_key = ed25519_signing_key(key_info)?;
+            blob.string(&Zeroizing::new(Vec::from(signing_key.to_bytes())));
+        }
+        alg => bail!(Error::BadPpk(format!("unsupported algorithm {}", alg))),
+    }
+    Ok(blob)
+}
+
+/// The base64 of the blob, as a line count field and its lines
+fn push_base64(ppk: &mut String, name: &str, blob: &[u8]) -> Result<()> {
+    let base64 = Zeroizing::new(Base64::encode_string(blob));
+    let lines: Vec<&[u8]> = base64.as_bytes().chunks(LINE_WIDTH).collect();
+    ppk.push_str(&format!("{}: {}\n", name, lines.len()));
+    for line in lines {
+        ppk.push_str(std::str::from_utf8(line)?);
+        ppk.push('\n');
+    }
+    Ok(())
+}
+
+/// Turn a private KeyInfo into a version 3 PPK file, encrypted with Argon2id
+/// and AES-256-CBC if a password is provided
+pub fn key_info_as_ppk(
+    key_info: &KeyInfo,
+    comment: Option<&str>,
+    password: Option<&str>,
+) -> Result<Zeroizing<String>> {
+    let key_info = with_public_key(key_info)?;
+    let (key_type, public_blob) = jwk_to_ssh_blob(&key_info_as_public_jwk(&key_info)?)?;
+    let mut private_blob = private_blob_of(&key_info)?;
+
+    let argon2 = match password {
+        Some(_) => {
+            let mut salt = vec![0u8; SALT_SIZE];
+            OsRng.fill_bytes(&mut salt);
+            let params = Params::new(ARGON2_MEMORY, ARGON2_PASSES, 1, None)
+                .map_err(|_| Error::BadCrypto)?;
+            // Encrypted blobs are padded to the block size with random bytes
+            while !private_blob.0.len().is_multiple_of(IV_SIZE) {
+                private_blob.0.push(OsRng.next_u32() as u8);
+            }
+            Some(Argon2Params {
+                algorithm: Algorithm::Argon2id,
+                params,
+                salt,
+            })
+        }
+        None => None,
+    };
+    let mut ppk = PpkFile {
+        version: 3,
+        key_type,
+        encryption: match password {
+            Some(_) => AES256_CBC.to_owned(),
+            None => NONE.to_owned(),
+        },
+        comment: comment.unwrap_or("").to_owned(),
+        public_blob,
+        argon2,
+        private_blob: Vec::new(),
+        mac: Vec::new(),
+    };
+    let keys = derive_keys(&ppk, password.unwrap_or(""))?;
+    ppk.mac = mac(&ppk, &keys.mac_key, &private_blob.0)?;
+    if password.is_some() {
+        let len = private_blob.0.len();
+        cbc::Encryptor::<aes::Aes256>::new_from_slices(&keys.cipher_key, &keys.iv)
+            .map_err(|_| Error::BadCrypto)?
+            .encrypt_padded_mut::<NoPadding>(&mut private_blob.0, len)
+            .map_err(|_| Error::BadCrypto)?;
+    }
+
+    let mut text = Zeroizing::new(format!("{}3: {}\n", HEADER, ppk.key_type));
+    text.push_str(&format!("Encryption: {}\n", ppk.encryption));
+    text.push_str(&format!("Comment: {}\n", ppk.comment));
+    push_base64(&mut text, "Public-Lines", &ppk.public_blob)?;
+    if let Some(argon2) = &ppk.argon2 {
+        text.push_str("Key-Derivation: Argon2id\n");
+        text.push_str(&format!("Argon2-Memory: {}\n", argon2.params.m_cost()));
+        text.push_str(&format!("Argon2-Passes: {}\n", argon2.params.t_cost()));
+        text.push_str(&format!("Argon2-Parallelism: {}\n", argon2.params.p_cost()));
+        text.push_str(&format!("Argon2-Salt: {}\n", hex::encode(&argon2.salt)));
+    }
+    push_base64(&mut text, "Private-Lines", &private_blob.0)?;
+    text.push_str(&format!("Private-MAC: {}\n", hex::encode(&ppk.mac)));
+    Ok(text)
+}
+
+/// Write the KeyInfo as a PPK file, protected by the output password if one
+/// is provided.  PPK files only hold private keys.
+pub fn key_info_to_ppk(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    if key_info.key_type != KeyType::Private || app_state.key_type == Some(KeyType::Public) {
+        bail!(Error::BadPpk("PuTTY key files hold private keys".to_owned()));
+    }
+    let comment = app_state.comment.clone();
+    if comment.as_deref().is_some_and(|c| c.contains(['\r', '\n'])) {
+        bail!(Error::BadPpk("the comment must be one line".to_owned()));
+    }
+    let ppk = key_info_as_ppk(key_info, comment.as_deref(), app_state.out_password.as_deref())?;
+    app_state.write_stream(ppk.as_bytes())
+}
@@ -0,0 +1,184 @@
+//! Raw key material, with no document structure
+//!
+//! HSMs and embedded devices often hold keys as bare bytes: the seed of an
+//! Ed25519 or X25519 key, the scalar or point of an EC key.  The bytes do not
+//! say what key they are, so `--in-format RAW` reads them with the algorithm
+//! of `--alg` and the curve of `--curve`.
+//!
+//! | Algorithm | Private key | Public key |
+//! |-----------|-------------|------------|
+//! | ECDSA | the scalar, the size of the field | a SEC1 point |
+//! | Ed25519 | 32 byte seed, or 64 bytes of seed and public key | 32 bytes, with `-t public` |
+//! | Ed448 | 57 byte seed | 57 bytes, with `-t public` |
+//! | X25519 | 32 bytes | 32 bytes, with `-t public` |
+//! | X448 | 56 bytes | 56 bytes, with `-t public` |
+//!
+//! `-e raw` writes the same bytes, the seed of a 64 byte Ed25519 key.  RSA
+//! keys have no raw form, as the modulus alone is not the key.
+use anyhow::{bail, Result};
+use ed25519_dalek::SigningKey;
+use pkcs8::der::{asn1::OctetString, Decodable, Document, Encodable};
+use sec1::{EcParameters, EcPrivateKey, EcPrivateKeyDocument};
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::openssh_docs::with_public_key;
+use crate::document::sec1_docs::{ec_point_to_key_info, ec_public_key, is_ec_point, sec1_to_private_key_info};
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::secp256k1;
+use crate::sm2;
+
+/// Size in bytes of the private and public keys of an OKP algorithm
+fn okp_key_size(alg: Alg) -> Option<usize> {
+    match alg {
+        Alg::EdDsa25519 | Alg::X25519 => Some(32),
+        Alg::EdDsa448 => Some(57),
+        Alg::X448 => Some(56),
+        _ => None,
+    }
+}
+
+/// A SEC1 ECPrivateKey document of the scalar, with the named curve and the
+/// public key
+fn ec_private_sec1(scalar: &[u8], curve: Curve) -> Result<Zeroizing<Vec<u8>>> {
+    let point = match curve {
+        Curve::P256 => p256::SecretKey::from_slice(scalar)
+            .map(|secret| secret.public_key().to_sec1_bytes().to_vec()),
+        Curve::P384 => p384::SecretKey::from_slice(scalar)
+            .map(|secret| secret.public_key().to_sec1_bytes().to_vec()),
+        Curve::P521 => p521::SecretKey::from_slice(scalar)
+            .map(|secret| secret.public_key().to_sec1_bytes().to_vec()),
+        Curve::Secp256k1 => return secp256k1::sec1_der(scalar),
+        Curve::Sm2 => return sm2::sec1_der(scalar),
+    }
+    .map_err(|_| Error::BadCrypto)?;
+    let ec_private_key = EcPrivateKey {
+        private_key: scalar,
+        parameters: Some(EcParameters::NamedCurve(curve.oid())),
+        public_key: Some(&point),
+    };
+    Ok(Zeroizing::new(ec_private_key.to_vec()?))
+}
+
+fn raw_ec_to_key_info(bytes: &[u8], curve: Curve) -> Result<KeyInfo> {
+    if is_ec_point(bytes, curve) {
+        return ec_point_to_key_info(bytes, curve);
+    }
+    if bytes.len() != curve.field_size() {
+        bail!(Error::BadRawKey(format!(
+            "expected a point, or a {} byte scalar",
+            curve.field_size()
+        )));
+    }
+    let sec1_doc = EcPrivateKeyDocument::from_der(&ec_private_sec1(bytes, curve)?)?;
+    sec1_to_private_key_info(&sec1_doc, Encoding::RAW)
+}
+
+fn raw_okp_to_key_info(bytes: &[u8], alg: Alg, key_type: KeyType) -> Result<KeyInfo> {
+    let size = okp_key_size(alg).ok_or(Error::NotSupported)?;
+    let key_info = KeyInfo::new()
+        .with_alg(alg)
+        .with_format(Format::PKCS8)
+        .with_encoding(Encoding::RAW);
+
+    if key_type == KeyType::Public {
+        if bytes.len() != size {
+            bail!(Error::BadRawKey(format!(
+                "expected {} bytes, not {}",
+                size,
+                bytes.len()
+            )));
+        }
+        return Ok(key_info.with_key_type(KeyType::Public).with_bytes(bytes));
+    }
+
+    // Ed25519 keys are often kept as the seed followed by the public key,
+    // as NaCl and libsodium do
+    let (seed, public_key) = match (alg, bytes.len()) {
+        (_, len) if len == size => (bytes, None),
+        (Alg::EdDsa25519, 64) => (&bytes[..32], Some(&bytes[32..])),
+        (_, len) => bail!(Error::BadRawKey(format!(
+            "expected {} bytes, not {}",
+            size, len
+        ))),
+    };
+    let private_key = Zeroizing::new(OctetString::new(seed)?.to_vec()?);
+    let mut key_info = key_info
+        .with_key_type(KeyType::Private)
+        .with_bytes(&private_key);
+    if alg == Alg::EdDsa25519 {
+        let signing_key = SigningKey::from_bytes(seed.try_into().map_err(|_| Error::BadCrypto)?);
+        let verifying_key = signing_key.verifying_key();
+        if public_key.is_some_and(|public_key| public_key != verifying_key.as_bytes()) {
+            bail!(Error::KeyMismatch);
+        }
+        key_info.set_public_key(verifying_key.as_bytes());
+    }
+    Ok(key_info)
+}
+
+/// Read raw key bytes as a key of the algorithm and curve the user gave.
+/// With a curve and no algorithm, the key is an EC key.  A private key is
+/// read, unless the key type is public or the bytes are an EC point.
+pub fn raw_to_key_info(app_state: &AppState, bytes: &[u8]) -> Result<KeyInfo> {
+    let alg = match (app_state.alg, app_state.curve) {
+        (Some(alg), _) => alg,
+        (None, Some(_)) => Alg::Ecdsa,
+        (None, None) => bail!(Error::MissingAlg),
+    };
+    match alg {
+        Alg::Ecdsa => raw_ec_to_key_info(bytes, app_state.curve.ok_or(Error::UnknownCurve)?),
+        Alg::EdDsa25519 | Alg::EdDsa448 | Alg::X25519 | Alg::X448 => {
+            raw_okp_to_key_info(bytes, alg, app_state.key_type.unwrap_or(KeyType::Private))
+        }
+        _ => bail!(Error::NotSupported),
+    }
+}
+
+/// The public key bytes of an OKP private key, derived from the private key
+/// if the key doesn't carry it
+fn okp_public_key(key_info: &KeyInfo) -> Result<Vec<u8>> {
+    with_public_key(key_info)?
+        .public_key
+        .ok_or_else(|| Error::NotSupported.into())
+}
+
+/// Write the raw key material: the scalar or point of an EC key, the seed or
+/// public key of an OKP key, or the bytes of a secret.  With `-t public`, the
+/// public key of a private key is written.
+pub fn key_info_to_raw(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let bytes = key_info
+        .bytes
+        .as_ref()
+        .map(|bytes| bytes.as_slice())
+        .ok_or_else(|| Error::MissingInput("key".to_owned()))?;
+    let public_only =
+        key_info.key_type == KeyType::Private && app_state.key_type == Some(KeyType::Public);
+    let raw = Zeroizing::new(match (key_info.alg, key_info.key_type) {
+        (Alg::Symmetric, KeyType::Secret) => bytes.to_vec(),
+        (Alg::Ecdsa, KeyType::Private) if public_only => ec_public_key(key_info, app_state.point_format)?,
+        (Alg::Ecdsa, KeyType::Private) => EcPrivateKey::from_der(bytes)
+            .map_err(|_| Error::BadCrypto)?
+            .private_key
+            .to_vec(),
+        (Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448, KeyType::Private)
+            if public_only =>
+        {
+            okp_public_key(key_info)?
+        }
+        (Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448, KeyType::Private) => {
+            OctetString::from_der(bytes)
+                .map_err(|_| Error::BadCrypto)?
+                .as_bytes()
+                .to_vec()
+        }
+        (
+            Alg::Ecdsa | Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448,
+            KeyType::Public,
+        ) => bytes.to_vec(),
+        (Alg::Rsa | Alg::RsaSsaPss, _) => bail!(Error::NoRawForm("RSA")),
+        _ => bail!(Error::NotSupported),
+    });
+    app_state.write_stream(&raw)
+}
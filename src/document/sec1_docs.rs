@@ -1,16 +1,165 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use log::trace;
+use p256::elliptic_curve::sec1::ToEncodedPoint;
 use sec1::{der::Document, LineEnding::CRLF};
-use sec1::EcPrivateKeyDocument;
+use sec1::der::{Decodable, Encodable};
+use sec1::{EcParameters, EcPrivateKey, EcPrivateKeyDocument};
+use std::str::FromStr;
+use zeroize::Zeroizing;
 
+use crate::alg_id::ec_encryption;
 use crate::app_state::AppState;
+use crate::errors::Error;
 use crate::key_info::KeyInfo;
-use crate::key_info::{Alg, Encoding, Format, KeyType};
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyType};
+use crate::pem;
+use crate::secp256k1;
+use crate::signature::ec_public_point;
+use crate::sm2;
+
+/// How the public point of an EC key is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PointFormat {
+    /// 0x02/0x03 || X
+    Compressed,
+    /// 0x04 || X || Y
+    Uncompressed,
+}
+
+impl PointFormat {
+    pub fn all() -> Vec<&'static str> {
+        vec!["compressed", "uncompressed"]
+    }
+}
+
+impl FromStr for PointFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<PointFormat> {
+        match s.to_lowercase().as_str() {
+            "compressed" => Ok(PointFormat::Compressed),
+            "uncompressed" => Ok(PointFormat::Uncompressed),
+            _ => Err(Error::UnknownPointFormat.into()),
+        }
+    }
+}
+
+/// Test that the bytes are a SEC1 encoded point of the expected size for the
+/// curve, either uncompressed (0x04 || X || Y) or compressed (0x02/0x03 || X)
+pub fn is_ec_point(point: &[u8], curve: Curve) -> bool {
+    let size = curve.field_size();
+    match point.first() {
+        Some(0x04) => point.len() == 1 + 2 * size,
+        Some(0x02 | 0x03) => point.len() == 1 + size,
+        _ => false,
+    }
+}
+
+/// The uncompressed form of a SEC1 point, which is checked to be on the
+/// curve
+pub fn uncompressed_point(point: &[u8], curve: Curve) -> Result<Vec<u8>> {
+    let uncompressed = match curve {
+        Curve::P256 => p256::PublicKey::from_sec1_bytes(point)
+            .map(|key| key.to_encoded_point(false).as_bytes().to_vec()),
+        Curve::P384 => p384::PublicKey::from_sec1_bytes(point)
+            .map(|key| key.to_encoded_point(false).as_bytes().to_vec()),
+        Curve::P521 => p521::PublicKey::from_sec1_bytes(point)
+            .map(|key| key.to_encoded_point(false).as_bytes().to_vec()),
+        Curve::Secp256k1 => return secp256k1::uncompressed_point(point),
+        Curve::Sm2 => return sm2::uncompressed_point(point),
+    };
+    uncompressed.map_err(|_| Error::BadEcPoint.into())
+}
+
+/// The compressed form of a SEC1 point, which is checked to be on the curve.
+/// The prefix is 0x02 for an even Y, and 0x03 for an odd Y.
+///
+/// ```
+/// use kt::document::sec1_docs::{compressed_point, uncompressed_point};
+/// use kt::key_info::Curve;
+/// // The P-256 base point, whose Y is odd
+/// let x = "6b17d1f2e12c4247f8bce6e563a440f277037d812deb33a0f4a13945d898c296";
+/// let y = "4fe342e2fe1a7f9b8ee7eb4a7c0f9e162bce33576b315ececbb6406837bf51f5";
+/// let point = hex::decode(format!("04{}{}", x, y)).unwrap();
+/// let compressed = compressed_point(&point, Curve::P256).unwrap();
+/// assert_eq!(hex::encode(&compressed), format!("03{}", x));
+/// assert_eq!(uncompressed_point(&compressed, Curve::P256).unwrap(), point);
+/// ```
+pub fn compressed_point(point: &[u8], curve: Curve) -> Result<Vec<u8>> {
+    let uncompressed = uncompressed_point(point, curve)?;
+    let size = curve.field_size();
+    let prefix = 0x02 | (uncompressed[2 * size] & 1);
+    Ok([&[prefix], &uncompressed[1..=size]].concat())
+}
+
+/// The SEC1 point in the point format
+pub fn encode_point(point: &[u8], curve: Curve, format: PointFormat) -> Result<Vec<u8>> {
+    match format {
+        PointFormat::Compressed => compressed_point(point, curve),
+        PointFormat::Uncompressed => uncompressed_point(point, curve),
+    }
+}
+
+/// The public point of an EC private key, derived from its scalar, in the
+/// point format, or else uncompressed.  The ECPrivateKey publicKey, which
+/// may be absent, is not trusted.
+pub fn ec_public_key(key_info: &KeyInfo, format: Option<PointFormat>) -> Result<Vec<u8>> {
+    let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+    let point = ec_public_point(key_info)?;
+    encode_point(&point, curve, format.unwrap_or(PointFormat::Uncompressed))
+}
+
+/// The EC KeyInfo with its public point in the point format.  A private key
+/// has its point in the ECPrivateKey publicKey, which is left out if it was.
+pub fn with_point_format(key_info: &KeyInfo, format: PointFormat) -> Result<KeyInfo> {
+    let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+    let bytes = key_info
+        .bytes
+        .as_deref()
+        .ok_or(Error::MissingInput("key".to_owned()))?;
+    let mut point_info = key_info.clone();
+    match key_info.key_type {
+        KeyType::Public => {
+            point_info.set_bytes(&encode_point(bytes, curve, format)?);
+        }
+        KeyType::Private => {
+            let ec_private_key = EcPrivateKey::from_der(bytes)?;
+            if let Some(public_key) = ec_private_key.public_key {
+                let point = encode_point(public_key, curve, format)?;
+                let private_key = EcPrivateKey {
+                    public_key: Some(&point),
+                    ..ec_private_key
+                };
+                point_info.set_bytes(&Zeroizing::new(private_key.to_vec()?));
+            }
+        }
+        _ => bail!(Error::UnknownKeyType),
+    }
+    Ok(point_info)
+}
+
+/// Turns a bare SEC1 encoded elliptic curve point into KeyInfo bytes
+///
+/// The point carries no curve information, so the curve must be provided.
+pub fn ec_point_to_key_info(point: &[u8], curve: Curve) -> Result<KeyInfo> {
+    if !is_ec_point(point, curve) {
+        bail!(Error::BadEcPoint);
+    }
+    let curve_oid = curve.oid();
+    let alg_id = ec_encryption(curve_oid.as_bytes())?;
+    let key_info = KeyInfo::new()
+        .with_alg_id(&alg_id)
+        .with_key_type(KeyType::Public)
+        .with_format(Format::SEC1)
+        .with_encoding(Encoding::RAW)
+        .with_bytes(point);
+    Ok(key_info)
+}
 
 pub fn sec1_to_private_key_info(
     sec1_doc: &EcPrivateKeyDocument,
     encoding: Encoding,
 ) -> Result<KeyInfo> {
-    println!("Doing SECG");
     let sec1 = sec1_doc.decode();
 
     let mut key_info = KeyInfo::new()
@@ -21,7 +170,7 @@ pub fn sec1_to_private_key_info(
         .with_bytes(sec1_doc.as_der());
 
     if let Some(params) = sec1.parameters {
-        println!("Parameters:       {:?}", &sec1.parameters);
+        trace!("Parameters: {:?}", &sec1.parameters);
         if let Some(oid) = params.named_curve() {
             key_info.set_oid(&oid);
         }
@@ -30,17 +179,43 @@ pub fn sec1_to_private_key_info(
     Ok(key_info)
 }
 
+/// The ECPrivateKey of a PKCS8 document, without the parameters.  PKCS8
+/// holds the named curve in the AlgorithmIdentifier, as openssl writes it.
+pub fn pk8_ec_private_key(bytes: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    let ec_private_key = EcPrivateKey::from_der(bytes)?;
+    let private_key = EcPrivateKey {
+        parameters: None,
+        ..ec_private_key
+    };
+    Ok(Zeroizing::new(private_key.to_vec()?))
+}
+
 /// Turn a PrivateKeyInfo into a SECG document
 pub fn private_key_info_to_sec1(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
 
     let bytes = key_info.bytes.clone().unwrap();
-    let pkd = EcPrivateKeyDocument::from_der(&bytes)?;
-    match app_state.encoding {
-        Encoding::DER => {
+    let mut pkd = EcPrivateKeyDocument::from_der(&bytes)?;
+    // PKCS8 holds the named curve in the AlgorithmIdentifier, rather than in
+    // the ECPrivateKey, and SEC1 needs it in the ECPrivateKey
+    if let (None, Some(curve)) = (pkd.decode().parameters, key_info.curve()) {
+        let private_key = EcPrivateKey {
+            parameters: Some(EcParameters::NamedCurve(curve.oid())),
+            ..pkd.decode()
+        };
+        pkd = EcPrivateKeyDocument::from_der(&Zeroizing::new(private_key.to_vec()?))?;
+    }
+    match (app_state.encoding, app_state.out_password.clone()) {
+        // Only PEM has a traditional encryption
+        (Encoding::DER, Some(_)) => bail!(Error::NotSupported),
+        (Encoding::PEM, Some(password)) => {
+            let text = Zeroizing::new(pem::wrap_encrypted(pem::EC_PRIVATE_KEY, pkd.as_der(), &password)?);
+            app_state.write_stream(text.as_bytes())?;
+        }
+        (Encoding::DER, None) => {
             let bytes = pkd.to_der();
             app_state.write_stream(&bytes)?;
         }
-        Encoding::PEM => {
+        (Encoding::PEM, None) => {
             let bytes = pkd.to_pem(CRLF)?;
             app_state.write_stream(bytes.as_bytes())?;
         }
@@ -48,3 +223,13 @@ pub fn private_key_info_to_sec1(app_state: &mut AppState, key_info: &KeyInfo) ->
     }
     Ok(())
 }
+
+/// Write the bare SEC1 encoded elliptic curve point of a public key
+pub fn ec_public_key_to_sec1(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let bytes = key_info.bytes.clone().unwrap();
+    match app_state.encoding {
+        Encoding::RAW => app_state.write_stream(&bytes)?,
+        _ => bail!(Error::NotSupported),
+    }
+    Ok(())
+}
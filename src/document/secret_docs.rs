@@ -0,0 +1,56 @@
+//! Symmetric secret keys, such as HMAC and AES keys
+//!
+//! Secrets have no document structure.  They are the raw key bytes, possibly
+//! in a text friendly form.
+use anyhow::{bail, Result};
+use base64ct::{Base64, Base64Unpadded, Base64Url, Base64UrlUnpadded, Encoding as _};
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::errors::Error;
+use crate::key_info::{Alg, Encoding, Format, KeyInfo, KeyType, SecretEncoding};
+
+/// Decode base64, in any of the standard or url safe alphabets, padded or not
+fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    Base64::decode_vec(text)
+        .or_else(|_| Base64Unpadded::decode_vec(text))
+        .or_else(|_| Base64Url::decode_vec(text))
+        .or_else(|_| Base64UrlUnpadded::decode_vec(text))
+        .map_err(|_| Error::BadSecret.into())
+}
+
+/// Turn a secret into KeyInfo bytes
+pub fn secret_to_key_info(secret: &[u8], secret_encoding: SecretEncoding) -> Result<KeyInfo> {
+    let bytes = Zeroizing::new(match secret_encoding {
+        SecretEncoding::Raw => secret.to_vec(),
+        SecretEncoding::Hex | SecretEncoding::Base64 => {
+            let text = std::str::from_utf8(secret).map_err(|_| Error::BadSecret)?.trim();
+            match secret_encoding {
+                SecretEncoding::Hex => hex::decode(text).map_err(|_| Error::BadSecret)?,
+                _ => base64_decode(text)?,
+            }
+        }
+    });
+    if bytes.is_empty() {
+        bail!(Error::BadSecret);
+    }
+
+    let key_info = KeyInfo::new()
+        .with_alg(Alg::Symmetric)
+        .with_key_type(KeyType::Secret)
+        .with_format(Format::Unknown)
+        .with_encoding(Encoding::RAW)
+        .with_key_length(bytes.len() as u32 * 8)
+        .with_bytes(&bytes);
+    Ok(key_info)
+}
+
+/// Write the raw bytes of a secret
+pub fn secret_key_to_raw(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let bytes = key_info.bytes.clone().unwrap();
+    match app_state.encoding {
+        Encoding::RAW => app_state.write_stream(&bytes)?,
+        _ => bail!(Error::NotSupported),
+    }
+    Ok(())
+}
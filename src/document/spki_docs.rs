@@ -8,8 +8,12 @@ use spki::{
     PublicKeyDocument,
 };
 
-use crate::alg_id::{rsa_encryption, rsapss_encryption};
+use crate::alg_id::{
+    composite_encryption, dh_encryption, dsa_encryption, ec_encryption, ml_dsa_encryption, okp_encryption, rsa_encryption, rsa_pss_params, rsapss_encryption,
+};
 use crate::app_state::AppState;
+use crate::document::jwk_docs::{jwk_to_key_info, key_info_as_public_jwk};
+use crate::document::openssh_docs::with_public_key;
 use crate::errors::Error;
 use crate::key_info::KeyInfo;
 use crate::key_info::{Alg, Encoding, Format, KeyType};
@@ -33,25 +37,52 @@ pub fn spki_to_key_info(spki_doc: &PublicKeyDocument, encoding: Encoding) -> Res
     Ok(key_info)
 }
 
-// pub fn spki_public_key_document(spki: &SubjectPublicKeyInfo)
-/// Turn a PKCS8 PrivateKeyInfo into a document
-pub fn key_info_to_spki(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
-    let alg = match app_state.alg()? {
+/// Turn public KeyInfo bytes into an SPKI document of the algorithm
+pub fn key_info_as_spki(key_info: &KeyInfo, alg: Alg) -> Result<PublicKeyDocument> {
+    let curve_oid = key_info.curve().map(|curve| curve.oid());
+    let alg = match alg {
         Alg::Rsa => rsa_encryption()?,
-        Alg::RsaSsaPss => rsapss_encryption()?,
+        Alg::RsaSsaPss => rsapss_encryption(rsa_pss_params(key_info))?,
+        Alg::Ecdsa => match &curve_oid {
+            Some(curve_oid) => ec_encryption(curve_oid.as_bytes())?,
+            None => bail!(Error::UnknownCurve),
+        },
+        alg @ (Alg::X25519 | Alg::X448 | Alg::EdDsa25519 | Alg::EdDsa448) => okp_encryption(alg)?,
+        Alg::Dsa => match &key_info.params {
+            Some(params) => dsa_encryption(params)?,
+            None => bail!(Error::MissingInput("DSA parameters".to_owned())),
+        },
+        Alg::Dh => match &key_info.params {
+            Some(params) => dh_encryption(params)?,
+            None => bail!(Error::MissingInput("DH parameters".to_owned())),
+        },
+        alg @ (Alg::MlDsa44 | Alg::MlDsa65 | Alg::MlDsa87) => ml_dsa_encryption(alg, key_info.oid)?,
+        Alg::Composite => composite_encryption(key_info.oid)?,
         _ => {
-            trace!("Unexpected algorithm: {:?}", app_state.alg);
+            trace!("Unexpected algorithm: {:?}", alg);
             bail!(Error::UnknownAlg);
         }
     };
 
-    let bytes = key_info.bytes.clone().unwrap();
+    let bytes = key_info.bytes.clone().ok_or(Error::MissingInput("key".to_owned()))?;
 
     let spki = SubjectPublicKeyInfo {
         algorithm: alg,
         subject_public_key: &bytes,
     };
-    let pkd: PublicKeyDocument = spki.try_into()?;
+    Ok(spki.try_into()?)
+}
+
+/// The DER SPKI document of the public key of a public or private KeyInfo
+pub fn public_spki_der(key_info: &KeyInfo) -> Result<Vec<u8>> {
+    let jwk = key_info_as_public_jwk(&with_public_key(key_info)?)?;
+    Ok(key_info_as_spki(&jwk_to_key_info(&jwk)?, key_info.alg)?.as_ref().to_vec())
+}
+
+// pub fn spki_public_key_document(spki: &SubjectPublicKeyInfo)
+/// Turn a PKCS8 PrivateKeyInfo into a document
+pub fn key_info_to_spki(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    let pkd = key_info_as_spki(key_info, app_state.alg()?)?;
 
     match app_state.encoding {
         Encoding::DER => {
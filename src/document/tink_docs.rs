@@ -0,0 +1,540 @@
+//! Google [Tink](https://developers.google.com/tink) cleartext keysets, as
+//! JSON
+//!
+//! A keyset is a list of keys, one of which is the primary key.  Each key is
+//! a protocol buffer of its key type, such as `EcdsaPrivateKey`, in base64.
+//! Keys of these types are read and written:
+//!
+//! * RsaSsaPkcs1PrivateKey and RsaSsaPkcs1PublicKey
+//! * EcdsaPrivateKey and EcdsaPublicKey, on P-256, P-384 and P-521
+//! * Ed25519PrivateKey and Ed25519PublicKey
+//!
+//! The primary key is read, unless a key ID selects another key.  A key is
+//! written as a keyset of one RAW key, so that its signatures are the same
+//! as those of any other library: SHA-256 for RSA, the hash that matches the
+//! curve size for ECDSA, and DER ECDSA signatures.
+//!
+//! Encrypted keysets need the KMS key that encrypts them, so they are not
+//! read.
+//!
+//! ```rust
+//! use kt::document::tink_docs::Keyset;
+//!
+//! let json = r#"{"primaryKeyId":1,"key":[{"keyData":{
+//!     "typeUrl":"type.googleapis.com/google.crypto.tink.Ed25519PublicKey",
+//!     "value":"EiDXWlmEQdsXtDrYxYnzKFGHKMC7ZIgcmA+kd1i3xeW9Ng==",
+//!     "keyMaterialType":"ASYMMETRIC_PUBLIC"},
+//!     "status":"ENABLED","keyId":1,"outputPrefixType":"RAW"}]}"#;
+//! let keyset = Keyset::from_json(json).unwrap();
+//! assert_eq!(keyset.select(None).unwrap().key_id, 1);
+//! ```
+use anyhow::{bail, Result};
+use base64ct::{Base64, Encoding as _};
+use pkcs8::der::Decodable;
+use rand_core::{OsRng, RngCore};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use rsa::BigUint;
+use sec1::EcPrivateKey;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::jwk_docs::{b64_encode, jwk_to_key_info, Jwk, KTY_EC, KTY_OKP, KTY_RSA};
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::signature::{
+    ec_public_point, ed25519_signing_key, ed25519_verifying_key, rsa_private_key, rsa_public_key,
+};
+
+/// Prefix of the type URLs of Tink keys
+const TYPE_URL_PREFIX: &str = "type.googleapis.com/google.crypto.tink.";
+
+const RSA_PRIVATE: &str = "RsaSsaPkcs1PrivateKey";
+const RSA_PUBLIC: &str = "RsaSsaPkcs1PublicKey";
+const ECDSA_PRIVATE: &str = "EcdsaPrivateKey";
+const ECDSA_PUBLIC: &str = "EcdsaPublicKey";
+const ED25519_PRIVATE: &str = "Ed25519PrivateKey";
+const ED25519_PUBLIC: &str = "Ed25519PublicKey";
+
+const ASYMMETRIC_PRIVATE: &str = "ASYMMETRIC_PRIVATE";
+const ASYMMETRIC_PUBLIC: &str = "ASYMMETRIC_PUBLIC";
+
+/// HashType values
+const SHA384: u64 = 2;
+const SHA256: u64 = 3;
+const SHA512: u64 = 4;
+/// EllipticCurveType values
+const NIST_P256: u64 = 2;
+const NIST_P384: u64 = 3;
+const NIST_P521: u64 = 4;
+/// EcdsaSignatureEncoding value of DER signatures
+const ECDSA_DER: u64 = 2;
+
+fn bad_tink(message: &str) -> anyhow::Error {
+    Error::BadTink(message.to_owned()).into()
+}
+
+/// The key material of a key
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyData {
+    /// Type of the key, such as
+    /// "type.googleapis.com/google.crypto.tink.EcdsaPrivateKey"
+    pub type_url: String,
+    /// The key protocol buffer, in base64
+    pub value: String,
+    /// Such as "ASYMMETRIC_PRIVATE"
+    pub key_material_type: String,
+}
+
+/// A key of a keyset
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TinkKey {
+    pub key_data: KeyData,
+    /// Such as "ENABLED"
+    pub status: String,
+    pub key_id: u32,
+    /// Prefix of signatures, such as "TINK" or "RAW"
+    pub output_prefix_type: String,
+}
+
+impl TinkKey {
+    /// The name of the key type, such as "EcdsaPrivateKey"
+    pub fn type_name(&self) -> &str {
+        self.key_data
+            .type_url
+            .strip_prefix(TYPE_URL_PREFIX)
+            .unwrap_or(&self.key_data.type_url)
+    }
+}
+
+/// A cleartext keyset
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Keyset {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub primary_key_id: Option<u32>,
+    pub key: Vec<TinkKey>,
+}
+
+impl Keyset {
+    /// Parse a keyset from its JSON representation
+    pub fn from_json(json: &str) -> Result<Self> {
+        let value: Value = serde_json::from_str(json).map_err(|e| Error::BadTink(e.to_string()))?;
+        if value.get("encryptedKeyset").is_some() {
+            bail!(Error::BadTink(
+                "encrypted keysets are not supported".to_owned()
+            ));
+        }
+        serde_json::from_value(value).map_err(|e| Error::BadTink(e.to_string()).into())
+    }
+
+    /// Serialize the keyset to JSON
+    pub fn to_json(&self, pretty: bool) -> Result<String> {
+        let json = match pretty {
+            true => serde_json::to_string_pretty(self),
+            false => serde_json::to_string(self),
+        };
+        json.map_err(|e| Error::BadTink(e.to_string()).into())
+    }
+
+    /// The key with the decimal key ID, or else the primary key
+    pub fn select(&self, key_id: Option<&str>) -> Result<&TinkKey> {
+        let wanted = match key_id {
+            Some(key_id) => Some(
+                key_id
+                    .parse::<u32>()
+                    .map_err(|_| Error::BadTink(format!("bad key ID {}", key_id)))?,
+            ),
+            None => self.primary_key_id,
+        };
+        match wanted {
+            Some(wanted) => self.key.iter().find(|key| key.key_id == wanted),
+            None => self.key.first(),
+        }
+        .ok_or_else(|| bad_tink("no such key"))
+    }
+}
+
+/// Test whether the bytes are a JSON keyset, cleartext or encrypted
+pub fn is_tink_keyset(bytes: &[u8]) -> bool {
+    match serde_json::from_slice::<Value>(bytes) {
+        Ok(Value::Object(object)) => {
+            object.get("key").is_some_and(Value::is_array) || object.contains_key("encryptedKeyset")
+        }
+        _ => false,
+    }
+}
+
+/// A field of a protocol buffer: a varint, or length delimited bytes
+enum ProtoValue<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| bad_tink("truncated key"))?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    bail!(Error::BadTink("bad varint".to_owned()))
+}
+
+/// The fields of a protocol buffer, as their numbers and values.  Fixed
+/// size fields are skipped.
+fn proto_fields(mut bytes: &[u8]) -> Result<Vec<(u64, ProtoValue<'_>)>> {
+    let mut fields = Vec::new();
+    while !bytes.is_empty() {
+        let key = read_varint(&mut bytes)?;
+        let skip = match key & 7 {
+            0 => {
+                fields.push((key >> 3, ProtoValue::Varint(read_varint(&mut bytes)?)));
+                0
+            }
+            1 => 8,
+            2 => {
+                let len = read_varint(&mut bytes)? as usize;
+                if bytes.len() < len {
+                    bail!(Error::BadTink("truncated key".to_owned()));
+                }
+                let (value, rest) = bytes.split_at(len);
+                fields.push((key >> 3, ProtoValue::Bytes(value)));
+                bytes = rest;
+                0
+            }
+            5 => 4,
+            _ => bail!(Error::BadTink("bad protocol buffer".to_owned())),
+        };
+        bytes = bytes.get(skip..).ok_or_else(|| bad_tink("truncated key"))?;
+    }
+    Ok(fields)
+}
+
+/// A bytes field.  Every bytes field of the keys is required.
+fn proto_bytes<'a>(fields: &[(u64, ProtoValue<'a>)], number: u64) -> Result<&'a [u8]> {
+    fields
+        .iter()
+        .find_map(|(n, value)| match value {
+            ProtoValue::Bytes(bytes) if *n == number => Some(*bytes),
+            _ => None,
+        })
+        .ok_or_else(|| Error::BadTink(format!("missing field {}", number)).into())
+}
+
+/// A varint field, which is 0 if it is not present
+fn proto_varint(fields: &[(u64, ProtoValue<'_>)], number: u64) -> u64 {
+    fields
+        .iter()
+        .find_map(|(n, value)| match value {
+            ProtoValue::Varint(value) if *n == number => Some(*value),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+fn write_varint(mut value: u64, out: &mut Vec<u8>) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// A varint field.  Zero is the default, so it is not written.
+fn varint_field(number: u64, value: u64) -> Vec<u8> {
+    let mut field = Vec::new();
+    if value != 0 {
+        write_varint(number << 3, &mut field);
+        write_varint(value, &mut field);
+    }
+    field
+}
+
+fn bytes_field(number: u64, value: &[u8]) -> Vec<u8> {
+    let mut field = Vec::new();
+    write_varint((number << 3) | 2, &mut field);
+    write_varint(value.len() as u64, &mut field);
+    field.extend_from_slice(value);
+    field
+}
+
+/// Tink keys hold integers as big endian two's complement, so they may have
+/// a leading zero byte.  The leading zeros are removed.
+fn unsigned(bytes: &[u8]) -> &[u8] {
+    let start = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    &bytes[start..]
+}
+
+/// A positive integer, as big endian two's complement
+fn signed(bytes: &[u8]) -> Vec<u8> {
+    let bytes = unsigned(bytes);
+    match bytes.first() {
+        Some(first) if first & 0x80 != 0 => [&[0][..], bytes].concat(),
+        Some(_) => bytes.to_vec(),
+        None => vec![0],
+    }
+}
+
+/// Left pad an integer to the field size of a curve
+fn pad(bytes: &[u8], size: usize) -> Result<Vec<u8>> {
+    let bytes = unsigned(bytes);
+    if bytes.len() > size {
+        bail!(Error::BadTink(
+            "integer is too large for the curve".to_owned()
+        ));
+    }
+    Ok([vec![0u8; size - bytes.len()], bytes.to_vec()].concat())
+}
+
+fn curve_from_tink(value: u64) -> Result<Curve> {
+    match value {
+        NIST_P256 => Ok(Curve::P256),
+        NIST_P384 => Ok(Curve::P384),
+        NIST_P521 => Ok(Curve::P521),
+        _ => bail!(Error::BadTink(format!("unsupported curve {}", value))),
+    }
+}
+
+/// The JWK of an RsaSsaPkcs1PublicKey, or of the public key of a private key
+fn rsa_public_jwk(public: &[u8]) -> Result<Jwk> {
+    let fields = proto_fields(public)?;
+    Ok(Jwk {
+        kty: KTY_RSA.to_owned(),
+        n: Some(b64_encode(unsigned(proto_bytes(&fields, 3)?))),
+        e: Some(b64_encode(unsigned(proto_bytes(&fields, 4)?))),
+        ..Default::default()
+    })
+}
+
+/// The JWK of an EcdsaPublicKey, and its curve
+fn ecdsa_public_jwk(public: &[u8]) -> Result<(Jwk, Curve)> {
+    let fields = proto_fields(public)?;
+    let params = proto_fields(proto_bytes(&fields, 2)?)?;
+    let curve = curve_from_tink(proto_varint(&params, 2))?;
+    let size = curve.field_size();
+    let jwk = Jwk {
+        kty: KTY_EC.to_owned(),
+        crv: Some(curve.jwk_crv().to_owned()),
+        x: Some(b64_encode(&pad(proto_bytes(&fields, 3)?, size)?)),
+        y: Some(b64_encode(&pad(proto_bytes(&fields, 4)?, size)?)),
+        ..Default::default()
+    };
+    Ok((jwk, curve))
+}
+
+fn ed25519_public_jwk(public: &[u8]) -> Result<Jwk> {
+    let fields = proto_fields(public)?;
+    Ok(Jwk {
+        kty: KTY_OKP.to_owned(),
+        crv: Some("Ed25519".to_owned()),
+        x: Some(b64_encode(proto_bytes(&fields, 2)?)),
+        ..Default::default()
+    })
+}
+
+/// The JWK of the key of a keyset
+fn tink_key_to_jwk(key: &TinkKey) -> Result<Jwk> {
+    let value = Zeroizing::new(
+        Base64::decode_vec(&key.key_data.value).map_err(|_| bad_tink("bad base64 key value"))?,
+    );
+    let fields = proto_fields(&value)?;
+    let jwk = match key.type_name() {
+        RSA_PUBLIC => rsa_public_jwk(&value)?,
+        ECDSA_PUBLIC => ecdsa_public_jwk(&value)?.0,
+        ED25519_PUBLIC => ed25519_public_jwk(&value)?,
+        RSA_PRIVATE => Jwk {
+            d: Some(b64_encode(unsigned(proto_bytes(&fields, 3)?))),
+            p: Some(b64_encode(unsigned(proto_bytes(&fields, 4)?))),
+            q: Some(b64_encode(unsigned(proto_bytes(&fields, 5)?))),
+            ..rsa_public_jwk(proto_bytes(&fields, 2)?)?
+        },
+        ECDSA_PRIVATE => {
+            let (jwk, curve) = ecdsa_public_jwk(proto_bytes(&fields, 2)?)?;
+            Jwk {
+                d: Some(b64_encode(&pad(
+                    proto_bytes(&fields, 3)?,
+                    curve.field_size(),
+                )?)),
+                ..jwk
+            }
+        }
+        ED25519_PRIVATE => Jwk {
+            d: Some(b64_encode(proto_bytes(&fields, 2)?)),
+            ..ed25519_public_jwk(proto_bytes(&fields, 3)?)?
+        },
+        name => bail!(Error::BadTink(format!("unsupported key type {}", name))),
+    };
+    Ok(jwk)
+}
+
+/// Turn a key of a keyset into KeyInfo bytes
+pub fn tink_key_to_key_info(key: &TinkKey) -> Result<KeyInfo> {
+    let jwk = tink_key_to_jwk(key)?;
+    Ok(jwk_to_key_info(&jwk)
+        .map_err(|e| Error::BadTink(e.to_string()))?
+        .with_encoding(Encoding::TINK)
+        .with_format(Format::TINK))
+}
+
+/// Read the primary key of a keyset, or the key selected by the key ID
+pub fn tink_to_key_info(app_state: &AppState, bytes: &[u8]) -> Result<KeyInfo> {
+    let text = std::str::from_utf8(bytes).map_err(|_| bad_tink("not UTF-8"))?;
+    let keyset = Keyset::from_json(text)?;
+    tink_key_to_key_info(keyset.select(app_state.key_id.as_deref())?)
+}
+
+/// The EcdsaPublicKey of a key, and its hash
+fn ecdsa_public_proto(key_info: &KeyInfo) -> Result<Vec<u8>> {
+    let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+    let (tink_curve, hash) = match curve {
+        Curve::P256 => (NIST_P256, SHA256),
+        Curve::P384 => (NIST_P384, SHA384),
+        Curve::P521 => (NIST_P521, SHA512),
+        Curve::Secp256k1 => bail!(Error::BadTink("secp256k1 keys are not supported".to_owned())),
+        Curve::Sm2 => bail!(Error::BadTink("SM2 keys are not supported".to_owned())),
+    };
+    let point = ec_public_point(key_info)?;
+    let size = curve.field_size();
+    if point.len() != 1 + 2 * size || point[0] != 0x04 {
+        bail!(Error::BadTink(
+            "only uncompressed EC points are supported".to_owned()
+        ));
+    }
+    let params = [
+        varint_field(1, hash),
+        varint_field(2, tink_curve),
+        varint_field(3, ECDSA_DER),
+    ]
+    .concat();
+    Ok([
+        bytes_field(2, &params),
+        bytes_field(3, &signed(&point[1..=size])),
+        bytes_field(4, &signed(&point[1 + size..])),
+    ]
+    .concat())
+}
+
+fn rsa_public_proto(key_info: &KeyInfo) -> Result<Vec<u8>> {
+    let key = rsa_public_key(key_info)?;
+    Ok([
+        bytes_field(2, &varint_field(1, SHA256)),
+        bytes_field(3, &signed(&key.n().to_bytes_be())),
+        bytes_field(4, &signed(&key.e().to_bytes_be())),
+    ]
+    .concat())
+}
+
+/// The key type name and protocol buffer of the key.  Only the public key
+/// of a private key is written if that is what is wanted.
+fn key_proto(key_info: &KeyInfo, public_only: bool) -> Result<(&'static str, Zeroizing<Vec<u8>>)> {
+    let private = key_info.key_type == KeyType::Private && !public_only;
+    let proto = match (key_info.alg, private) {
+        (Alg::Rsa, false) => (RSA_PUBLIC, rsa_public_proto(key_info)?),
+        (Alg::Rsa, true) => {
+            let key = rsa_private_key(key_info)?;
+            let (p, q) = match key.primes() {
+                [p, q] => (p, q),
+                _ => bail!(Error::BadTink(
+                    "multi-prime RSA keys are not supported".to_owned()
+                )),
+            };
+            let one = BigUint::from(1u32);
+            let dp = key.d() % (p - &one);
+            let dq = key.d() % (q - &one);
+            // The CRT coefficient, the inverse of q mod p
+            let crt = q.modpow(&(p - 2u32), p);
+            let fields = [key.d(), p, q, &dp, &dq, &crt]
+                .iter()
+                .zip(3..)
+                .map(|(n, number)| bytes_field(number, &signed(&n.to_bytes_be())))
+                .collect::<Vec<_>>()
+                .concat();
+            let public = rsa_public_proto(key_info)?;
+            (RSA_PRIVATE, [bytes_field(2, &public), fields].concat())
+        }
+        (Alg::Ecdsa, false) => (ECDSA_PUBLIC, ecdsa_public_proto(key_info)?),
+        (Alg::Ecdsa, true) => {
+            let bytes = key_info
+                .bytes
+                .as_ref()
+                .ok_or_else(|| Error::MissingInput("key".to_owned()))?;
+            let ec_private_key = EcPrivateKey::from_der(bytes)?;
+            let public = ecdsa_public_proto(key_info)?;
+            (
+                ECDSA_PRIVATE,
+                [
+                    bytes_field(2, &public),
+                    bytes_field(3, &signed(ec_private_key.private_key)),
+                ]
+                .concat(),
+            )
+        }
+        (Alg::EdDsa25519, false) => (
+            ED25519_PUBLIC,
+            bytes_field(2, ed25519_verifying_key(key_info)?.as_bytes()),
+        ),
+        (Alg::EdDsa25519, true) => {
+            let key = ed25519_signing_key(key_info)?;
+            let public = bytes_field(2, key.verifying_key().as_bytes());
+            (
+                ED25519_PRIVATE,
+                [bytes_field(2, key.as_bytes()), bytes_field(3, &public)].concat(),
+            )
+        }
+        _ => bail!(Error::BadTink(
+            "only RSA, ECDSA and Ed25519 keys can be written".to_owned()
+        )),
+    };
+    Ok((proto.0, Zeroizing::new(proto.1)))
+}
+
+/// Build a keyset of one RAW key, with the key ID
+pub fn build_keyset(key_info: &KeyInfo, key_id: u32, public_only: bool) -> Result<Keyset> {
+    let (type_name, proto) = key_proto(key_info, public_only)?;
+    let key_material_type = match type_name {
+        RSA_PRIVATE | ECDSA_PRIVATE | ED25519_PRIVATE => ASYMMETRIC_PRIVATE,
+        _ => ASYMMETRIC_PUBLIC,
+    };
+    Ok(Keyset {
+        primary_key_id: Some(key_id),
+        key: vec![TinkKey {
+            key_data: KeyData {
+                type_url: format!("{}{}", TYPE_URL_PREFIX, type_name),
+                value: Base64::encode_string(&proto),
+                key_material_type: key_material_type.to_owned(),
+            },
+            status: "ENABLED".to_owned(),
+            key_id,
+            output_prefix_type: "RAW".to_owned(),
+        }],
+    })
+}
+
+/// Write the key as a cleartext keyset.  The key ID is the kid, if it is a
+/// number, or else random.
+pub fn key_info_to_tink(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    if app_state.out_password.is_some() {
+        bail!(Error::BadTink(
+            "keysets are written in cleartext. Tink encrypts keysets with a KMS key".to_owned()
+        ));
+    }
+    let key_id = match &app_state.key_id {
+        Some(kid) => kid
+            .parse::<u32>()
+            .map_err(|_| Error::BadTink(format!("the key ID {} is not a number", kid)))?,
+        None => OsRng.next_u32() & 0x7fff_ffff,
+    };
+    let public_only = app_state.key_type == Some(KeyType::Public);
+    let keyset = build_keyset(key_info, key_id, public_only)?;
+    let json = Zeroizing::new(keyset.to_json(app_state.pretty)?);
+    app_state.write_stream(json.as_bytes())
+}
@@ -0,0 +1,403 @@
+//! TPM 2.0 keys, as the TSS2 PEM of the tpm2-tss-engine and the tpm2
+//! provider of openssl ([draft-bottomley-tpm2-keys](https://www.hansenpartnership.com/draft-bottomley-tpm2-keys.html))
+//!
+//! A `TSS2 PRIVATE KEY` is not a private key.  It holds the public area of
+//! the key, and its private area encrypted by a parent key that never leaves
+//! the TPM.  Only that TPM can load the key, so it can not be converted to
+//! any other format.
+//!
+//! The key is recognized, so that kt says why it can not be read, and
+//! `kt show` lists what is known without the TPM: the parent handle, the
+//! policy, and the algorithm and attributes of the wrapped key.
+use std::fmt;
+
+use anyhow::Result;
+use yasna::Tag;
+
+use crate::errors::Error;
+use crate::key_info::Encoding;
+use crate::pem;
+
+/// Key that the TPM loads under its parent
+const LOADABLE_KEY: &str = "2.23.133.10.1.3";
+/// Key that is duplicated to the TPM, and imported under its parent
+const IMPORTABLE_KEY: &str = "2.23.133.10.1.4";
+/// Data sealed by the TPM
+const SEALED_KEY: &str = "2.23.133.10.1.5";
+
+const TPM_ALG_RSA: u16 = 0x0001;
+const TPM_ALG_KEYEDHASH: u16 = 0x0008;
+const TPM_ALG_NULL: u16 = 0x0010;
+const TPM_ALG_ECC: u16 = 0x0023;
+/// Exponent of an RSA public area that does not give one
+const DEFAULT_RSA_EXPONENT: u32 = 65537;
+
+/// Algorithm of the wrapped key, from its public area
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TpmAlg {
+    Rsa {
+        bits: u16,
+        exponent: u32,
+    },
+    Ecc {
+        curve: u16,
+    },
+    /// Sealed data
+    KeyedHash,
+    Other(u16),
+}
+
+impl fmt::Display for TpmAlg {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TpmAlg::Rsa { bits, exponent } => write!(f, "RSA {} bits, exponent {}", bits, exponent),
+            TpmAlg::Ecc { curve } => write!(f, "ECC {}", curve_name(*curve)),
+            TpmAlg::KeyedHash => write!(f, "Keyed hash (sealed data)"),
+            TpmAlg::Other(alg) => write!(f, "0x{:04x}", alg),
+        }
+    }
+}
+
+/// What is known of a TPM key without the TPM
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TpmKey {
+    pub encoding: Encoding,
+    /// Loadable, importable or sealed
+    pub kind: &'static str,
+    /// Whether the key is used with no password
+    pub empty_auth: bool,
+    /// TPM handle of the parent key
+    pub parent: u32,
+    /// Command codes of the policy that must be satisfied to use the key
+    pub policy: Vec<u32>,
+    /// Number of signed policies that may authorize the key
+    pub auth_policies: usize,
+    pub description: Option<String>,
+    /// Bytes of the encrypted seed of an importable key
+    pub secret_len: Option<usize>,
+    pub alg: TpmAlg,
+    /// Hash of the name of the key
+    pub name_alg: u16,
+    /// TPMA_OBJECT attributes, such as fixedTPM and sign
+    pub attributes: u32,
+    /// Bytes of the private area, encrypted by the parent
+    pub private_len: usize,
+}
+
+/// The DER of a TSS2 PEM, or the bytes, if they are DER
+fn tss2_der(bytes: &[u8]) -> Option<(Vec<u8>, Encoding)> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if text.trim_start().starts_with("-----BEGIN") => {
+            match pem::unwrap(text.trim_start().as_bytes()) {
+                Ok((label, der)) if label == pem::TSS2_PRIVATE_KEY => Some((der, Encoding::PEM)),
+                _ => None,
+            }
+        }
+        _ => Some((bytes.to_vec(), Encoding::DER)),
+    }
+}
+
+/// The type OID of a TPMKey
+fn tpm_key_type(der: &[u8]) -> Option<String> {
+    yasna::parse_der(der, |r| {
+        r.read_sequence(|r| {
+            let oid = r.next().read_oid()?;
+            while r.read_optional(|r| r.read_der())?.is_some() {}
+            Ok(oid.to_string())
+        })
+    })
+    .ok()
+    .filter(|oid| [LOADABLE_KEY, IMPORTABLE_KEY, SEALED_KEY].contains(&oid.as_str()))
+}
+
+/// Test whether the bytes are a TPMKey, PEM or DER
+pub fn is_tss2(bytes: &[u8]) -> bool {
+    tss2_der(bytes).is_some_and(|(der, _)| tpm_key_type(&der).is_some())
+}
+
+/// Read what is known of a TPMKey, PEM or DER
+pub fn tss2_key(bytes: &[u8]) -> Result<TpmKey> {
+    let (der, encoding) =
+        tss2_der(bytes).ok_or_else(|| Error::BadTpmKey("not a TSS2 key".to_owned()))?;
+    let parsed = yasna::parse_der(&der, |r| {
+        r.read_sequence(|r| {
+            let oid = r.next().read_oid()?;
+            let empty_auth = r
+                .read_optional(|r| r.read_tagged(Tag::context(0), |r| r.read_bool()))?
+                .unwrap_or(false);
+            let policy = r
+                .read_optional(|r| {
+                    r.read_tagged(Tag::context(1), |r| {
+                        r.collect_sequence_of(|r| {
+                            r.read_sequence(|r| {
+                                let command_code =
+                                    r.next().read_tagged(Tag::context(0), |r| r.read_u32())?;
+                                r.next().read_tagged(Tag::context(1), |r| r.read_bytes())?;
+                                Ok(command_code)
+                            })
+                        })
+                    })
+                })?
+                .unwrap_or_default();
+            let secret = r.read_optional(|r| r.read_tagged(Tag::context(2), |r| r.read_bytes()))?;
+            let auth_policies = r
+                .read_optional(|r| {
+                    r.read_tagged(Tag::context(3), |r| r.collect_sequence_of(|r| r.read_der()))
+                })?
+                .map_or(0, |policies| policies.len());
+            let description =
+                r.read_optional(|r| r.read_tagged(Tag::context(4), |r| r.read_utf8string()))?;
+            r.read_optional(|r| r.read_tagged(Tag::context(5), |r| r.read_bool()))?;
+            let parent = r.next().read_u32()?;
+            let public = r.next().read_bytes()?;
+            let private = r.next().read_bytes()?;
+            Ok((
+                oid.to_string(),
+                empty_auth,
+                policy,
+                secret,
+                auth_policies,
+                description,
+                parent,
+                public,
+                private,
+            ))
+        })
+    })
+    .map_err(|e| Error::BadTpmKey(e.to_string()))?;
+    let (oid, empty_auth, policy, secret, auth_policies, description, parent, public, private) =
+        parsed;
+
+    let kind = match oid.as_str() {
+        LOADABLE_KEY => "Loadable",
+        IMPORTABLE_KEY => "Importable",
+        SEALED_KEY => "Sealed",
+        _ => return Err(Error::BadTpmKey(format!("unknown key type {}", oid)).into()),
+    };
+    let (alg, name_alg, attributes) =
+        public_area(&public).ok_or_else(|| Error::BadTpmKey("bad public area".to_owned()))?;
+    Ok(TpmKey {
+        encoding,
+        kind,
+        empty_auth,
+        parent,
+        policy,
+        auth_policies,
+        description,
+        secret_len: secret.map(|secret| secret.len()),
+        alg,
+        name_alg,
+        attributes,
+        private_len: private.len(),
+    })
+}
+
+/// Big endian TPM structures
+struct TpmReader<'a>(&'a [u8]);
+
+impl<'a> TpmReader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.0.len() < len {
+            return None;
+        }
+        let (bytes, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        Some(u16::from_be_bytes(self.bytes(2)?.try_into().ok()?))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_be_bytes(self.bytes(4)?.try_into().ok()?))
+    }
+
+    /// A TPM2B, a buffer after its u16 size
+    fn sized(&mut self) -> Option<&'a [u8]> {
+        let len = self.u16()?;
+        self.bytes(len.into())
+    }
+
+    /// A scheme, with the hash that follows any scheme but TPM_ALG_NULL
+    fn scheme(&mut self) -> Option<()> {
+        if self.u16()? != TPM_ALG_NULL {
+            self.u16()?;
+        }
+        Some(())
+    }
+}
+
+/// The algorithm, name algorithm and attributes of a TPM2B_PUBLIC
+fn public_area(public: &[u8]) -> Option<(TpmAlg, u16, u32)> {
+    let mut r = TpmReader(TpmReader(public).sized()?);
+    let alg = r.u16()?;
+    let name_alg = r.u16()?;
+    let attributes = r.u32()?;
+    r.sized()?;
+    let alg = match alg {
+        TPM_ALG_RSA | TPM_ALG_ECC => {
+            // The symmetric algorithm of a storage key, with its key bits
+            // and mode
+            if r.u16()? != TPM_ALG_NULL {
+                r.u16()?;
+                r.u16()?;
+            }
+            r.scheme()?;
+            match alg {
+                TPM_ALG_RSA => {
+                    let bits = r.u16()?;
+                    let exponent = match r.u32()? {
+                        0 => DEFAULT_RSA_EXPONENT,
+                        exponent => exponent,
+                    };
+                    TpmAlg::Rsa { bits, exponent }
+                }
+                _ => TpmAlg::Ecc { curve: r.u16()? },
+            }
+        }
+        TPM_ALG_KEYEDHASH => TpmAlg::KeyedHash,
+        alg => TpmAlg::Other(alg),
+    };
+    Some((alg, name_alg, attributes))
+}
+
+fn curve_name(curve: u16) -> String {
+    match curve {
+        0x0003 => "NIST P-256".to_owned(),
+        0x0004 => "NIST P-384".to_owned(),
+        0x0005 => "NIST P-521".to_owned(),
+        0x0010 => "BN P-256".to_owned(),
+        0x0020 => "SM2 P-256".to_owned(),
+        curve => format!("0x{:04x}", curve),
+    }
+}
+
+fn hash_name(alg: u16) -> String {
+    match alg {
+        0x0004 => "SHA1".to_owned(),
+        0x000b => "SHA256".to_owned(),
+        0x000c => "SHA384".to_owned(),
+        0x000d => "SHA512".to_owned(),
+        0x0012 => "SM3-256".to_owned(),
+        alg => format!("0x{:04x}", alg),
+    }
+}
+
+/// The handle, and the hierarchy or kind of object it is
+fn handle_name(handle: u32) -> String {
+    let name = match handle {
+        0x4000_0001 => "Owner",
+        0x4000_0007 => "Null",
+        0x4000_000b => "Endorsement",
+        0x4000_000c => "Platform",
+        handle if handle >> 24 == 0x81 => "Persistent",
+        handle if handle >> 24 == 0x80 => "Transient",
+        _ => return format!("0x{:08x}", handle),
+    };
+    format!("0x{:08x} ({})", handle, name)
+}
+
+/// The name of a policy command
+fn command_name(command_code: u32) -> String {
+    let name = match command_code {
+        0x0149 => "PolicyNV",
+        0x0151 => "PolicySecret",
+        0x0160 => "PolicySigned",
+        0x016a => "PolicyAuthorize",
+        0x016b => "PolicyAuthValue",
+        0x016c => "PolicyCommandCode",
+        0x016d => "PolicyCounterTimer",
+        0x016f => "PolicyLocality",
+        0x0171 => "PolicyOR",
+        0x017f => "PolicyPCR",
+        0x018c => "PolicyPassword",
+        0x0192 => "PolicyAuthorizeNV",
+        _ => return format!("0x{:04x}", command_code),
+    };
+    name.to_owned()
+}
+
+/// The names of the set TPMA_OBJECT attributes
+fn attribute_names(attributes: u32) -> String {
+    const NAMES: [(u32, &str); 11] = [
+        (1 << 1, "fixedTPM"),
+        (1 << 2, "stClear"),
+        (1 << 4, "fixedParent"),
+        (1 << 5, "sensitiveDataOrigin"),
+        (1 << 6, "userWithAuth"),
+        (1 << 7, "adminWithPolicy"),
+        (1 << 10, "noDA"),
+        (1 << 11, "encryptedDuplication"),
+        (1 << 16, "restricted"),
+        (1 << 17, "decrypt"),
+        (1 << 18, "sign"),
+    ];
+    NAMES
+        .iter()
+        .filter(|(bit, _)| attributes & bit != 0)
+        .map(|(_, name)| *name)
+        .collect::<Vec<&str>>()
+        .join(" | ")
+}
+
+impl fmt::Display for TpmKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Key Type: Private\nEncoding: {:?}\nFormat: TSS2",
+            self.encoding
+        )?;
+        writeln!(f, "Algorithm: {}", self.alg)?;
+        if let Some(description) = &self.description {
+            writeln!(f, "Comment: {}", description)?;
+        }
+        writeln!(f, "TPM Key\n\tType: {}", self.kind)?;
+        writeln!(f, "\tParent: {}", handle_name(self.parent))?;
+        writeln!(f, "\tEmpty Auth: {}", self.empty_auth)?;
+        match self.policy.is_empty() {
+            true => writeln!(f, "\tPolicy: None")?,
+            false => writeln!(
+                f,
+                "\tPolicy: {}",
+                self.policy
+                    .iter()
+                    .map(|command_code| command_name(*command_code))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            )?,
+        }
+        if self.auth_policies > 0 {
+            writeln!(f, "\tAuthorized Policies: {}", self.auth_policies)?;
+        }
+        writeln!(f, "\tName Algorithm: {}", hash_name(self.name_alg))?;
+        writeln!(
+            f,
+            "\tAttributes: 0x{:08x} {}",
+            self.attributes,
+            attribute_names(self.attributes)
+        )?;
+        if let Some(secret_len) = self.secret_len {
+            writeln!(f, "\tEncrypted Seed: {} bytes", secret_len)?;
+        }
+        writeln!(
+            f,
+            "\tPrivate Area: {} bytes, encrypted by the parent",
+            self.private_len
+        )
+    }
+}
+
+/// The error of a TPM key, which only its TPM can load
+pub fn tss2_error(bytes: &[u8]) -> Error {
+    match tss2_key(bytes) {
+        Ok(tpm_key) => Error::TpmKey(format!(
+            "it is wrapped by the parent {}.  Use kt show to see what is known of it",
+            handle_name(tpm_key.parent)
+        )),
+        Err(e) => match e.downcast::<Error>() {
+            Ok(e) => e,
+            Err(e) => Error::BadTpmKey(e.to_string()),
+        },
+    }
+}
@@ -0,0 +1,127 @@
+//! X.509 certificates ([RFC 5280](https://www.rfc-editor.org/rfc/rfc5280))
+//!
+//! A certificate is read for the SubjectPublicKeyInfo it holds, so that the
+//! public key can be shown and converted like an SPKI document.  The
+//! signature algorithm the issuer signed the certificate with is kept as the
+//! KeyInfo signature algorithm.  Nothing about the certificate is verified.
+use anyhow::Result;
+use pkcs8::der::{asn1::Any, Decoder};
+use spki::{AlgorithmIdentifier, ObjectIdentifier, PublicKeyDocument, SubjectPublicKeyInfo};
+
+use crate::csr::attribute_name;
+use crate::document::spki_docs::spki_to_key_info;
+use crate::errors::Error;
+use crate::key_info::{Encoding, Format, KeyInfo};
+use crate::pem;
+
+/// Tag of the optional `[0] EXPLICIT` version of a TBSCertificate
+const VERSION_TAG: u8 = 0xa0;
+
+/// The parts of a certificate that kt reads
+pub struct Certificate<'a> {
+    /// The subject's Name
+    pub subject: Any<'a>,
+    /// The subject's public key
+    pub spki: SubjectPublicKeyInfo<'a>,
+    /// The algorithm the issuer signed the certificate with
+    pub signature_algorithm: AlgorithmIdentifier<'a>,
+}
+
+/// Parse a DER Certificate.  The fields other than the subject, the public
+/// key and the signature algorithm are skipped.
+pub fn parse_certificate(der: &[u8]) -> Result<Certificate<'_>> {
+    let mut decoder = Decoder::new(der)?;
+    let certificate = decoder.sequence(|cert| {
+        let (subject, spki) = cert.sequence(|tbs| {
+            if tbs.peek_byte() == Some(VERSION_TAG) {
+                tbs.any()?;
+            }
+            // serialNumber, signature, issuer and validity
+            for _ in 0..4 {
+                tbs.any()?;
+            }
+            let subject = tbs.any()?;
+            let spki: SubjectPublicKeyInfo = tbs.decode()?;
+            // The unique identifiers and extensions
+            while !tbs.is_finished() {
+                tbs.any()?;
+            }
+            Ok((subject, spki))
+        })?;
+        let signature_algorithm: AlgorithmIdentifier = cert.decode()?;
+        cert.bit_string()?;
+        Ok(Certificate {
+            subject,
+            spki,
+            signature_algorithm,
+        })
+    })?;
+    Ok(decoder.finish(certificate)?)
+}
+
+/// The subject of a certificate as text, such as `CN=example.com, O=Example`
+pub fn subject_to_string(subject: &Any<'_>) -> Result<String> {
+    let mut attributes = Vec::new();
+    let mut name = Decoder::new(subject.value())?;
+    while !name.is_finished() {
+        // Each RelativeDistinguishedName is a SET of attributes
+        let rdn = name.any()?;
+        let mut rdn = Decoder::new(rdn.value())?;
+        while !rdn.is_finished() {
+            rdn.sequence(|attribute| {
+                let oid: ObjectIdentifier = attribute.decode()?;
+                let value = attribute.any()?;
+                attributes.push(format!(
+                    "{}={}",
+                    attribute_name(oid),
+                    String::from_utf8_lossy(value.value())
+                ));
+                Ok(())
+            })?;
+        }
+    }
+    Ok(attributes.join(", "))
+}
+
+/// Test whether the text is one or more PEM certificates
+pub fn is_certificate_pem(text: &str) -> bool {
+    pem::label(text.trim_start().as_bytes()).is_ok_and(|label| label == pem::CERTIFICATE)
+}
+
+/// The DER certificates of PEM text, in order.  Other documents are ignored.
+pub fn pem_certificates(text: &str) -> Result<Vec<Vec<u8>>> {
+    let certs: Vec<Vec<u8>> = pem::unwrap_all(text.as_bytes())?
+        .into_iter()
+        .filter(|(label, _)| label == pem::CERTIFICATE)
+        .map(|(_, der)| der)
+        .collect();
+    if certs.is_empty() {
+        return Err(Error::BadCertificate("no certificates".to_owned()).into());
+    }
+    Ok(certs)
+}
+
+/// The certificate of a chain at the index.  Chains, such as
+/// `fullchain.pem`, start with the leaf certificate, at index 0.
+pub fn select_certificate(certs: &[Vec<u8>], index: usize) -> Result<&[u8]> {
+    match certs.get(index) {
+        Some(cert) => Ok(cert),
+        None => Err(Error::BadCertificate(format!(
+            "no certificate {} in a chain of {}",
+            index,
+            certs.len()
+        ))
+        .into()),
+    }
+}
+
+/// Turn the public key of a DER certificate into KeyInfo bytes, matching a
+/// key discovered from SPKI
+pub fn certificate_to_key_info(der: &[u8], encoding: Encoding) -> Result<KeyInfo> {
+    let certificate =
+        parse_certificate(der).map_err(|e| Error::BadCertificate(e.to_string()))?;
+    let spki_doc: PublicKeyDocument = certificate.spki.try_into()?;
+    Ok(spki_to_key_info(&spki_doc, encoding)?
+        .with_format(Format::X509)
+        .with_signature_alg_id(&certificate.signature_algorithm))
+}
@@ -0,0 +1,252 @@
+//! XML Signature key values, for SAML metadata and .NET
+//! ([RSAKeyValue](https://www.w3.org/TR/xmldsig-core1/#sec-RSAKeyValue),
+//! [ECKeyValue](https://www.w3.org/TR/xmldsig-core1/#sec-ECKeyValue))
+//!
+//! An RSA key is written as `RSA.ToXmlString` of .NET writes it: the
+//! modulus and exponent, and for a private key, the primes, CRT parameters
+//! and private exponent, each padded to the size .NET imports.  An EC public
+//! key is written as the ECKeyValue of XML Signature 1.1, with the OID of its
+//! named curve and its uncompressed point.  ECKeyValue has no private key, so
+//! EC private keys are only written with `-t public`.
+//!
+//! The elements are found by their local name, so that they are read with
+//! or without a namespace prefix, and inside a `KeyInfo` or `KeyValue`.
+//!
+//! ```rust
+//! use kt::document::xml_docs::is_xml_key;
+//!
+//! assert!(is_xml_key(b"<ds:KeyValue><ds:RSAKeyValue><ds:Modulus>"));
+//! assert!(!is_xml_key(b"{\"kty\":\"RSA\"}"));
+//! ```
+use anyhow::{bail, Result};
+use base64ct::{Base64, Encoding as _};
+use rsa::traits::{PrivateKeyParts, PublicKeyParts};
+use rsa::BigUint;
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::document::jwk_docs::{b64_encode, jwk_to_key_info, Jwk, KTY_EC, KTY_RSA};
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::signature::{ec_public_point, rsa_private_key, rsa_public_key};
+
+/// Namespace of ECKeyValue
+const DSIG11_NS: &str = "http://www.w3.org/2009/xmldsig11#";
+/// Prefix of the URI of a named curve
+const URN_OID: &str = "urn:oid:";
+
+fn bad_xml(message: &str) -> anyhow::Error {
+    Error::BadXml(message.to_owned()).into()
+}
+
+/// The attributes and the content of the first element with the local name,
+/// whatever its prefix
+fn find_element<'a>(xml: &'a str, name: &str) -> Option<(&'a str, &'a str)> {
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = rest.find('>')?;
+        let tag = &rest[..end];
+        let qname = tag
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        let local = qname.rsplit(':').next().unwrap_or_default();
+        if local != name || tag.starts_with('/') {
+            continue;
+        }
+        let attributes = tag[qname.len()..].trim_end_matches('/');
+        if tag.ends_with('/') {
+            return Some((attributes, ""));
+        }
+        let content = &rest[end + 1..];
+        let close = content.find(&format!("</{}>", qname))?;
+        return Some((attributes, &content[..close]));
+    }
+    None
+}
+
+/// The value of an attribute, quoted with either quote
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let at = attributes.find(&format!("{}=", name))?;
+    let value = &attributes[at + name.len() + 1..];
+    let quote = value.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let value = &value[1..];
+    Some(&value[..value.find(quote)?])
+}
+
+/// The bytes of the base64 content of an element, if it is there
+fn element_bytes(xml: &str, name: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    let (_, content) = match find_element(xml, name) {
+        Some(element) => element,
+        None => return Ok(None),
+    };
+    let text: String = content.chars().filter(|c| !c.is_whitespace()).collect();
+    let bytes =
+        Base64::decode_vec(&text).map_err(|_| Error::BadXml(format!("{} is not base64", name)))?;
+    Ok(Some(Zeroizing::new(bytes)))
+}
+
+fn required_bytes(xml: &str, name: &str) -> Result<Zeroizing<Vec<u8>>> {
+    element_bytes(xml, name)?.ok_or_else(|| Error::BadXml(format!("no {} element", name)).into())
+}
+
+/// Test whether the bytes are an RSAKeyValue or ECKeyValue
+pub fn is_xml_key(bytes: &[u8]) -> bool {
+    std::str::from_utf8(bytes).is_ok_and(|text| {
+        text.trim_start().starts_with('<')
+            && (find_start(text, "RSAKeyValue") || find_start(text, "ECKeyValue"))
+    })
+}
+
+/// Whether an element with the local name starts in the text
+fn find_start(xml: &str, name: &str) -> bool {
+    xml.split('<').skip(1).any(|tag| {
+        let qname = tag
+            .split(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .next()
+            .unwrap_or_default();
+        qname.rsplit(':').next() == Some(name)
+    })
+}
+
+fn rsa_xml_to_jwk(xml: &str) -> Result<Jwk> {
+    let mut jwk = Jwk {
+        kty: KTY_RSA.to_owned(),
+        n: Some(b64_encode(&required_bytes(xml, "Modulus")?)),
+        e: Some(b64_encode(&required_bytes(xml, "Exponent")?)),
+        ..Default::default()
+    };
+    if let Some(d) = element_bytes(xml, "D")? {
+        jwk.d = Some(b64_encode(&d));
+        jwk.p = element_bytes(xml, "P")?.map(|p| b64_encode(&p));
+        jwk.q = element_bytes(xml, "Q")?.map(|q| b64_encode(&q));
+    }
+    Ok(jwk)
+}
+
+fn ec_xml_to_jwk(xml: &str) -> Result<Jwk> {
+    let (attributes, _) =
+        find_element(xml, "NamedCurve").ok_or_else(|| bad_xml("no NamedCurve element"))?;
+    let oid = attribute(attributes, "URI")
+        .and_then(|uri| uri.strip_prefix(URN_OID))
+        .ok_or_else(|| bad_xml("the NamedCurve URI is not an OID"))?;
+    let oid = oid
+        .parse()
+        .map_err(|_| Error::BadXml(format!("bad curve OID {}", oid)))?;
+    let curve = Curve::try_from(&oid)?;
+    let point = required_bytes(xml, "PublicKey")?;
+    let size = curve.field_size();
+    if point.len() != 1 + 2 * size || point[0] != 0x04 {
+        bail!(bad_xml("only uncompressed EC points are supported"));
+    }
+    Ok(Jwk {
+        kty: KTY_EC.to_owned(),
+        crv: Some(curve.jwk_crv().to_owned()),
+        x: Some(b64_encode(&point[1..=size])),
+        y: Some(b64_encode(&point[1 + size..])),
+        ..Default::default()
+    })
+}
+
+/// Read an RSAKeyValue or ECKeyValue
+pub fn xml_to_key_info(bytes: &[u8]) -> Result<KeyInfo> {
+    let xml = std::str::from_utf8(bytes).map_err(|_| bad_xml("not UTF-8"))?;
+    let jwk = match find_element(xml, "RSAKeyValue") {
+        Some((_, content)) => rsa_xml_to_jwk(content)?,
+        None => match find_element(xml, "ECKeyValue") {
+            Some((_, content)) => ec_xml_to_jwk(content)?,
+            None => bail!(bad_xml("no RSAKeyValue or ECKeyValue element")),
+        },
+    };
+    Ok(jwk_to_key_info(&jwk)
+        .map_err(|e| Error::BadXml(e.to_string()))?
+        .with_encoding(Encoding::XML)
+        .with_format(Format::XML))
+}
+
+/// The number as base64, left padded with zeros to the size
+fn padded_base64(n: &BigUint, size: usize) -> String {
+    let bytes = Zeroizing::new(n.to_bytes_be());
+    let zeros = vec![0; size.saturating_sub(bytes.len())];
+    let padded = Zeroizing::new([&zeros, bytes.as_slice()].concat());
+    Base64::encode_string(&padded)
+}
+
+fn rsa_key_value(key_info: &KeyInfo, public_only: bool) -> Result<Zeroizing<String>> {
+    let public_key = rsa_public_key(key_info)?;
+    let modulus_len = public_key.n().bits().div_ceil(8);
+    let mut xml = Zeroizing::new(format!(
+        "<RSAKeyValue><Modulus>{}</Modulus><Exponent>{}</Exponent>",
+        padded_base64(public_key.n(), modulus_len),
+        Base64::encode_string(&public_key.e().to_bytes_be())
+    ));
+    if key_info.key_type == KeyType::Private && !public_only {
+        let private_key = rsa_private_key(key_info)?;
+        let (p, q) = match private_key.primes() {
+            [p, q] => (p, q),
+            _ => bail!(bad_xml("multi-prime RSA keys are not supported")),
+        };
+        let prime_len = modulus_len.div_ceil(2);
+        let one = BigUint::from(1u32);
+        let dp = private_key.d() % (p - &one);
+        let dq = private_key.d() % (q - &one);
+        // The CRT coefficient, the inverse of q mod p
+        let inverse_q = q.modpow(&(p - 2u32), p);
+        for (name, n) in [
+            ("P", p),
+            ("Q", q),
+            ("DP", &dp),
+            ("DQ", &dq),
+            ("InverseQ", &inverse_q),
+        ] {
+            xml.push_str(&format!(
+                "<{0}>{1}</{0}>",
+                name,
+                padded_base64(n, prime_len)
+            ));
+        }
+        xml.push_str(&format!(
+            "<D>{}</D>",
+            padded_base64(private_key.d(), modulus_len)
+        ));
+    }
+    xml.push_str("</RSAKeyValue>");
+    Ok(xml)
+}
+
+fn ec_key_value(key_info: &KeyInfo, public_only: bool) -> Result<Zeroizing<String>> {
+    if key_info.key_type == KeyType::Private && !public_only {
+        bail!(bad_xml(
+            "ECKeyValue only holds a public key.  Use -t public"
+        ));
+    }
+    let curve = key_info.curve().ok_or(Error::UnknownCurve)?;
+    let point = ec_public_point(key_info)?;
+    let size = curve.field_size();
+    if point.len() != 1 + 2 * size || point[0] != 0x04 {
+        bail!(bad_xml("only uncompressed EC points are supported"));
+    }
+    Ok(Zeroizing::new(format!(
+        "<dsig11:ECKeyValue xmlns:dsig11=\"{}\"><dsig11:NamedCurve URI=\"{}{}\"/><dsig11:PublicKey>{}</dsig11:PublicKey></dsig11:ECKeyValue>",
+        DSIG11_NS,
+        URN_OID,
+        curve.oid(),
+        Base64::encode_string(&point)
+    )))
+}
+
+/// Write the key as an RSAKeyValue or ECKeyValue.  With `-t public`, the
+/// public key of a private key is written.
+pub fn key_info_to_xml(app_state: &mut AppState, key_info: &KeyInfo) -> Result<()> {
+    if app_state.out_password.is_some() {
+        bail!(bad_xml("key values are not encrypted"));
+    }
+    let public_only = app_state.key_type == Some(KeyType::Public);
+    let xml = match key_info.alg {
+        Alg::Rsa => rsa_key_value(key_info, public_only)?,
+        Alg::Ecdsa => ec_key_value(key_info, public_only)?,
+        _ => bail!(bad_xml("only RSA and EC keys can be written")),
+    };
+    app_state.write_stream(xml.as_bytes())
+}
@@ -0,0 +1,137 @@
+//! The Ed448 curve ([RFC 8032](https://www.rfc-editor.org/rfc/rfc8032)), for
+//! the public key of an Ed448 private key
+//!
+//! Only what is needed to write keys is here: the public key of a private
+//! key's seed.  The arithmetic is not constant time, so kt does not sign with
+//! Ed448 keys.
+use anyhow::{bail, Result};
+use rsa::BigUint;
+use sha3::digest::ExtendableOutput;
+use sha3::Shake256;
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+
+/// Size in bytes of a seed, and of an encoded point
+pub const KEY_SIZE: usize = 57;
+
+/// The field prime, 2^448 - 2^224 - 1
+const P: &str = "fffffffffffffffffffffffffffffffffffffffffffffffffffffffeffffffffffffffffffffffffffffffffffffffffffffffffffffffff";
+/// The base point
+const BX: &str = "224580040295924300187604334099896036246789641632564134246125461686950415467406032909029192869357953282578032075146446173674602635247710";
+const BY: &str = "298819210078481492676017930443930673437544040154080242095928241372331506189835876003536878655418784733982303233503462500531545062832660";
+/// The curve is x^2 + y^2 = 1 + d x^2 y^2, with d = -39081
+const D: u32 = 39081;
+
+/// A point in projective coordinates (X : Y : Z), for x = X/Z and y = Y/Z
+type Point = (BigUint, BigUint, BigUint);
+
+/// Arithmetic modulo p, and on the curve
+struct Field {
+    p: BigUint,
+    d: BigUint,
+}
+
+impl Field {
+    fn new() -> Self {
+        let p = BigUint::parse_bytes(P.as_bytes(), 16).unwrap_or_default();
+        let d = &p - D;
+        Field { p, d }
+    }
+
+    /// a - b mod p, for a and b less than p
+    fn sub(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        (a + &self.p - b) % &self.p
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b % &self.p
+    }
+
+    /// The sum of two points.  The formula of RFC 8032 is complete, so it
+    /// also doubles a point.
+    fn add(&self, (x1, y1, z1): &Point, (x2, y2, z2): &Point) -> Point {
+        let a = self.mul(z1, z2);
+        let b = self.mul(&a, &a);
+        let c = self.mul(x1, x2);
+        let d = self.mul(y1, y2);
+        let e = self.mul(&self.mul(&self.d, &c), &d);
+        let f = self.sub(&b, &e);
+        let g = (&b + &e) % &self.p;
+        let h = self.mul(&(x1 + y1), &(x2 + y2));
+        let x3 = self.mul(&self.mul(&a, &f), &self.sub(&self.sub(&h, &c), &d));
+        let y3 = self.mul(&self.mul(&a, &g), &self.sub(&d, &c));
+        let z3 = self.mul(&f, &g);
+        (x3, y3, z3)
+    }
+
+    /// The point multiplied by the little endian scalar
+    fn multiply(&self, point: &Point, scalar: &[u8]) -> Point {
+        let one = BigUint::from(1u32);
+        let mut product: Point = (BigUint::default(), one.clone(), one);
+        for byte in scalar.iter().rev() {
+            for bit in (0..8).rev() {
+                product = self.add(&product, &product);
+                if byte >> bit & 1 == 1 {
+                    product = self.add(&product, point);
+                }
+            }
+        }
+        product
+    }
+
+    /// The little endian y coordinate, with the low bit of x in the top bit
+    /// of the last byte
+    fn encode(&self, (x, y, z): &Point) -> Vec<u8> {
+        let z = z.modpow(&(&self.p - 2u32), &self.p);
+        let x = self.mul(x, &z);
+        let y = self.mul(y, &z);
+        let mut bytes = y.to_bytes_le();
+        bytes.resize(KEY_SIZE, 0);
+        if x.to_bytes_le().first().is_some_and(|b| b & 1 == 1) {
+            bytes[KEY_SIZE - 1] |= 0x80;
+        }
+        bytes
+    }
+}
+
+/// `len` bytes of SHAKE256, as Ed448 hashes with it
+fn shake256(data: &[u8], len: usize) -> Vec<u8> {
+    let mut output = vec![0u8; len];
+    Shake256::digest_xof(data, &mut output);
+    output
+}
+
+/// The public key of the 57 byte seed of a private key
+///
+/// ```rust
+/// use kt::ed448::public_key;
+///
+/// // The first test vector of RFC 8032, section 7.4
+/// let seed = hex::decode(
+///     "6c82a562cb808d10d632be89c8513ebf6c929f34ddfa8c9f63c9960ef6e348a3\
+///      528c8a3fcc2f044e39a3fc5b94492f8f032e7549a20098f95b",
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     hex::encode(public_key(&seed).unwrap()),
+///     "5fd7449b59b461fd2ce787ec616ad46a1da1342485a70e1f8a0ea75d80e96778\
+///      edf124769b46c7061bd6783df1e50f6cd1fa1abeafe8256180"
+/// );
+/// ```
+pub fn public_key(seed: &[u8]) -> Result<Vec<u8>> {
+    if seed.len() != KEY_SIZE {
+        bail!(Error::BadCrypto);
+    }
+    // The scalar is the first half of the hash of the seed, pruned
+    let mut scalar = Zeroizing::new(shake256(seed, 2 * KEY_SIZE));
+    scalar.truncate(KEY_SIZE);
+    scalar[0] &= 0xfc;
+    scalar[KEY_SIZE - 2] |= 0x80;
+    scalar[KEY_SIZE - 1] = 0;
+
+    let field = Field::new();
+    let decimal = |n: &str| BigUint::parse_bytes(n.as_bytes(), 10).unwrap_or_default();
+    let base: Point = (decimal(BX), decimal(BY), BigUint::from(1u32));
+    Ok(field.encode(&field.multiply(&base, &scalar)))
+}
@@ -0,0 +1,134 @@
+//! Encrypt small payloads to a key, and decrypt them
+//!
+//! `kt encrypt` encrypts a payload, from a file or stdin, to the public key
+//! of any key kt reads, and `kt decrypt` decrypts it with the private key, so
+//! that a key can be tried out directly.  RSA keys use RSAES-OAEP with
+//! SHA-256, and MGF1 with SHA-256, as
+//! `openssl pkeyutl -pkeyopt rsa_padding_mode:oaep -pkeyopt rsa_oaep_md:sha256`
+//! does, so the payload must be shorter than the modulus.  X25519, P-256,
+//! P-384 and P-521 keys use [HPKE](crate::hpke) in the base mode, with an
+//! empty info and AAD, and the ciphertext is the encapsulated key followed by
+//! the AES-GCM ciphertext.  HPKE has no KEM for secp256k1 or SM2 keys, so
+//! they can not be encrypted to.
+use anyhow::{bail, Result};
+use base64ct::{Base64, Encoding as _};
+use pkcs8::der::{asn1::OctetString, Decodable};
+use rand_core::OsRng;
+use rsa::traits::PublicKeyParts;
+use rsa::Oaep;
+use sec1::EcPrivateKey;
+use sha2::Sha256;
+
+use crate::app_state::AppState;
+use crate::detached::read_data;
+use crate::discover::discover;
+use crate::document::openssh_docs::with_public_key;
+use crate::errors::Error;
+use crate::hpke::{self, Kem};
+use crate::key_info::{Alg, Curve, KeyInfo, KeyType};
+use crate::signature::{ec_public_point, rsa_private_key, rsa_public_key};
+
+/// Size in bytes of a SHA-256 hash
+const HASH_SIZE: usize = 32;
+
+/// The HPKE KEM of an X25519, P-256, P-384 or P-521 key
+fn hpke_kem(key_info: &KeyInfo) -> Option<Kem> {
+    match (key_info.alg, key_info.curve()) {
+        (Alg::X25519, _) => Some(Kem::X25519),
+        (Alg::Ecdsa, Some(Curve::P256)) => Some(Kem::P256),
+        (Alg::Ecdsa, Some(Curve::P384)) => Some(Kem::P384),
+        (Alg::Ecdsa, Some(Curve::P521)) => Some(Kem::P521),
+        _ => None,
+    }
+}
+
+fn key_bytes(key_info: &KeyInfo) -> Result<&[u8]> {
+    key_info
+        .bytes
+        .as_deref()
+        .map(Vec::as_slice)
+        .ok_or_else(|| Error::MissingInput("key".to_owned()).into())
+}
+
+/// The serialized public key HPKE encrypts to, of a public or a private key
+fn hpke_public_key(kem: Kem, key_info: &KeyInfo) -> Result<Vec<u8>> {
+    match (kem, key_info.key_type) {
+        (Kem::P256 | Kem::P384 | Kem::P521, _) => ec_public_point(key_info),
+        (Kem::X25519, KeyType::Private) => with_public_key(key_info)?
+            .public_key
+            .ok_or_else(|| Error::BadCrypto.into()),
+        (Kem::X25519, _) => Ok(key_bytes(key_info)?.to_vec()),
+    }
+}
+
+/// The private key HPKE decrypts with: the X25519 private key, or the EC
+/// scalar
+fn hpke_private_key(kem: Kem, key_info: &KeyInfo) -> Result<Vec<u8>> {
+    let bytes = key_bytes(key_info)?;
+    let private_key = match kem {
+        Kem::X25519 => OctetString::from_der(bytes).map(|octets| octets.as_bytes().to_vec()),
+        Kem::P256 | Kem::P384 | Kem::P521 => EcPrivateKey::from_der(bytes).map(|key| key.private_key.to_vec()),
+    };
+    private_key.map_err(|_| Error::BadCrypto.into())
+}
+
+/// Encrypt the payload to the input key, and write the ciphertext
+pub fn encrypt(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    let plaintext = read_data(app_state)?;
+
+    let ciphertext = match (key_info.alg, hpke_kem(&key_info)) {
+        (Alg::Rsa | Alg::RsaSsaPss, _) => {
+            let public_key = rsa_public_key(&key_info)?;
+            let max_size = public_key.size().saturating_sub(2 * HASH_SIZE + 2);
+            if plaintext.len() > max_size {
+                bail!(Error::PayloadTooLong(max_size));
+            }
+            public_key
+                .encrypt(&mut OsRng, Oaep::new::<Sha256>(), &plaintext)
+                .map_err(|_| Error::BadCrypto)?
+        }
+        (_, Some(kem)) => {
+            let public_key = hpke_public_key(kem, &key_info)?;
+            let (enc, ciphertext) = hpke::seal(kem, &public_key, b"", b"", &plaintext, &mut OsRng)?;
+            [enc, ciphertext].concat()
+        }
+        _ => bail!(Error::NotSupported),
+    };
+    match app_state.base64 {
+        true => app_state.write_stream(format!("{}\n", Base64::encode_string(&ciphertext)).as_bytes()),
+        false => app_state.write_stream(&ciphertext),
+    }
+}
+
+/// Decrypt the ciphertext with the input private key, and write the
+/// payload.  A ciphertext that does not decrypt is returned as
+/// Error::DecryptionFailed.
+pub fn decrypt(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    if key_info.key_type != KeyType::Private {
+        bail!(Error::TypeMismatch);
+    }
+    let mut ciphertext = read_data(app_state)?;
+    if app_state.base64 {
+        let text = std::str::from_utf8(&ciphertext).map_err(|_| Error::DecryptionFailed)?;
+        let text: String = text.split_whitespace().collect();
+        ciphertext = Base64::decode_vec(&text).map_err(|_| Error::DecryptionFailed)?;
+    }
+
+    let plaintext = match (key_info.alg, hpke_kem(&key_info)) {
+        (Alg::Rsa | Alg::RsaSsaPss, _) => rsa_private_key(&key_info)?
+            .decrypt(Oaep::new::<Sha256>(), &ciphertext)
+            .map_err(|_| Error::DecryptionFailed)?,
+        (_, Some(kem)) => {
+            if ciphertext.len() < kem.enc_size() {
+                bail!(Error::DecryptionFailed);
+            }
+            let (enc, ciphertext) = ciphertext.split_at(kem.enc_size());
+            let private_key = zeroize::Zeroizing::new(hpke_private_key(kem, &key_info)?);
+            hpke::open(kem, &private_key, enc, b"", b"", ciphertext)?
+        }
+        _ => bail!(Error::NotSupported),
+    };
+    app_state.write_stream(&zeroize::Zeroizing::new(plaintext))
+}
@@ -49,6 +49,26 @@ pub enum Error {
     #[error("Uknown or unsupported encoding")]
     UnknownEncoding,
 
+    /// Represents unknown or unsupported elliptic curve`.
+    #[error("Unknown or unsupported curve")]
+    UnknownCurve,
+
+    /// Represents a malformed elliptic curve point`.
+    #[error("Bad elliptic curve point")]
+    BadEcPoint,
+
+    /// Represents unknown or unsupported target consumer`.
+    #[error("Unknown or unsupported target consumer")]
+    UnknownConsumer,
+
+    /// Represents unknown or unsupported export target`.
+    #[error("Unknown or unsupported export target")]
+    UnknownExportTarget,
+
+    /// Represents unknown batch failure kind`.
+    #[error("Unknown batch failure kind")]
+    UnknownFailureKind,
+
     /// Represents unknown or unsupported key type`.
     #[error("Uknown key type")]
     UnknownKeyType,
@@ -65,6 +85,254 @@ pub enum Error {
     #[error("Bad crypto error")]
     BadCrypto,
 
+    /// Represents a failure to run an external command
+    #[error("Command failed: {0}")]
+    Command(String),
+
+    /// Represents two keys that are not the same key
+    #[error("Keys do not match")]
+    KeyMismatch,
+
+    /// Represents a signature that did not verify
+    #[error("Signature verification failed")]
+    BadSignature,
+
     #[error("Missing input: {0}")]
     MissingInput(String),
+
+    /// Represents a symmetric secret that could not be decoded
+    #[error("Bad secret key")]
+    BadSecret,
+
+    /// Represents a password that failed to decrypt the input
+    #[error("Incorrect password")]
+    BadPassword,
+
+    /// Represents a malformed or unsupported JSON Web Encryption document
+    #[error("Bad JWE: {0}")]
+    BadJwe(String),
+
+    /// Represents malformed PEM armor
+    #[error("Bad PEM: {0}")]
+    BadPem(String),
+
+    /// Represents a failure to fetch remote input
+    #[error("Fetch failed: {0}")]
+    Fetch(String),
+
+    /// Represents remote input without the `http` feature
+    #[error("Reading from a URL requires the http feature")]
+    HttpDisabled,
+
+    /// Represents a malformed JSON Web Token
+    #[error("Bad JWT: {0}")]
+    BadJwt(String),
+
+    /// Represents a key, or kid, that is already in a key set
+    #[error("Key is already in the set: {0}")]
+    DuplicateKey(String),
+
+    /// Represents failures of a batch command that were not tolerated
+    #[error("Batch failed: {0} failures of a --fail-on kind")]
+    BatchFailed(usize),
+
+    /// Represents a malformed or unsupported JSON Web Key
+    #[error("Bad JWK: {0}")]
+    BadJwk(String),
+
+    /// Represents a key that can not be written as a COSE_Key
+    #[error("Bad COSE_Key: {0}")]
+    BadCose(String),
+
+    /// Represents a key that can not be written as an OpenSSH key
+    #[error("Bad OpenSSH key: {0}")]
+    BadOpenSsh(String),
+
+    /// Represents a path that is not a regular file, such as a symbolic link
+    #[error("Not a regular file: {0}")]
+    NotAFile(String),
+
+    /// Represents a umask that is not an octal mode
+    #[error("Bad umask: {0}")]
+    BadUmask(String),
+
+    /// Represents an owner that is not a known user or group
+    #[error("Bad owner: {0}")]
+    BadOwner(String),
+
+    /// Represents unknown event stream format
+    #[error("Unknown event format")]
+    UnknownEventFormat,
+
+    /// Represents a PuTTY private key that can not be read
+    #[error("Bad PuTTY key: {0}")]
+    BadPpk(String),
+
+    /// Represents unknown fingerprint hash
+    #[error("Unknown fingerprint hash")]
+    UnknownFingerprintHash,
+
+    /// Represents unknown fingerprint style
+    #[error("Unknown fingerprint style")]
+    UnknownFingerprintStyle,
+
+    /// Represents a known_hosts file that can not be read
+    #[error("Bad known_hosts: {0}")]
+    BadKnownHosts(String),
+
+    /// Represents a host that has no key in a known_hosts file
+    #[error("Host not found: {0}")]
+    HostNotFound(String),
+
+    /// Represents a certificate that can not be read
+    #[error("Bad certificate: {0}")]
+    BadCertificate(String),
+
+    /// Represents a CSR subject that can not be read
+    #[error("Bad subject: {0}")]
+    BadSubject(String),
+
+    /// Represents a subject alternative name that can not be read
+    #[error("Bad subject alternative name: {0}")]
+    BadSubjectAltName(String),
+
+    /// Represents unknown key usage
+    #[error("Unknown key usage")]
+    UnknownKeyUsage,
+
+    /// Represents a PKCS12 keystore that can not be read
+    #[error("Bad PKCS12: {0}")]
+    BadPkcs12(String),
+
+    /// Represents a PKCS7 certificate bundle that can not be read
+    #[error("Bad PKCS7: {0}")]
+    BadPkcs7(String),
+
+    /// Represents an OpenPGP key that can not be read
+    #[error("Bad OpenPGP key: {0}")]
+    BadOpenPgp(String),
+
+    /// Represents a Tink keyset that can not be read
+    #[error("Bad Tink keyset: {0}")]
+    BadTink(String),
+
+    /// Represents an Ethereum keystore that can not be read
+    #[error("Bad Ethereum keystore: {0}")]
+    BadEthereum(String),
+
+    /// Represents raw key bytes that are not a key of the algorithm given
+    #[error("Bad raw key: {0}")]
+    BadRawKey(String),
+
+    /// Represents hex input that can not be decoded
+    #[error("Bad hex input")]
+    BadHex,
+
+    /// Represents an unknown hex output style
+    #[error("Unknown hex style")]
+    UnknownHexStyle,
+
+    /// Represents an unknown key derivation function
+    #[error("Unknown KDF")]
+    UnknownKdf,
+
+    /// Represents an unknown cipher
+    #[error("Unknown cipher")]
+    UnknownCipher,
+
+    /// Represents password encryption parameters that can not be used
+    #[error("Bad password encryption parameters: {0}")]
+    BadPbeParams(String),
+
+    /// Represents an unknown PKCS8 version
+    #[error("Unknown PKCS8 version")]
+    UnknownPkcs8Version,
+
+    /// Represents a TPM key that can not be read
+    #[error("Bad TPM key: {0}")]
+    BadTpmKey(String),
+
+    /// Represents a TPM key, which only its TPM can load
+    #[error("TPM key can not be read: {0}")]
+    TpmKey(String),
+
+    /// Represents a Windows CNG key blob that can not be read or written
+    #[error("Bad CNG key blob: {0}")]
+    BadCng(String),
+
+    /// Represents an XML key value that can not be read or written
+    #[error("Bad XML key: {0}")]
+    BadXml(String),
+
+    /// Represents an input that is not well formed ASN.1
+    #[error("Bad ASN.1: {0}")]
+    BadAsn1(String),
+
+    /// Represents a DSA key that can not be read
+    #[error("Bad DSA key: {0}")]
+    BadDsa(String),
+
+    /// Represents DH parameters or a DH key that can not be read
+    #[error("Bad DH parameters: {0}")]
+    BadDh(String),
+
+    /// Represents an ML-DSA key that can not be read
+    #[error("Bad ML-DSA key: {0}")]
+    BadMlDsa(String),
+
+    /// Represents a composite key that can not be read or taken apart
+    #[error("Bad composite key: {0}")]
+    BadComposite(String),
+
+    /// Represents a composite key component that is not supported
+    #[error("Unknown composite key component")]
+    UnknownComponent,
+
+    /// Represents an unsupported RSASSA-PSS hash
+    #[error("Unknown RSASSA-PSS hash")]
+    UnknownPssHash,
+
+    /// Represents an unsupported EC point format
+    #[error("Unknown EC point format")]
+    UnknownPointFormat,
+
+    /// Represents an RSA public exponent that can not be used
+    #[error("Bad RSA public exponent {0}: it must be odd and at least 3")]
+    BadRsaExponent(u64),
+
+    /// Represents a key generation seed that can not be used
+    #[error("Bad seed: {0}")]
+    BadSeed(String),
+
+    /// Represents a key that failed validation
+    #[error("Invalid key: {0} failed checks")]
+    InvalidKey(usize),
+
+    /// Represents an unsupported detached signature format
+    #[error("Unknown signature format")]
+    UnknownSignatureFormat,
+
+    /// Represents a ciphertext that did not decrypt with the key
+    #[error("Decryption failed")]
+    DecryptionFailed,
+
+    /// Represents a payload too long to encrypt with the key
+    #[error("Payload too long: the key encrypts at most {0} bytes")]
+    PayloadTooLong(usize),
+
+    /// Represents an output file that is the input file
+    #[error("The output file is the input file: {0}")]
+    SameFile(String),
+
+    /// Represents a key of an algorithm that kt does not sign with
+    #[error("Signing with {0} keys is not supported")]
+    SigningNotSupported(&'static str),
+
+    /// Represents a bad PKCS#10 certificate signing request
+    #[error("Bad certificate signing request: {0}")]
+    BadCsr(String),
+
+    /// Represents a key of an algorithm that has no raw key material form
+    #[error("{0} keys have no RAW form. Use DER or JWK")]
+    NoRawForm(&'static str),
 }
@@ -0,0 +1,252 @@
+//! Machine readable progress, warnings and results
+//!
+//! By default kt tells a person what it is doing with lines of text on
+//! stderr.  With `--events jsonl`, every one of those messages, and every log
+//! line, is instead written to stderr as a JSON object on a line of its own,
+//! so that GUI wrappers and scripts can drive kt without scraping the text.
+//! Output that is the product of a command, such as a converted key, still
+//! goes to stdout or the output file.
+//!
+//! Each line has these members:
+//!
+//! * `schema` - the version of this schema, currently 1
+//! * `time` - when the event happened, as RFC 3339
+//! * `command` - the subcommand, such as `convert` or `jwks build`
+//! * `event` - one of `start`, `progress`, `warning`, `log` or `result`
+//! * `code` - a stable identifier for warnings, log levels and failed
+//!   results.  Not present on every event
+//! * `message` - the text that would otherwise be written for a person
+//! * `data` - an object of event specific members.  Not present if empty
+//!
+//! Every run starts with a `start` event and ends with one `result` event,
+//! whose `data.status` is `ok` or `error`.  The `code` of a failed result is
+//! a batch failure kind, such as `bad-password`.  Members may be added to
+//! `data` in later versions of the schema, but not removed.  Invalid
+//! arguments are reported by clap, as text, before the stream starts.
+//!
+//! ```rust
+//! use kt::events::Event;
+//!
+//! let event = Event::warning("weak-key", "RSA keys under 2048 bits are weak").with("bits", 1024);
+//! assert_eq!(
+//!     event.to_json("convert", "2023-11-14T22:13:20Z"),
+//!     r#"{"schema":1,"time":"2023-11-14T22:13:20Z","command":"convert","event":"warning","code":"weak-key","message":"RSA keys under 2048 bits are weak","data":{"bits":1024}}"#
+//! );
+//! ```
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use crate::batch::FailureKind;
+use crate::errors::Error;
+use crate::timestamp;
+
+/// Version of the event schema
+pub const SCHEMA: u32 = 1;
+
+/// Formats of the event stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventFormat {
+    /// One JSON object per line
+    JsonLines,
+}
+
+impl EventFormat {
+    pub fn all() -> Vec<&'static str> {
+        vec!["jsonl"]
+    }
+}
+
+impl FromStr for EventFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<EventFormat> {
+        match s.to_lowercase().as_str() {
+            "jsonl" => Ok(EventFormat::JsonLines),
+            _ => Err(Error::UnknownEventFormat.into()),
+        }
+    }
+}
+
+impl fmt::Display for EventFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txt = match self {
+            EventFormat::JsonLines => "jsonl",
+        };
+        write!(f, "{}", txt)
+    }
+}
+
+/// The kinds of event
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    /// The command has started
+    Start,
+    /// A step of the command is done
+    Progress,
+    /// Something the user should know about, that doesn't stop the command
+    Warning,
+    /// A log line, enabled by `RUST_LOG`
+    Log,
+    /// The command has finished, successfully or not
+    Result,
+}
+
+/// An event of the stream
+#[derive(Clone, Debug, Serialize)]
+pub struct Event {
+    /// The text written for a person, if any, when events are not enabled
+    #[serde(skip)]
+    pub text: Option<String>,
+    pub event: EventKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    pub data: Map<String, Value>,
+}
+
+/// An event with the common members of a line
+#[derive(Serialize)]
+struct Line<'a> {
+    schema: u32,
+    time: &'a str,
+    command: &'a str,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+impl Event {
+    pub fn new(event: EventKind, message: &str) -> Self {
+        Self {
+            text: Some(message.to_owned()),
+            event,
+            code: None,
+            message: message.to_owned(),
+            data: Map::new(),
+        }
+    }
+
+    /// A step of the command is done
+    pub fn progress(message: &str) -> Self {
+        Self::new(EventKind::Progress, message)
+    }
+
+    /// A warning, written for a person as `warning: message`
+    pub fn warning(code: &str, message: &str) -> Self {
+        Self::new(EventKind::Warning, message)
+            .with_code(code)
+            .with_text(&format!("warning: {}", message))
+    }
+
+    pub fn with_code(mut self, code: &str) -> Self {
+        self.code = Some(code.to_owned());
+        self
+    }
+
+    /// Add a member to `data`
+    pub fn with(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.data.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Replace the text written for a person
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = Some(text.to_owned());
+        self
+    }
+
+    /// Only write the event to the event stream
+    pub fn silent(mut self) -> Self {
+        self.text = None;
+        self
+    }
+
+    /// The event as a line of the stream
+    pub fn to_json(&self, command: &str, time: &str) -> String {
+        let line = Line {
+            schema: SCHEMA,
+            time,
+            command,
+            event: self,
+        };
+        serde_json::to_string(&line).unwrap_or_default()
+    }
+}
+
+/// The stream events are written to, once enabled
+struct Stream {
+    format: EventFormat,
+    command: String,
+    local_time: bool,
+}
+
+static STREAM: OnceLock<Stream> = OnceLock::new();
+
+/// Write events for the command, rather than text, from now on
+pub fn init(format: EventFormat, command: &str, local_time: bool) {
+    let _ = STREAM.set(Stream {
+        format,
+        command: command.to_owned(),
+        local_time,
+    });
+}
+
+/// Whether events are written, rather than text
+pub fn enabled() -> bool {
+    STREAM.get().is_some()
+}
+
+/// Write the event to stderr, as text for a person or as a line of the
+/// event stream
+pub fn emit(event: Event) {
+    let mut stderr = std::io::stderr().lock();
+    let _ = match STREAM.get() {
+        Some(stream) => match stream.format {
+            EventFormat::JsonLines => writeln!(
+                stderr,
+                "{}",
+                event.to_json(&stream.command, &timestamp::now(stream.local_time))
+            ),
+        },
+        None => match &event.text {
+            Some(text) => writeln!(stderr, "{}", text),
+            None => Ok(()),
+        },
+    };
+}
+
+/// The log line as a line of the event stream, if events are enabled
+pub fn log_line(record: &log::Record) -> Option<String> {
+    let stream = STREAM.get()?;
+    let event = Event::new(EventKind::Log, &record.args().to_string())
+        .with_code(&record.level().to_string().to_lowercase())
+        .with("target", record.target());
+    Some(event.to_json(&stream.command, &timestamp::now(stream.local_time)))
+}
+
+/// Write the start event
+pub fn start() {
+    emit(
+        Event::new(EventKind::Start, "started")
+            .with("version", env!("CARGO_PKG_VERSION"))
+            .silent(),
+    );
+}
+
+/// Write the result event.  Errors are left for the caller to report when
+/// events are not enabled.
+pub fn finish(result: &Result<()>) {
+    let event = match result {
+        Ok(()) => Event::new(EventKind::Result, "done").with("status", "ok"),
+        Err(e) => Event::new(EventKind::Result, &format!("{:#}", e))
+            .with_code(FailureKind::of(e).name())
+            .with("status", "error"),
+    };
+    emit(event.silent());
+}
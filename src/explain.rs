@@ -0,0 +1,422 @@
+//! A field by field breakdown of the document a key was read from
+//!
+//! `kt explain` reads the key as `kt show` does, then names each field of
+//! the PKCS8, SPKI, PKCS1 or SEC1 structures it was read from, with its value
+//! or size, such as `RSAPrivateKey.version = 0 (two-prime)` or
+//! `RSAPrivateKey.privateExponent: 2048 bits`.  The structure a field holds,
+//! such as the RSAPrivateKey of a PrivateKeyInfo, is explained below it.
+//! Other formats are explained by the key material they hold.
+//!
+//! Private values are given by their size, never their value.
+//!
+//! ```rust
+//! use kt::explain::uint_bits;
+//!
+//! assert_eq!(uint_bits(&[0x01, 0x00, 0x01]), 17);
+//! assert_eq!(uint_bits(&[0x00, 0x80]), 8);
+//! assert_eq!(uint_bits(&[]), 0);
+//! ```
+use anyhow::Result;
+use pkcs1::{RsaPrivateKeyDocument, RsaPublicKeyDocument};
+use pkcs8::der::{asn1::Any, Decodable, Document};
+use sec1::EcPrivateKeyDocument;
+use std::fmt::Write as _;
+
+use crate::alg_id::RsaPssParams;
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::key_info::{Alg, Format, KeyInfo, KeyType};
+use crate::oids::{oid_to_str, RSASSA_PSS};
+
+/// Indent of the structure held by a field
+const INDENT: &str = "    ";
+
+/// The number of bits of a big endian unsigned integer, without its leading
+/// zeros
+pub fn uint_bits(bytes: &[u8]) -> usize {
+    let bytes = match bytes.iter().position(|b| *b != 0) {
+        Some(first) => &bytes[first..],
+        None => return 0,
+    };
+    bytes.len() * 8 - bytes[0].leading_zeros() as usize
+}
+
+/// A small unsigned integer, such as a public exponent, as a number, or else
+/// its size
+fn uint_value(bytes: &[u8]) -> String {
+    if uint_bits(bytes) <= 64 {
+        let value = bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+        format!(" = {}", value)
+    } else {
+        format!(": {} bits", uint_bits(bytes))
+    }
+}
+
+fn line(out: &mut String, indent: &str, text: &str) {
+    let _ = writeln!(out, "{}{}", indent, text);
+}
+
+fn explain_rsa_private_key(der: &[u8], indent: &str, out: &mut String) -> bool {
+    let pk1_doc = match RsaPrivateKeyDocument::from_der(der) {
+        Ok(pk1_doc) => pk1_doc,
+        Err(_) => return false,
+    };
+    let pk1 = pk1_doc.decode();
+    let version = match pk1.version().is_multi() {
+        true => "1 (multi-prime)",
+        false => "0 (two-prime)",
+    };
+    line(out, indent, &format!("RSAPrivateKey.version = {}", version));
+    line(
+        out,
+        indent,
+        &format!(
+            "RSAPrivateKey.modulus: {} bits",
+            uint_bits(pk1.modulus.as_bytes())
+        ),
+    );
+    line(
+        out,
+        indent,
+        &format!(
+            "RSAPrivateKey.publicExponent{}",
+            uint_value(pk1.public_exponent.as_bytes())
+        ),
+    );
+    for (name, value) in [
+        ("privateExponent", pk1.private_exponent),
+        ("prime1", pk1.prime1),
+        ("prime2", pk1.prime2),
+        ("exponent1", pk1.exponent1),
+        ("exponent2", pk1.exponent2),
+        ("coefficient", pk1.coefficient),
+    ] {
+        line(
+            out,
+            indent,
+            &format!(
+                "RSAPrivateKey.{}: {} bits",
+                name,
+                uint_bits(value.as_bytes())
+            ),
+        );
+    }
+    if let Some(other_prime_infos) = &pk1.other_prime_infos {
+        line(
+            out,
+            indent,
+            &format!(
+                "RSAPrivateKey.otherPrimeInfos: {} more primes",
+                other_prime_infos.len()
+            ),
+        );
+    }
+    true
+}
+
+fn explain_rsa_public_key(der: &[u8], indent: &str, out: &mut String) -> bool {
+    let pk1_doc = match RsaPublicKeyDocument::from_der(der) {
+        Ok(pk1_doc) => pk1_doc,
+        Err(_) => return false,
+    };
+    let pk1 = pk1_doc.decode();
+    line(
+        out,
+        indent,
+        &format!(
+            "RSAPublicKey.modulus: {} bits",
+            uint_bits(pk1.modulus.as_bytes())
+        ),
+    );
+    line(
+        out,
+        indent,
+        &format!(
+            "RSAPublicKey.publicExponent{}",
+            uint_value(pk1.public_exponent.as_bytes())
+        ),
+    );
+    true
+}
+
+fn ec_point(point: &[u8]) -> String {
+    match point.first() {
+        Some(0x04) => format!(
+            "{} bytes, uncompressed (04 || x || y), of {} byte coordinates",
+            point.len(),
+            (point.len() - 1) / 2
+        ),
+        Some(0x02 | 0x03) => format!(
+            "{} bytes, compressed (02 or 03 || x), of {} byte coordinates",
+            point.len(),
+            point.len() - 1
+        ),
+        _ => format!("{} bytes", point.len()),
+    }
+}
+
+fn explain_ec_private_key(der: &[u8], indent: &str, out: &mut String) -> bool {
+    let sec1_doc = match EcPrivateKeyDocument::from_der(der) {
+        Ok(sec1_doc) => sec1_doc,
+        Err(_) => return false,
+    };
+    let sec1 = sec1_doc.decode();
+    line(out, indent, "ECPrivateKey.version = 1 (ecPrivkeyVer1)");
+    line(
+        out,
+        indent,
+        &format!("ECPrivateKey.privateKey: {} bytes", sec1.private_key.len()),
+    );
+    let parameters = match sec1.parameters.and_then(|params| params.named_curve()) {
+        Some(oid) => format!(" = namedCurve {}", oid_to_str(&oid)),
+        None => ": absent, the curve is given by the AlgorithmIdentifier".to_owned(),
+    };
+    line(
+        out,
+        indent,
+        &format!("ECPrivateKey.parameters{}", parameters),
+    );
+    let public_key = match sec1.public_key {
+        Some(point) => ec_point(point),
+        None => "absent".to_owned(),
+    };
+    line(
+        out,
+        indent,
+        &format!("ECPrivateKey.publicKey: {}", public_key),
+    );
+    true
+}
+
+/// Explain the private key of a PrivateKeyInfo, or of another format
+fn explain_private_key(key_info: &KeyInfo, bytes: &[u8], indent: &str, out: &mut String) {
+    let explained = match key_info.alg {
+        Alg::Rsa | Alg::RsaSsaPss => explain_rsa_private_key(bytes, indent, out),
+        Alg::Ecdsa => explain_ec_private_key(bytes, indent, out),
+        _ => match bytes {
+            // The CurvePrivateKey of RFC 8410 is an OCTET STRING
+            [0x04, len, key @ ..] if *len as usize == key.len() => {
+                line(
+                    out,
+                    indent,
+                    &format!("CurvePrivateKey: OCTET STRING of {} bytes", key.len()),
+                );
+                true
+            }
+            _ => false,
+        },
+    };
+    if !explained {
+        line(out, indent, &format!("Private key: {} bytes", bytes.len()));
+    }
+}
+
+/// Explain the public key of a SubjectPublicKeyInfo, or of another format
+fn explain_public_key(key_info: &KeyInfo, bytes: &[u8], indent: &str, out: &mut String) {
+    let explained = match key_info.alg {
+        Alg::Rsa | Alg::RsaSsaPss => explain_rsa_public_key(bytes, indent, out),
+        Alg::Ecdsa => {
+            line(out, indent, &format!("ECPoint: {}", ec_point(bytes)));
+            true
+        }
+        _ => false,
+    };
+    if !explained {
+        line(out, indent, &format!("Public key: {} bytes", bytes.len()));
+    }
+}
+
+/// The name of the structure a private or public key is held in
+fn key_structure(key_info: &KeyInfo) -> &'static str {
+    match (key_info.alg, key_info.key_type) {
+        (Alg::Rsa | Alg::RsaSsaPss, KeyType::Private) => "an RSAPrivateKey",
+        (Alg::Rsa | Alg::RsaSsaPss, _) => "an RSAPublicKey",
+        (Alg::Ecdsa, KeyType::Private) => "an ECPrivateKey",
+        (Alg::Ecdsa, _) => "an ECPoint",
+        (_, KeyType::Private) => "a CurvePrivateKey",
+        _ => "the public key",
+    }
+}
+
+/// Explain the AlgorithmIdentifier of a PrivateKeyInfo or
+/// SubjectPublicKeyInfo
+fn explain_alg_id(key_info: &KeyInfo, name: &str, indent: &str, out: &mut String) {
+    let oid = match key_info.oid {
+        Some(oid) => oid,
+        None => return,
+    };
+    line(
+        out,
+        indent,
+        &format!("{}.algorithm = {}", name, oid_to_str(&oid)),
+    );
+    let params = match &key_info.params {
+        Some(params) => params,
+        None => {
+            line(out, indent, &format!("{}.parameters: absent", name));
+            return;
+        }
+    };
+    if oid == RSASSA_PSS {
+        if let Ok(pss) = RsaPssParams::from_der(params) {
+            line(
+                out,
+                indent,
+                &format!("{}.parameters: RSASSA-PSS-params", name),
+            );
+            let indent = format!("{}{}", indent, INDENT);
+            for (field, value) in [
+                ("hashAlgorithm", oid_to_str(&pss.hash)),
+                (
+                    "maskGenAlgorithm",
+                    format!(
+                        "{} with {}",
+                        oid_to_str(&pss.mask_gen),
+                        oid_to_str(&pss.mask_gen_hash)
+                    ),
+                ),
+                ("saltLength", pss.salt_length.to_string()),
+                ("trailerField", pss.trailer_field.to_string()),
+            ] {
+                line(
+                    out,
+                    &indent,
+                    &format!("RSASSA-PSS-params.{} = {}", field, value),
+                );
+            }
+            return;
+        }
+    }
+    let parameters = match Any::from_der(params) {
+        Ok(any) if any.is_null() => " = NULL".to_owned(),
+        Ok(any) => match any.oid() {
+            Ok(curve) => format!(" = namedCurve {}", oid_to_str(&curve)),
+            Err(_) => format!(": {} bytes", params.len()),
+        },
+        Err(_) => format!(": {} bytes", params.len()),
+    };
+    line(out, indent, &format!("{}.parameters{}", name, parameters));
+}
+
+fn explain_pkcs8(key_info: &KeyInfo, bytes: &[u8], out: &mut String) {
+    if let Some(encryption) = &key_info.encryption {
+        let cost = match encryption.scrypt {
+            Some((block_size, parallelism)) => format!(
+                "cost {}, block size {}, parallelism {}",
+                encryption.iterations, block_size, parallelism
+            ),
+            None => format!("{} iterations", encryption.iterations),
+        };
+        line(
+            out,
+            "",
+            &format!(
+                "EncryptedPrivateKeyInfo.encryptionAlgorithm = {}, {}, {}, {} byte salt, {}",
+                encryption.scheme, encryption.kdf, cost, encryption.salt_len, encryption.cipher
+            ),
+        );
+        line(
+            out,
+            "",
+            "EncryptedPrivateKeyInfo.encryptedData: decrypted to the PrivateKeyInfo below",
+        );
+    }
+    let version = match key_info.public_key {
+        Some(_) => "1 (v2, OneAsymmetricKey)",
+        None => "0 (v1)",
+    };
+    line(out, "", &format!("PrivateKeyInfo.version = {}", version));
+    explain_alg_id(key_info, "PrivateKeyInfo.privateKeyAlgorithm", "", out);
+    line(
+        out,
+        "",
+        &format!(
+            "PrivateKeyInfo.privateKey: {} bytes, {}",
+            bytes.len(),
+            key_structure(key_info)
+        ),
+    );
+    explain_private_key(key_info, bytes, INDENT, out);
+    if let Some(public_key) = &key_info.public_key {
+        line(
+            out,
+            "",
+            &format!("PrivateKeyInfo.publicKey: {} bytes", public_key.len()),
+        );
+    }
+}
+
+fn explain_spki(key_info: &KeyInfo, bytes: &[u8], indent: &str, out: &mut String) {
+    explain_alg_id(key_info, "SubjectPublicKeyInfo.algorithm", indent, out);
+    line(
+        out,
+        indent,
+        &format!(
+            "SubjectPublicKeyInfo.subjectPublicKey: {} bits, {}",
+            bytes.len() * 8,
+            key_structure(key_info)
+        ),
+    );
+    explain_public_key(key_info, bytes, &format!("{}{}", indent, INDENT), out);
+}
+
+/// The breakdown of the document of the key
+pub fn explain_key_info(key_info: &KeyInfo) -> String {
+    let mut out = format!(
+        "Encoding: {:?}\nFormat: {:?}\n",
+        key_info.encoding, key_info.format
+    );
+    let bytes = match &key_info.bytes {
+        Some(bytes) => bytes.as_slice(),
+        None => {
+            line(&mut out, "", "The key material was not read");
+            return out;
+        }
+    };
+    match (key_info.format, key_info.key_type) {
+        (Format::PKCS8, KeyType::Private) => explain_pkcs8(key_info, bytes, &mut out),
+        (Format::SPKI, _) => explain_spki(key_info, bytes, "", &mut out),
+        (Format::X509, _) => {
+            if let Some(oid) = key_info.signature_oid {
+                line(
+                    &mut out,
+                    "",
+                    &format!("Certificate.signatureAlgorithm = {}", oid_to_str(&oid)),
+                );
+            }
+            line(
+                &mut out,
+                "",
+                "Certificate.tbsCertificate.subjectPublicKeyInfo: a SubjectPublicKeyInfo",
+            );
+            explain_spki(key_info, bytes, INDENT, &mut out);
+        }
+        (Format::PKCS1 | Format::SEC1, KeyType::Private) => {
+            explain_private_key(key_info, bytes, "", &mut out)
+        }
+        (Format::PKCS1 | Format::SEC1, _) => explain_public_key(key_info, bytes, "", &mut out),
+        (format, key_type) => {
+            line(
+                &mut out,
+                "",
+                &format!(
+                    "The {:?} document is explained by the key it holds, {}",
+                    format,
+                    key_structure(key_info)
+                ),
+            );
+            match key_type {
+                KeyType::Private => explain_private_key(key_info, bytes, INDENT, &mut out),
+                _ => explain_public_key(key_info, bytes, INDENT, &mut out),
+            }
+        }
+    }
+    out
+}
+
+/// Write the breakdown of the document of the input key
+pub fn explain(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    let explanation = explain_key_info(&key_info);
+    app_state.write_stream(explanation.as_bytes())
+}
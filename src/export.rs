@@ -0,0 +1,184 @@
+//! Export a key, and its certificate, for a specific server
+//!
+//! Each [ExportTarget] expects its own set of files, formats and
+//! permissions.  For example, HAProxy wants the certificate chain and key in
+//! one PEM file, while Java wants a PKCS12 keystore.
+use anyhow::{bail, Result};
+use log::warn;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::app_state::AppState;
+use crate::conversion::convert;
+use crate::discover::discover;
+use crate::document::pkcs12_docs::build_pkcs12;
+use crate::document::pkcs8_docs::{key_info_to_pk8_document, Pkcs8Version};
+use crate::errors::Error;
+use crate::key_info::{Encoding, Format, KeyInfo, KeyType};
+use crate::output::OutputPolicy;
+use crate::pbe::PbeParams;
+use crate::pem;
+
+/// Servers that keys can be exported for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportTarget {
+    /// `<name>.key` and `<name>.crt`, as used by `ssl_certificate_key` and
+    /// `ssl_certificate`
+    Nginx,
+    /// `<name>.pem`, holding the certificate chain followed by the key
+    Haproxy,
+    /// `<name>.key` and `<name>.crt`.  The key must not be readable by others,
+    /// or PostgreSQL will refuse to start
+    Postgres,
+    /// `<name>.p12`, a password protected PKCS12 keystore
+    JavaKeystore,
+}
+
+impl ExportTarget {
+    pub fn all() -> Vec<&'static str> {
+        vec!["nginx", "haproxy", "postgres", "java-keystore"]
+    }
+}
+
+impl FromStr for ExportTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<ExportTarget> {
+        match s.to_lowercase().as_str() {
+            "nginx" => Ok(ExportTarget::Nginx),
+            "haproxy" => Ok(ExportTarget::Haproxy),
+            "postgres" => Ok(ExportTarget::Postgres),
+            "java-keystore" => Ok(ExportTarget::JavaKeystore),
+            _ => Err(Error::UnknownExportTarget.into()),
+        }
+    }
+}
+
+impl fmt::Display for ExportTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txt = match self {
+            ExportTarget::Nginx => "nginx",
+            ExportTarget::Haproxy => "haproxy",
+            ExportTarget::Postgres => "postgres",
+            ExportTarget::JavaKeystore => "java-keystore",
+        };
+        write!(f, "{}", txt)
+    }
+}
+
+/// The certificate chain as PEM
+fn certs_to_pem(certs: &[Vec<u8>]) -> Result<String> {
+    let mut text = String::new();
+    for cert in certs {
+        text.push_str(&pem::wrap(pem::CERTIFICATE, cert)?);
+    }
+    Ok(text)
+}
+
+/// Write the private key as unencrypted PEM, after any other content
+fn write_key_pem(file: File, prefix: &[u8], key_info: &KeyInfo) -> Result<()> {
+    let mut file_state = AppState {
+        out_stream: Box::new(file),
+        alg: Some(key_info.alg),
+        key_type: Some(KeyType::Private),
+        format: Some(Format::PKCS8),
+        encoding: Encoding::PEM,
+        ..Default::default()
+    };
+    file_state.write_stream(prefix)?;
+    convert(&mut file_state, key_info)
+}
+
+/// Write the key and certificate chain as separate PEM files
+fn write_key_and_cert(
+    output: &OutputPolicy,
+    out_dir: &Path,
+    name: &str,
+    key_info: &KeyInfo,
+    certs: &[Vec<u8>],
+) -> Result<()> {
+    let key_path = out_dir.join(format!("{}.key", name));
+    write_key_pem(output.create_file(&key_path, true)?, &[], key_info)?;
+    if !certs.is_empty() {
+        let cert_path = out_dir.join(format!("{}.crt", name));
+        let mut file = output.create_file(&cert_path, false)?;
+        std::io::Write::write_all(&mut file, certs_to_pem(certs)?.as_bytes())
+            .map_err(Error::WriteFileError)?;
+    }
+    Ok(())
+}
+
+/// Write a PKCS12 keystore, with the first certificate as the key's
+/// certificate, and the rest as its chain.  The keystore is protected with
+/// PBES2 and AES-256, which openssl 3 reads without `-legacy`.
+fn write_keystore(
+    output: &OutputPolicy,
+    path: &Path,
+    name: &str,
+    password: &str,
+    key_info: &KeyInfo,
+    certs: &[Vec<u8>],
+) -> Result<()> {
+    let key_der = key_info_to_pk8_document(key_info.alg, key_info, Pkcs8Version::V1)?;
+    let keystore = build_pkcs12(key_der.as_ref(), certs, Some(name), password, &PbeParams::default())?;
+    let mut file = output.create_file(path, true)?;
+    std::io::Write::write_all(&mut file, &keystore).map_err(Error::WriteFileError)?;
+    Ok(())
+}
+
+/// Export the input private key, and certificate, for the target server
+pub fn export(app_state: &mut AppState) -> Result<()> {
+    let target = app_state
+        .export_target
+        .ok_or_else(|| Error::MissingInput("export target".to_owned()))?;
+    let out_dir = app_state
+        .out_dir
+        .clone()
+        .ok_or_else(|| Error::MissingInput("output directory".to_owned()))?;
+    let out_dir = PathBuf::from(out_dir);
+    let name = app_state
+        .export_name
+        .clone()
+        .unwrap_or_else(|| "server".to_owned());
+
+    let key_info = discover(app_state)?;
+    if key_info.key_type != KeyType::Private {
+        bail!(Error::TypeMismatch);
+    }
+    let certs = match &app_state.cert_file {
+        Some(cert_file) => pem::read_certs(cert_file)?,
+        None => Vec::new(),
+    };
+    if certs.is_empty() {
+        warn!("No certificate was provided for {}", target);
+    }
+    let output = app_state.output;
+    output.create_dir_all(&out_dir)?;
+
+    match target {
+        ExportTarget::Nginx | ExportTarget::Postgres => {
+            write_key_and_cert(&output, &out_dir, &name, &key_info, &certs)?
+        }
+        ExportTarget::Haproxy => {
+            let path = out_dir.join(format!("{}.pem", name));
+            let certs = certs_to_pem(&certs)?;
+            write_key_pem(output.create_file(&path, true)?, certs.as_bytes(), &key_info)?;
+        }
+        ExportTarget::JavaKeystore => {
+            if certs.is_empty() {
+                bail!(Error::MissingInput(
+                    "certificate for the keystore".to_owned()
+                ));
+            }
+            let password = app_state
+                .out_password
+                .clone()
+                .ok_or_else(|| Error::MissingInput("keystore password".to_owned()))?;
+            let path = out_dir.join(format!("{}.p12", name));
+            write_keystore(&output, &path, &name, &password, &key_info, &certs)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,222 @@
+//! Fingerprints of public keys, as `ssh-keygen -l`
+//!
+//! A fingerprint is the hash of the public key, in one of two styles:
+//!
+//! * `ssh` - the hash of the SSH wire encoding of the key, the base64 of an
+//!   `authorized_keys` line.  These are the fingerprints `ssh-keygen -l` and
+//!   ssh clients show.
+//! * `spki` - the hash of the DER SubjectPublicKeyInfo of the key, which is
+//!   what is pinned for TLS keys.
+//!
+//! SHA-256 fingerprints are written as `SHA256:` and the base64 of the hash,
+//! without padding.  Legacy MD5 fingerprints are written as `MD5:` and the
+//! colon separated hex of the hash.  Each key is written on a line of its own,
+//! as ssh-keygen does: the key size in bits, the fingerprint, the comment of
+//! the key, and the key type.
+//!
+//! A pin is the padded base64 of the SHA-256 of the DER SubjectPublicKeyInfo,
+//! as HPKP `pin-sha256` and certificate pinning libraries use it.
+//!
+//! ```rust
+//! use kt::fingerprint::{FingerprintHash, format_hash};
+//!
+//! assert_eq!(
+//!     format_hash(FingerprintHash::Md5, b"abc"),
+//!     "MD5:90:01:50:98:3c:d2:4f:b0:d6:96:3f:7d:28:e1:7f:72"
+//! );
+//! ```
+use anyhow::Result;
+use base64ct::{Base64, Base64Unpadded, Encoding as _};
+use md5::Md5;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::io::Cursor;
+use std::str::FromStr;
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::document::jwk_docs::{b64_decode, key_info_as_public_jwk, Jwk};
+use crate::document::openssh_docs::{
+    is_openssh_public_key, jwk_to_ssh_blob, openssh_public_line_to_key_info, public_key_lines,
+    with_public_key,
+};
+use crate::document::spki_docs::public_spki_der;
+use crate::errors::Error;
+use crate::key_info::KeyInfo;
+
+/// Hashes of fingerprints
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FingerprintHash {
+    Sha256,
+    /// Legacy fingerprints, as older ssh clients show
+    Md5,
+}
+
+impl FingerprintHash {
+    pub fn all() -> Vec<&'static str> {
+        vec!["sha256", "md5"]
+    }
+}
+
+impl FromStr for FingerprintHash {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<FingerprintHash> {
+        match s.to_lowercase().as_str() {
+            "sha256" => Ok(FingerprintHash::Sha256),
+            "md5" => Ok(FingerprintHash::Md5),
+            _ => Err(Error::UnknownFingerprintHash.into()),
+        }
+    }
+}
+
+impl fmt::Display for FingerprintHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txt = match self {
+            FingerprintHash::Sha256 => "SHA256",
+            FingerprintHash::Md5 => "MD5",
+        };
+        write!(f, "{}", txt)
+    }
+}
+
+/// What the fingerprint is a hash of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FingerprintStyle {
+    /// The SSH wire encoding of the key
+    Ssh,
+    /// The DER SubjectPublicKeyInfo of the key
+    Spki,
+}
+
+impl FingerprintStyle {
+    pub fn all() -> Vec<&'static str> {
+        vec!["ssh", "spki"]
+    }
+}
+
+impl FromStr for FingerprintStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<FingerprintStyle> {
+        match s.to_lowercase().as_str() {
+            "ssh" => Ok(FingerprintStyle::Ssh),
+            "spki" => Ok(FingerprintStyle::Spki),
+            _ => Err(Error::UnknownFingerprintStyle.into()),
+        }
+    }
+}
+
+/// The fingerprint of the bytes, as ssh-keygen writes it
+pub fn format_hash(hash: FingerprintHash, bytes: &[u8]) -> String {
+    match hash {
+        FingerprintHash::Sha256 => {
+            format!("{}:{}", hash, Base64Unpadded::encode_string(&Sha256::digest(bytes)))
+        }
+        FingerprintHash::Md5 => {
+            let hex: Vec<String> = Md5::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect();
+            format!("{}:{}", hash, hex.join(":"))
+        }
+    }
+}
+
+/// The size in bits of the public key, as ssh-keygen counts it
+fn key_bits(jwk: &Jwk) -> Result<usize> {
+    let bits = match (&jwk.n, jwk.crv.as_deref()) {
+        (Some(n), _) => {
+            let n = b64_decode("n", n)?;
+            let n = &n[n.iter().take_while(|b| **b == 0).count()..];
+            n.first()
+                .map_or(0, |b| (n.len() - 1) * 8 + (8 - b.leading_zeros() as usize))
+        }
+        (None, Some("P-256")) => 256,
+        (None, Some("P-384")) => 384,
+        (None, Some("P-521")) => 521,
+        (None, _) => b64_decode("x", jwk.x.as_deref().unwrap_or_default())?.len() * 8,
+    };
+    Ok(bits)
+}
+
+/// The size in bits of the public key of the KeyInfo, as ssh-keygen counts
+/// it
+pub fn key_size(key_info: &KeyInfo) -> Result<usize> {
+    key_bits(&key_info_as_public_jwk(&with_public_key(key_info)?)?)
+}
+
+/// The key type, as ssh-keygen shows it
+fn key_type_name(jwk: &Jwk) -> String {
+    match (jwk.kty.as_str(), jwk.crv.as_deref()) {
+        ("EC", _) => "ECDSA".to_owned(),
+        (_, Some(crv)) => crv.to_uppercase(),
+        (kty, None) => kty.to_owned(),
+    }
+}
+
+/// The fingerprint of the public key of the KeyInfo
+pub fn key_fingerprint(
+    key_info: &KeyInfo,
+    hash: FingerprintHash,
+    style: FingerprintStyle,
+) -> Result<String> {
+    let jwk = key_info_as_public_jwk(&with_public_key(key_info)?)?;
+    let bytes = match style {
+        FingerprintStyle::Ssh => jwk_to_ssh_blob(&jwk)?.1,
+        FingerprintStyle::Spki => public_spki_der(key_info)?,
+    };
+    Ok(format_hash(hash, &bytes))
+}
+
+/// The line ssh-keygen writes for the key: the size, fingerprint, comment
+/// and type
+pub fn fingerprint_line(
+    key_info: &KeyInfo,
+    hash: FingerprintHash,
+    style: FingerprintStyle,
+) -> Result<String> {
+    let jwk = key_info_as_public_jwk(&with_public_key(key_info)?)?;
+    Ok(format!(
+        "{} {} {} ({})",
+        key_bits(&jwk)?,
+        key_fingerprint(key_info, hash, style)?,
+        key_info.comment.as_deref().unwrap_or("no comment"),
+        key_type_name(&jwk)
+    ))
+}
+
+/// The SPKI pin of the public key of the KeyInfo
+pub fn spki_pin(key_info: &KeyInfo) -> Result<String> {
+    Ok(Base64::encode_string(&Sha256::digest(public_spki_der(key_info)?)))
+}
+
+/// Write the SPKI pin of the input key
+pub fn pin(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    let pin = spki_pin(&key_info)?;
+    app_state.write_stream(format!("{}\n", pin).as_bytes())
+}
+
+/// Write the fingerprint of the input key.
+///
+/// An `authorized_keys` file has a line for each of its keys.
+pub fn fingerprint(app_state: &mut AppState) -> Result<()> {
+    let hash = app_state.fingerprint_hash;
+    let style = app_state.fingerprint_style;
+    let bytes = app_state.read_stream()?;
+    let text = std::str::from_utf8(&bytes).ok();
+    let key_infos = match text {
+        Some(text) if app_state.secret.is_none() && is_openssh_public_key(text) => {
+            public_key_lines(text)
+                .map(openssh_public_line_to_key_info)
+                .collect::<Result<Vec<_>>>()?
+        }
+        _ => {
+            app_state.in_stream = Box::new(Cursor::new(bytes));
+            vec![discover(app_state)?]
+        }
+    };
+    for key_info in &key_infos {
+        let line = fingerprint_line(key_info, hash, style)?;
+        app_state.write_stream(format!("{}\n", line).as_bytes())?;
+    }
+    Ok(())
+}
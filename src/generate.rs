@@ -0,0 +1,257 @@
+//! Key generation
+//!
+//! Generated keys are returned as [KeyInfo], in the same form as keys that
+//! are discovered, so they can be written with [convert](crate::conversion::convert)
+//! in any supported format.
+//!
+//! Keys are generated from the operating system's random number generator,
+//! or, for tests and fixtures, deterministically from a seed with
+//! [SeededRng].  Keys generated from a seed are only as secret as the seed,
+//! and must never be used in production.
+use anyhow::{bail, Result};
+use ed25519_dalek::SigningKey;
+use hmac::{Hmac, Mac};
+use pkcs8::der::{asn1::OctetString, Encodable};
+use rand_core::{CryptoRng, CryptoRngCore, OsRng, RngCore};
+use sha2::Sha256;
+use std::fs;
+use rsa::pkcs1::EncodeRsaPrivateKey;
+use rsa::BigUint;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::app_state::{AppState, Command};
+use crate::conversion::convert;
+use crate::document::jwk_docs::key_info_as_public_jwk;
+use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::oids;
+use crate::secp256k1;
+use crate::sm2;
+use crate::xdh::x25519_public_key;
+
+/// RSA key lengths that `kt generate rsa` generates
+pub const RSA_BITS: [&str; 3] = ["2048", "3072", "4096"];
+
+/// The usual RSA public exponent, F4
+pub const DEFAULT_EXPONENT: u64 = 65537;
+
+/// Generate an RSA private key of the modulus length in bits
+///
+/// The key bytes are a PKCS1 RSAPrivateKey.  The public exponent must be
+/// odd and at least 3.
+pub fn generate_rsa(bits: usize, exponent: u64, rng: &mut dyn CryptoRngCore) -> Result<KeyInfo> {
+    if exponent < 3 || exponent.is_multiple_of(2) {
+        bail!(Error::BadRsaExponent(exponent));
+    }
+    let private_key = rsa::RsaPrivateKey::new_with_exp(rng, bits, &BigUint::from(exponent))
+        .map_err(|_| Error::BadCrypto)?;
+    let pk1_der = private_key.to_pkcs1_der().map_err(|_| Error::BadCrypto)?;
+
+    let key_info = KeyInfo::new()
+        .with_alg(Alg::Rsa)
+        .with_key_type(KeyType::Private)
+        .with_format(Format::PKCS1)
+        .with_key_length(bits as u32)
+        .with_bytes(pk1_der.as_bytes());
+    Ok(key_info)
+}
+
+/// A deterministic random number generator, HMAC_DRBG with SHA-256 of
+/// NIST SP 800-90A, instantiated with a seed and never reseeded.
+///
+/// **Unsafe for production.**  Everything generated with it can be
+/// reproduced by anyone with the seed.  It is for CI pipelines and test
+/// fixtures, which need the same keys every run.
+///
+/// ```rust
+/// use kt::generate::{generate_ed25519, SeededRng};
+///
+/// let first = generate_ed25519(&mut SeededRng::new(b"kt test fixture")).unwrap();
+/// let second = generate_ed25519(&mut SeededRng::new(b"kt test fixture")).unwrap();
+/// assert_eq!(first.bytes, second.bytes);
+/// assert_eq!(first.public_key, second.public_key);
+/// ```
+#[derive(Zeroize, ZeroizeOnDrop)]
+pub struct SeededRng {
+    key: [u8; 32],
+    value: [u8; 32],
+}
+
+impl SeededRng {
+    /// Instantiate the generator with the seed as its entropy input
+    pub fn new(seed: &[u8]) -> Self {
+        let mut rng = Self {
+            key: [0u8; 32],
+            value: [1u8; 32],
+        };
+        rng.update(seed);
+        rng
+    }
+
+    fn hmac(&self, parts: &[&[u8]]) -> [u8; 32] {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC takes keys of any size");
+        for part in parts {
+            mac.update(part);
+        }
+        mac.finalize().into_bytes().into()
+    }
+
+    /// The HMAC_DRBG update function
+    fn update(&mut self, data: &[u8]) {
+        self.key = self.hmac(&[&self.value, &[0x00], data]);
+        self.value = self.hmac(&[&self.value]);
+        if !data.is_empty() {
+            self.key = self.hmac(&[&self.value, &[0x01], data]);
+            self.value = self.hmac(&[&self.value]);
+        }
+    }
+}
+
+impl RngCore for SeededRng {
+    fn next_u32(&mut self) -> u32 {
+        rand_core::impls::next_u32_via_fill(self)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        rand_core::impls::next_u64_via_fill(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(32) {
+            self.value = self.hmac(&[&self.value]);
+            chunk.copy_from_slice(&self.value[..chunk.len()]);
+        }
+        self.update(&[]);
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl CryptoRng for SeededRng {}
+
+/// The seed of `--seed` or `--seed-file`, if one was provided
+fn seed(app_state: &AppState) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    let seed = match (&app_state.seed, &app_state.seed_file) {
+        (Some(hex_seed), _) => Zeroizing::new(
+            hex::decode(hex_seed.trim()).map_err(|_| Error::BadSeed("the seed is not hex".to_owned()))?,
+        ),
+        (None, Some(filename)) => Zeroizing::new(
+            fs::read(filename).map_err(|e| Error::BadSeed(format!("{}: {}", filename, e)))?,
+        ),
+        (None, None) => return Ok(None),
+    };
+    if seed.is_empty() {
+        bail!(Error::BadSeed("the seed is empty".to_owned()));
+    }
+    Ok(Some(seed))
+}
+
+/// Generate a new key, as the `generate` subcommand asked, and write it
+/// as a private key of the format and encoding of the AppState.
+///
+/// If a public key file was asked for, the SPKI of the key is also written
+/// to it, in the same encoding.  JWK output is given the RFC 7638 thumbprint
+/// of the key as its kid.
+pub fn generate(app_state: &mut AppState) -> Result<()> {
+    let mut rng: Box<dyn CryptoRngCore> = match seed(app_state)? {
+        Some(seed) => {
+            let message = "The key is generated from a seed, and is only as secret as the seed. Never use it in production";
+            events::emit(Event::warning("deterministic", message));
+            Box::new(SeededRng::new(&seed))
+        }
+        None => Box::new(OsRng),
+    };
+    let key_info = match app_state.command {
+        Command::GenerateEc => {
+            let curve = app_state
+                .curve
+                .ok_or_else(|| Error::MissingInput("curve".to_owned()))?;
+            generate_ec(curve, rng.as_mut())?
+        }
+        Command::GenerateEd25519 => generate_ed25519(rng.as_mut())?,
+        Command::GenerateX25519 => generate_x25519(rng.as_mut())?,
+        _ => generate_rsa(app_state.rsa_bits, app_state.rsa_exponent, rng.as_mut())?,
+    };
+    // JWKs are identified by their thumbprint, unless a kid was provided
+    if app_state.encoding == Encoding::JWK && app_state.key_id.is_none() {
+        app_state.key_id = Some(key_info_as_public_jwk(&key_info)?.thumbprint()?);
+    }
+    app_state.alg = Some(key_info.alg);
+    app_state.key_type = Some(KeyType::Private);
+    convert(app_state, &key_info)?;
+
+    if let Some(filename) = app_state.pub_out_file.clone() {
+        app_state.create_out_file(&filename, false)?;
+        app_state.key_type = Some(KeyType::Public);
+        app_state.format = Some(Format::SPKI);
+        app_state.out_password = None;
+        convert(app_state, &key_info)?;
+    }
+    Ok(())
+}
+
+/// Generate an elliptic curve private key
+///
+/// The key bytes are a SEC1 document, with the named curve and public key.
+pub fn generate_ec(curve: Curve, mut rng: &mut dyn CryptoRngCore) -> Result<KeyInfo> {
+    let sec1_der = match curve {
+        Curve::P256 => p256::SecretKey::random(&mut rng).to_sec1_der(),
+        Curve::P384 => p384::SecretKey::random(&mut rng).to_sec1_der(),
+        Curve::P521 => p521::SecretKey::random(&mut rng).to_sec1_der(),
+        Curve::Secp256k1 => Ok(secp256k1::random_sec1_der(rng)?),
+        Curve::Sm2 => Ok(sm2::random_sec1_der(rng)?),
+    }
+    .map_err(|_| Error::BadCrypto)?;
+
+    let key_info = KeyInfo::new()
+        .with_alg(Alg::Ecdsa)
+        .with_key_type(KeyType::Private)
+        .with_format(Format::SEC1)
+        .with_oid(&curve.oid())
+        .with_bytes(&sec1_der);
+    Ok(key_info)
+}
+
+/// Generate an Ed25519 private key
+///
+/// The key bytes are the CurvePrivateKey OCTET STRING of the seed, as in
+/// PKCS8, with the public key alongside.
+pub fn generate_ed25519(rng: &mut dyn CryptoRngCore) -> Result<KeyInfo> {
+    let mut seed = Zeroizing::new([0u8; 32]);
+    rng.fill_bytes(seed.as_mut());
+    let signing_key = SigningKey::from_bytes(&seed);
+    let private_key = Zeroizing::new(OctetString::new(seed.as_slice())?.to_vec()?);
+
+    let key_info = KeyInfo::new()
+        .with_alg(Alg::EdDsa25519)
+        .with_key_type(KeyType::Private)
+        .with_format(Format::PKCS8)
+        .with_oid(&oids::ED_DSA25519)
+        .with_bytes(&private_key)
+        .with_public_key(signing_key.verifying_key().as_bytes());
+    Ok(key_info)
+}
+
+/// Generate an X25519 private key
+///
+/// The key bytes are the CurvePrivateKey OCTET STRING of the private key,
+/// as in PKCS8, with the public key alongside.
+pub fn generate_x25519(rng: &mut dyn CryptoRngCore) -> Result<KeyInfo> {
+    let mut private_key = Zeroizing::new([0u8; 32]);
+    rng.fill_bytes(private_key.as_mut());
+    let public_key = x25519_public_key(private_key.as_slice())?;
+    let curve_private_key = Zeroizing::new(OctetString::new(private_key.as_slice())?.to_vec()?);
+
+    let key_info = KeyInfo::new()
+        .with_alg(Alg::X25519)
+        .with_key_type(KeyType::Private)
+        .with_format(Format::PKCS8)
+        .with_oid(&oids::X25519)
+        .with_bytes(&curve_private_key)
+        .with_public_key(&public_key);
+    Ok(key_info)
+}
@@ -0,0 +1,128 @@
+//! Hex text of DER documents and raw key material, for embedded and
+//! debugging work
+//!
+//! Hex is read with or without a `0x` prefix, with the bytes separated by
+//! colons, spaces or nothing, and across lines, as `openssl` and `xxd -p`
+//! print it.
+//!
+//! The bytes can also be written as C or Rust source, ready to paste into
+//! firmware.
+//!
+//! ```rust
+//! use kt::hex_text::{decode, encode, HexStyle};
+//!
+//! let bytes = [0x30, 0x03, 0x02, 0x01, 0xff];
+//! assert_eq!(encode(&bytes, HexStyle::Plain), "30030201ff");
+//! assert_eq!(encode(&bytes, HexStyle::Prefixed), "0x30030201ff");
+//! assert_eq!(encode(&bytes, HexStyle::Colon), "30:03:02:01:ff");
+//! assert_eq!(decode("0x30:03:02:01:FF").unwrap(), bytes);
+//!
+//! assert_eq!(
+//!     kt::hex_text::rust_array(&bytes),
+//!     "const KEY: [u8; 5] = [\n    0x30, 0x03, 0x02, 0x01, 0xff,\n];\n"
+//! );
+//! ```
+use std::str::FromStr;
+
+use anyhow::Result;
+
+use crate::errors::Error;
+
+/// Bytes per line of source arrays
+const ARRAY_LINE_BYTES: usize = 12;
+
+/// How hex output is written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HexStyle {
+    /// Lower case digits, with nothing between the bytes
+    #[default]
+    Plain,
+    /// As Plain, after a `0x` prefix
+    Prefixed,
+    /// Each byte separated by a colon, as `openssl` prints keys
+    Colon,
+}
+
+impl HexStyle {
+    pub fn all() -> Vec<&'static str> {
+        vec!["plain", "prefixed", "colon"]
+    }
+}
+
+impl FromStr for HexStyle {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<HexStyle> {
+        match s.to_lowercase().as_str() {
+            "plain" => Ok(HexStyle::Plain),
+            "prefixed" | "0x" => Ok(HexStyle::Prefixed),
+            "colon" => Ok(HexStyle::Colon),
+            _ => Err(Error::UnknownHexStyle.into()),
+        }
+    }
+}
+
+/// The bytes as hex, in the style
+pub fn encode(bytes: &[u8], style: HexStyle) -> String {
+    match style {
+        HexStyle::Plain => hex::encode(bytes),
+        HexStyle::Prefixed => format!("0x{}", hex::encode(bytes)),
+        HexStyle::Colon => bytes
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<Vec<String>>()
+            .join(":"),
+    }
+}
+
+/// The bytes of hex text, in any of the styles, upper or lower case
+pub fn decode(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    let digits: String = text
+        .chars()
+        .filter(|c| *c != ':' && !c.is_whitespace())
+        .collect();
+    if digits.is_empty() {
+        return Err(Error::BadHex.into());
+    }
+    hex::decode(digits).map_err(|_| Error::BadHex.into())
+}
+
+/// The DER document of hex text.  Only a document that starts as a DER
+/// SEQUENCE is taken, so that other text is not mistaken for hex.
+pub fn from_hex_der(bytes: &[u8]) -> Option<Vec<u8>> {
+    let der = decode(std::str::from_utf8(bytes).ok()?).ok()?;
+    match der.first() {
+        Some(0x30) => Some(der),
+        _ => None,
+    }
+}
+
+/// The lines of a source array, each indented and ending with a comma
+fn array_lines(bytes: &[u8]) -> String {
+    bytes
+        .chunks(ARRAY_LINE_BYTES)
+        .map(|line| {
+            let line: Vec<String> = line.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+            format!("    {},\n", line.join(", "))
+        })
+        .collect()
+}
+
+/// The bytes as a C array definition, with its length
+pub fn c_array(bytes: &[u8]) -> String {
+    format!(
+        "const uint8_t key[] = {{\n{}}};\nconst size_t key_len = {};\n",
+        array_lines(bytes),
+        bytes.len()
+    )
+}
+
+/// The bytes as a Rust array constant
+pub fn rust_array(bytes: &[u8]) -> String {
+    format!("const KEY: [u8; {}] = [\n{}];\n", bytes.len(), array_lines(bytes))
+}
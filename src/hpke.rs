@@ -0,0 +1,343 @@
+//! Hybrid Public Key Encryption ([RFC 9180](https://www.rfc-editor.org/rfc/rfc9180))
+//!
+//! Only the single-shot base mode is here, for `kt encrypt` and `kt decrypt`
+//! of small payloads to X25519 and EC keys.  Each KEM is used with the KDF
+//! and AEAD of its registered suite: DHKEM(X25519, HKDF-SHA256) and
+//! DHKEM(P-256, HKDF-SHA256) with HKDF-SHA256 and AES-128-GCM,
+//! DHKEM(P-384, HKDF-SHA384) with HKDF-SHA384 and AES-256-GCM, and
+//! DHKEM(P-521, HKDF-SHA512) with HKDF-SHA512 and AES-256-GCM.  The
+//! encapsulated key comes before the ciphertext.
+//!
+//! ```rust
+//! use kt::hpke::{open, Kem};
+//!
+//! // The base mode test vector of RFC 9180, appendix A.1.1
+//! let sk_r = hex::decode("4612c550263fc8ad58375df3f557aac531d26850903e55a9f23f21d8534e8ac8").unwrap();
+//! let enc = hex::decode("37fda3567bdbd628e88668c3c8d7e97d1d1253b6d4ea6d44c150f741f1bf4431").unwrap();
+//! let ciphertext = hex::decode(
+//!     "f938558b5d72f1a23810b4be2ab4f84331acc02fc97babc53a52ae8218a355a96d8770ac83d07bea87e13c512a",
+//! )
+//! .unwrap();
+//! let plaintext = open(Kem::X25519, &sk_r, &enc, b"Ode on a Grecian Urn", b"Count-0", &ciphertext).unwrap();
+//! assert_eq!(plaintext, b"Beauty is truth, truth beauty");
+//!
+//! // The base mode test vector of RFC 9180, appendix A.6.1
+//! let sk_r = hex::decode(
+//!     "01462680369ae375e4b3791070a7458ed527842f6a98a79ff5e0d4cbde83c27196a3916956655523a6a2556a7af62c5cadabe2ef9da3760bb21e005202f7b2462847",
+//! )
+//! .unwrap();
+//! let enc = hex::decode(
+//!     "040138b385ca16bb0d5fa0c0665fbbd7e69e3ee29f63991d3e9b5fa740aab8900aaeed46ed73a49055758425a0ce36507c54b29cc5b85a5cee6bae0cf1c21f2731ece2013dc3fb7c8d21654bb161b463962ca19e8c654ff24c94dd2898de12051f1ed0692237fb02b2f8d1dc1c73e9b366b529eb436e98a996ee522aef863dd5739d2f29b0",
+//! )
+//! .unwrap();
+//! let ciphertext = hex::decode(
+//!     "170f8beddfe949b75ef9c387e201baf4132fa7374593dfafa90768788b7b2b200aafcc6d80ea4c795a7c5b841a",
+//! )
+//! .unwrap();
+//! let plaintext = open(Kem::P521, &sk_r, &enc, b"Ode on a Grecian Urn", b"Count-0", &ciphertext).unwrap();
+//! assert_eq!(plaintext, b"Beauty is truth, truth beauty");
+//! ```
+use aes_gcm::{
+    aead::{Aead as _, KeyInit, Payload},
+    Aes128Gcm, Aes256Gcm, Nonce,
+};
+use anyhow::{bail, Result};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use hmac::{Hmac, Mac};
+use p256::elliptic_curve::sec1::ToEncodedPoint;
+use rand_core::CryptoRngCore;
+use sha2::{Sha256, Sha384, Sha512};
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+
+/// Size in bytes of an AES-GCM nonce
+const NONCE_SIZE: usize = 12;
+/// The base mode, without a PSK or sender authentication
+const MODE_BASE: u8 = 0x00;
+
+/// The HKDFs of the KEMs and suites
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kdf {
+    HkdfSha256,
+    HkdfSha384,
+    HkdfSha512,
+}
+
+impl Kdf {
+    fn id(&self) -> u16 {
+        match self {
+            Kdf::HkdfSha256 => 0x0001,
+            Kdf::HkdfSha384 => 0x0002,
+            Kdf::HkdfSha512 => 0x0003,
+        }
+    }
+
+    /// HMAC of the concatenated parts, with the hash of the HKDF
+    fn hmac(&self, key: &[u8], parts: &[&[u8]]) -> Zeroizing<Vec<u8>> {
+        fn mac<M: Mac + KeyInit>(key: &[u8], parts: &[&[u8]]) -> Zeroizing<Vec<u8>> {
+            let mut mac = <M as Mac>::new_from_slice(key).expect("HMAC takes keys of any size");
+            for part in parts {
+                mac.update(part);
+            }
+            Zeroizing::new(mac.finalize().into_bytes().to_vec())
+        }
+        match self {
+            Kdf::HkdfSha256 => mac::<Hmac<Sha256>>(key, parts),
+            Kdf::HkdfSha384 => mac::<Hmac<Sha384>>(key, parts),
+            Kdf::HkdfSha512 => mac::<Hmac<Sha512>>(key, parts),
+        }
+    }
+}
+
+/// The AEADs of the suites
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Aead {
+    Aes128Gcm,
+    Aes256Gcm,
+}
+
+impl Aead {
+    fn id(&self) -> u16 {
+        match self {
+            Aead::Aes128Gcm => 0x0001,
+            Aead::Aes256Gcm => 0x0002,
+        }
+    }
+
+    /// Size in bytes of a key
+    fn key_size(&self) -> usize {
+        match self {
+            Aead::Aes128Gcm => 16,
+            Aead::Aes256Gcm => 32,
+        }
+    }
+
+    fn seal(&self, key: &[u8], nonce: &[u8], payload: Payload) -> Result<Vec<u8>> {
+        let ciphertext = match self {
+            Aead::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+                .map_err(|_| Error::BadCrypto)?
+                .encrypt(Nonce::from_slice(nonce), payload),
+            Aead::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .map_err(|_| Error::BadCrypto)?
+                .encrypt(Nonce::from_slice(nonce), payload),
+        };
+        ciphertext.map_err(|_| Error::BadCrypto.into())
+    }
+
+    fn open(&self, key: &[u8], nonce: &[u8], payload: Payload) -> Result<Vec<u8>> {
+        let plaintext = match self {
+            Aead::Aes128Gcm => Aes128Gcm::new_from_slice(key)
+                .map_err(|_| Error::BadCrypto)?
+                .decrypt(Nonce::from_slice(nonce), payload),
+            Aead::Aes256Gcm => Aes256Gcm::new_from_slice(key)
+                .map_err(|_| Error::BadCrypto)?
+                .decrypt(Nonce::from_slice(nonce), payload),
+        };
+        plaintext.map_err(|_| Error::DecryptionFailed.into())
+    }
+}
+
+/// The KEMs of the keys kt encrypts to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kem {
+    /// DHKEM(X25519, HKDF-SHA256)
+    X25519,
+    /// DHKEM(P-256, HKDF-SHA256)
+    P256,
+    /// DHKEM(P-384, HKDF-SHA384)
+    P384,
+    /// DHKEM(P-521, HKDF-SHA512)
+    P521,
+}
+
+impl Kem {
+    fn id(&self) -> u16 {
+        match self {
+            Kem::X25519 => 0x0020,
+            Kem::P256 => 0x0010,
+            Kem::P384 => 0x0011,
+            Kem::P521 => 0x0012,
+        }
+    }
+
+    /// The KDF of the KEM, which is also the KDF of its suite
+    fn kdf(&self) -> Kdf {
+        match self {
+            Kem::X25519 | Kem::P256 => Kdf::HkdfSha256,
+            Kem::P384 => Kdf::HkdfSha384,
+            Kem::P521 => Kdf::HkdfSha512,
+        }
+    }
+
+    /// The AEAD of the suite of the KEM
+    fn aead(&self) -> Aead {
+        match self {
+            Kem::X25519 | Kem::P256 => Aead::Aes128Gcm,
+            Kem::P384 | Kem::P521 => Aead::Aes256Gcm,
+        }
+    }
+
+    /// Size in bytes of the KEM shared secret, the size of the KDF hash
+    fn secret_size(&self) -> usize {
+        match self {
+            Kem::X25519 | Kem::P256 => 32,
+            Kem::P384 => 48,
+            Kem::P521 => 64,
+        }
+    }
+
+    /// Size in bytes of the encapsulated key, a public key
+    pub fn enc_size(&self) -> usize {
+        match self {
+            Kem::X25519 => 32,
+            Kem::P256 => 65,
+            Kem::P384 => 97,
+            Kem::P521 => 133,
+        }
+    }
+
+    /// The Diffie-Hellman shared secret of the private key and the public
+    /// key, and the serialized public key of the private key
+    fn dh(&self, private_key: &[u8], public_key: &[u8]) -> Result<(Zeroizing<Vec<u8>>, Vec<u8>)> {
+        match self {
+            Kem::X25519 => {
+                let scalar: [u8; 32] = private_key.try_into().map_err(|_| Error::BadCrypto)?;
+                let point: [u8; 32] = public_key.try_into().map_err(|_| Error::BadCrypto)?;
+                let shared = MontgomeryPoint(point).mul_clamped(scalar).to_bytes();
+                // A small order public key gives an all zero shared secret
+                if shared == [0u8; 32] {
+                    bail!(Error::BadCrypto);
+                }
+                let own = MontgomeryPoint::mul_base_clamped(scalar).to_bytes().to_vec();
+                Ok((Zeroizing::new(shared.to_vec()), own))
+            }
+            Kem::P256 => {
+                let secret = p256::SecretKey::from_slice(private_key).map_err(|_| Error::BadCrypto)?;
+                let public = p256::PublicKey::from_sec1_bytes(public_key).map_err(|_| Error::BadEcPoint)?;
+                let shared = (public.to_projective() * *secret.to_nonzero_scalar()).to_affine();
+                let shared = shared.to_encoded_point(false);
+                let x = shared.x().ok_or(Error::BadCrypto)?;
+                let own = secret.public_key().to_encoded_point(false).as_bytes().to_vec();
+                Ok((Zeroizing::new(x.to_vec()), own))
+            }
+            Kem::P384 => {
+                let secret = p384::SecretKey::from_slice(private_key).map_err(|_| Error::BadCrypto)?;
+                let public = p384::PublicKey::from_sec1_bytes(public_key).map_err(|_| Error::BadEcPoint)?;
+                let shared = (public.to_projective() * *secret.to_nonzero_scalar()).to_affine();
+                let shared = shared.to_encoded_point(false);
+                let x = shared.x().ok_or(Error::BadCrypto)?;
+                let own = secret.public_key().to_encoded_point(false).as_bytes().to_vec();
+                Ok((Zeroizing::new(x.to_vec()), own))
+            }
+            Kem::P521 => {
+                let secret = p521::SecretKey::from_slice(private_key).map_err(|_| Error::BadCrypto)?;
+                let public = p521::PublicKey::from_sec1_bytes(public_key).map_err(|_| Error::BadEcPoint)?;
+                let shared = (public.to_projective() * *secret.to_nonzero_scalar()).to_affine();
+                let shared = shared.to_encoded_point(false);
+                let x = shared.x().ok_or(Error::BadCrypto)?;
+                let own = secret.public_key().to_encoded_point(false).as_bytes().to_vec();
+                Ok((Zeroizing::new(x.to_vec()), own))
+            }
+        }
+    }
+
+    /// A random ephemeral private key
+    fn ephemeral_key(&self, mut rng: &mut dyn CryptoRngCore) -> Zeroizing<Vec<u8>> {
+        match self {
+            Kem::X25519 => {
+                let mut private_key = Zeroizing::new(vec![0u8; 32]);
+                rng.fill_bytes(&mut private_key);
+                private_key
+            }
+            Kem::P256 => Zeroizing::new(p256::SecretKey::random(&mut rng).to_bytes().to_vec()),
+            Kem::P384 => Zeroizing::new(p384::SecretKey::random(&mut rng).to_bytes().to_vec()),
+            Kem::P521 => Zeroizing::new(p521::SecretKey::random(&mut rng).to_bytes().to_vec()),
+        }
+    }
+}
+
+/// LabeledExtract of RFC 9180
+fn labeled_extract(kdf: Kdf, suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> Zeroizing<Vec<u8>> {
+    kdf.hmac(salt, &[b"HPKE-v1", suite_id, label, ikm])
+}
+
+/// LabeledExpand of RFC 9180, for lengths of at most one hash
+fn labeled_expand(
+    kdf: Kdf,
+    suite_id: &[u8],
+    prk: &[u8],
+    label: &[u8],
+    info: &[u8],
+    length: usize,
+) -> Zeroizing<Vec<u8>> {
+    let length_bytes = (length as u16).to_be_bytes();
+    let mut okm = kdf.hmac(prk, &[&length_bytes, b"HPKE-v1", suite_id, label, info, &[0x01]]);
+    okm.truncate(length);
+    okm
+}
+
+/// The KEM shared secret of a Diffie-Hellman shared secret, and the
+/// encapsulated and recipient public keys
+fn extract_and_expand(kem: Kem, dh: &[u8], enc: &[u8], public_key: &[u8]) -> Zeroizing<Vec<u8>> {
+    let kdf = kem.kdf();
+    let suite_id = [&b"KEM"[..], &kem.id().to_be_bytes()].concat();
+    let eae_prk = labeled_extract(kdf, &suite_id, b"", b"eae_prk", dh);
+    let kem_context = [enc, public_key].concat();
+    labeled_expand(kdf, &suite_id, &eae_prk, b"shared_secret", &kem_context, kem.secret_size())
+}
+
+/// The AEAD key and nonce of the base mode key schedule
+fn key_schedule(kem: Kem, shared_secret: &[u8], info: &[u8]) -> (Zeroizing<Vec<u8>>, Zeroizing<Vec<u8>>) {
+    let (kdf, aead) = (kem.kdf(), kem.aead());
+    let suite_id = [
+        &b"HPKE"[..],
+        &kem.id().to_be_bytes(),
+        &kdf.id().to_be_bytes(),
+        &aead.id().to_be_bytes(),
+    ]
+    .concat();
+    let psk_id_hash = labeled_extract(kdf, &suite_id, b"", b"psk_id_hash", b"");
+    let info_hash = labeled_extract(kdf, &suite_id, b"", b"info_hash", info);
+    let context = [&[MODE_BASE][..], &psk_id_hash, &info_hash].concat();
+    let secret = labeled_extract(kdf, &suite_id, shared_secret, b"secret", b"");
+    (
+        labeled_expand(kdf, &suite_id, &secret, b"key", &context, aead.key_size()),
+        labeled_expand(kdf, &suite_id, &secret, b"base_nonce", &context, NONCE_SIZE),
+    )
+}
+
+/// Encrypt the plaintext to the serialized public key of the recipient.
+/// The encapsulated key and the ciphertext are returned.
+pub fn seal(
+    kem: Kem,
+    public_key: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+    rng: &mut dyn CryptoRngCore,
+) -> Result<(Vec<u8>, Vec<u8>)> {
+    let ephemeral_key = kem.ephemeral_key(rng);
+    let (dh, enc) = kem.dh(&ephemeral_key, public_key)?;
+    let shared_secret = extract_and_expand(kem, &dh, &enc, public_key);
+    let (key, nonce) = key_schedule(kem, &shared_secret, info);
+    let ciphertext = kem.aead().seal(&key, &nonce, Payload { msg: plaintext, aad })?;
+    Ok((enc, ciphertext))
+}
+
+/// Decrypt the ciphertext with the private key of the recipient, and the
+/// encapsulated key.  A ciphertext that does not decrypt is returned as
+/// Error::DecryptionFailed.
+pub fn open(
+    kem: Kem,
+    private_key: &[u8],
+    enc: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>> {
+    let (dh, public_key) = kem.dh(private_key, enc)?;
+    let shared_secret = extract_and_expand(kem, &dh, enc, &public_key);
+    let (key, nonce) = key_schedule(kem, &shared_secret, info);
+    kem.aead().open(&key, &nonce, Payload { msg: ciphertext, aad })
+}
@@ -0,0 +1,61 @@
+//! Remote input over HTTPS
+//!
+//! Only available with the `http` feature.  Lets `--in` name a URL, such as
+//! an issuer's `/.well-known/jwks.json`, rather than a local file, and lets
+//! `--issuer` find an OpenID Connect provider's keys.
+use anyhow::{bail, Result};
+use log::{info, warn};
+use std::io::Read;
+use std::time::Duration;
+
+use crate::errors::Error;
+
+/// Largest response that will be read.  Key sets are small.
+const MAX_RESPONSE_LEN: u64 = 1024 * 1024;
+
+/// Time allowed for the whole request
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetch the body of an https URL.
+///
+/// Plain http is refused, since keys fetched over it cannot be trusted.
+pub fn fetch(url: &str) -> Result<Vec<u8>> {
+    if !url.starts_with("https://") {
+        bail!(Error::Fetch(format!("{}: only https URLs are supported", url)));
+    }
+    info!("Fetching {}", url);
+    let response = ureq::get(url)
+        .timeout(TIMEOUT)
+        .call()
+        .map_err(|e| Error::Fetch(e.to_string()))?;
+
+    let mut bytes = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_RESPONSE_LEN + 1)
+        .read_to_end(&mut bytes)
+        .map_err(Error::IOEReadError)?;
+    if bytes.len() as u64 > MAX_RESPONSE_LEN {
+        bail!(Error::Fetch(format!("{}: response is too large", url)));
+    }
+    Ok(bytes)
+}
+
+/// Fetch the JWKS of an OpenID Connect issuer.
+///
+/// The issuer's `/.well-known/openid-configuration` is fetched, and its
+/// `jwks_uri` is followed.
+pub fn fetch_issuer_jwks(issuer: &str) -> Result<Vec<u8>> {
+    let issuer = issuer.trim_end_matches('/');
+    let config_url = format!("{}/.well-known/openid-configuration", issuer);
+    let config: serde_json::Value = serde_json::from_slice(&fetch(&config_url)?)
+        .map_err(|e| Error::Fetch(format!("{}: {}", config_url, e)))?;
+
+    if config["issuer"].as_str().map(|s| s.trim_end_matches('/')) != Some(issuer) {
+        warn!("{} names a different issuer: {}", config_url, config["issuer"]);
+    }
+    let jwks_uri = config["jwks_uri"]
+        .as_str()
+        .ok_or_else(|| Error::Fetch(format!("{}: missing jwks_uri", config_url)))?;
+    fetch(jwks_uri)
+}
@@ -0,0 +1,330 @@
+//! JSON Web Key Set management
+//!
+//! Builds a [Jwks] from a collection of key files, and explodes a [Jwks]
+//! into a collection of key files.  A [Jwks] can also be served over HTTP on
+//! localhost, for testing token validation.
+use anyhow::{bail, Result};
+use log::{debug, info, warn};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::app_state::AppState;
+use crate::batch::BatchReport;
+use crate::discover::discover;
+use crate::document::jwk_docs::{jwk_to_key_info, key_info_as_jwk, Jwk, Jwks};
+use crate::document::spki_docs::key_info_to_spki;
+use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::key_info::{Encoding, Format, KeyType};
+
+/// List the files in a directory, sorted by name so that the output is stable
+fn dir_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(Error::ReadFileError)? {
+        let path = entry.map_err(Error::ReadFileError)?.path();
+        if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Read a key file as a public JWK, with the thumbprint as the kid.
+///
+/// Private keys are reduced to their public key.
+fn public_jwk(app_state: &AppState, path: &Path) -> Result<Jwk> {
+    let mut file_state = AppState {
+        in_file: Some(path.display().to_string()),
+        in_stream: Box::new(fs::File::open(path).map_err(Error::ReadFileError)?),
+        in_password: app_state.in_password.clone(),
+        password_provider: app_state.password_provider.clone(),
+        password_retries: app_state.password_retries,
+        key_type: Some(KeyType::Public),
+        ..Default::default()
+    };
+    let key_info = discover(&mut file_state)?;
+    let mut jwk = key_info_as_jwk(&file_state, &key_info)?;
+    jwk.kid = Some(jwk.thumbprint()?);
+    Ok(jwk)
+}
+
+/// Collect the public keys of the files into a JWKS, skipping duplicate
+/// keys.  Files that cannot be read as a key are recorded in the report.
+fn collect_jwks(app_state: &AppState, paths: &[PathBuf], report: &mut BatchReport) -> Jwks {
+    let mut jwks = Jwks::default();
+    let mut kids = HashSet::new();
+    for path in paths {
+        match public_jwk(app_state, path) {
+            Ok(jwk) => {
+                let kid = jwk.kid.clone().unwrap_or_default();
+                if kids.insert(kid.clone()) {
+                    info!("Adding {} as {}", path.display(), kid);
+                    jwks.keys.push(jwk);
+                } else {
+                    info!("Skipping {}: duplicate of {}", path.display(), kid);
+                }
+            }
+            Err(e) => report.record(&path.display().to_string(), &e),
+        }
+    }
+    jwks
+}
+
+/// Build a JWKS from every key file in the input directory.
+///
+/// Files that cannot be read as a key are skipped, and summarized at the end
+/// as a [BatchReport].  Keys are identified by
+/// their RFC 7638 thumbprint, which is also used to drop duplicates, such as
+/// a private key and its matching public key.
+pub fn build(app_state: &mut AppState) -> Result<()> {
+    let dir = app_state
+        .in_dir
+        .clone()
+        .ok_or_else(|| Error::MissingInput("directory".to_owned()))?;
+
+    let mut report = BatchReport::default();
+    let jwks = collect_jwks(app_state, &dir_files(Path::new(&dir))?, &mut report);
+    let json = jwks.to_json(app_state.pretty)?;
+    app_state.write_stream(json.as_bytes())?;
+    report.finish(&app_state.fail_on)
+}
+
+/// Add the public key of the input key file to an existing JWKS.
+///
+/// The kid is the one provided, or the RFC 7638 thumbprint.  It must not
+/// already be used in the set, and the key must not already be in the set
+/// under another kid.  If the set file doesn't exist, a new set is started.
+pub fn add(app_state: &mut AppState) -> Result<()> {
+    let set_file = app_state
+        .set_file
+        .clone()
+        .ok_or_else(|| Error::MissingInput("JWKS".to_owned()))?;
+    let mut jwks = match Path::new(&set_file).exists() {
+        true => {
+            let bytes = fs::read(&set_file).map_err(Error::ReadFileError)?;
+            let json =
+                std::str::from_utf8(&bytes).map_err(|_| Error::BadJwk("not UTF-8".to_owned()))?;
+            Jwks::from_json(json)?
+        }
+        false => {
+            info!("Starting a new JWKS for {}", set_file);
+            Jwks::default()
+        }
+    };
+
+    let key_info = discover(app_state)?;
+    let key_state = AppState {
+        alg: Some(key_info.alg),
+        key_type: Some(KeyType::Public),
+        ..Default::default()
+    };
+    let mut jwk = key_info_as_jwk(&key_state, &key_info)?;
+    let thumbprint = jwk.thumbprint()?;
+
+    for existing in &jwks.keys {
+        if existing.thumbprint().ok().as_ref() == Some(&thumbprint) {
+            bail!(Error::DuplicateKey(format!(
+                "same key as kid {}",
+                existing.kid.as_deref().unwrap_or("none")
+            )));
+        }
+    }
+    let kid = app_state.key_id.clone().unwrap_or(thumbprint);
+    if jwks
+        .keys
+        .iter()
+        .any(|existing| existing.kid.as_deref() == Some(kid.as_str()))
+    {
+        bail!(Error::DuplicateKey(format!("kid {} is already used", kid)));
+    }
+    info!("Adding key as {}", kid);
+    jwk.kid = Some(kid);
+    jwks.keys.push(jwk);
+
+    // The output is often the set file itself, so it is only opened now that
+    // the set has been read
+    let json = jwks.to_json(app_state.pretty)?;
+    match &app_state.out_file {
+        Some(out_file) => app_state.output.write_file(Path::new(out_file), json.as_bytes(), false)?,
+        None => app_state.write_stream(json.as_bytes())?,
+    }
+    Ok(())
+}
+
+/// Make a kid safe to use as a file name
+fn kid_to_file_name(kid: &str) -> String {
+    kid.chars()
+        .map(|c| match c {
+            'A'..='Z' | 'a'..='z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Write the public key of a JWK as an SPKI PEM file
+fn write_spki(app_state: &AppState, jwk: &Jwk, path: &Path) -> Result<()> {
+    let key_info = jwk_to_key_info(&jwk.to_public())?;
+    let mut file_state = AppState {
+        out_file: Some(path.display().to_string()),
+        out_stream: Box::new(app_state.output.create_file(path, false)?),
+        alg: Some(key_info.alg),
+        key_type: Some(KeyType::Public),
+        format: Some(Format::SPKI),
+        encoding: Encoding::PEM,
+        ..Default::default()
+    };
+    key_info_to_spki(&mut file_state, &key_info)
+}
+
+/// Explode a JWKS into one SPKI PEM file per key.
+///
+/// Files are named by kid, or by RFC 7638 thumbprint if the key has no kid, or
+/// the kid was already used.  Keys that have no public key, such as symmetric
+/// keys, are skipped, and summarized at the end as a [BatchReport].
+pub fn explode(app_state: &mut AppState) -> Result<()> {
+    let out_dir = app_state
+        .out_dir
+        .clone()
+        .ok_or_else(|| Error::MissingInput("output directory".to_owned()))?;
+    let out_dir = Path::new(&out_dir);
+    app_state.output.create_dir_all(out_dir)?;
+
+    let bytes = app_state.read_stream()?;
+    let json = std::str::from_utf8(&bytes).map_err(|_| Error::BadJwk("not UTF-8".to_owned()))?;
+    let jwks = Jwks::from_json(json)?;
+
+    let mut report = BatchReport::default();
+    let mut names = HashSet::new();
+    for jwk in &jwks.keys {
+        let thumbprint = match jwk.thumbprint() {
+            Ok(thumbprint) => thumbprint,
+            Err(e) => {
+                report.record(jwk.kid.as_deref().unwrap_or("key without kid"), &e);
+                continue;
+            }
+        };
+        let mut name = jwk
+            .kid
+            .as_deref()
+            .map(kid_to_file_name)
+            .unwrap_or_else(|| thumbprint.clone());
+        if !names.insert(name.clone()) {
+            warn!("Duplicate kid {}. Using thumbprint {}", name, thumbprint);
+            name = thumbprint;
+            names.insert(name.clone());
+        }
+
+        let path = out_dir.join(format!("{}.pem", name));
+        match write_spki(app_state, jwk, &path) {
+            Ok(()) => info!("Wrote {}", path.display()),
+            Err(e) => {
+                // Don't leave an empty file behind
+                let _ = fs::remove_file(&path);
+                report.record(&name, &e);
+            }
+        }
+    }
+    report.finish(&app_state.fail_on)
+}
+
+/// Port the JWKS is served on, if none is given
+pub const DEFAULT_PORT: u16 = 8080;
+
+/// Paths the JWKS is served on
+const JWKS_PATHS: [&str; 2] = ["/.well-known/jwks.json", "/jwks.json"];
+
+/// How long a client has to send its request, or to read the response.  The
+/// server answers one request at a time, so a client that sends nothing
+/// would otherwise block it.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Answer one HTTP request
+fn respond(mut stream: TcpStream, json: &str) -> Result<()> {
+    let mut request_line = String::new();
+    let mut reader = BufReader::new(stream.try_clone().map_err(Error::IOEReadError)?);
+    reader
+        .read_line(&mut request_line)
+        .map_err(Error::IOEReadError)?;
+    // Drain the headers
+    let mut line = String::new();
+    while reader.read_line(&mut line).map_err(Error::IOEReadError)? > 2 {
+        line.clear();
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (
+        parts.next().unwrap_or_default(),
+        parts.next().unwrap_or_default(),
+    );
+    debug!("{} {}", method, path);
+    let (status, body) = match (method, JWKS_PATHS.contains(&path)) {
+        ("GET", true) => ("200 OK", json),
+        ("GET", false) => ("404 Not Found", "{}"),
+        _ => ("405 Method Not Allowed", "{}"),
+    };
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    stream
+        .write_all(response.as_bytes())
+        .map_err(Error::IOEWriteError)?;
+    Ok(())
+}
+
+/// Serve the public keys of the input files as a JWKS on localhost.
+///
+/// Directories are expanded to the files they hold.  The JWKS is served on
+/// `/.well-known/jwks.json` and `/jwks.json` until the process is stopped.
+pub fn serve(app_state: &mut AppState) -> Result<()> {
+    let mut paths = Vec::new();
+    for input in &app_state.in_files {
+        let path = PathBuf::from(input);
+        match path.is_dir() {
+            true => paths.extend(dir_files(&path)?),
+            false => paths.push(path),
+        }
+    }
+    let mut report = BatchReport::default();
+    let jwks = collect_jwks(app_state, &paths, &mut report);
+    report.finish(&app_state.fail_on)?;
+    if jwks.keys.is_empty() {
+        return Err(Error::MissingInput("public key".to_owned()).into());
+    }
+    let json = jwks.to_json(false)?;
+
+    let address = format!("127.0.0.1:{}", app_state.port.unwrap_or(DEFAULT_PORT));
+    let listener = TcpListener::bind(&address).map_err(Error::IOEReadError)?;
+    let url = format!("http://{}{}", address, JWKS_PATHS[0]);
+    events::emit(
+        Event::progress(&format!("Serving {} keys on {}", jwks.keys.len(), url))
+            .with("keys", jwks.keys.len())
+            .with("url", url),
+    );
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let timeouts = stream
+                    .set_read_timeout(Some(REQUEST_TIMEOUT))
+                    .and_then(|_| stream.set_write_timeout(Some(REQUEST_TIMEOUT)));
+                if let Err(e) = timeouts {
+                    warn!("Connection failed: {}", e);
+                    continue;
+                }
+                // A client that times out fails its own request only
+                if let Err(e) = respond(stream, &json) {
+                    warn!("Request failed: {}", e);
+                }
+            }
+            Err(e) => warn!("Connection failed: {}", e),
+        }
+    }
+    Ok(())
+}
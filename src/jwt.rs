@@ -0,0 +1,165 @@
+//! JSON Web Tokens ([RFC 7519](https://www.rfc-editor.org/rfc/rfc7519))
+//!
+//! Signs a JSON payload with any private key kt can read, in the JWS compact
+//! serialization, and verifies such tokens with a key or a JWKS.
+use anyhow::{bail, Result};
+use base64ct::{Base64UrlUnpadded, Encoding as _};
+use log::{debug, info};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::fs;
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::document::jwk_docs::{jwk_to_key_info, Jwks};
+use crate::errors::Error;
+use crate::key_info::KeyInfo;
+use crate::signature::{sign as sign_message, verify as verify_message, SignatureScheme};
+
+/// The JOSE header of a signed JWT
+#[derive(Clone, Debug, Serialize)]
+pub struct JwtHeader {
+    pub alg: String,
+    pub typ: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+}
+
+/// Read the payload from the payload file, or from stdin if the key was
+/// read from a file.
+fn read_payload(app_state: &mut AppState) -> Result<Vec<u8>> {
+    match &app_state.payload_file {
+        Some(filename) => fs::read(filename).map_err(|e| Error::ReadFileError(e).into()),
+        None => {
+            let mut bytes = Vec::new();
+            std::io::Read::read_to_end(&mut std::io::stdin(), &mut bytes)
+                .map_err(Error::IOEReadError)?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Sign a JWT with the input private key.
+///
+/// The JOSE alg is derived from the key.  The payload must be JSON, and is
+/// compacted before signing.
+pub fn sign(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    let scheme = SignatureScheme::for_key(&key_info)?;
+
+    let payload: serde_json::Value = serde_json::from_slice(&read_payload(app_state)?)
+        .map_err(|e| Error::MissingInput(format!("JSON payload: {}", e)))?;
+
+    let header = JwtHeader {
+        alg: scheme.jose_alg().to_owned(),
+        typ: "JWT".to_owned(),
+        kid: app_state.key_id.clone(),
+    };
+    let signing_input = format!(
+        "{}.{}",
+        Base64UrlUnpadded::encode_string(serde_json::to_string(&header)?.as_bytes()),
+        Base64UrlUnpadded::encode_string(serde_json::to_string(&payload)?.as_bytes())
+    );
+    let signature = sign_message(&key_info, scheme, signing_input.as_bytes())?;
+
+    let token = format!(
+        "{}.{}\n",
+        signing_input,
+        Base64UrlUnpadded::encode_string(&signature)
+    );
+    app_state.write_stream(token.as_bytes())?;
+    Ok(())
+}
+
+fn b64_json(part: &str) -> Result<Value> {
+    let bytes = Base64UrlUnpadded::decode_vec(part)
+        .map_err(|_| Error::BadJwt("not base64url".to_owned()))?;
+    serde_json::from_slice(&bytes).map_err(|e| Error::BadJwt(e.to_string()).into())
+}
+
+/// Read the verification keys from the key file.
+///
+/// The file may hold a JWKS, in which case only the keys matching the token
+/// kid are returned, or a single key in any format kt can discover.
+fn verification_keys(app_state: &AppState, kid: Option<&str>) -> Result<Vec<KeyInfo>> {
+    let key_file = app_state
+        .key_file
+        .clone()
+        .ok_or_else(|| Error::MissingInput("key".to_owned()))?;
+    let bytes = fs::read(&key_file).map_err(Error::ReadFileError)?;
+
+    if let Ok(jwks) = std::str::from_utf8(&bytes)
+        .map_err(anyhow::Error::from)
+        .and_then(Jwks::from_json)
+    {
+        let keys: Vec<KeyInfo> = jwks
+            .keys
+            .iter()
+            .filter(|jwk| kid.is_none() || jwk.kid.as_deref() == kid)
+            .filter_map(|jwk| jwk_to_key_info(jwk).ok())
+            .collect();
+        if keys.is_empty() {
+            bail!(Error::MissingInput(format!(
+                "JWKS key with kid {}",
+                kid.unwrap_or("any")
+            )));
+        }
+        return Ok(keys);
+    }
+
+    let mut key_state = AppState {
+        in_file: Some(key_file),
+        in_stream: Box::new(std::io::Cursor::new(bytes)),
+        in_password: app_state.in_password.clone(),
+        password_provider: app_state.password_provider.clone(),
+        password_retries: app_state.password_retries,
+        ..Default::default()
+    };
+    Ok(vec![discover(&mut key_state)?])
+}
+
+/// Verify a JWT with a public key, or a JWKS.
+///
+/// The verification algorithm is taken from the token header, and must suit
+/// the discovered key.  The header and claims are written once the signature
+/// is verified.  Any failure is returned as an error.
+pub fn verify(app_state: &mut AppState) -> Result<()> {
+    let bytes = app_state.read_stream()?;
+    let token = std::str::from_utf8(&bytes)
+        .map_err(|_| Error::BadJwt("not UTF-8".to_owned()))?
+        .trim();
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        bail!(Error::BadJwt("not a compact serialization".to_owned()));
+    }
+    let header = b64_json(parts[0])?;
+    let claims = b64_json(parts[1])?;
+    let signature = Base64UrlUnpadded::decode_vec(parts[2])
+        .map_err(|_| Error::BadJwt("not base64url".to_owned()))?;
+
+    let alg = header["alg"]
+        .as_str()
+        .ok_or_else(|| Error::BadJwt("missing alg".to_owned()))?;
+    let scheme = SignatureScheme::from_jose_alg(alg)?;
+    let signing_input = format!("{}.{}", parts[0], parts[1]);
+
+    let keys = verification_keys(app_state, header["kid"].as_str())?;
+    let mut verified = false;
+    for key_info in keys.iter().filter(|key_info| scheme.accepts_key(key_info)) {
+        match verify_message(key_info, scheme, signing_input.as_bytes(), &signature) {
+            Ok(()) => {
+                verified = true;
+                break;
+            }
+            Err(e) => debug!("Key did not verify: {}", e),
+        }
+    }
+    if !verified {
+        bail!(Error::BadSignature);
+    }
+    info!("Verified {} signature", alg);
+
+    let output = serde_json::to_string_pretty(&json!({ "header": header, "claims": claims }))?;
+    app_state.write_stream(format!("{}\n", output).as_bytes())?;
+    Ok(())
+}
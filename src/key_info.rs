@@ -17,10 +17,15 @@ use pkcs8::der::{Any, Decodable};
 use pkcs8::{AlgorithmIdentifier, ObjectIdentifier};
 use zeroize::Zeroizing;
 
-use crate::alg_id::alg_params;
+use crate::alg_id::{alg_params, signature_hash, signature_key_alg, GostParams, RsaPssParams};
+use crate::document::composite_docs::{components_to_str, is_composite};
+use crate::document::dh_docs::dh_generator;
+use crate::document::ethereum_docs::ethereum_address;
+use crate::document::pkcs1_docs::rsa_prime_count;
 use crate::errors::Error;
 use crate::oids;
 use crate::oids::oid_to_str;
+use crate::pbe::PbeInfo;
 
 /// Supported key algorithms
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -35,6 +40,27 @@ pub enum Alg {
     EdDsa448,
     EdDsa25519Ph,
     EdDsa448Ph,
+    /// DSA, read so that old keys can be moved to PKCS8.  kt does not sign
+    /// with DSA keys
+    Dsa,
+    /// PKCS#3 Diffie-Hellman.  kt does not agree keys with DH keys
+    Dh,
+    /// GOST R 34.10-2012 with a 256 bit key.  Read so that keys can be
+    /// identified, but not converted
+    Gost256,
+    /// GOST R 34.10-2012 with a 512 bit key
+    Gost512,
+    /// ML-DSA of FIPS 204, at security category 2.  Keys are read and
+    /// written, but not used to sign
+    MlDsa44,
+    /// ML-DSA at security category 3
+    MlDsa65,
+    /// ML-DSA at security category 5
+    MlDsa87,
+    /// An ML-DSA key paired with a traditional key, of the draft composite
+    /// signatures.  The OID names the pair
+    Composite,
+    Symmetric,
 }
 
 impl Alg {
@@ -45,14 +71,40 @@ impl Alg {
             "ECDSA",
             "X25519",
             "X448",
+            "ED25519",
+            "EDDSA25519",
+            "ED_DSA25519",
+            "ED448",
             "EDDSA448",
             "ED_DSA448",
             "EDDSA448PH",
             "ED_DSA448_PH",
             "EDDSA25519PH",
             "ED_DSA25519_PH",
+            "DSA",
+            "DH",
+            "GOST2012_256",
+            "GOST2012_512",
+            "ML-DSA-44",
+            "ML-DSA-65",
+            "ML-DSA-87",
+            "COMPOSITE",
+            "SYMMETRIC",
         ]
     }
+
+    /// The length in bits of the keys of the algorithm, if they are all of
+    /// the same length, as the keys of the OKP algorithms are
+    pub fn key_length(&self) -> Option<u32> {
+        match self {
+            Alg::X25519 | Alg::EdDsa25519 | Alg::EdDsa25519Ph => Some(256),
+            Alg::X448 => Some(448),
+            Alg::EdDsa448 | Alg::EdDsa448Ph => Some(456),
+            Alg::Gost256 => Some(256),
+            Alg::Gost512 => Some(512),
+            _ => None,
+        }
+    }
 }
 
 impl TryFrom<&ObjectIdentifier> for Alg {
@@ -68,6 +120,14 @@ impl TryFrom<&ObjectIdentifier> for Alg {
             oids::ED_DSA448 => Ok(Self::EdDsa448),
             oids::ED_DSA25519_PH => Ok(Self::EdDsa25519Ph),
             oids::ED_DSA448_PH => Ok(Self::EdDsa448Ph),
+            oids::DSA => Ok(Self::Dsa),
+            oids::DH => Ok(Self::Dh),
+            oids::GOST2012_256 => Ok(Self::Gost256),
+            oids::GOST2012_512 => Ok(Self::Gost512),
+            oids::ML_DSA_44 | oids::ML_DSA_44_IPD => Ok(Self::MlDsa44),
+            oids::ML_DSA_65 | oids::ML_DSA_65_IPD => Ok(Self::MlDsa65),
+            oids::ML_DSA_87 | oids::ML_DSA_87_IPD => Ok(Self::MlDsa87),
+            oid if is_composite(&oid) => Ok(Self::Composite),
             _ => Err(Error::UnknownAlg.into()),
         }
     }
@@ -82,10 +142,19 @@ impl FromStr for Alg {
             "ECDSA" => Ok(Alg::Ecdsa),
             "X25519" => Ok(Alg::X25519),
             "X448" => Ok(Alg::X448),
-            "EDDSA448" | "ED_DSA448" => Ok(Alg::EdDsa448),
-            "EDDSA25519" | "ED_DSA25519" => Ok(Alg::EdDsa25519),
+            "ED448" | "EDDSA448" | "ED_DSA448" => Ok(Alg::EdDsa448),
+            "ED25519" | "EDDSA25519" | "ED_DSA25519" => Ok(Alg::EdDsa25519),
             "EDDSA448PH" | "ED_DSA448_PH" => Ok(Alg::EdDsa448Ph),
             "EDDSA25519PH" | "ED_DSA25519_PH" => Ok(Alg::EdDsa25519Ph),
+            "DSA" => Ok(Alg::Dsa),
+            "DH" => Ok(Alg::Dh),
+            "GOST2012_256" => Ok(Alg::Gost256),
+            "GOST2012_512" => Ok(Alg::Gost512),
+            "ML-DSA-44" | "ML_DSA_44" | "MLDSA44" => Ok(Alg::MlDsa44),
+            "ML-DSA-65" | "ML_DSA_65" | "MLDSA65" => Ok(Alg::MlDsa65),
+            "ML-DSA-87" | "ML_DSA_87" | "MLDSA87" => Ok(Alg::MlDsa87),
+            "COMPOSITE" => Ok(Alg::Composite),
+            "SYMMETRIC" => Ok(Alg::Symmetric),
             _ => Err(Error::UnknownAlg.into()),
         }
     }
@@ -104,6 +173,128 @@ impl fmt::Display for Alg {
             Alg::EdDsa448 => "id-EdDSA448",
             Alg::EdDsa25519Ph => "id-EdDSA25519-ph",
             Alg::EdDsa448Ph => "id-EdDSA448-ph",
+            Alg::Dsa => "id-dsa",
+            Alg::Dh => "dhKeyAgreement",
+            Alg::Gost256 => "id-tc26-gost3410-12-256",
+            Alg::Gost512 => "id-tc26-gost3410-12-512",
+            Alg::MlDsa44 => "id-ml-dsa-44",
+            Alg::MlDsa65 => "id-ml-dsa-65",
+            Alg::MlDsa87 => "id-ml-dsa-87",
+            Alg::Composite => "composite",
+            Alg::Symmetric => "symmetric",
+        };
+
+        write!(f, "{}", txt)
+    }
+}
+
+/// Supported named elliptic curves
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Curve {
+    P256,
+    P384,
+    P521,
+    /// The Koblitz curve of Ethereum and Bitcoin keys.  Keys sign with ECDSA
+    /// and SHA-256, ES256K in JOSE
+    Secp256k1,
+    /// The SM2 curve of Chinese standard keys, sm2p256v1.  Keys are read and
+    /// written, but not used to sign
+    Sm2,
+}
+
+impl Curve {
+    pub fn all() -> Vec<&'static str> {
+        vec![
+            "P-256",
+            "P-384",
+            "P-521",
+            "PRIME256V1",
+            "SECP256R1",
+            "SECP384R1",
+            "SECP521R1",
+            "SECP256K1",
+            "SM2",
+        ]
+    }
+
+    /// The named curve ObjectIdentifier
+    pub fn oid(&self) -> ObjectIdentifier {
+        match self {
+            Curve::P256 => oids::PRIME_256_V1,
+            Curve::P384 => oids::SECP384R1,
+            Curve::P521 => oids::SECP521R1,
+            Curve::Secp256k1 => oids::SECP256K1,
+            Curve::Sm2 => oids::SM2,
+        }
+    }
+
+    /// The JWK (RFC 7518) curve name
+    pub fn jwk_crv(&self) -> &'static str {
+        match self {
+            Curve::P256 => "P-256",
+            Curve::P384 => "P-384",
+            Curve::P521 => "P-521",
+            Curve::Secp256k1 => "secp256k1",
+            Curve::Sm2 => "SM2",
+        }
+    }
+
+    /// Size in bytes of a field element, such as each coordinate of a point
+    pub fn field_size(&self) -> usize {
+        match self {
+            Curve::P256 => 32,
+            Curve::P384 => 48,
+            Curve::P521 => 66,
+            Curve::Secp256k1 | Curve::Sm2 => 32,
+        }
+    }
+
+    /// Size in bits of the order of the curve, the length of its keys
+    pub fn key_length(&self) -> u32 {
+        match self {
+            Curve::P256 | Curve::Secp256k1 | Curve::Sm2 => 256,
+            Curve::P384 => 384,
+            Curve::P521 => 521,
+        }
+    }
+}
+
+impl TryFrom<&ObjectIdentifier> for Curve {
+    type Error = anyhow::Error;
+    fn try_from(oid: &ObjectIdentifier) -> Result<Curve> {
+        match *oid {
+            oids::PRIME_256_V1 => Ok(Self::P256),
+            oids::SECP384R1 => Ok(Self::P384),
+            oids::SECP521R1 => Ok(Self::P521),
+            oids::SECP256K1 => Ok(Self::Secp256k1),
+            oids::SM2 => Ok(Self::Sm2),
+            _ => Err(Error::UnknownCurve.into()),
+        }
+    }
+}
+
+impl FromStr for Curve {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Curve> {
+        match s.to_uppercase().as_str() {
+            "P-256" | "PRIME256V1" | "SECP256R1" => Ok(Curve::P256),
+            "P-384" | "SECP384R1" => Ok(Curve::P384),
+            "P-521" | "SECP521R1" => Ok(Curve::P521),
+            "SECP256K1" => Ok(Curve::Secp256k1),
+            "SM2" | "SM2P256V1" => Ok(Curve::Sm2),
+            _ => Err(Error::UnknownCurve.into()),
+        }
+    }
+}
+
+impl fmt::Display for Curve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let txt = match self {
+            Curve::P256 => "prime256v1",
+            Curve::P384 => "secp384r1",
+            Curve::P521 => "secp521r1",
+            Curve::Secp256k1 => "secp256k1",
+            Curve::Sm2 => "SM2",
         };
 
         write!(f, "{}", txt)
@@ -117,11 +308,14 @@ pub enum KeyType {
     Public,
     Private,
     KeyPair,
+    Secret,
+    /// Domain parameters with no key, such as PKCS#3 DH parameters
+    Parameters,
 }
 
 impl KeyType {
     pub fn all() -> Vec<&'static str> {
-        vec!["PUBLIC", "PRIVATE", "KEYPAIR"]
+        vec!["PUBLIC", "PRIVATE", "KEYPAIR", "SECRET"]
     }
 }
 
@@ -133,6 +327,7 @@ impl FromStr for KeyType {
             "PUBLIC" => Ok(KeyType::Public),
             "PRIVATE" => Ok(KeyType::Private),
             "KEYPAIR" => Ok(KeyType::KeyPair),
+            "SECRET" => Ok(KeyType::Secret),
             _ => Err(Error::UnknownKeyType.into()),
         }
     }
@@ -146,6 +341,37 @@ pub enum Format {
     PKCS8,
     SPKI,
     SEC1,
+    JWK,
+    /// OpenSSH openssh-key-v1 private key, or `authorized_keys` public key
+    OPENSSH,
+    /// PuTTY private key, version 2 or 3
+    PPK,
+    /// X.509 certificate, read for its public key.  Input only
+    X509,
+    /// PKCS#12 keystore, read for its private key or a certificate.  Input
+    /// only
+    PKCS12,
+    /// PKCS#7 certificate bundle, read for the public key of a certificate.
+    /// Input only
+    PKCS7,
+    /// PKCS#10 certificate signing request, read for its public key.  Input
+    /// only
+    PKCS10,
+    /// OpenPGP transferable public or secret key, binary or ASCII armored.
+    /// Input only
+    OPENPGP,
+    /// Google Tink cleartext keyset
+    TINK,
+    /// Ethereum V3 keystore of a secp256k1 private key.  Input only
+    ETHEREUM,
+    /// Windows CNG BCRYPT_RSAKEY_BLOB or BCRYPT_ECCKEY_BLOB
+    CNG,
+    /// XML Signature RSAKeyValue or ECKeyValue
+    XML,
+    /// OpenSSL traditional DSA private key.  Input only
+    DSA,
+    /// PKCS#3 DH parameters.  Input, and PEM or DER output, only
+    DH,
 }
 
 impl Format {
@@ -163,6 +389,20 @@ impl FromStr for Format {
             "PKCS1" => Ok(Format::PKCS1),
             "SPKI" => Ok(Format::SPKI),
             "SEC1" => Ok(Format::SEC1),
+            "JWK" => Ok(Format::JWK),
+            "OPENSSH" => Ok(Format::OPENSSH),
+            "PPK" => Ok(Format::PPK),
+            "X509" => Ok(Format::X509),
+            "PKCS12" => Ok(Format::PKCS12),
+            "PKCS7" => Ok(Format::PKCS7),
+            "PKCS10" => Ok(Format::PKCS10),
+            "OPENPGP" => Ok(Format::OPENPGP),
+            "TINK" => Ok(Format::TINK),
+            "ETHEREUM" => Ok(Format::ETHEREUM),
+            "CNG" => Ok(Format::CNG),
+            "XML" => Ok(Format::XML),
+            "DSA" => Ok(Format::DSA),
+            "DH" => Ok(Format::DH),
             _ => Ok(Format::Unknown),
         }
     }
@@ -175,11 +415,57 @@ pub enum Encoding {
     PEM,
     DER,
     JWK,
+    /// Bare key material, with no document around it: the scalar or point of
+    /// an EC key, the seed or public key of an OKP key, or a secret
+    RAW,
+    /// PEM in a JSON envelope, as `{"kty-hint":"RSA","pem":"-----BEGIN..."}`
+    #[allow(non_camel_case_types)]
+    PEM_JSON,
+    /// CBOR COSE_Key (RFC 9052).  Output only
+    COSE,
+    /// OpenSSH keys: `authorized_keys` public key lines and openssh-key-v1
+    /// private keys
+    OPENSSH,
+    /// PuTTY `.ppk` private keys
+    PPK,
+    /// Password protected PKCS12 keystore of a private key and its
+    /// certificates.  Output only
+    P12,
+    /// ASCII armored OpenPGP key, self-signed for a user ID.  Output only
+    OPENPGP,
+    /// Google Tink cleartext keyset JSON
+    TINK,
+    /// Ethereum V3 keystore JSON, encrypted with a password.  Input only
+    ETHEREUM,
+    /// Windows CNG key blob, as `BCryptExportKey` writes it
+    CNG,
+    /// XML Signature RSAKeyValue, as .NET writes it, or ECKeyValue
+    XML,
+    /// A DER document as a single line of base64
+    B64,
+    /// A DER document as a single line of unpadded base64url
+    B64URL,
+    /// A DER document as hex
+    HEX,
+    /// Raw key material, as for RAW, as hex
+    #[allow(non_camel_case_types)]
+    RAW_HEX,
+    /// A DER document as a C `uint8_t` array definition.  Output only
+    #[allow(non_camel_case_types)]
+    C_ARRAY,
+    /// A DER document as a Rust `[u8; N]` constant.  Output only
+    #[allow(non_camel_case_types)]
+    RUST_ARRAY,
 }
 
 impl Encoding {
     pub fn all() -> Vec<&'static str> {
-        vec!["PEM", "DER", "JWK"]
+        vec!["PEM", "DER", "JWK", "RAW", "PEM_JSON", "COSE", "OPENSSH", "PPK", "P12", "OPENPGP", "TINK", "CNG", "XML", "B64", "B64URL", "HEX", "RAW_HEX", "C_ARRAY", "RUST_ARRAY"]
+    }
+
+    /// Encodings of input that can not be discovered, and must be given
+    pub fn input_all() -> Vec<&'static str> {
+        vec!["RAW", "B64", "B64URL", "HEX", "RAW_HEX"]
     }
 }
 impl FromStr for Encoding {
@@ -190,6 +476,52 @@ impl FromStr for Encoding {
             "PEM" => Ok(Encoding::PEM),
             "DER" => Ok(Encoding::DER),
             "JWK" => Ok(Encoding::JWK),
+            "RAW" => Ok(Encoding::RAW),
+            "PEM_JSON" => Ok(Encoding::PEM_JSON),
+            "COSE" => Ok(Encoding::COSE),
+            "OPENSSH" => Ok(Encoding::OPENSSH),
+            "PPK" => Ok(Encoding::PPK),
+            "P12" => Ok(Encoding::P12),
+            "OPENPGP" => Ok(Encoding::OPENPGP),
+            "TINK" => Ok(Encoding::TINK),
+            "CNG" => Ok(Encoding::CNG),
+            "XML" => Ok(Encoding::XML),
+            "B64" => Ok(Encoding::B64),
+            "B64URL" => Ok(Encoding::B64URL),
+            "HEX" => Ok(Encoding::HEX),
+            "RAW_HEX" => Ok(Encoding::RAW_HEX),
+            "C_ARRAY" => Ok(Encoding::C_ARRAY),
+            "RUST_ARRAY" => Ok(Encoding::RUST_ARRAY),
+            _ => Err(Error::UnknownEncoding.into()),
+        }
+    }
+}
+
+/// Supported input forms of symmetric secret keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SecretEncoding {
+    /// The bytes of the input are the secret
+    Raw,
+    /// Hex encoded secret
+    Hex,
+    /// Base64 or base64url encoded secret, with or without padding
+    Base64,
+}
+
+impl SecretEncoding {
+    pub fn all() -> Vec<&'static str> {
+        vec!["RAW", "HEX", "BASE64"]
+    }
+}
+
+impl FromStr for SecretEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<SecretEncoding> {
+        match s.to_uppercase().as_str() {
+            "RAW" => Ok(SecretEncoding::Raw),
+            "HEX" => Ok(SecretEncoding::Hex),
+            "BASE64" => Ok(SecretEncoding::Base64),
             _ => Err(Error::UnknownEncoding.into()),
         }
     }
@@ -217,6 +549,24 @@ pub struct KeyInfo {
     /// The inner key bytes from the formatted document. Not the entire doc.  
     /// Although Zeroize is used (to zeroize on drop), security has not been verified!
     pub bytes: Option<Zeroizing<Vec<u8>>>,
+    /// Public key bytes carried alongside a private key, such as the optional
+    /// publicKey field of a PKCS8 v2 document, or the `x` member of an OKP JWK.
+    pub public_key: Option<Vec<u8>>,
+    /// The input document was password protected, such as an encrypted
+    /// PKCS8 document or a JWE
+    pub encrypted: bool,
+    /// How an encrypted PKCS8 document was encrypted, which is known even
+    /// without the password
+    pub encryption: Option<PbeInfo>,
+    /// For certificates and CSRs, the signature algorithm OID.  This is
+    /// separate from the key's own AlgorithmIdentifier.
+    pub signature_oid: Option<ObjectIdentifier>,
+    /// For certificates and CSRs, the signature algorithm parameters, such
+    /// as RSASSA-PSS-params.
+    pub signature_params: Option<Vec<u8>>,
+    /// Comment of an OpenSSH or PuTTY key, such as user@host, or the friendly
+    /// name of a PKCS12 bag
+    pub comment: Option<String>,
 }
 
 impl KeyInfo {
@@ -230,6 +580,12 @@ impl KeyInfo {
             oid: None,
             params: None,
             bytes: None,
+            public_key: None,
+            encrypted: false,
+            encryption: None,
+            signature_oid: None,
+            signature_params: None,
+            comment: None,
         }
     }
 
@@ -255,6 +611,33 @@ impl KeyInfo {
         self
     }
 
+    /// Mutable variant to set whether the input was encrypted
+    pub fn set_encrypted(&mut self, encrypted: bool) -> &mut Self {
+        self.encrypted = encrypted;
+        self
+    }
+
+    /// Chainable variant to set whether the input was encrypted
+    pub fn with_encrypted(mut self, encrypted: bool) -> Self {
+        self.set_encrypted(encrypted);
+        self
+    }
+
+    /// Mutable variant to set how the input was encrypted
+    pub fn set_encryption(&mut self, encryption: PbeInfo) -> &mut Self {
+        self.encrypted = true;
+        self.encryption = Some(encryption);
+        self
+    }
+
+    /// Chainable variant to set how the input was encrypted
+    pub fn with_encryption(mut self, encryption: Option<PbeInfo>) -> Self {
+        if let Some(encryption) = encryption {
+            self.set_encryption(encryption);
+        }
+        self
+    }
+
     /// Mutable variant to set the key_type
     pub fn set_key_type(&mut self, key_type: KeyType) -> &mut Self {
         self.key_type = key_type;
@@ -284,6 +667,9 @@ impl KeyInfo {
     /// Mutable variant to set the alg
     pub fn set_alg(&mut self, alg: Alg) -> &mut Self {
         self.alg = alg;
+        if let Some(key_length) = alg.key_length() {
+            self.key_length = Some(key_length);
+        }
         self
     }
 
@@ -305,6 +691,18 @@ impl KeyInfo {
         self
     }
 
+    /// Mutable variant to set the public key bytes of a private key
+    pub fn set_public_key(&mut self, public_key: &[u8]) -> &mut Self {
+        self.public_key = Some(public_key.to_vec());
+        self
+    }
+
+    /// Chainable variant to set the public key bytes of a private key
+    pub fn with_public_key(mut self, public_key: &[u8]) -> Self {
+        self.set_public_key(public_key);
+        self
+    }
+
     // Mutable variant to set the oid from PKCS8 and SPKI formats
     pub fn set_oid(&mut self, oid: &ObjectIdentifier) -> &mut Self {
         self.oid = Some(*oid);
@@ -339,6 +737,46 @@ impl KeyInfo {
         self.params = alg_params(alg_id);
         self
     }
+
+    /// Mutable variant to set the signature algorithm of a certificate or CSR
+    pub fn set_signature_alg_id(&mut self, alg_id: &AlgorithmIdentifier) -> &mut Self {
+        self.signature_oid = Some(alg_id.oid);
+        self.signature_params = alg_params(alg_id);
+        self
+    }
+
+    /// Chainable variant to set the signature algorithm of a certificate or CSR
+    pub fn with_signature_alg_id(mut self, alg_id: &AlgorithmIdentifier) -> Self {
+        self.set_signature_alg_id(alg_id);
+        self
+    }
+
+    /// Mutable variant to set the comment of an OpenSSH or PuTTY key
+    pub fn set_comment(&mut self, comment: &str) -> &mut Self {
+        self.comment = Some(comment.to_owned());
+        self
+    }
+
+    /// Chainable variant to set the comment.  An empty comment is no comment.
+    pub fn with_comment(mut self, comment: &str) -> Self {
+        if !comment.is_empty() {
+            self.set_comment(comment);
+        }
+        self
+    }
+
+    /// The named curve of an elliptic curve key.
+    ///
+    /// The curve is taken from the AlgorithmIdentifier parameters, or from
+    /// the oid for SEC1 documents, which carry the curve directly.
+    pub fn curve(&self) -> Option<Curve> {
+        if let Some(params) = &self.params {
+            if let Ok(oid) = Any::from_der(params).and_then(|any| any.oid()) {
+                return Curve::try_from(&oid).ok();
+            }
+        }
+        self.oid.as_ref().and_then(|oid| Curve::try_from(oid).ok())
+    }
 }
 
 impl Default for KeyInfo {
@@ -356,6 +794,9 @@ impl fmt::Debug for KeyInfo {
             .field("key_length", &self.key_length)
             .field("alg", &self.alg)
             .field("oid", &self.oid)
+            .field("encrypted", &self.encrypted)
+            .field("encryption", &self.encryption)
+            .field("signature_oid", &self.signature_oid)
             .finish()
     }
 }
@@ -372,18 +813,72 @@ impl fmt::Display for KeyInfo {
             Some(key_length) => format!("Key Length: {:?}\n", key_length),
             None => "".to_owned(),
         };
+        // The NIST name of a curve, with the SEC name openssl gives it
+        let curve = match self.curve() {
+            Some(Curve::Secp256k1) => "Curve: secp256k1\n".to_owned(),
+            Some(Curve::Sm2) => "Curve: SM2\n".to_owned(),
+            Some(curve) => format!("Curve: {} ({})\n", curve.jwk_crv(), curve),
+            None => "".to_owned(),
+        };
+        let address = match self.curve() {
+            Some(Curve::Secp256k1) => match ethereum_address(self) {
+                Ok(address) => format!("Ethereum Address: {}\n", address),
+                Err(_) => "".to_owned(),
+            },
+            _ => "".to_owned(),
+        };
+        let generator = match (self.alg, &self.params) {
+            (Alg::Dh, Some(params)) => match dh_generator(params) {
+                Some(generator) => format!("Generator: {}\n", generator),
+                None => "".to_owned(),
+            },
+            _ => "".to_owned(),
+        };
+        let primes = match (self.alg, self.key_type, &self.bytes) {
+            (Alg::Rsa | Alg::RsaSsaPss, KeyType::Private, Some(bytes)) => match rsa_prime_count(bytes) {
+                Some(primes) => format!("Primes: {}\n", primes),
+                None => "".to_owned(),
+            },
+            _ => "".to_owned(),
+        };
+        let components = match (self.alg, &self.oid) {
+            (Alg::Composite, Some(oid)) => match components_to_str(oid) {
+                Some(components) => format!("Components: {}\n", components),
+                None => "".to_owned(),
+            },
+            _ => "".to_owned(),
+        };
+        let comment = match &self.comment {
+            Some(comment) => format!("Comment: {}\n", comment),
+            None => "".to_owned(),
+        };
         let alg_id = alg_id_to_str(self.oid, self.params.as_ref());
+        let signature_alg = signature_alg_to_str(self.signature_oid, self.signature_params.as_ref());
+        let encryption = match &self.encryption {
+            Some(encryption) => encryption.to_string(),
+            None => "".to_owned(),
+        };
 
         write!(
             f,
-            "{}{}{}{}{}{}",
-            &key_type, &encoding, &format, &alg, &key_length, &alg_id
+            "{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
+            &key_type, &encoding, &format, &alg, &key_length, &primes, &curve, &address, &generator, &components, &comment, &alg_id, &signature_alg, &encryption
         )
     }
 }
 
 fn alg_id_to_str(oid: Option<ObjectIdentifier>, params: Option<&Vec<u8>>) -> String {
     match oid {
+        Some(oid @ (oids::GOST2012_256 | oids::GOST2012_512)) => format!(
+            "Algorithm Identifier\n\tObject Identifier: {}\n{}",
+            oid_to_str(&oid),
+            gost_params_to_str(params)
+        ),
+        Some(oid @ oids::RSASSA_PSS) => format!(
+            "Algorithm Identifier\n\tObject Identifier: {}\n{}",
+            oid_to_str(&oid),
+            rsa_pss_params_to_str(params)
+        ),
         Some(oid) => format!(
             "Algorithm Identifier\n\tObject Identifier: {}{}\n",
             oid_to_str(&oid),
@@ -393,6 +888,50 @@ fn alg_id_to_str(oid: Option<ObjectIdentifier>, params: Option<&Vec<u8>>) -> Str
     }
 }
 
+/// The signature algorithm is shown separately from the key algorithm, along
+/// with the hash and key algorithm it implies, so that mismatches are visible.
+fn signature_alg_to_str(oid: Option<ObjectIdentifier>, params: Option<&Vec<u8>>) -> String {
+    let oid = match oid {
+        Some(oid) => oid,
+        None => return "".to_owned(),
+    };
+    let mut txt = format!("Signature Algorithm\n\tObject Identifier: {}\n", oid_to_str(&oid));
+    if let Some(alg) = signature_key_alg(&oid) {
+        txt.push_str(&format!("\tKey Algorithm: {}\n", alg));
+    }
+    if let Some(hash) = signature_hash(&oid) {
+        txt.push_str(&format!("\tHash Algorithm: {}\n", oid_to_str(&hash)));
+    }
+    if oid == oids::RSASSA_PSS {
+        match params.map(|bytes| RsaPssParams::from_der(bytes)) {
+            Some(Ok(pss)) => txt.push_str(&format!("\tParameters: RSASSA-PSS\n{}", pss)),
+            Some(Err(_)) => txt.push_str("\tParameters: Unknown\n"),
+            None => txt.push_str("\tParameters: Missing\n"),
+        }
+    }
+    txt
+}
+
+/// The RSASSA-PSS-params of an RSASSA-PSS key restrict the key to them.  A key
+/// without them may be used with any.
+fn rsa_pss_params_to_str(params: Option<&Vec<u8>>) -> String {
+    match params.map(|bytes| RsaPssParams::from_der(bytes)) {
+        Some(Ok(pss)) => format!("\tParameters: RSASSA-PSS\n{}", pss),
+        Some(Err(_)) => "\tParameters: Unknown\n".to_owned(),
+        None => "\tParameters: None, the key is not restricted\n".to_owned(),
+    }
+}
+
+/// The parameter set of a GOST key is a SEQUENCE, rather than the OID of a
+/// named curve
+fn gost_params_to_str(params: Option<&Vec<u8>>) -> String {
+    match params.map(|bytes| GostParams::from_der(bytes)) {
+        Some(Ok(gost)) => format!("\tParameters: GOST R 34.10-2012\n{}", gost),
+        Some(Err(_)) => "\tParameters: Unknown\n".to_owned(),
+        None => "\tParameters: Missing\n".to_owned(),
+    }
+}
+
 fn option_any_to_str(opt: Option<&Vec<u8>>) -> String {
     let no_val = "".to_owned();
     if let Some(bytes) = opt {
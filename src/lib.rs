@@ -1,11 +1,49 @@
 #![doc = include_str!("../README.md")]
 pub mod alg_id;
+pub mod asn1;
 pub mod app_state;
+pub mod batch;
+pub mod check;
 pub mod cli;
+pub mod compare;
+pub mod consumer;
 pub mod conversion;
+pub mod csr;
+pub mod debug_dump;
+pub mod detached;
 pub mod discover;
+pub mod doctor;
 pub mod document;
+pub mod ed448;
+pub mod encrypt;
 pub mod errors;
+pub mod events;
+pub mod explain;
+pub mod export;
+pub mod fingerprint;
+pub mod generate;
+pub mod hex_text;
+pub mod hpke;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod jwks;
+pub mod jwt;
 pub mod key_info;
+pub mod matching;
+pub mod migrate;
+pub mod ml_dsa;
+pub mod normalize;
 pub mod oids;
+pub mod output;
+pub mod passwd;
+pub mod password;
+pub mod pbe;
+pub mod pem;
+pub mod secp256k1;
+pub mod selfsign;
+pub mod shred;
+pub mod signature;
+pub mod sm2;
+pub mod timestamp;
+pub mod xdh;
 
@@ -11,8 +11,25 @@
 //!
 use anyhow::Result;
 use clap::{Arg, *};
+use kt::alg_id::PssHash;
+use kt::batch::FailureKind;
 use kt::cli::process;
-use kt::key_info::{Alg, Encoding, Format, KeyType};
+use kt::consumer::TargetConsumer;
+use kt::detached::SignatureFormat;
+use kt::document::composite_docs::Component;
+use kt::document::sec1_docs::PointFormat;
+use kt::document::jwk_docs::{key_ops_all, KeyUse};
+use kt::document::pkcs8_docs::Pkcs8Version;
+use kt::events::{self, EventFormat};
+use kt::export::ExportTarget;
+use kt::fingerprint::{FingerprintHash, FingerprintStyle};
+use kt::generate::RSA_BITS;
+use kt::hex_text::HexStyle;
+use kt::key_info::{Alg, Curve, Encoding, Format, KeyType, SecretEncoding};
+use kt::migrate::MigrationTarget;
+use kt::pbe::{Cipher, Kdf};
+use kt::selfsign::KeyUsage;
+use kt::timestamp::init_logger;
 
 fn main() -> Result<()> {
     // Grab info from Cargo.toml to show inhelp.
@@ -20,11 +37,47 @@ fn main() -> Result<()> {
     const VERSION: &str = env!("CARGO_PKG_VERSION");
     const DESC: &str = env!("CARGO_PKG_DESCRIPTION");
 
-    env_logger::init();
-
     let args = Command::new(NAME)
         .version(VERSION)
         .about(DESC)
+        .arg(
+            Arg::new("local-time")
+                .long("local-time")
+                .help("Write timestamps in local time, instead of UTC")
+                .global(true)
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("password-retries")
+                .long("password-retries")
+                .value_name("N")
+                .help("Times to ask again at the terminal if the input password is incorrect")
+                .global(true)
+                .default_value("3")
+                .value_parser(clap::value_parser!(u32)),
+        )
+        .arg(
+            Arg::new("umask")
+                .long("umask")
+                .value_name("MODE")
+                .help("Octal umask for the files kt creates, such as 077")
+                .global(true),
+        )
+        .arg(
+            Arg::new("owner")
+                .long("owner")
+                .value_name("USER:GROUP")
+                .help("Owner of the files kt creates.  Usually requires root")
+                .global(true),
+        )
+        .arg(
+            Arg::new("events")
+                .long("events")
+                .value_name("FORMAT")
+                .help("Write progress, warnings and the result to stderr as machine readable events")
+                .global(true)
+                .value_parser(clap::builder::PossibleValuesParser::new(EventFormat::all())),
+        )
         .subcommand(
             Command::new("show")
                 .about("Display info about the provided key")
@@ -33,14 +86,116 @@ fn main() -> Result<()> {
                         .long("in")
                         .short('i')
                         .value_name("FILE")
-                        .help("Sets the input file to use")
+                        .help("Sets the input file or https URL to use")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("issuer")
+                        .long("issuer")
+                        .value_name("URL")
+                        .help("Read the signing keys of an OpenID Connect issuer")
+                        .required(false)
+                        .conflicts_with("in"),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .help("Key ID of the key to select from a JWKS or Tink keyset, or of an OpenPGP key or subkey")
                         .required(false),
                 )
                 .arg(
                     Arg::new("inpass")
                         .long("inpass")
                         .value_name("PASSWORD")
-                        .help("password for protected input")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("curve")
+                        .long("curve")
+                        .value_name("CURVE")
+                        .help("Named curve of a bare SEC1 elliptic curve point, a RAW EC key, or an ECPrivateKey without one, input")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Curve::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("secret")
+                        .long("secret")
+                        .value_name("ENCODING")
+                        .help("Read the input as a symmetric secret key")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            SecretEncoding::all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("in-format")
+                        .long("in-format")
+                        .value_name("ENCODING")
+                        .help("Encoding of the input: RAW or RAW_HEX key bytes, of the algorithm of --alg and the curve of --curve, or B64, B64URL or HEX DER")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            Encoding::input_all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("alg")
+                        .long("alg")
+                        .short('a')
+                        .help("Key algorithm of RAW input")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Alg::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("cert-index")
+                        .long("cert-index")
+                        .value_name("N")
+                        .help("Certificate of a chain to read the key of, from 0.  Defaults to the leaf, 0")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("friendly-name")
+                        .long("friendly-name")
+                        .value_name("NAME")
+                        .help("Friendly name of the key, or certificate, to read of a PKCS12 keystore")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("p12-cert")
+                        .long("p12-cert")
+                        .help("Read a certificate of a PKCS12 keystore, rather than its private key")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("debug-dump")
+                        .long("debug-dump")
+                        .value_name("DIR")
+                        .help("Write redacted diagnostics of the input, for bug reports")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("lenient")
+                        .long("lenient")
+                        .help("Repair line endings, whitespace, wrapping and byte order marks of PEM input, and report what was repaired")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("hexdump")
+                        .long("hexdump")
+                        .help("Show a hex dump of the input, marked where each ASN.1 element starts")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("host")
+                        .long("host")
+                        .value_name("HOST")
+                        .help("Read the input as known_hosts, and show the keys of the host")
                         .required(false),
                 ),
         )
@@ -52,16 +207,89 @@ fn main() -> Result<()> {
                         .long("in")
                         .short('i')
                         .value_name("FILE")
-                        .help("Sets the input file to use")
+                        .help("Sets the input file or https URL to use")
                         .required(false),
                 )
+                .arg(
+                    Arg::new("issuer")
+                        .long("issuer")
+                        .value_name("URL")
+                        .help("Read the signing keys of an OpenID Connect issuer")
+                        .required(false)
+                        .conflicts_with("in"),
+                )
                 .arg(
                     Arg::new("inpass")
                         .long("inpass")
                         .value_name("PASSWORD")
-                        .help("password for protected input")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("curve")
+                        .long("curve")
+                        .value_name("CURVE")
+                        .help("Named curve of a bare SEC1 elliptic curve point, a RAW EC key, or an ECPrivateKey without one, input")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Curve::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("secret")
+                        .long("secret")
+                        .value_name("ENCODING")
+                        .help("Read the input as a symmetric secret key")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            SecretEncoding::all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("in-format")
+                        .long("in-format")
+                        .value_name("ENCODING")
+                        .help("Encoding of the input: RAW or RAW_HEX key bytes, of the algorithm of --alg and the curve of --curve, or B64, B64URL or HEX DER")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            Encoding::input_all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("cert-index")
+                        .long("cert-index")
+                        .value_name("N")
+                        .help("Certificate of a chain to read the key of, from 0.  Defaults to the leaf, 0")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("friendly-name")
+                        .long("friendly-name")
+                        .value_name("NAME")
+                        .help("Friendly name of the key, or certificate, to read of a PKCS12 keystore, and of P12 output")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("p12-cert")
+                        .long("p12-cert")
+                        .help("Read a certificate of a PKCS12 keystore, rather than its private key")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("debug-dump")
+                        .long("debug-dump")
+                        .value_name("DIR")
+                        .help("Write redacted diagnostics of the input, for bug reports")
                         .required(false),
                 )
+                .arg(
+                    Arg::new("lenient")
+                        .long("lenient")
+                        .help("Repair line endings, whitespace, wrapping and byte order marks of PEM input, and report what was repaired")
+                        .action(ArgAction::SetTrue),
+                )
                 .arg(
                     Arg::new("out")
                         .long("out")
@@ -74,7 +302,7 @@ fn main() -> Result<()> {
                     Arg::new("outpass")
                         .long("outpass")
                         .value_name("PASSWORD")
-                        .help("Password protected ouput")
+                        .help("Password protected ouput.  Required for P12")
                         .required(false),
                 )
                 .arg(
@@ -87,18 +315,111 @@ fn main() -> Result<()> {
                         .default_value("PEM")
                         .ignore_case(true),
                 )
+                .arg(
+                    Arg::new("pem-label")
+                        .long("pem-label")
+                        .value_name("LABEL")
+                        .help("Label of PEM output, such as \"PRIVATE KEY\", instead of the label of the format")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("hex-style")
+                        .long("hex-style")
+                        .value_name("STYLE")
+                        .help("How HEX and RAW_HEX output is written")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(HexStyle::all()))
+                        .default_value("plain")
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("component")
+                        .long("component")
+                        .help("Component of a composite key to write, instead of the composite key")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Component::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("pss-hash")
+                        .long("pss-hash")
+                        .help("Hash of the RSASSA-PSS-params of --alg RSASSA_PSS output, also used by MGF1")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(PssHash::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("pss-salt-len")
+                        .long("pss-salt-len")
+                        .value_name("BYTES")
+                        .help("Salt length of the RSASSA-PSS-params of --alg RSASSA_PSS output.  Defaults to the hash length")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u32)),
+                )
+                .arg(
+                    Arg::new("point-format")
+                        .long("point-format")
+                        .help("How the public point of EC output is written.  JWK only holds uncompressed points")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(PointFormat::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("kdf")
+                        .long("kdf")
+                        .help("Key derivation of password protected PKCS8 and P12 output")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Kdf::all()))
+                        .default_value("pbkdf2")
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("iterations")
+                        .long("iterations")
+                        .value_name("N")
+                        .help("PBKDF2 iterations, or scrypt cost, of password protected output")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    Arg::new("salt-len")
+                        .long("salt-len")
+                        .value_name("BYTES")
+                        .help("Salt length of password protected output")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("cipher")
+                        .long("cipher")
+                        .help("Cipher of password protected PKCS8 and P12 output")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Cipher::all()))
+                        .default_value("aes256")
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("pkcs8-version")
+                        .long("pkcs8-version")
+                        .value_name("VERSION")
+                        .help("PKCS8 version of private key output.  Version 2 also holds the public key")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Pkcs8Version::all()))
+                        .default_value("1")
+                        .ignore_case(true),
+                )
                 .arg(
                     Arg::new("kid")
                         .long("kid")
                         .short('k')
-                        .help("Key ID for JWT")
+                        .help("Key ID for JWT and Tink output, or of the OpenPGP key or subkey to read")
                         .required(false),
                 )
                 .arg(
                     Arg::new("alg")
                         .long("alg")
                         .short('a')
-                        .help("Key algoritmm to output")
+                        .help("Key algoritmm to output, and of RAW input")
                         .required(false)
                         .value_parser(clap::builder::PossibleValuesParser::new(Alg::all()))
                         .ignore_case(true),
@@ -122,9 +443,1444 @@ fn main() -> Result<()> {
                         .required(false)
                         .value_parser(clap::builder::PossibleValuesParser::new(Format::all()))
                         .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("target-consumer")
+                        .long("target-consumer")
+                        .value_name("CONSUMER")
+                        .help("Software the output is for.  Warns about output it would reject")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            TargetConsumer::all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("pretty")
+                        .long("pretty")
+                        .help("Indent JSON output")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("jwk-alg")
+                        .long("jwk-alg")
+                        .value_name("ALG")
+                        .help("JOSE algorithm for the JWK alg member, such as ES256")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("use")
+                        .long("use")
+                        .value_name("USE")
+                        .help("JWK use member")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(KeyUse::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("key-ops")
+                        .long("key-ops")
+                        .value_name("OPS")
+                        .help("Comma separated JWK key_ops member, such as sign,verify")
+                        .required(false)
+                        .value_delimiter(',')
+                        .value_parser(clap::builder::PossibleValuesParser::new(key_ops_all())),
+                )
+                .arg(
+                    Arg::new("cert")
+                        .long("cert")
+                        .value_name("FILE")
+                        .help("Certificate, or PEM certificate chain, to add to JWK output as x5c, or to P12 output")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("ca")
+                        .long("ca")
+                        .value_name("FILE")
+                        .help("CA certificates to add to P12 output after --cert.  May be repeated")
+                        .required(false)
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("comment")
+                        .long("comment")
+                        .value_name("COMMENT")
+                        .help("Comment of OpenSSH and PuTTY output, such as user@host, or the user ID of OpenPGP output")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("jwks")
+                .about("Manage JSON Web Key Sets")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("build")
+                        .about("Build a JWKS from a directory of keys")
+                        .arg(
+                            Arg::new("dir")
+                                .value_name("DIR")
+                                .help("Directory of keys to collect")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("inpass")
+                                .long("inpass")
+                                .value_name("PASSWORD")
+                                .help("password for protected input: pass:, env:, file: or prompt")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("Sets the output file to use")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("fail-on")
+                                .long("fail-on")
+                                .value_name("KINDS")
+                                .help("Failures that fail the command.  Defaults to bad-password,io")
+                                .required(false)
+                                .value_delimiter(',')
+                                .value_parser(clap::builder::PossibleValuesParser::new(
+                                    FailureKind::all().into_iter().chain(["all", "none"]),
+                                ))
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("pretty")
+                                .long("pretty")
+                                .help("Indent JSON output")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("explode")
+                        .about("Write each key of a JWKS as an SPKI PEM file")
+                        .arg(
+                            Arg::new("in")
+                                .value_name("FILE")
+                                .help("JWKS file to explode.  If not provided, stdin is used")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("out-dir")
+                                .long("out-dir")
+                                .short('d')
+                                .value_name("DIR")
+                                .help("Directory to write the key files to")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("fail-on")
+                                .long("fail-on")
+                                .value_name("KINDS")
+                                .help("Failures that fail the command.  Defaults to bad-password,io")
+                                .required(false)
+                                .value_delimiter(',')
+                                .value_parser(clap::builder::PossibleValuesParser::new(
+                                    FailureKind::all().into_iter().chain(["all", "none"]),
+                                ))
+                                .ignore_case(true),
+                        ),
+                )
+                .subcommand(
+                    Command::new("add")
+                        .about("Add a key to a JWKS")
+                        .arg(
+                            Arg::new("set")
+                                .long("set")
+                                .value_name("FILE")
+                                .help("JWKS to add the key to")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("in")
+                                .long("in")
+                                .short('i')
+                                .value_name("FILE")
+                                .help("Key to add.  If not provided, stdin is used")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("inpass")
+                                .long("inpass")
+                                .value_name("PASSWORD")
+                                .help("password for protected input: pass:, env:, file: or prompt")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("kid")
+                                .long("kid")
+                                .short('k')
+                                .value_name("KID")
+                                .help("Key ID for the new key.  Defaults to its thumbprint")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("Sets the output file to use")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("pretty")
+                                .long("pretty")
+                                .help("Indent JSON output")
+                                .action(ArgAction::SetTrue),
+                        ),
+                )
+                .subcommand(
+                    Command::new("serve")
+                        .about("Serve the public keys as a JWKS on localhost")
+                        .arg(
+                            Arg::new("in")
+                                .value_name("FILE")
+                                .help("Key files, or directories of keys, to serve")
+                                .required(true)
+                                .num_args(1..),
+                        )
+                        .arg(
+                            Arg::new("inpass")
+                                .long("inpass")
+                                .value_name("PASSWORD")
+                                .help("password for protected input: pass:, env:, file: or prompt")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("port")
+                                .long("port")
+                                .short('p')
+                                .value_name("PORT")
+                                .help("Local port to listen on.  Defaults to 8080")
+                                .required(false)
+                                .value_parser(clap::value_parser!(u16)),
+                        )
+                        .arg(
+                            Arg::new("fail-on")
+                                .long("fail-on")
+                                .value_name("KINDS")
+                                .help("Failures that fail the command.  Defaults to bad-password,io")
+                                .required(false)
+                                .value_delimiter(',')
+                                .value_parser(clap::builder::PossibleValuesParser::new(
+                                    FailureKind::all().into_iter().chain(["all", "none"]),
+                                ))
+                                .ignore_case(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("jwt")
+                .about("Work with JSON Web Tokens")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("sign")
+                        .about("Sign a JSON payload as a JWT")
+                        .arg(
+                            Arg::new("in")
+                                .long("in")
+                                .short('i')
+                                .value_name("FILE")
+                                .help("Private key to sign with")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("inpass")
+                                .long("inpass")
+                                .value_name("PASSWORD")
+                                .help("password for protected input: pass:, env:, file: or prompt")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("payload")
+                                .long("payload")
+                                .short('p')
+                                .value_name("FILE")
+                                .help("JSON payload to sign.  If not provided, stdin is used")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("kid")
+                                .long("kid")
+                                .short('k')
+                                .value_name("KID")
+                                .help("Key ID to add to the JWT header")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("Sets the output file to use")
+                                .required(false),
+                        ),
+                )
+                .subcommand(
+                    Command::new("verify")
+                        .about("Verify a JWT, and display its header and claims")
+                        .arg(
+                            Arg::new("in")
+                                .long("in")
+                                .short('i')
+                                .value_name("FILE")
+                                .help("JWT to verify.  If not provided, stdin is used")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("key")
+                                .long("key")
+                                .value_name("FILE")
+                                .help("Public key or JWKS to verify with")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::new("inpass")
+                                .long("inpass")
+                                .value_name("PASSWORD")
+                                .help("password for a protected key")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("Sets the output file to use")
+                                .required(false),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("compare-impl")
+                .about("Compare the key with the output of another tool")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Sets the input file to use")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("with-command")
+                        .long("with-command")
+                        .value_name("COMMAND")
+                        .help("Command to run with the key on stdin, such as \"openssl pkey -pubout\"")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Sets the output file to use")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write the key, and certificate, in the files a server expects")
+                .arg(
+                    Arg::new("for")
+                        .long("for")
+                        .value_name("TARGET")
+                        .help("Server to export for")
+                        .required(true)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            ExportTarget::all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key to export")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("cert")
+                        .long("cert")
+                        .value_name("FILE")
+                        .help("Certificate, or PEM certificate chain, for the key")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("out-dir")
+                        .long("out-dir")
+                        .short('d')
+                        .value_name("DIR")
+                        .help("Directory to write the files to")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("name")
+                        .long("name")
+                        .short('n')
+                        .value_name("NAME")
+                        .help("Base name of the files.  Defaults to server")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("outpass")
+                        .long("outpass")
+                        .value_name("PASSWORD")
+                        .help("Keystore password, for java-keystore")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Replace a key with a new key of another algorithm")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key to replace")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("ALG")
+                        .help("Algorithm of the new key")
+                        .required(true)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            MigrationTarget::all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("File to write the new key to.  Defaults to stdout")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("outpass")
+                        .long("outpass")
+                        .value_name("PASSWORD")
+                        .help("Password for the new key.  Defaults to the input password")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .value_name("KID")
+                        .help("Key ID of a JWK new key")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("shred")
+                        .long("shred")
+                        .help("Shred the old key once the new key is written")
+                        .requires("in")
+                        .requires("out")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("passwd")
+                .about("Change or remove the password of a private key")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key to change the password of")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("Current password: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("File to write the key to, which may be the input.  Defaults to stdout")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("outpass")
+                        .long("outpass")
+                        .value_name("PASSWORD")
+                        .help("New password.  If not provided, the password is removed")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kdf")
+                        .long("kdf")
+                        .help("Key derivation of a PKCS8 key")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Kdf::all()))
+                        .default_value("pbkdf2")
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("iterations")
+                        .long("iterations")
+                        .value_name("N")
+                        .help("PBKDF2 iterations, or scrypt cost, of a PKCS8 key")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    Arg::new("salt-len")
+                        .long("salt-len")
+                        .value_name("BYTES")
+                        .help("Salt length of a PKCS8 key")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("cipher")
+                        .long("cipher")
+                        .help("Cipher of a PKCS8 key")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(Cipher::all()))
+                        .default_value("aes256")
+                        .ignore_case(true),
+                ),
+        )
+        .subcommand(
+            Command::new("shred")
+                .about("Overwrite and remove key files")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Key files to shred")
+                        .required(true)
+                        .num_args(1..),
+                )
+                .arg(
+                    Arg::new("passes")
+                        .long("passes")
+                        .value_name("N")
+                        .help("Passes of random bytes to overwrite with.  Defaults to 3")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Shred files that are not recognized as keys")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("fingerprint")
+                .about("Show the fingerprint of the provided key, as ssh-keygen -l")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Key, or authorized_keys file, to fingerprint")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .help("Key ID of the key to select from a JWKS")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("cert-index")
+                        .long("cert-index")
+                        .value_name("N")
+                        .help("Certificate of a chain to read the key of, from 0.  Defaults to the leaf, 0")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("friendly-name")
+                        .long("friendly-name")
+                        .value_name("NAME")
+                        .help("Friendly name of the key, or certificate, to read of a PKCS12 keystore")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("p12-cert")
+                        .long("p12-cert")
+                        .help("Read a certificate of a PKCS12 keystore, rather than its private key")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("hash")
+                        .long("hash")
+                        .short('E')
+                        .value_name("HASH")
+                        .help("Hash of the fingerprint.  Defaults to sha256")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            FingerprintHash::all(),
+                        ))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("style")
+                        .long("style")
+                        .value_name("STYLE")
+                        .help("Hash the SSH encoding, or the SPKI DER, of the key.  Defaults to ssh")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(
+                            FingerprintStyle::all(),
+                        ))
+                        .ignore_case(true),
+                ),
+        )
+        .subcommand(
+            Command::new("csr")
+                .about("Create a PKCS#10 certificate signing request for the provided private key")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key to sign the request with.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .help("Key ID of the key to select from a JWKS")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("subject")
+                        .long("subject")
+                        .value_name("SUBJECT")
+                        .help("Subject, such as /C=US/O=Example/CN=www.example.com")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("san")
+                        .long("san")
+                        .value_name("NAME")
+                        .help("Subject alternative name: DNS:, IP:, email: or URI:.  May be repeated")
+                        .required(false)
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Sets the output file to use")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("encoding")
+                        .long("encoding")
+                        .short('e')
+                        .value_name("ENCODING")
+                        .help("Encoding of the request.  Defaults to PEM")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(["PEM", "DER"]))
+                        .ignore_case(true),
+                ),
+        )
+        .subcommand(
+            Command::new("selfsign")
+                .about("Create a self-signed X.509 certificate for the provided private key")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key to sign the certificate with.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .help("Key ID of the key to select from a JWKS")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("subject")
+                        .long("subject")
+                        .value_name("SUBJECT")
+                        .help("Subject, and issuer, such as /O=Example/CN=www.example.com")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("san")
+                        .long("san")
+                        .value_name("NAME")
+                        .help("Subject alternative name: DNS:, IP:, email: or URI:.  May be repeated")
+                        .required(false)
+                        .action(ArgAction::Append),
+                )
+                .arg(
+                    Arg::new("days")
+                        .long("days")
+                        .value_name("DAYS")
+                        .help("Days the certificate is valid for, from now.  Defaults to 30")
+                        .required(false)
+                        .value_parser(clap::value_parser!(u32).range(1..)),
+                )
+                .arg(
+                    Arg::new("key-usage")
+                        .long("key-usage")
+                        .value_name("USAGES")
+                        .help("Comma separated key usages.  Defaults to digitalSignature, and keyEncipherment for RSA keys")
+                        .required(false)
+                        .value_delimiter(',')
+                        .value_parser(clap::builder::PossibleValuesParser::new(KeyUsage::all()))
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("ca")
+                        .long("ca")
+                        .help("Make a CA certificate, that can sign other certificates")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Sets the output file to use")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("encoding")
+                        .long("encoding")
+                        .short('e')
+                        .value_name("ENCODING")
+                        .help("Encoding of the certificate.  Defaults to PEM")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(["PEM", "DER"]))
+                        .ignore_case(true),
+                ),
+        )
+        .subcommand(
+            Command::new("pin")
+                .about("Show the SPKI pin, the base64 SHA-256 of the SubjectPublicKeyInfo, of the provided key")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Key, or certificate, to pin.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .help("Key ID of the key to select from a JWKS")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("cert-index")
+                        .long("cert-index")
+                        .value_name("N")
+                        .help("Certificate of a chain to read the key of, from 0.  Defaults to the leaf, 0")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("friendly-name")
+                        .long("friendly-name")
+                        .value_name("NAME")
+                        .help("Friendly name of the key, or certificate, to read of a PKCS12 keystore")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("p12-cert")
+                        .long("p12-cert")
+                        .help("Read a certificate of a PKCS12 keystore, rather than its private key")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("explain")
+                .about("Explain the document of the provided key, field by field")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Key, or certificate, to explain.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .help("Key ID of the key to select from a JWKS")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("cert-index")
+                        .long("cert-index")
+                        .value_name("N")
+                        .help("Certificate of a chain to read the key of, from 0.  Defaults to the leaf, 0")
+                        .required(false)
+                        .value_parser(clap::value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("friendly-name")
+                        .long("friendly-name")
+                        .value_name("NAME")
+                        .help("Friendly name of the key, or certificate, to read of a PKCS12 keystore")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("p12-cert")
+                        .long("p12-cert")
+                        .help("Read a certificate of a PKCS12 keystore, rather than its private key")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("normalize")
+                .about("Write the provided key in its canonical encoding, to deduplicate or diff keys")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Key to normalize.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .help("Key ID of the key to select from a JWKS")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("File to write the key to, which may be the input.  Defaults to stdout")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("encoding")
+                        .long("encoding")
+                        .short('e')
+                        .help("Encoding of the output.  Defaults to PEM")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(["PEM", "DER"]))
+                        .ignore_case(true),
+                ),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Explain why the provided key can not be read, and how to fix it")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Key that can not be read.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("asn1")
+                .about("Dump the ASN.1 elements of the input, as openssl asn1parse does")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("PEM, DER, hex or base64 input.  If not provided, stdin is used")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("generate")
+                .about("Generate a new private key")
+                .subcommand_required(true)
+                .subcommand(
+                    Command::new("rsa")
+                        .about("Generate an RSA private key")
+                        .arg(
+                            Arg::new("bits")
+                                .long("bits")
+                                .short('b')
+                                .value_name("BITS")
+                                .help("Length of the modulus in bits")
+                                .required(false)
+                                .value_parser(clap::builder::PossibleValuesParser::new(RSA_BITS))
+                                .default_value("2048"),
+                        )
+                        .arg(
+                            Arg::new("exponent")
+                                .long("exponent")
+                                .short('e')
+                                .value_name("E")
+                                .help("Public exponent, which must be odd")
+                                .required(false)
+                                .value_parser(clap::value_parser!(u64))
+                                .default_value("65537"),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .short('f')
+                                .help("Format of the private key")
+                                .required(false)
+                                .value_parser(clap::builder::PossibleValuesParser::new(["PKCS1", "PKCS8"]))
+                                .default_value("PKCS8")
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("encoding")
+                                .long("encoding")
+                                .help("Type of output encoding.  -e is the public exponent")
+                                .required(false)
+                                .value_parser(clap::builder::PossibleValuesParser::new(Encoding::all()))
+                                .default_value("PEM")
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("File to write the key to.  Defaults to stdout")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("seed")
+                                .long("seed")
+                                .value_name("HEX")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the hex seed, for tests and fixtures")
+                                .required(false)
+                                .conflicts_with("seed-file"),
+                        )
+                        .arg(
+                            Arg::new("seed-file")
+                                .long("seed-file")
+                                .value_name("FILE")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the bytes of the file, for tests and fixtures")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("outpass")
+                                .long("outpass")
+                                .value_name("PASSWORD")
+                                .help("Password protected output: pass:, env:, file: or prompt")
+                                .required(false),
+                        ),
+                )
+                .subcommand(
+                    Command::new("ec")
+                        .about("Generate an elliptic curve private key")
+                        .arg(
+                            Arg::new("curve")
+                                .long("curve")
+                                .short('c')
+                                .value_name("CURVE")
+                                .help("Named curve of the key")
+                                .required(true)
+                                .value_parser(clap::builder::PossibleValuesParser::new(Curve::all()))
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("format")
+                                .long("format")
+                                .short('f')
+                                .help("Format of the private key")
+                                .required(false)
+                                .value_parser(clap::builder::PossibleValuesParser::new(["SEC1", "PKCS8"]))
+                                .default_value("PKCS8")
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("encoding")
+                                .long("encoding")
+                                .short('e')
+                                .help("Type of output encoding")
+                                .required(false)
+                                .value_parser(clap::builder::PossibleValuesParser::new(Encoding::all()))
+                                .default_value("PEM")
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("File to write the key to.  Defaults to stdout")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("pubout")
+                                .long("pubout")
+                                .value_name("FILE")
+                                .help("File to also write the SPKI public key to, in the same encoding")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("seed")
+                                .long("seed")
+                                .value_name("HEX")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the hex seed, for tests and fixtures")
+                                .required(false)
+                                .conflicts_with("seed-file"),
+                        )
+                        .arg(
+                            Arg::new("seed-file")
+                                .long("seed-file")
+                                .value_name("FILE")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the bytes of the file, for tests and fixtures")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("outpass")
+                                .long("outpass")
+                                .value_name("PASSWORD")
+                                .help("Password protected output: pass:, env:, file: or prompt")
+                                .required(false),
+                        ),
+                )
+                .subcommand(
+                    Command::new("ed25519")
+                        .about("Generate an Ed25519 private key")
+                        .arg(
+                            Arg::new("encoding")
+                                .long("encoding")
+                                .short('e')
+                                .help("Type of output encoding: PEM or DER PKCS8, JWK, OPENSSH and others")
+                                .required(false)
+                                .value_parser(clap::builder::PossibleValuesParser::new(Encoding::all()))
+                                .default_value("PEM")
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("kid")
+                                .long("kid")
+                                .short('k')
+                                .help("Key ID of JWK output.  Defaults to the thumbprint of the key")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("File to write the key to.  Defaults to stdout")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("seed")
+                                .long("seed")
+                                .value_name("HEX")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the hex seed, for tests and fixtures")
+                                .required(false)
+                                .conflicts_with("seed-file"),
+                        )
+                        .arg(
+                            Arg::new("seed-file")
+                                .long("seed-file")
+                                .value_name("FILE")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the bytes of the file, for tests and fixtures")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("outpass")
+                                .long("outpass")
+                                .value_name("PASSWORD")
+                                .help("Password protected output: pass:, env:, file: or prompt")
+                                .required(false),
+                        ),
+                )
+                .subcommand(
+                    Command::new("x25519")
+                        .about("Generate an X25519 private key")
+                        .arg(
+                            Arg::new("encoding")
+                                .long("encoding")
+                                .short('e')
+                                .help("Type of output encoding: PEM or DER PKCS8, JWK, OPENSSH and others")
+                                .required(false)
+                                .value_parser(clap::builder::PossibleValuesParser::new(Encoding::all()))
+                                .default_value("PEM")
+                                .ignore_case(true),
+                        )
+                        .arg(
+                            Arg::new("kid")
+                                .long("kid")
+                                .short('k')
+                                .help("Key ID of JWK output.  Defaults to the thumbprint of the key")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("out")
+                                .long("out")
+                                .short('o')
+                                .value_name("FILE")
+                                .help("File to write the key to.  Defaults to stdout")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("seed")
+                                .long("seed")
+                                .value_name("HEX")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the hex seed, for tests and fixtures")
+                                .required(false)
+                                .conflicts_with("seed-file"),
+                        )
+                        .arg(
+                            Arg::new("seed-file")
+                                .long("seed-file")
+                                .value_name("FILE")
+                                .help("UNSAFE FOR PRODUCTION. Generate the key deterministically from the bytes of the file, for tests and fixtures")
+                                .required(false),
+                        )
+                        .arg(
+                            Arg::new("outpass")
+                                .long("outpass")
+                                .value_name("PASSWORD")
+                                .help("Password protected output: pass:, env:, file: or prompt")
+                                .required(false),
+                        ),
+                ),
+        )
+        .subcommand(
+            Command::new("match")
+                .about("Check that a public key belongs to a private key.  Exits with 1 if it does not")
+                .arg(
+                    Arg::new("private")
+                        .long("private")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("public")
+                        .long("public")
+                        .short('p')
+                        .value_name("FILE")
+                        .help("Public key, or certificate, in any format kt reads")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("check")
+                .about("Validate a key: that its components are consistent.  Exits with 1 if any check fails")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Key to check.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("sign")
+                .about("Write a detached signature of data with a private key")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key to sign with: RSA, EC on P-256, P-384, P-521 or secp256k1, or Ed25519.  Ed448 and SM2 keys are not supported")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for protected input: pass:, env:, file: or prompt")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("data")
+                        .long("data")
+                        .short('d')
+                        .value_name("FILE")
+                        .help("Data to sign.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("How the signature is written")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(SignatureFormat::all()))
+                        .default_value("base64")
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("pss")
+                        .long("pss")
+                        .help("Sign with RSASSA-PSS, rather than PKCS1 v1.5, with an RSA key")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("kid")
+                        .long("kid")
+                        .short('k')
+                        .value_name("KID")
+                        .help("Key ID to add to the JWS header")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Sets the output file to use")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("verify")
+                .about("Verify a detached signature of data.  Exits with 1 if it does not verify")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Public key, or private key, to verify with")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for a protected key")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("data")
+                        .long("data")
+                        .short('d')
+                        .value_name("FILE")
+                        .help("Data that was signed.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("signature")
+                        .long("signature")
+                        .short('s')
+                        .value_name("FILE")
+                        .help("Detached signature to verify")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .short('f')
+                        .help("How the signature is written")
+                        .required(false)
+                        .value_parser(clap::builder::PossibleValuesParser::new(SignatureFormat::all()))
+                        .default_value("base64")
+                        .ignore_case(true),
+                )
+                .arg(
+                    Arg::new("pss")
+                        .long("pss")
+                        .help("Verify an RSASSA-PSS, rather than PKCS1 v1.5, signature with an RSA key")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("encrypt")
+                .about("Encrypt a small payload to a key, with RSA-OAEP or HPKE")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Public key, or private key, to encrypt to")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for a protected key")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("data")
+                        .long("data")
+                        .short('d')
+                        .value_name("FILE")
+                        .help("Payload to encrypt.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("base64")
+                        .long("base64")
+                        .help("Write the ciphertext as base64")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Sets the output file to use")
+                        .required(false),
+                ),
+        )
+        .subcommand(
+            Command::new("decrypt")
+                .about("Decrypt a payload encrypted with kt encrypt")
+                .arg(
+                    Arg::new("in")
+                        .long("in")
+                        .short('i')
+                        .value_name("FILE")
+                        .help("Private key to decrypt with")
+                        .required(true),
+                )
+                .arg(
+                    Arg::new("inpass")
+                        .long("inpass")
+                        .value_name("PASSWORD")
+                        .help("password for a protected key")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("data")
+                        .long("data")
+                        .short('d')
+                        .value_name("FILE")
+                        .help("Ciphertext to decrypt.  If not provided, stdin is used")
+                        .required(false),
+                )
+                .arg(
+                    Arg::new("base64")
+                        .long("base64")
+                        .help("Read the ciphertext as base64")
+                        .action(ArgAction::SetTrue),
+                )
+                .arg(
+                    Arg::new("out")
+                        .long("out")
+                        .short('o')
+                        .value_name("FILE")
+                        .help("Sets the output file to use")
+                        .required(false),
                 ),
         )
         .get_matches();
 
-    process(&args)
+    init_logger(args.get_flag("local-time"));
+    match process(&args) {
+        // The error has already been written as the result event
+        Err(_) if events::enabled() => std::process::exit(1),
+        result => result,
+    }
 }
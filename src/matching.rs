@@ -0,0 +1,76 @@
+//! Check that a public key belongs to a private key
+//!
+//! `kt match` reads a private key and a public key, each in any format kt
+//! reads, derives the public key of the private key from its private key
+//! material, and checks that it is the given public key.  A report is
+//! written either way, and the exit code is 0 only if the keys match, so the
+//! command can be used in scripts.
+use anyhow::{bail, Result};
+use std::fs;
+
+use crate::app_state::AppState;
+use crate::compare::public_material;
+use crate::discover::discover;
+use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::key_info::{Alg, KeyInfo, KeyType};
+
+/// Read the public key file.  A private key is read as its public key
+fn discover_public(app_state: &AppState, filename: &str) -> Result<KeyInfo> {
+    let mut key_state = AppState {
+        in_file: Some(filename.to_owned()),
+        in_stream: Box::new(fs::File::open(filename).map_err(Error::ReadFileError)?),
+        in_password: app_state.in_password.clone(),
+        password_provider: app_state.password_provider.clone(),
+        password_retries: app_state.password_retries,
+        curve: app_state.curve,
+        ..Default::default()
+    };
+    discover(&mut key_state)
+}
+
+/// The algorithm of the key, with its curve if it has one
+fn describe(key_info: &KeyInfo) -> String {
+    match (key_info.alg, key_info.curve()) {
+        (Alg::Ecdsa, Some(curve)) => format!("{} {}", key_info.alg, curve),
+        _ => key_info.alg.to_string(),
+    }
+}
+
+/// Check that the public key is the public key of the private key, and
+/// write the result.  Keys that don't match are returned as
+/// Error::KeyMismatch.
+pub fn match_keys(app_state: &mut AppState) -> Result<()> {
+    let public_file = app_state
+        .public_file
+        .clone()
+        .ok_or_else(|| Error::MissingInput("public key".to_owned()))?;
+    let private_key = discover(app_state)?;
+    if private_key.key_type != KeyType::Private {
+        let message = "The key of --private is not a private key";
+        events::emit(Event::warning("not-private", message).with_text(message));
+        return Err(Error::TypeMismatch.into());
+    }
+    let public_key = discover_public(app_state, &public_file)?;
+
+    // The public key of the private key is derived from the private key
+    // material, not taken from the public key the document may carry
+    let same = match (public_material(&private_key)?, public_material(&public_key)?) {
+        (Some(ours), Some(theirs)) => ours == theirs,
+        _ => false,
+    };
+    let private_name = app_state.in_file.as_deref().unwrap_or("stdin");
+    let report = format!(
+        "Private Key: {} ({})\nPublic Key:  {} ({})\nResult:      {}\n",
+        private_name,
+        describe(&private_key),
+        public_file,
+        describe(&public_key),
+        if same { "match" } else { "no match" }
+    );
+    app_state.write_stream(report.as_bytes())?;
+    if !same {
+        bail!(Error::KeyMismatch);
+    }
+    Ok(())
+}
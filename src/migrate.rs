@@ -0,0 +1,223 @@
+//! Move to a key of another algorithm
+//!
+//! Keys can't be converted from one algorithm to another, such as RSA to
+//! Ed25519.  So `kt migrate` generates a new key of the target algorithm,
+//! and writes it in the same format, encoding and encryption as the old key,
+//! so that it can take the old key's place.  A checklist of the steps to
+//! finish the move, with the thumbprints of both keys, is written to stderr.
+//! With `--shred`, the old key is shredded once the new key is written.
+//!
+//! The new key can't be written over the old key: the output file is created
+//! before the old key is read, and the old key is the one to shred.
+//!
+//! ```rust
+//! use kt::migrate::check_out_file;
+//!
+//! let dir = std::env::temp_dir();
+//! let old_key = dir.join("kt-migrate-doc.pem");
+//! std::fs::write(&old_key, b"old key").unwrap();
+//! let old_name = old_key.to_str().unwrap();
+//!
+//! // The same file, by another path, is refused
+//! let other_path = dir.join(".").join("kt-migrate-doc.pem");
+//! assert!(check_out_file(Some(old_name), other_path.to_str().unwrap()).is_err());
+//! assert!(check_out_file(Some(old_name), dir.join("kt-migrate-new.pem").to_str().unwrap()).is_ok());
+//! assert!(check_out_file(None, old_name).is_ok());
+//! assert_eq!(std::fs::read(&old_key).unwrap(), b"old key");
+//! std::fs::remove_file(&old_key).unwrap();
+//! ```
+use anyhow::{bail, Result};
+use rand_core::OsRng;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::app_state::AppState;
+use crate::conversion::convert;
+use crate::discover::discover;
+use crate::document::jwk_docs::key_info_as_public_jwk;
+use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::generate::{generate_ec, generate_ed25519};
+use crate::key_info::{Alg, Curve, Encoding, Format, KeyInfo, KeyType};
+use crate::shred::shred_file;
+
+/// Algorithms that keys can be migrated to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MigrationTarget {
+    Ed25519,
+    Ec(Curve),
+}
+
+impl MigrationTarget {
+    pub fn all() -> Vec<&'static str> {
+        vec!["ED25519", "P-256", "P-384", "P-521"]
+    }
+
+    /// Generate a private key of the target algorithm
+    pub fn generate(&self) -> Result<KeyInfo> {
+        match self {
+            MigrationTarget::Ed25519 => generate_ed25519(&mut OsRng),
+            MigrationTarget::Ec(curve) => generate_ec(*curve, &mut OsRng),
+        }
+    }
+}
+
+impl FromStr for MigrationTarget {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<MigrationTarget> {
+        match s.to_uppercase().as_str() {
+            "ED25519" => Ok(MigrationTarget::Ed25519),
+            _ => Ok(MigrationTarget::Ec(Curve::from_str(s)?)),
+        }
+    }
+}
+
+impl fmt::Display for MigrationTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationTarget::Ed25519 => write!(f, "Ed25519"),
+            MigrationTarget::Ec(curve) => write!(f, "ECDSA {}", curve.jwk_crv()),
+        }
+    }
+}
+
+/// The format of the new key, given the format of the old key.
+///
+/// EC private keys are written as SEC1, and Ed25519 keys as PKCS8, unless
+/// the old key is a JWK or an OpenSSH key.
+fn migrated_format(old_format: Format, alg: Alg) -> Format {
+    match (old_format, alg) {
+        (Format::JWK, _) => Format::JWK,
+        (Format::OPENSSH, _) => Format::OPENSSH,
+        (Format::PPK, _) => Format::PPK,
+        (_, Alg::Ecdsa) => Format::SEC1,
+        _ => Format::PKCS8,
+    }
+}
+
+/// The canonical path of a file that may not exist yet
+fn canonical_path(filename: &str) -> Option<PathBuf> {
+    let path = Path::new(filename);
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(parent.canonicalize().ok()?.join(path.file_name()?))
+}
+
+/// Refuse an output file that is the input file, by any path.  The output
+/// file is truncated when it is created, before the old key is read.
+pub fn check_out_file(in_file: Option<&str>, out_file: &str) -> Result<()> {
+    if let Some(in_file) = in_file {
+        if canonical_path(in_file).is_some() && canonical_path(in_file) == canonical_path(out_file) {
+            bail!(Error::SameFile(out_file.to_owned()));
+        }
+    }
+    Ok(())
+}
+
+/// The RFC 7638 thumbprint of the public key
+fn thumbprint(key_info: &KeyInfo) -> String {
+    key_info_as_public_jwk(key_info)
+        .and_then(|jwk| jwk.thumbprint())
+        .unwrap_or_else(|_| "unavailable".to_owned())
+}
+
+/// Generate a new key to replace the input key, and print a checklist
+pub fn migrate(app_state: &mut AppState) -> Result<()> {
+    let target = app_state
+        .migration_target
+        .ok_or_else(|| Error::MissingInput("target algorithm".to_owned()))?;
+    let old_key = discover(app_state)?;
+    if old_key.key_type != KeyType::Private {
+        let message = "kt migrate replaces a private key. Provide the private key to migrate";
+        events::emit(Event::warning("not-private", message).with_text(message));
+        return Err(Error::TypeMismatch.into());
+    }
+
+    let new_key = target.generate()?;
+    let format = migrated_format(old_key.format, new_key.alg);
+    if format != old_key.format {
+        let message = format!("The new key is written as {:?}, not {:?}", format, old_key.format);
+        events::emit(
+            Event::warning("format-changed", &message)
+                .with("from", format!("{:?}", old_key.format))
+                .with("to", format!("{:?}", format))
+                .with_text(&message),
+        );
+    }
+    // Protect the new key as the old key was, with the same password unless
+    // a new one is provided
+    if old_key.encrypted && app_state.out_password.is_none() {
+        app_state.out_password = Some(app_state.input_password()?.to_string());
+    }
+    // SEC1 keys are only protected by the traditional encryption of PEM
+    if app_state.out_password.is_some() && format == Format::SEC1 && old_key.encoding != Encoding::PEM {
+        let message = "Password protected SEC1 DER keys are not supported. Decrypt the old key first";
+        events::emit(Event::warning("encrypted-sec1", message).with_text(message));
+        return Err(Error::NotSupported.into());
+    }
+    app_state.alg = Some(new_key.alg);
+    app_state.key_type = Some(KeyType::Private);
+    app_state.format = Some(format);
+    app_state.encoding = old_key.encoding;
+    convert(app_state, &new_key)?;
+
+    let old_name = app_state.in_file.as_deref().unwrap_or("stdin");
+    let new_name = app_state.out_file.as_deref().unwrap_or("the new key file");
+    let old_thumbprint = thumbprint(&old_key);
+    let new_thumbprint = thumbprint(&new_key);
+    let mut checklist = vec![
+        (
+            false,
+            format!(
+                "Add the new public key to your JWKS:\n        kt jwks add --set jwks.json --in {} --out jwks.json",
+                new_name
+            ),
+        ),
+        (false, "Replace the old public key in SSH authorized_keys files".to_owned()),
+        (false, "Move signers and clients to the new key, and its algorithm".to_owned()),
+        (
+            false,
+            format!(
+                "Remove the old key, kid {}, from the JWKS once tokens signed with it expire",
+                old_thumbprint
+            ),
+        ),
+    ];
+    match (app_state.shred, &app_state.in_file) {
+        (true, Some(in_file)) => {
+            app_state.out_stream.flush().map_err(Error::WriteFileError)?;
+            shred_file(Path::new(in_file), app_state.passes)?;
+            checklist.push((true, format!("Shredded {}", old_name)));
+        }
+        _ => checklist.push((false, format!("Delete {}", old_name))),
+    }
+
+    let message = format!("Migrated {} ({}) to {}", old_name, old_key.alg, target);
+    let mut text = format!(
+        "{}\n  Old key thumbprint: {}\n  New key thumbprint: {}\nChecklist:",
+        message, old_thumbprint, new_thumbprint
+    );
+    for (done, item) in &checklist {
+        text.push_str(&format!("\n  [{}] {}", if *done { "x" } else { " " }, item));
+    }
+    let checklist: Vec<serde_json::Value> = checklist
+        .into_iter()
+        .map(|(done, item)| serde_json::json!({ "done": done, "item": item }))
+        .collect();
+    events::emit(
+        Event::progress(&message)
+            .with("old_thumbprint", old_thumbprint)
+            .with("new_thumbprint", new_thumbprint)
+            .with("checklist", checklist)
+            .with_text(&text),
+    );
+    Ok(())
+}
@@ -0,0 +1,383 @@
+//! The public keys of ML-DSA private keys
+//! ([FIPS 204](https://csrc.nist.gov/pubs/fips/204/final))
+//!
+//! PKCS8 ([RFC 9881](https://www.rfc-editor.org/rfc/rfc9881)) holds an ML-DSA
+//! private key as its 32 byte seed, as the expanded private key, or as both.
+//! Only what is needed to read and write keys is here: the public key of the
+//! seed, or of the expanded key.  The arithmetic is not constant time, so kt
+//! does not sign with ML-DSA keys.
+use anyhow::{bail, Result};
+use sha3::digest::ExtendableOutput;
+use sha3::{Shake128, Shake256};
+use yasna::Tag;
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+use crate::key_info::Alg;
+use crate::oids;
+
+/// The modulus of the polynomial coefficients
+const Q: i64 = 8_380_417;
+/// The number of coefficients of a polynomial
+const N: usize = 256;
+/// The bits dropped from t by Power2Round
+const D: u32 = 13;
+/// 256^-1 mod q, which scales the inverse NTT
+const N_INV: i64 = 8_347_681;
+/// Size in bytes of the seed ξ, and of ρ
+pub const SEED_SIZE: usize = 32;
+
+/// A polynomial of Z_q[X]/(X^256 + 1), by its coefficients from 0 to q - 1
+type Poly = [i64; N];
+
+/// The sizes of the matrix A, k by l, and the bound η of the secret vectors
+struct Params {
+    k: usize,
+    l: usize,
+    eta: i64,
+}
+
+impl Params {
+    fn of(alg: Alg) -> Result<Params> {
+        match alg {
+            Alg::MlDsa44 => Ok(Params { k: 4, l: 4, eta: 2 }),
+            Alg::MlDsa65 => Ok(Params { k: 6, l: 5, eta: 4 }),
+            Alg::MlDsa87 => Ok(Params { k: 8, l: 7, eta: 2 }),
+            _ => bail!(Error::UnknownAlg),
+        }
+    }
+
+    /// The bits of a packed coefficient of s1 and s2
+    fn eta_bits(&self) -> u32 {
+        if self.eta == 2 {
+            3
+        } else {
+            4
+        }
+    }
+
+    fn public_key_size(&self) -> usize {
+        SEED_SIZE + self.k * 320
+    }
+
+    fn expanded_key_size(&self) -> usize {
+        let eta_poly = 32 * self.eta_bits() as usize;
+        128 + (self.k + self.l) * eta_poly + self.k * 32 * D as usize
+    }
+}
+
+/// Test whether the OID is a draft ML-DSA OID, whose keys were made by the
+/// initial public draft of FIPS 204, and so have different public keys
+pub fn is_draft(oid: &pkcs8::ObjectIdentifier) -> bool {
+    matches!(
+        *oid,
+        oids::ML_DSA_44_IPD | oids::ML_DSA_65_IPD | oids::ML_DSA_87_IPD
+    )
+}
+
+/// The size in bytes of the public key of an ML-DSA algorithm
+pub fn public_key_size(alg: Alg) -> Option<usize> {
+    Params::of(alg).ok().map(|params| params.public_key_size())
+}
+
+/// The PKCS8 private key of a seed, in its seed form, `[0] OCTET STRING`
+pub fn seed_der(seed: &[u8]) -> Zeroizing<Vec<u8>> {
+    Zeroizing::new(yasna::construct_der(|writer| {
+        writer.write_tagged_implicit(Tag::context(0), |writer| writer.write_bytes(seed))
+    }))
+}
+
+/// The seed and the expanded key of a private key, either of which may be
+/// absent
+type PrivateKeyForms = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// The seed and the expanded key of a PKCS8 private key, in any of its
+/// three forms
+fn read_private_key(der: &[u8]) -> Result<PrivateKeyForms> {
+    let key = match der.first() {
+        Some(0x80) => yasna::parse_der(der, |reader| {
+            reader
+                .read_tagged_implicit(Tag::context(0), |reader| reader.read_bytes())
+                .map(|seed| (Some(seed), None))
+        }),
+        Some(0x04) => yasna::parse_der(der, |reader| {
+            reader.read_bytes().map(|key| (None, Some(key)))
+        }),
+        Some(0x30) => yasna::parse_der(der, |reader| {
+            reader.read_sequence(|reader| {
+                let seed = reader.next().read_bytes()?;
+                let key = reader.next().read_bytes()?;
+                Ok((Some(seed), Some(key)))
+            })
+        }),
+        _ => bail!(Error::BadMlDsa("unknown private key form".to_owned())),
+    };
+    key.map_err(|e| Error::BadMlDsa(e.to_string()).into())
+}
+
+/// The seed of a PKCS8 private key, if it holds one
+pub fn private_key_seed(der: &[u8]) -> Result<Option<Zeroizing<Vec<u8>>>> {
+    let (seed, _) = read_private_key(der)?;
+    Ok(seed.map(Zeroizing::new))
+}
+
+/// The public key of a PKCS8 private key.  A key with both forms must agree
+/// with itself.
+///
+/// ```rust
+/// use kt::key_info::Alg;
+/// use kt::ml_dsa::{public_key, seed_der};
+///
+/// let public_key = public_key(Alg::MlDsa44, &seed_der(&[0u8; 32])).unwrap();
+/// assert_eq!(public_key.len(), 1312);
+/// assert_eq!(hex::encode(&public_key[..8]), "ba71f9f64e11baeb");
+/// ```
+pub fn public_key(alg: Alg, private_key: &[u8]) -> Result<Vec<u8>> {
+    let params = Params::of(alg)?;
+    let (seed, expanded) = read_private_key(private_key)?;
+    let from_seed = seed
+        .map(|seed| public_key_of_seed(&params, &seed))
+        .transpose()?;
+    let from_expanded = expanded
+        .map(|key| public_key_of_expanded(&params, &key))
+        .transpose()?;
+    match (from_seed, from_expanded) {
+        (Some(a), Some(b)) if a != b => bail!(Error::BadMlDsa(
+            "the seed and the expanded key differ".to_owned()
+        )),
+        (Some(public_key), _) | (None, Some(public_key)) => Ok(public_key),
+        (None, None) => bail!(Error::BadMlDsa("no private key".to_owned())),
+    }
+}
+
+/// ML-DSA.KeyGen_internal, as far as the public key
+fn public_key_of_seed(params: &Params, seed: &[u8]) -> Result<Vec<u8>> {
+    if seed.len() != SEED_SIZE {
+        bail!(Error::BadMlDsa(format!("bad seed length {}", seed.len())));
+    }
+    let input = [seed, &[params.k as u8, params.l as u8]].concat();
+    let expanded = Zeroizing::new(shake256(&input, 128));
+    let (rho, rho_prime) = (&expanded[..32], &expanded[32..96]);
+
+    let s: Vec<Poly> = (0..params.k + params.l)
+        .map(|r| rej_bounded_poly(params.eta, &[rho_prime, &(r as u16).to_le_bytes()].concat()))
+        .collect();
+    let (s1, s2) = s.split_at(params.l);
+    Ok(encode_public_key(rho, s1, s2))
+}
+
+/// The public key of the expanded private key ρ || K || tr || s1 || s2 || t0,
+/// whose tr = H(pk) is checked
+fn public_key_of_expanded(params: &Params, key: &[u8]) -> Result<Vec<u8>> {
+    if key.len() != params.expanded_key_size() {
+        bail!(Error::BadMlDsa(format!(
+            "bad private key length {}",
+            key.len()
+        )));
+    }
+    let bits = params.eta_bits();
+    let poly_size = 32 * bits as usize;
+    let s: Vec<Poly> = key[128..128 + (params.k + params.l) * poly_size]
+        .chunks_exact(poly_size)
+        .map(|packed| {
+            let mut poly = unpack(packed, bits);
+            for c in poly.iter_mut() {
+                *c = (params.eta - *c).rem_euclid(Q);
+            }
+            poly
+        })
+        .collect();
+    let (s1, s2) = s.split_at(params.l);
+    let public_key = encode_public_key(&key[..32], s1, s2);
+    if shake256(&public_key, 64) != key[64..128] {
+        bail!(Error::BadMlDsa(
+            "tr is not the hash of the public key".to_owned()
+        ));
+    }
+    Ok(public_key)
+}
+
+/// pkEncode(ρ, t1), of t = NTT^-1(A NTT(s1)) + s2
+fn encode_public_key(rho: &[u8], s1: &[Poly], s2: &[Poly]) -> Vec<u8> {
+    let s1_hat: Vec<Poly> = s1.iter().map(ntt).collect();
+    let mut public_key = rho.to_vec();
+    for (r, s2) in s2.iter().enumerate() {
+        let mut t_hat = [0i64; N];
+        for (s, s1_hat) in s1_hat.iter().enumerate() {
+            let a_hat = rej_ntt_poly(&[rho, &[s as u8, r as u8]].concat());
+            for (t, (a, b)) in t_hat.iter_mut().zip(a_hat.iter().zip(s1_hat)) {
+                *t = (*t + a * b) % Q;
+            }
+        }
+        let mut t1 = ntt_inverse(&t_hat);
+        for (t, s2) in t1.iter_mut().zip(s2) {
+            // Power2Round keeps the high bits of t
+            let t_plus = (*t + s2) % Q;
+            let mut t0 = t_plus % (1 << D);
+            if t0 > 1 << (D - 1) {
+                t0 -= 1 << D;
+            }
+            *t = (t_plus - t0) >> D;
+        }
+        public_key.extend(pack(&t1, 10));
+    }
+    public_key
+}
+
+/// `len` bytes of SHAKE128, as the matrix is sampled with it
+fn shake128(data: &[u8], len: usize) -> Vec<u8> {
+    let mut output = vec![0u8; len];
+    Shake128::digest_xof(data, &mut output);
+    output
+}
+
+/// `len` bytes of SHAKE256
+fn shake256(data: &[u8], len: usize) -> Vec<u8> {
+    let mut output = vec![0u8; len];
+    Shake256::digest_xof(data, &mut output);
+    output
+}
+
+/// The bytes of an extendable output function, which are sampled from until
+/// `sample` has a polynomial.  The output is only ever extended, so a longer
+/// squeeze samples the same polynomial.
+fn sample(
+    xof: fn(&[u8], usize) -> Vec<u8>,
+    seed: &[u8],
+    len: usize,
+    sample: impl Fn(&[u8]) -> Option<Poly>,
+) -> Poly {
+    let mut len = len;
+    loop {
+        if let Some(poly) = sample(&xof(seed, len)) {
+            return poly;
+        }
+        len *= 2;
+    }
+}
+
+/// RejNTTPoly, a polynomial of A in the NTT domain
+fn rej_ntt_poly(seed: &[u8]) -> Poly {
+    sample(shake128, seed, 5 * 168, |bytes| {
+        let mut poly = [0i64; N];
+        let coefficients = bytes
+            .chunks_exact(3)
+            .map(|b| i64::from(b[0]) | i64::from(b[1]) << 8 | i64::from(b[2] & 0x7f) << 16)
+            .filter(|z| *z < Q);
+        let mut j = 0;
+        for (c, z) in poly.iter_mut().zip(coefficients) {
+            *c = z;
+            j += 1;
+        }
+        (j == N).then_some(poly)
+    })
+}
+
+/// RejBoundedPoly, a polynomial of s1 or s2 of coefficients from -η to η
+fn rej_bounded_poly(eta: i64, seed: &[u8]) -> Poly {
+    sample(shake256, seed, 2 * 136, |bytes| {
+        let mut poly = [0i64; N];
+        let coefficients = bytes
+            .iter()
+            .flat_map(|b| [i64::from(b & 0x0f), i64::from(b >> 4)])
+            .filter_map(|b| match eta {
+                2 if b < 15 => Some(2 - b % 5),
+                4 if b < 9 => Some(4 - b),
+                _ => None,
+            });
+        let mut j = 0;
+        for (c, z) in poly.iter_mut().zip(coefficients) {
+            *c = z.rem_euclid(Q);
+            j += 1;
+        }
+        (j == N).then_some(poly)
+    })
+}
+
+/// ζ^BitRev8(m) mod q, for the 1753, a 512th root of unity
+fn zetas() -> [i64; N] {
+    let mut powers = [1i64; N];
+    for i in 1..N {
+        powers[i] = powers[i - 1] * 1753 % Q;
+    }
+    let mut zetas = [0i64; N];
+    for (m, zeta) in zetas.iter_mut().enumerate() {
+        *zeta = powers[(m as u8).reverse_bits() as usize];
+    }
+    zetas
+}
+
+fn ntt(w: &Poly) -> Poly {
+    let zetas = zetas();
+    let mut w = *w;
+    let mut m = 0;
+    let mut len = 128;
+    while len >= 1 {
+        for start in (0..N).step_by(2 * len) {
+            m += 1;
+            for j in start..start + len {
+                let t = zetas[m] * w[j + len] % Q;
+                w[j + len] = (w[j] - t).rem_euclid(Q);
+                w[j] = (w[j] + t) % Q;
+            }
+        }
+        len /= 2;
+    }
+    w
+}
+
+fn ntt_inverse(w: &Poly) -> Poly {
+    let zetas = zetas();
+    let mut w = *w;
+    let mut m = N;
+    let mut len = 1;
+    while len < N {
+        for start in (0..N).step_by(2 * len) {
+            m -= 1;
+            let z = Q - zetas[m];
+            for j in start..start + len {
+                let t = w[j];
+                w[j] = (t + w[j + len]) % Q;
+                w[j + len] = z * (t - w[j + len]).rem_euclid(Q) % Q;
+            }
+        }
+        len *= 2;
+    }
+    for c in w.iter_mut() {
+        *c = *c * N_INV % Q;
+    }
+    w
+}
+
+/// Pack coefficients of `bits` bits, least significant bit first
+fn pack(poly: &Poly, bits: u32) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(N * bits as usize / 8);
+    let (mut acc, mut acc_bits) = (0u64, 0u32);
+    for c in poly {
+        acc |= (*c as u64) << acc_bits;
+        acc_bits += bits;
+        while acc_bits >= 8 {
+            bytes.push(acc as u8);
+            acc >>= 8;
+            acc_bits -= 8;
+        }
+    }
+    bytes
+}
+
+/// Unpack coefficients of `bits` bits, least significant bit first
+fn unpack(bytes: &[u8], bits: u32) -> Poly {
+    let mut poly = [0i64; N];
+    let (mut acc, mut acc_bits) = (0u64, 0u32);
+    let mut bytes = bytes.iter();
+    for c in poly.iter_mut() {
+        while acc_bits < bits {
+            acc |= u64::from(*bytes.next().unwrap_or(&0)) << acc_bits;
+            acc_bits += 8;
+        }
+        *c = (acc & ((1 << bits) - 1)) as i64;
+        acc >>= bits;
+        acc_bits -= bits;
+    }
+    poly
+}
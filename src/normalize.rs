@@ -0,0 +1,131 @@
+//! Canonical encoding of a key, so that keys can be deduplicated and diffed
+//! byte for byte
+//!
+//! `kt normalize` reads any key kt reads, and writes it again as DER, or as
+//! PEM with only the armor lines, 64 character lines and CRLF line endings.
+//! PKCS8, SPKI, PKCS1 and SEC1 keys keep their format, and their
+//! AlgorithmIdentifier as it was read, such as RSASSA-PSS parameters.  Keys
+//! of other formats are written as PKCS8, or as SPKI if they are public.
+//! Certificates are written as the SPKI of their key.
+//!
+//! The DER is decoded and encoded again, so that the same key is always the
+//! same bytes, whatever the tool that wrote it.  The output is never
+//! encrypted, as encryption is salted.
+use anyhow::{bail, Result};
+use pkcs1::{RsaPrivateKeyDocument, RsaPublicKeyDocument};
+use pkcs8::der::{asn1::Any, Decodable, Document, Encodable};
+use pkcs8::{AlgorithmIdentifier, PrivateKeyDocument, PrivateKeyInfo, SubjectPublicKeyInfo};
+use sec1::EcPrivateKeyDocument;
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::document::pkcs8_docs::{key_info_to_pk8_document, Pkcs8Version};
+use crate::document::spki_docs::key_info_as_spki;
+use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::key_info::{Alg, Encoding, Format, KeyInfo, KeyType};
+use crate::pem;
+
+/// The AlgorithmIdentifier the key was read with, if it was read with one
+fn read_alg_id(key_info: &KeyInfo) -> Result<Option<AlgorithmIdentifier<'_>>> {
+    let oid = match key_info.oid {
+        Some(oid) => oid,
+        None => return Ok(None),
+    };
+    let parameters = match &key_info.params {
+        Some(params) => Some(Any::from_der(params)?),
+        None => None,
+    };
+    Ok(Some(AlgorithmIdentifier { oid, parameters }))
+}
+
+fn key_bytes(key_info: &KeyInfo) -> Result<&[u8]> {
+    key_info
+        .bytes
+        .as_deref()
+        .map(Vec::as_slice)
+        .ok_or_else(|| Error::MissingInput("key".to_owned()).into())
+}
+
+fn canonical_pk8(key_info: &KeyInfo) -> Result<Zeroizing<Vec<u8>>> {
+    let bytes = key_bytes(key_info)?;
+    let pkd: PrivateKeyDocument = match read_alg_id(key_info)? {
+        Some(alg_id) => {
+            let mut pki = PrivateKeyInfo::new(alg_id, bytes);
+            pki.public_key = key_info.public_key.as_deref();
+            pki.try_into()?
+        }
+        None => key_info_to_pk8_document(key_info.alg, key_info, Pkcs8Version::V1)?,
+    };
+    Ok(Zeroizing::new(pkd.as_der().to_vec()))
+}
+
+fn canonical_spki(key_info: &KeyInfo) -> Result<Zeroizing<Vec<u8>>> {
+    let bytes = key_bytes(key_info)?;
+    let der = match read_alg_id(key_info)? {
+        Some(algorithm) => SubjectPublicKeyInfo {
+            algorithm,
+            subject_public_key: bytes,
+        }
+        .to_vec()?,
+        None => key_info_as_spki(key_info, key_info.alg)?.as_der().to_vec(),
+    };
+    Ok(Zeroizing::new(der))
+}
+
+/// The PEM label and the canonical DER of the key
+pub fn canonical_der(key_info: &KeyInfo) -> Result<(&'static str, Zeroizing<Vec<u8>>)> {
+    if key_info.alg == Alg::Symmetric {
+        bail!(Error::NotSupported);
+    }
+    let private = key_info.key_type == KeyType::Private;
+    let der = match (key_info.format, private) {
+        (Format::PKCS1, true) => {
+            let pk1_doc = RsaPrivateKeyDocument::from_der(key_bytes(key_info)?)?;
+            (
+                pem::RSA_PRIVATE_KEY,
+                Zeroizing::new(pk1_doc.decode().to_der()?.as_der().to_vec()),
+            )
+        }
+        (Format::PKCS1, false) => {
+            let pk1_doc = RsaPublicKeyDocument::from_der(key_bytes(key_info)?)?;
+            (
+                pem::RSA_PUBLIC_KEY,
+                Zeroizing::new(pk1_doc.decode().to_vec()?),
+            )
+        }
+        // A SEC1 public key is a bare EC point, with no document of its own
+        (Format::SEC1, true) => {
+            let sec1_doc = EcPrivateKeyDocument::from_der(key_bytes(key_info)?)?;
+            (
+                pem::EC_PRIVATE_KEY,
+                Zeroizing::new(sec1_doc.decode().to_vec()?),
+            )
+        }
+        (_, true) => (pem::PRIVATE_KEY, canonical_pk8(key_info)?),
+        (_, false) => (pem::PUBLIC_KEY, canonical_spki(key_info)?),
+    };
+    Ok(der)
+}
+
+/// Write the input key in its canonical encoding
+pub fn normalize(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    let (label, der) = canonical_der(&key_info)?;
+    if key_info.encrypted {
+        let message = "The key was password protected, and is written without a password";
+        events::emit(Event::warning("decrypted", message).with_text(message));
+    }
+    // The output may be the input, which has now been read
+    if let Some(out_file) = app_state.out_file.clone() {
+        app_state.create_out_file(&out_file, key_info.key_type == KeyType::Private)?;
+    }
+    match app_state.encoding {
+        Encoding::DER => app_state.write_stream(&der),
+        _ => {
+            let pem = Zeroizing::new(pem::wrap(label, &der)?);
+            app_state.write_stream(pem.as_bytes())
+        }
+    }
+}
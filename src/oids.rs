@@ -6,17 +6,89 @@ pub const RSASSA_PSS: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1
 pub const RSA_ENCRYPTION: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.1.1");
 pub const ECDSA: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.2.1");
 pub const PRIME_256_V1: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.3.1.7");
+pub const SECP384R1: ObjectIdentifier = ObjectIdentifier::new("1.3.132.0.34");
+pub const SECP521R1: ObjectIdentifier = ObjectIdentifier::new("1.3.132.0.35");
+pub const SECP256K1: ObjectIdentifier = ObjectIdentifier::new("1.3.132.0.10");
+pub const SM2: ObjectIdentifier = ObjectIdentifier::new("1.2.156.10197.1.301");
 pub const X25519: ObjectIdentifier = ObjectIdentifier::new("1.3.101.110");
 pub const X448: ObjectIdentifier = ObjectIdentifier::new("1.3.101.111");
 pub const ED_DSA25519: ObjectIdentifier = ObjectIdentifier::new("1.3.101.112");
 pub const ED_DSA448: ObjectIdentifier = ObjectIdentifier::new("1.3.101.113");
 pub const ED_DSA25519_PH: ObjectIdentifier = ObjectIdentifier::new("1.3.101.114");
 pub const ED_DSA448_PH: ObjectIdentifier = ObjectIdentifier::new("1.3.101.115");
+pub const DSA: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10040.4.1");
+pub const DH: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.3.1");
+pub const GOST2012_256: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.1.1.1");
+pub const GOST2012_512: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.1.1.2");
+
+// GOST R 34.10 parameter sets, and GOST R 34.11-2012 digests
+pub const GOST2012_256_A: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.2.1.1.1");
+pub const GOST2012_256_B: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.2.1.1.2");
+pub const GOST2012_256_C: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.2.1.1.3");
+pub const GOST2012_256_D: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.2.1.1.4");
+pub const GOST2012_512_A: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.2.1.2.1");
+pub const GOST2012_512_B: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.2.1.2.2");
+pub const GOST2012_512_C: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.2.1.2.3");
+pub const GOST2001_CRYPTOPRO_A: ObjectIdentifier = ObjectIdentifier::new("1.2.643.2.2.35.1");
+pub const GOST2001_CRYPTOPRO_B: ObjectIdentifier = ObjectIdentifier::new("1.2.643.2.2.35.2");
+pub const GOST2001_CRYPTOPRO_C: ObjectIdentifier = ObjectIdentifier::new("1.2.643.2.2.35.3");
+pub const GOST2001_CRYPTOPRO_XCH_A: ObjectIdentifier = ObjectIdentifier::new("1.2.643.2.2.36.0");
+pub const GOST2001_CRYPTOPRO_XCH_B: ObjectIdentifier = ObjectIdentifier::new("1.2.643.2.2.36.1");
+pub const STREEBOG256: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.1.2.2");
+pub const STREEBOG512: ObjectIdentifier = ObjectIdentifier::new("1.2.643.7.1.1.2.3");
+
+// ML-DSA, of FIPS 204, and the draft OIDs of the Open Quantum Safe provider
+// for the initial public draft
+pub const ML_DSA_44: ObjectIdentifier = ObjectIdentifier::new("2.16.840.1.101.3.4.3.17");
+pub const ML_DSA_65: ObjectIdentifier = ObjectIdentifier::new("2.16.840.1.101.3.4.3.18");
+pub const ML_DSA_87: ObjectIdentifier = ObjectIdentifier::new("2.16.840.1.101.3.4.3.19");
+pub const ML_DSA_44_IPD: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.4.1.2.267.12.4.4");
+pub const ML_DSA_65_IPD: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.4.1.2.267.12.6.5");
+pub const ML_DSA_87_IPD: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.4.1.2.267.12.8.7");
+
+// Composite ML-DSA signatures of the draft draft-ietf-lamps-pq-composite-sigs
+pub const MLDSA44_RSA2048_PSS: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.37");
+pub const MLDSA44_RSA2048_PKCS15: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.38");
+pub const MLDSA44_ED25519: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.39");
+pub const MLDSA44_ECDSA_P256: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.40");
+pub const MLDSA65_RSA3072_PSS: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.41");
+pub const MLDSA65_RSA3072_PKCS15: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.42");
+pub const MLDSA65_RSA4096_PSS: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.43");
+pub const MLDSA65_RSA4096_PKCS15: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.44");
+pub const MLDSA65_ECDSA_P256: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.45");
+pub const MLDSA65_ECDSA_P384: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.46");
+pub const MLDSA65_ECDSA_BRAINPOOL_P256R1: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.47");
+pub const MLDSA65_ED25519: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.48");
+pub const MLDSA87_ECDSA_P384: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.49");
+pub const MLDSA87_ECDSA_BRAINPOOL_P384R1: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.50");
+pub const MLDSA87_ED448: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.51");
+pub const MLDSA87_RSA3072_PSS: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.52");
+pub const MLDSA87_RSA4096_PSS: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.53");
+pub const MLDSA87_ECDSA_P521: ObjectIdentifier = ObjectIdentifier::new("1.3.6.1.5.5.7.6.54");
+
+// Hash and mask generation algorithms
+pub const SHA1: ObjectIdentifier = ObjectIdentifier::new("1.3.14.3.2.26");
+pub const SHA256: ObjectIdentifier = ObjectIdentifier::new("2.16.840.1.101.3.4.2.1");
+pub const SHA384: ObjectIdentifier = ObjectIdentifier::new("2.16.840.1.101.3.4.2.2");
+pub const SHA512: ObjectIdentifier = ObjectIdentifier::new("2.16.840.1.101.3.4.2.3");
+pub const MGF1: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.1.8");
+
+// Signature algorithms
+pub const SHA1_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.1.5");
+pub const SHA256_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.1.11");
+pub const SHA384_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.1.12");
+pub const SHA512_WITH_RSA: ObjectIdentifier = ObjectIdentifier::new("1.2.840.113549.1.1.13");
+pub const ECDSA_WITH_SHA1: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.4.1");
+pub const ECDSA_WITH_SHA256: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.4.3.2");
+pub const ECDSA_WITH_SHA384: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.4.3.3");
+pub const ECDSA_WITH_SHA512: ObjectIdentifier = ObjectIdentifier::new("1.2.840.10045.4.3.4");
 
 pub const RSA_ENCRYPTION_BYTES: [u8; 9] = [42, 134, 72, 134, 247, 13, 1, 1, 1];
 pub const RSASSA_PSS_BYTES: [u8; 9] = [42, 134, 72, 134, 247, 13, 1, 1, 10];
 pub const ECDSA_BYTES: [u8; 7] = [42, 134, 72, 206, 61, 2, 1];
 pub const PRIME_256_V1_BYTES: [u8; 8] = [42, 134, 72, 206, 61, 3, 1, 7];
+pub const SECP384R1_BYTES: [u8; 5] = [43, 129, 4, 0, 34];
+pub const SECP521R1_BYTES: [u8; 5] = [43, 129, 4, 0, 35];
 pub const X25519_BYTES: [u8;3] = [43, 101, 110];
 pub const X448_BYTES: [u8;3] = [43, 101, 111];
 pub const ED_DSA25519_BYTES: [u8;3] = [43, 101, 112];
@@ -31,12 +103,71 @@ pub fn oid_to_str(oid: &ObjectIdentifier) -> String {
         RSASSA_PSS => format!("rsassaPss: {}", oid),
         ECDSA => format!("id-ecPublicKey: {}", oid),
         PRIME_256_V1 => format!("prime256v1: {}", oid),
+        SECP384R1 => format!("secp384r1: {}", oid),
+        SECP521R1 => format!("secp521r1: {}", oid),
+        SECP256K1 => format!("secp256k1: {}", oid),
+        SM2 => format!("SM2: {}", oid),
         X25519 => format!("id-X25519: {}", oid),
         X448 => format!("id-X448: {}", oid),
         ED_DSA25519 => format!("id-EdDSA25519: {}", oid),
-        ED_DSA448 => format!("id-EdDSA448-ph: {}", oid),
-        ED_DSA25519_PH => format!("id-EdDS25519-ph: {}", oid),
+        ED_DSA448 => format!("id-EdDSA448: {}", oid),
+        ED_DSA25519_PH => format!("id-EdDSA25519-ph: {}", oid),
         ED_DSA448_PH=> format!("id-EdDSA448-ph: {}", oid),
+        DSA => format!("id-dsa: {}", oid),
+        DH => format!("dhKeyAgreement: {}", oid),
+        GOST2012_256 => format!("id-tc26-gost3410-12-256: {}", oid),
+        GOST2012_512 => format!("id-tc26-gost3410-12-512: {}", oid),
+        GOST2012_256_A => format!("id-tc26-gost-3410-12-256-paramSetA: {}", oid),
+        GOST2012_256_B => format!("id-tc26-gost-3410-12-256-paramSetB: {}", oid),
+        GOST2012_256_C => format!("id-tc26-gost-3410-12-256-paramSetC: {}", oid),
+        GOST2012_256_D => format!("id-tc26-gost-3410-12-256-paramSetD: {}", oid),
+        GOST2012_512_A => format!("id-tc26-gost-3410-12-512-paramSetA: {}", oid),
+        GOST2012_512_B => format!("id-tc26-gost-3410-12-512-paramSetB: {}", oid),
+        GOST2012_512_C => format!("id-tc26-gost-3410-12-512-paramSetC: {}", oid),
+        GOST2001_CRYPTOPRO_A => format!("id-GostR3410-2001-CryptoPro-A-ParamSet: {}", oid),
+        GOST2001_CRYPTOPRO_B => format!("id-GostR3410-2001-CryptoPro-B-ParamSet: {}", oid),
+        GOST2001_CRYPTOPRO_C => format!("id-GostR3410-2001-CryptoPro-C-ParamSet: {}", oid),
+        GOST2001_CRYPTOPRO_XCH_A => format!("id-GostR3410-2001-CryptoPro-XchA-ParamSet: {}", oid),
+        GOST2001_CRYPTOPRO_XCH_B => format!("id-GostR3410-2001-CryptoPro-XchB-ParamSet: {}", oid),
+        STREEBOG256 => format!("id-tc26-gost3411-12-256: {}", oid),
+        STREEBOG512 => format!("id-tc26-gost3411-12-512: {}", oid),
+        ML_DSA_44 => format!("id-ml-dsa-44: {}", oid),
+        ML_DSA_65 => format!("id-ml-dsa-65: {}", oid),
+        ML_DSA_87 => format!("id-ml-dsa-87: {}", oid),
+        ML_DSA_44_IPD => format!("ML-DSA-44-ipd: {}", oid),
+        ML_DSA_65_IPD => format!("ML-DSA-65-ipd: {}", oid),
+        ML_DSA_87_IPD => format!("ML-DSA-87-ipd: {}", oid),
+        MLDSA44_RSA2048_PSS => format!("id-MLDSA44-RSA2048-PSS-SHA256: {}", oid),
+        MLDSA44_RSA2048_PKCS15 => format!("id-MLDSA44-RSA2048-PKCS15-SHA256: {}", oid),
+        MLDSA44_ED25519 => format!("id-MLDSA44-Ed25519-SHA512: {}", oid),
+        MLDSA44_ECDSA_P256 => format!("id-MLDSA44-ECDSA-P256-SHA256: {}", oid),
+        MLDSA65_RSA3072_PSS => format!("id-MLDSA65-RSA3072-PSS-SHA512: {}", oid),
+        MLDSA65_RSA3072_PKCS15 => format!("id-MLDSA65-RSA3072-PKCS15-SHA512: {}", oid),
+        MLDSA65_RSA4096_PSS => format!("id-MLDSA65-RSA4096-PSS-SHA512: {}", oid),
+        MLDSA65_RSA4096_PKCS15 => format!("id-MLDSA65-RSA4096-PKCS15-SHA512: {}", oid),
+        MLDSA65_ECDSA_P256 => format!("id-MLDSA65-ECDSA-P256-SHA512: {}", oid),
+        MLDSA65_ECDSA_P384 => format!("id-MLDSA65-ECDSA-P384-SHA512: {}", oid),
+        MLDSA65_ECDSA_BRAINPOOL_P256R1 => format!("id-MLDSA65-ECDSA-brainpoolP256r1-SHA512: {}", oid),
+        MLDSA65_ED25519 => format!("id-MLDSA65-Ed25519-SHA512: {}", oid),
+        MLDSA87_ECDSA_P384 => format!("id-MLDSA87-ECDSA-P384-SHA512: {}", oid),
+        MLDSA87_ECDSA_BRAINPOOL_P384R1 => format!("id-MLDSA87-ECDSA-brainpoolP384r1-SHA512: {}", oid),
+        MLDSA87_ED448 => format!("id-MLDSA87-Ed448-SHAKE256: {}", oid),
+        MLDSA87_RSA3072_PSS => format!("id-MLDSA87-RSA3072-PSS-SHA512: {}", oid),
+        MLDSA87_RSA4096_PSS => format!("id-MLDSA87-RSA4096-PSS-SHA512: {}", oid),
+        MLDSA87_ECDSA_P521 => format!("id-MLDSA87-ECDSA-P521-SHA512: {}", oid),
+        SHA1 => format!("sha1: {}", oid),
+        SHA256 => format!("sha256: {}", oid),
+        SHA384 => format!("sha384: {}", oid),
+        SHA512 => format!("sha512: {}", oid),
+        MGF1 => format!("mgf1: {}", oid),
+        SHA1_WITH_RSA => format!("sha1WithRSAEncryption: {}", oid),
+        SHA256_WITH_RSA => format!("sha256WithRSAEncryption: {}", oid),
+        SHA384_WITH_RSA => format!("sha384WithRSAEncryption: {}", oid),
+        SHA512_WITH_RSA => format!("sha512WithRSAEncryption: {}", oid),
+        ECDSA_WITH_SHA1 => format!("ecdsa-with-SHA1: {}", oid),
+        ECDSA_WITH_SHA256 => format!("ecdsa-with-SHA256: {}", oid),
+        ECDSA_WITH_SHA384 => format!("ecdsa-with-SHA384: {}", oid),
+        ECDSA_WITH_SHA512 => format!("ecdsa-with-SHA512: {}", oid),
         _ => format!("Unknown OID: {}", oid),
     }
 }
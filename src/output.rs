@@ -0,0 +1,180 @@
+//! Permissions and ownership of the files kt creates
+//!
+//! `--umask` replaces the process umask, so that it applies to every file and
+//! directory kt creates.  `--owner user:group` changes the owner of each of
+//! them once created, which normally requires running as root.  Both are only
+//! supported on unix.
+use anyhow::{bail, Result};
+use log::info;
+#[cfg(not(unix))]
+use log::warn;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::Path;
+
+use crate::errors::Error;
+
+/// The user and group to give created files.  Either may be left unchanged.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Owner {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+}
+
+/// Parse an octal umask, such as `077`
+pub fn parse_umask(umask: &str) -> Result<u32> {
+    match u32::from_str_radix(umask, 8) {
+        Ok(umask) if umask <= 0o777 => Ok(umask),
+        _ => bail!(Error::BadUmask(umask.to_owned())),
+    }
+}
+
+/// Parse `user:group`, `user` or `:group`.  Users and groups may be names or
+/// numeric ids.
+pub fn parse_owner(owner: &str) -> Result<Owner> {
+    let (user, group) = owner.split_once(':').unwrap_or((owner, ""));
+    let uid = match user {
+        "" => None,
+        user => Some(user.parse().or_else(|_| user_id(user))?),
+    };
+    let gid = match group {
+        "" => None,
+        group => Some(group.parse().or_else(|_| group_id(group))?),
+    };
+    if uid.is_none() && gid.is_none() {
+        bail!(Error::BadOwner(owner.to_owned()));
+    }
+    Ok(Owner { uid, gid })
+}
+
+#[cfg(unix)]
+fn user_id(name: &str) -> Result<u32> {
+    let c_name = std::ffi::CString::new(name).map_err(|_| Error::BadOwner(name.to_owned()))?;
+    // SAFETY: the name is a valid C string, and the entry is read before any
+    // other call could reuse it
+    let entry = unsafe { libc::getpwnam(c_name.as_ptr()) };
+    match entry.is_null() {
+        true => bail!(Error::BadOwner(format!("no user {}", name))),
+        false => Ok(unsafe { (*entry).pw_uid }),
+    }
+}
+
+#[cfg(unix)]
+fn group_id(name: &str) -> Result<u32> {
+    let c_name = std::ffi::CString::new(name).map_err(|_| Error::BadOwner(name.to_owned()))?;
+    // SAFETY: as for user_id
+    let entry = unsafe { libc::getgrnam(c_name.as_ptr()) };
+    match entry.is_null() {
+        true => bail!(Error::BadOwner(format!("no group {}", name))),
+        false => Ok(unsafe { (*entry).gr_gid }),
+    }
+}
+
+#[cfg(not(unix))]
+fn user_id(name: &str) -> Result<u32> {
+    bail!(Error::BadOwner(format!("no user {}", name)))
+}
+
+#[cfg(not(unix))]
+fn group_id(name: &str) -> Result<u32> {
+    bail!(Error::BadOwner(format!("no group {}", name)))
+}
+
+/// The umask and owner applied to created files
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutputPolicy {
+    pub umask: Option<u32>,
+    pub owner: Option<Owner>,
+}
+
+impl OutputPolicy {
+    /// Replace the process umask, if one was provided
+    pub fn apply_umask(&self) -> Result<()> {
+        match self.umask {
+            #[cfg(unix)]
+            Some(umask) => {
+                // SAFETY: umask has no failure modes
+                unsafe { libc::umask(umask as libc::mode_t) };
+                Ok(())
+            }
+            #[cfg(not(unix))]
+            Some(_) => bail!(Error::NotSupported),
+            None => Ok(()),
+        }
+    }
+
+    /// The permissions of a file created with the mode, after the umask
+    pub fn mode(&self, mode: u32) -> u32 {
+        mode & !self.umask.unwrap_or(0)
+    }
+
+    /// Give the file or directory to the owner, if one was provided
+    pub fn set_owner(&self, path: &Path) -> Result<()> {
+        let owner = match self.owner {
+            Some(owner) => owner,
+            None => return Ok(()),
+        };
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::chown(path, owner.uid, owner.gid)
+                .map_err(Error::WriteFileError)?;
+            info!("Changed owner of {}", path.display());
+            Ok(())
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = (owner, path);
+            bail!(Error::NotSupported)
+        }
+    }
+
+    /// Create, or truncate, a file, readable only by the owner if it holds a
+    /// private key
+    pub fn create_file(&self, path: &Path, private: bool) -> Result<File> {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        info!("Writing {}", path.display());
+        #[cfg(unix)]
+        if private {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            let mode = self.mode(0o600);
+            options.mode(mode);
+            let file = options.open(path).map_err(Error::WriteFileError)?;
+            // The mode only applies to new files, so also fix up existing ones
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))
+                .map_err(Error::WriteFileError)?;
+            self.set_owner(path)?;
+            return Ok(file);
+        }
+        #[cfg(not(unix))]
+        if private {
+            warn!("Restrict access to {} to its owner", path.display());
+        }
+        let file = options.open(path).map_err(Error::WriteFileError)?;
+        self.set_owner(path)?;
+        Ok(file)
+    }
+
+    /// Create, or replace, a file with the bytes, readable only by the owner
+    /// if it holds a private key
+    pub fn write_file(&self, path: &Path, bytes: &[u8], private: bool) -> Result<()> {
+        self.create_file(path, private)?
+            .write_all(bytes)
+            .map_err(|e| Error::WriteFileError(e).into())
+    }
+
+    /// Create a directory and its missing parents.  Only the directories
+    /// that are created are given to the owner.
+    pub fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let missing: Vec<&Path> = path
+            .ancestors()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .take_while(|dir| !dir.exists())
+            .collect();
+        fs::create_dir_all(path).map_err(Error::WriteFileError)?;
+        for dir in missing.iter().rev() {
+            self.set_owner(dir)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,90 @@
+//! Change or remove the password of a private key
+//!
+//! `kt passwd` decrypts the key with the input password, and writes it again
+//! in the same format and encoding, protected by the output password.  With
+//! no output password, the protection is removed.  The output file may be
+//! the input file, as the key is read before it is written.
+//!
+//! Encrypted PKCS8 keys are protected with PBES2, as `--kdf`, `--iterations`,
+//! `--salt-len` and `--cipher` say.  PKCS1 and SEC1 PEM keys keep the
+//! traditional encryption of PEM, and OpenSSH and PuTTY keys their own.
+use anyhow::Result;
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::document::{
+    openssh_docs::key_info_to_openssh, pkcs1_docs::rsa_private_key_to_pk1,
+    pkcs8_docs::{private_key_info_to_pk8, Pkcs8Version}, ppk_docs::key_info_to_ppk,
+    sec1_docs::private_key_info_to_sec1,
+};
+use crate::errors::Error;
+use crate::events::{self, Event};
+use crate::key_info::{Encoding, Format, KeyType};
+
+/// Write the input key again, protected by the output password
+pub fn passwd(app_state: &mut AppState) -> Result<()> {
+    let key_info = discover(app_state)?;
+    if key_info.key_type != KeyType::Private {
+        let message = "Only private keys are password protected";
+        events::emit(Event::warning("not-private", message).with_text(message));
+        return Err(Error::TypeMismatch.into());
+    }
+    if !key_info.encrypted && app_state.out_password.is_none() {
+        let message = "The key is not password protected, and no new password was given";
+        events::emit(Event::warning("not-encrypted", message).with_text(message));
+        return Err(Error::MissingInput("output password".to_owned()).into());
+    }
+    let format = key_info.format;
+    if app_state.out_password.is_some()
+        && matches!(format, Format::PKCS1 | Format::SEC1)
+        && key_info.encoding != Encoding::PEM
+    {
+        let message = "PKCS1 and SEC1 DER keys can not be password protected";
+        events::emit(Event::warning("encrypted-der", message).with_text(message));
+        return Err(Error::NotSupported.into());
+    }
+
+    app_state.alg = Some(key_info.alg);
+    app_state.key_type = Some(KeyType::Private);
+    app_state.format = Some(format);
+    app_state.encoding = key_info.encoding;
+    // A PKCS8 key keeps its version
+    if format == Format::PKCS8 && key_info.public_key.is_some() {
+        app_state.pkcs8_version = Pkcs8Version::V2;
+    }
+    if app_state.comment.is_none() {
+        app_state.comment = key_info.comment.clone();
+    }
+    // The output may be the input, which has now been read
+    if let Some(out_file) = app_state.out_file.clone() {
+        app_state.create_out_file(&out_file, true)?;
+    }
+    match format {
+        Format::PKCS8 => private_key_info_to_pk8(app_state, &key_info)?,
+        Format::PKCS1 => rsa_private_key_to_pk1(app_state, &key_info)?,
+        Format::SEC1 => private_key_info_to_sec1(app_state, &key_info)?,
+        Format::OPENSSH => key_info_to_openssh(app_state, &key_info)?,
+        Format::PPK => key_info_to_ppk(app_state, &key_info)?,
+        _ => {
+            let message = format!(
+                "kt passwd does not write {:?} keys.  Use kt convert with --outpass",
+                format
+            );
+            events::emit(Event::warning("format-unsupported", &message).with_text(&message));
+            return Err(Error::NotSupported.into());
+        }
+    }
+
+    let name = app_state.in_file.as_deref().unwrap_or("stdin");
+    let message = match (key_info.encrypted, app_state.out_password.is_some()) {
+        (true, true) => format!("Changed the password of {}", name),
+        (true, false) => format!("Removed the password of {}", name),
+        _ => format!("Added a password to {}", name),
+    };
+    events::emit(
+        Event::progress(&message)
+            .with("format", format!("{:?}", format))
+            .with_text(&message),
+    );
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! Passwords for encrypted input
+//!
+//! kt only asks for a password when it finds an encrypted document, such as
+//! an encrypted PKCS8 key or a protected JWK.  The password is asked of the
+//! [PasswordProvider] in the [AppState](crate::app_state::AppState), so an
+//! application that embeds kt can ask its user in its own way, rather than
+//! load the password into the AppState before it is known to be needed.
+//!
+//! ```rust
+//! use std::rc::Rc;
+//! use kt::app_state::AppState;
+//! use kt::password::CallbackPassword;
+//!
+//! let app_state = AppState {
+//!     in_file: Some("key.pem".to_owned()),
+//!     password_provider: Some(Rc::new(CallbackPassword(|name: &str| {
+//!         Ok(format!("password for {}", name))
+//!     }))),
+//!     ..Default::default()
+//! };
+//! assert_eq!(app_state.input_password().unwrap().as_str(), "password for key.pem");
+//! ```
+use anyhow::{bail, Result};
+use log::warn;
+use std::fs::File;
+use std::io::Read;
+use std::rc::Rc;
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+
+/// A source of passwords for encrypted input
+pub trait PasswordProvider {
+    /// The password for the encrypted document `name`, such as a file name
+    fn password(&self, name: &str) -> Result<Zeroizing<String>>;
+}
+
+/// A password given up front, such as `pass:<password>`
+pub struct FixedPassword(pub Zeroizing<String>);
+
+impl PasswordProvider for FixedPassword {
+    fn password(&self, _name: &str) -> Result<Zeroizing<String>> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A password in an environment variable, such as `env:<variable>`
+pub struct EnvPassword(pub String);
+
+impl PasswordProvider for EnvPassword {
+    fn password(&self, _name: &str) -> Result<Zeroizing<String>> {
+        std::env::var(&self.0).map(Zeroizing::new).map_err(|_| {
+            warn!("${} is not set", self.0);
+            Error::MissingInput("password".to_owned()).into()
+        })
+    }
+}
+
+/// A password in a file, such as `file:<filename>`
+pub struct FilePassword(pub String);
+
+impl PasswordProvider for FilePassword {
+    fn password(&self, _name: &str) -> Result<Zeroizing<String>> {
+        let mut file = File::open(&self.0).map_err(Error::ReadFileError)?;
+        let mut buf = Zeroizing::new(String::new());
+        file.read_to_string(&mut buf).map_err(Error::IOEReadError)?;
+        Ok(buf)
+    }
+}
+
+/// A password typed at the terminal, without echo
+pub struct PromptPassword;
+
+impl PasswordProvider for PromptPassword {
+    fn password(&self, name: &str) -> Result<Zeroizing<String>> {
+        rpassword::prompt_password(format!("Password for {}: ", name))
+            .map(Zeroizing::new)
+            .map_err(|_| Error::MissingInput("password".to_owned()).into())
+    }
+}
+
+/// A password from a function, such as a dialog of the embedding application
+pub struct CallbackPassword<F>(pub F)
+where
+    F: Fn(&str) -> Result<String>;
+
+impl<F> PasswordProvider for CallbackPassword<F>
+where
+    F: Fn(&str) -> Result<String>,
+{
+    fn password(&self, name: &str) -> Result<Zeroizing<String>> {
+        (self.0)(name).map(Zeroizing::new)
+    }
+}
+
+/// Parse a password arg, similar to openssl
+///
+/// The arg may be of 4 forms:
+/// 1. "pass:<value>": The value after the colon is the actual password
+/// 2. "env:<value>": The value after the colon is an environment variable
+///    that holds the password
+/// 3. "file:<value>": The value after the colon is a file that holds the
+///    password
+/// 4. "prompt": The password is typed at the terminal
+pub fn parse_provider(arg: &str) -> Result<Rc<dyn PasswordProvider>> {
+    if arg.eq_ignore_ascii_case("prompt") {
+        return Ok(Rc::new(PromptPassword));
+    }
+    let parts = arg.split(':').collect::<Vec<&str>>();
+    // If there's not enough args, bail
+    if parts.len() < 2 {
+        bail!(Error::BadPasswordArg);
+    }
+    let mode = parts[0].to_owned();
+    // If the password contains a ':', join them
+    let target = parts[1..].join("");
+
+    match mode.to_lowercase().as_str() {
+        "pass" => Ok(Rc::new(FixedPassword(Zeroizing::new(target)))),
+        "env" => Ok(Rc::new(EnvPassword(target))),
+        "file" => Ok(Rc::new(FilePassword(target))),
+        _ => bail!(Error::BadPasswordArg),
+    }
+}
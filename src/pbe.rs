@@ -0,0 +1,321 @@
+//! Password based encryption of written keys, with PBES2 ([RFC 8018](https://www.rfc-editor.org/rfc/rfc8018#section-6.2))
+//!
+//! Encrypted PKCS8 keys, and the bags of PKCS12 keystores, are protected
+//! with a key derived from the password, by PBKDF2-HMAC-SHA256 or scrypt,
+//! and AES-CBC.  By default keys are protected as openssl 3 protects them:
+//! PBKDF2 with 2048 iterations, a 16 byte salt, and AES-256-CBC.
+//!
+//! With scrypt, the iterations are the cost N, which is a power of 2, with
+//! a block size of 8 and no parallelism, as `openssl pkcs8 -scrypt` writes.
+//! PBES2 parameters hold the cost in 16 bits, so it is at most 32768.
+//!
+//! The parameters of an encrypted key that is read are kept as a
+//! [PbeInfo], which needs no password, so that the protection of a key can
+//! be audited.
+//!
+//! ```rust
+//! use kt::pbe::{Cipher, Kdf, PbeParams};
+//!
+//! assert_eq!(PbeParams::default().iterations(), 2048);
+//!
+//! let pbe = PbeParams {
+//!     kdf: Kdf::Scrypt,
+//!     iterations: Some(1024),
+//!     cipher: Cipher::Aes128,
+//!     ..Default::default()
+//! };
+//! let (cost, key_size) = pbe
+//!     .with_parameters(|params| {
+//!         Ok((params.kdf.scrypt().map(|scrypt| scrypt.cost_parameter), params.encryption.key_size()))
+//!     })
+//!     .unwrap();
+//! assert_eq!(cost, Some(1024));
+//! assert_eq!(key_size, 16);
+//! ```
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use pkcs8::pkcs5::{pbes1, pbes2, EncryptionScheme};
+use rand_core::{OsRng, RngCore};
+
+use crate::errors::Error;
+
+/// PBKDF2 iterations of written keys.  The same as openssl
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 2048;
+/// scrypt cost of written keys.  The same as openssl
+pub const DEFAULT_SCRYPT_COST: u32 = 16384;
+/// Bytes of the salt of written keys
+pub const DEFAULT_SALT_LEN: usize = 16;
+/// Largest scrypt cost, which PBES2 parameters hold in 16 bits
+const MAX_SCRYPT_COST: u32 = 32768;
+/// scrypt block size
+const SCRYPT_R: u32 = 8;
+/// scrypt parallelism
+const SCRYPT_P: u32 = 1;
+/// Shortest salt that is written, as RFC 8018 recommends
+const MIN_SALT_LEN: usize = 8;
+/// Longest salt that is written
+const MAX_SALT_LEN: usize = 64;
+
+/// Derivation of the encryption key from the password
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Kdf {
+    /// PBKDF2-HMAC-SHA256
+    #[default]
+    Pbkdf2,
+    /// scrypt, which is memory hard
+    Scrypt,
+}
+
+impl Kdf {
+    pub fn all() -> Vec<&'static str> {
+        vec!["pbkdf2", "scrypt"]
+    }
+}
+
+impl FromStr for Kdf {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Kdf> {
+        match s.to_lowercase().as_str() {
+            "pbkdf2" => Ok(Kdf::Pbkdf2),
+            "scrypt" => Ok(Kdf::Scrypt),
+            _ => Err(Error::UnknownKdf.into()),
+        }
+    }
+}
+
+/// Cipher of the encrypted key
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Cipher {
+    /// AES-128-CBC
+    Aes128,
+    /// AES-256-CBC
+    #[default]
+    Aes256,
+}
+
+impl Cipher {
+    pub fn all() -> Vec<&'static str> {
+        vec!["aes128", "aes256"]
+    }
+}
+
+impl FromStr for Cipher {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Cipher> {
+        match s.to_lowercase().replace('-', "").as_str() {
+            "aes128" | "aes128cbc" => Ok(Cipher::Aes128),
+            "aes256" | "aes256cbc" => Ok(Cipher::Aes256),
+            _ => Err(Error::UnknownCipher.into()),
+        }
+    }
+}
+
+/// How written keys are encrypted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PbeParams {
+    pub kdf: Kdf,
+    /// PBKDF2 iterations, or the scrypt cost.  If not provided, the default
+    /// of the KDF
+    pub iterations: Option<u32>,
+    /// Bytes of random salt
+    pub salt_len: usize,
+    pub cipher: Cipher,
+}
+
+impl Default for PbeParams {
+    fn default() -> Self {
+        Self {
+            kdf: Kdf::default(),
+            iterations: None,
+            salt_len: DEFAULT_SALT_LEN,
+            cipher: Cipher::default(),
+        }
+    }
+}
+
+impl PbeParams {
+    /// The PBKDF2 iterations, or the scrypt cost
+    pub fn iterations(&self) -> u32 {
+        match (self.iterations, self.kdf) {
+            (Some(iterations), _) => iterations,
+            (None, Kdf::Pbkdf2) => DEFAULT_PBKDF2_ITERATIONS,
+            (None, Kdf::Scrypt) => DEFAULT_SCRYPT_COST,
+        }
+    }
+
+    /// Call `encrypt` with PBES2 parameters of a new random salt and IV
+    pub fn with_parameters<T>(
+        &self,
+        encrypt: impl FnOnce(pbes2::Parameters) -> Result<T>,
+    ) -> Result<T> {
+        if !(MIN_SALT_LEN..=MAX_SALT_LEN).contains(&self.salt_len) {
+            bail!(Error::BadPbeParams(format!(
+                "the salt must be {} to {} bytes",
+                MIN_SALT_LEN, MAX_SALT_LEN
+            )));
+        }
+        let mut salt = vec![0u8; self.salt_len];
+        let mut iv = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut iv);
+
+        let iterations = self.iterations();
+        let params = match self.kdf {
+            Kdf::Pbkdf2 => {
+                if iterations == 0 {
+                    bail!(Error::BadPbeParams(
+                        "at least 1 iteration is needed".to_owned()
+                    ));
+                }
+                match self.cipher {
+                    Cipher::Aes128 => {
+                        pbes2::Parameters::pbkdf2_sha256_aes128cbc(iterations, &salt, &iv)
+                    }
+                    Cipher::Aes256 => {
+                        pbes2::Parameters::pbkdf2_sha256_aes256cbc(iterations, &salt, &iv)
+                    }
+                }
+            }
+            Kdf::Scrypt => {
+                if !iterations.is_power_of_two() || iterations < 2 {
+                    bail!(Error::BadPbeParams(
+                        "the scrypt cost must be a power of 2".to_owned()
+                    ));
+                }
+                if iterations > MAX_SCRYPT_COST {
+                    bail!(Error::BadPbeParams(format!(
+                        "the scrypt cost can be at most {}",
+                        MAX_SCRYPT_COST
+                    )));
+                }
+                let scrypt_params =
+                    scrypt::Params::new(iterations.trailing_zeros() as u8, SCRYPT_R, SCRYPT_P)
+                        .map_err(|_| Error::BadPbeParams("bad scrypt cost".to_owned()))?;
+                match self.cipher {
+                    Cipher::Aes128 => {
+                        pbes2::Parameters::scrypt_aes128cbc(scrypt_params, &salt, &iv)
+                    }
+                    Cipher::Aes256 => {
+                        pbes2::Parameters::scrypt_aes256cbc(scrypt_params, &salt, &iv)
+                    }
+                }
+            }
+        }
+        .map_err(|e| Error::BadPbeParams(e.to_string()))?;
+        encrypt(params)
+    }
+}
+
+/// How an encrypted key that was read is protected
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PbeInfo {
+    /// PBES1 or PBES2
+    pub scheme: &'static str,
+    /// Key derivation, such as PBKDF2-HMAC-SHA256
+    pub kdf: String,
+    /// PBKDF iterations, or the scrypt cost
+    pub iterations: u32,
+    /// scrypt block size and parallelism
+    pub scrypt: Option<(u16, u16)>,
+    /// Bytes of salt
+    pub salt_len: usize,
+    /// Cipher, such as AES-256-CBC
+    pub cipher: String,
+}
+
+impl PbeInfo {
+    pub fn from_scheme(scheme: &EncryptionScheme) -> Option<Self> {
+        match scheme {
+            EncryptionScheme::Pbes1(params) => {
+                let digest = match params.encryption.digest() {
+                    pbes1::DigestAlgorithm::Md2 => "MD2",
+                    pbes1::DigestAlgorithm::Md5 => "MD5",
+                    pbes1::DigestAlgorithm::Sha1 => "SHA1",
+                };
+                let cipher = match params.encryption.cipher() {
+                    pbes1::SymmetricCipher::DesCbc => "DES-CBC",
+                    pbes1::SymmetricCipher::Rc2Cbc => "RC2-CBC",
+                };
+                Some(Self {
+                    scheme: "PBES1",
+                    kdf: format!("PBKDF1-{}", digest),
+                    iterations: params.iteration_count.into(),
+                    scrypt: None,
+                    salt_len: params.salt.len(),
+                    cipher: cipher.to_owned(),
+                })
+            }
+            EncryptionScheme::Pbes2(params) => {
+                let cipher = match params.encryption.oid() {
+                    pbes2::AES_128_CBC_OID => "AES-128-CBC".to_owned(),
+                    pbes2::AES_192_CBC_OID => "AES-192-CBC".to_owned(),
+                    pbes2::AES_256_CBC_OID => "AES-256-CBC".to_owned(),
+                    oid => oid.to_string(),
+                };
+                let (kdf, iterations, scrypt, salt_len) =
+                    match (params.kdf.pbkdf2(), params.kdf.scrypt()) {
+                        (Some(pbkdf2), _) => (
+                            format!("PBKDF2-{}", prf_name(pbkdf2.prf)),
+                            pbkdf2.iteration_count,
+                            None,
+                            pbkdf2.salt.len(),
+                        ),
+                        (_, Some(scrypt)) => (
+                            "scrypt".to_owned(),
+                            scrypt.cost_parameter.into(),
+                            Some((scrypt.block_size, scrypt.parallelization)),
+                            scrypt.salt.len(),
+                        ),
+                        _ => return None,
+                    };
+                Some(Self {
+                    scheme: "PBES2",
+                    kdf,
+                    iterations,
+                    scrypt,
+                    salt_len,
+                    cipher,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn prf_name(prf: pbes2::Pbkdf2Prf) -> &'static str {
+    match prf {
+        pbes2::Pbkdf2Prf::HmacWithSha1 => "HMAC-SHA1",
+        pbes2::Pbkdf2Prf::HmacWithSha224 => "HMAC-SHA224",
+        pbes2::Pbkdf2Prf::HmacWithSha256 => "HMAC-SHA256",
+        pbes2::Pbkdf2Prf::HmacWithSha384 => "HMAC-SHA384",
+        pbes2::Pbkdf2Prf::HmacWithSha512 => "HMAC-SHA512",
+        _ => "HMAC",
+    }
+}
+
+impl fmt::Display for PbeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Encryption\n\tScheme: {}\n\tKDF: {}",
+            self.scheme, self.kdf
+        )?;
+        match self.scrypt {
+            Some((block_size, parallelism)) => writeln!(
+                f,
+                "\tCost: {}\n\tBlock Size: {}\n\tParallelism: {}",
+                self.iterations, block_size, parallelism
+            )?,
+            None => writeln!(f, "\tIterations: {}", self.iterations)?,
+        }
+        writeln!(
+            f,
+            "\tSalt Length: {}\n\tCipher: {}",
+            self.salt_len, self.cipher
+        )
+    }
+}
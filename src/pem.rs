@@ -0,0 +1,489 @@
+//! PEM armor ([RFC 7468](https://www.rfc-editor.org/rfc/rfc7468))
+//!
+//! Label constants for the documents kt understands, the mapping between
+//! labels and [Format], and helpers to add or remove the armor around any
+//! DER document.
+//!
+//! PKCS1 and SEC1 private keys can also be written with the traditional
+//! OpenSSL encryption of PEM, for tools too old to read encrypted PKCS8.
+//! Keys with that encryption are read with AES-128, AES-192 and AES-256, or
+//! 3DES, in CBC mode.
+//!
+//! ```rust
+//! use kt::pem::{unwrap, wrap, PUBLIC_KEY};
+//!
+//! let der = [0x30, 0x03, 0x02, 0x01, 0x01];
+//! let pem = wrap(PUBLIC_KEY, &der).unwrap();
+//! assert!(pem.starts_with("-----BEGIN PUBLIC KEY-----"));
+//!
+//! let (label, bytes) = unwrap(pem.as_bytes()).unwrap();
+//! assert_eq!(label, PUBLIC_KEY);
+//! assert_eq!(bytes, der);
+//! ```
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use anyhow::{bail, Result};
+use base64ct::{Base64, Base64Unpadded, Base64Url, Base64UrlUnpadded, Encoding as _};
+use md5::{Digest, Md5};
+use pkcs8::der::pem::{self, LineEnding};
+use rand_core::{OsRng, RngCore};
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+use crate::key_info::{Encoding, Format, KeyType};
+use crate::pbe::PbeInfo;
+
+/// PKCS8 PrivateKeyInfo
+pub const PRIVATE_KEY: &str = "PRIVATE KEY";
+/// PKCS8 EncryptedPrivateKeyInfo
+pub const ENCRYPTED_PRIVATE_KEY: &str = "ENCRYPTED PRIVATE KEY";
+/// X.509 SubjectPublicKeyInfo
+pub const PUBLIC_KEY: &str = "PUBLIC KEY";
+/// PKCS1 RSAPrivateKey
+pub const RSA_PRIVATE_KEY: &str = "RSA PRIVATE KEY";
+/// PKCS1 RSAPublicKey
+pub const RSA_PUBLIC_KEY: &str = "RSA PUBLIC KEY";
+/// SEC1 ECPrivateKey
+pub const EC_PRIVATE_KEY: &str = "EC PRIVATE KEY";
+/// SEC1 ECPrivateKey of an SM2 key, as OpenSSL labels it
+pub const SM2_PRIVATE_KEY: &str = "SM2 PRIVATE KEY";
+/// OpenSSL traditional DSAPrivateKey
+pub const DSA_PRIVATE_KEY: &str = "DSA PRIVATE KEY";
+/// PKCS#3 DHParameter
+pub const DH_PARAMETERS: &str = "DH PARAMETERS";
+/// SEC1 ECParameters
+pub const EC_PARAMETERS: &str = "EC PARAMETERS";
+/// OpenSSH openssh-key-v1 private key
+pub const OPENSSH_PRIVATE_KEY: &str = "OPENSSH PRIVATE KEY";
+/// X.509 Certificate
+pub const CERTIFICATE: &str = "CERTIFICATE";
+/// PKCS10 CertificationRequest
+pub const CERTIFICATE_REQUEST: &str = "CERTIFICATE REQUEST";
+/// PKCS7 ContentInfo, such as a certificate bundle
+pub const PKCS7: &str = "PKCS7";
+/// TPM 2.0 TPMKey, wrapped by a TPM
+pub const TSS2_PRIVATE_KEY: &str = "TSS2 PRIVATE KEY";
+
+/// The document format a label identifies, if kt knows it
+pub fn label_to_format(label: &str) -> Option<Format> {
+    match label {
+        PRIVATE_KEY | ENCRYPTED_PRIVATE_KEY => Some(Format::PKCS8),
+        PUBLIC_KEY => Some(Format::SPKI),
+        RSA_PRIVATE_KEY | RSA_PUBLIC_KEY => Some(Format::PKCS1),
+        EC_PRIVATE_KEY | SM2_PRIVATE_KEY => Some(Format::SEC1),
+        DSA_PRIVATE_KEY => Some(Format::DSA),
+        DH_PARAMETERS => Some(Format::DH),
+        OPENSSH_PRIVATE_KEY => Some(Format::OPENSSH),
+        CERTIFICATE => Some(Format::X509),
+        CERTIFICATE_REQUEST => Some(Format::PKCS10),
+        PKCS7 => Some(Format::PKCS7),
+        _ => None,
+    }
+}
+
+/// The label for a document format and key type.
+///
+/// `encrypted` only applies to PKCS8 private keys.
+pub fn format_to_label(format: Format, key_type: KeyType, encrypted: bool) -> Result<&'static str> {
+    match (format, key_type) {
+        (Format::PKCS8, KeyType::Private) if encrypted => Ok(ENCRYPTED_PRIVATE_KEY),
+        (Format::PKCS8, KeyType::Private) => Ok(PRIVATE_KEY),
+        (Format::PKCS8 | Format::SPKI, KeyType::Public) => Ok(PUBLIC_KEY),
+        (Format::PKCS1, KeyType::Private) => Ok(RSA_PRIVATE_KEY),
+        (Format::PKCS1, KeyType::Public) => Ok(RSA_PUBLIC_KEY),
+        (Format::SEC1, KeyType::Private) => Ok(EC_PRIVATE_KEY),
+        (Format::DH, KeyType::Parameters) => Ok(DH_PARAMETERS),
+        _ => Err(Error::UnknownFormat.into()),
+    }
+}
+
+/// Whether a label kt does not know still names a key document, such as
+/// `ECDSA PRIVATE KEY` or `ED25519 PUBLIC KEY`, so that the DER it holds
+/// can be discovered.  OpenPGP armor and PEM encryption headers are not
+/// taken.
+pub fn is_recognizable_label(label: &str) -> bool {
+    label_to_format(label).is_none()
+        && !label.starts_with("PGP ")
+        && (label.ends_with("PRIVATE KEY") || label.ends_with("PUBLIC KEY"))
+}
+
+/// The DER document of a PEM document with a nonstandard but recognizable
+/// label
+pub fn unwrap_nonstandard(pem: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(pem).ok()?.trim_start();
+    match label(text.as_bytes()) {
+        Ok(label) if is_recognizable_label(label) => {
+            unwrap(text.as_bytes()).ok().map(|(_, der)| der)
+        }
+        _ => None,
+    }
+}
+
+/// Read the label of a PEM document, without decoding it
+pub fn label(pem: &[u8]) -> Result<&str> {
+    pem::decode_label(pem).map_err(|e| Error::BadPem(e.to_string()).into())
+}
+
+/// Wrap a DER document in PEM armor, with CRLF line endings
+pub fn wrap(label: &str, der: &[u8]) -> Result<String> {
+    pem::encode_string(label, LineEnding::CRLF, der)
+        .map_err(|e| Error::BadPem(e.to_string()).into())
+}
+
+/// The key of traditional PEM encryption, from OpenSSL's EVP_BytesToKey
+/// with MD5, one iteration, and the first 8 bytes of the IV as the salt
+fn bytes_to_key(password: &str, salt: &[u8], key_len: usize) -> Zeroizing<Vec<u8>> {
+    let mut key = Zeroizing::new(Vec::with_capacity(key_len + 16));
+    let mut block: Vec<u8> = Vec::new();
+    while key.len() < key_len {
+        let input = Zeroizing::new([&block, password.as_bytes(), salt].concat());
+        block = Md5::digest(&input).to_vec();
+        key.extend_from_slice(&block);
+    }
+    key.truncate(key_len);
+    key
+}
+
+/// Wrap a PKCS1 or SEC1 private key in PEM armor, encrypted with AES-256-CBC
+/// as OpenSSL traditionally encrypts PEM, with the `Proc-Type` and
+/// `DEK-Info` headers
+pub fn wrap_encrypted(label: &str, der: &[u8], password: &str) -> Result<String> {
+    let mut iv = [0u8; 16];
+    OsRng.fill_bytes(&mut iv);
+    let key = bytes_to_key(password, &iv[..8], 32);
+    let ciphertext = cbc::Encryptor::<aes::Aes256>::new_from_slices(&key, &iv)
+        .map_err(|_| Error::BadCrypto)?
+        .encrypt_padded_vec_mut::<Pkcs7>(der);
+
+    let body = Base64::encode_string(&ciphertext);
+    let mut text = format!("-----BEGIN {}-----\r\n", label);
+    text.push_str("Proc-Type: 4,ENCRYPTED\r\n");
+    text.push_str(&format!("DEK-Info: AES-256-CBC,{}\r\n\r\n", hex::encode_upper(iv)));
+    for line in body.as_bytes().chunks(64) {
+        text.push_str(std::str::from_utf8(line).map_err(|e| Error::BadPem(e.to_string()))?);
+        text.push_str("\r\n");
+    }
+    text.push_str(&format!("-----END {}-----\r\n", label));
+    Ok(text)
+}
+
+/// A PEM document with traditional encryption
+struct EncryptedPem {
+    label: String,
+    /// Cipher of the DEK-Info header, such as AES-256-CBC
+    cipher: String,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+}
+
+/// Whether the text is PEM with traditional encryption, which has the
+/// `Proc-Type: 4,ENCRYPTED` header
+pub fn is_encrypted(text: &str) -> bool {
+    text.lines().any(|line| line.trim() == "Proc-Type: 4,ENCRYPTED")
+}
+
+fn bad_pem(message: &str) -> anyhow::Error {
+    Error::BadPem(message.to_owned()).into()
+}
+
+/// Split PEM with traditional encryption into its label, DEK-Info and body
+fn parse_encrypted(text: &str) -> Result<EncryptedPem> {
+    let mut lines = text.lines().map(str::trim).skip_while(|line| !line.starts_with("-----BEGIN "));
+    let label = lines
+        .next()
+        .and_then(|line| line.strip_prefix("-----BEGIN "))
+        .and_then(|line| line.strip_suffix("-----"))
+        .ok_or_else(|| bad_pem("no BEGIN line"))?
+        .to_owned();
+    let mut dek_info = None;
+    let mut body = String::new();
+    for line in lines {
+        if line.starts_with("-----END ") {
+            break;
+        }
+        match line.split_once(':') {
+            Some(("DEK-Info", value)) => dek_info = Some(value.trim().to_owned()),
+            Some(_) => {}
+            None => body.push_str(line),
+        }
+    }
+    let (cipher, iv) = dek_info
+        .as_deref()
+        .and_then(|dek_info| dek_info.split_once(','))
+        .ok_or_else(|| bad_pem("no DEK-Info header"))?;
+    Ok(EncryptedPem {
+        label,
+        cipher: cipher.trim().to_uppercase(),
+        iv: hex::decode(iv.trim()).map_err(|_| bad_pem("bad DEK-Info IV"))?,
+        ciphertext: Base64::decode_vec(&body).map_err(|e| Error::BadPem(e.to_string()))?,
+    })
+}
+
+/// The key length and IV length of a DEK-Info cipher
+fn dek_cipher_sizes(cipher: &str) -> Result<(usize, usize)> {
+    match cipher {
+        "AES-128-CBC" => Ok((16, 16)),
+        "AES-192-CBC" => Ok((24, 16)),
+        "AES-256-CBC" => Ok((32, 16)),
+        "DES-EDE3-CBC" => Ok((24, 8)),
+        _ => Err(Error::BadPem(format!("unsupported cipher {}", cipher)).into()),
+    }
+}
+
+/// How PEM with traditional encryption is encrypted, which is known without
+/// the password
+pub fn encrypted_info(text: &str) -> Result<PbeInfo> {
+    let pem = parse_encrypted(text)?;
+    dek_cipher_sizes(&pem.cipher)?;
+    Ok(PbeInfo {
+        scheme: "PEM",
+        kdf: "EVP_BytesToKey-MD5".to_owned(),
+        iterations: 1,
+        scrypt: None,
+        salt_len: 8,
+        cipher: pem.cipher,
+    })
+}
+
+/// Decrypt PEM with traditional encryption, returning the label and the DER
+/// document.  A wrong password is Error::BadPassword, if the padding shows
+/// it.
+pub fn unwrap_encrypted(text: &str, password: &str) -> Result<(String, Zeroizing<Vec<u8>>)> {
+    let pem = parse_encrypted(text)?;
+    let (key_len, iv_len) = dek_cipher_sizes(&pem.cipher)?;
+    if pem.iv.len() != iv_len {
+        bail!(bad_pem("bad DEK-Info IV"));
+    }
+    let key = bytes_to_key(password, &pem.iv[..8], key_len);
+    let ciphertext = &pem.ciphertext;
+    let der = match pem.cipher.as_str() {
+        "AES-128-CBC" => cbc::Decryptor::<aes::Aes128>::new_from_slices(&key, &pem.iv)
+            .map_err(|_| Error::BadCrypto)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        "AES-192-CBC" => cbc::Decryptor::<aes::Aes192>::new_from_slices(&key, &pem.iv)
+            .map_err(|_| Error::BadCrypto)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        "AES-256-CBC" => cbc::Decryptor::<aes::Aes256>::new_from_slices(&key, &pem.iv)
+            .map_err(|_| Error::BadCrypto)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+        _ => cbc::Decryptor::<des::TdesEde3>::new_from_slices(&key, &pem.iv)
+            .map_err(|_| Error::BadCrypto)?
+            .decrypt_padded_vec_mut::<Pkcs7>(ciphertext),
+    }
+    .map_err(|_| Error::BadPassword)?;
+    Ok((pem.label, Zeroizing::new(der)))
+}
+
+/// Remove the PEM armor, returning the label and the DER document
+pub fn unwrap(pem: &[u8]) -> Result<(String, Vec<u8>)> {
+    let (label, der) = pem::decode_vec(pem).map_err(|e| Error::BadPem(e.to_string()))?;
+    Ok((label.to_owned(), der))
+}
+
+/// Remove the PEM armor from every document in the text, such as each
+/// certificate of a chain.  Text outside of the documents is ignored.
+pub fn unwrap_all(pem: &[u8]) -> Result<Vec<(String, Vec<u8>)>> {
+    const BEGIN: &str = "-----BEGIN ";
+    const END: &str = "-----END ";
+    const DASHES: &str = "-----";
+
+    let text = std::str::from_utf8(pem).map_err(|e| Error::BadPem(e.to_string()))?;
+    let mut documents = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find(BEGIN) {
+        let block = &rest[start..];
+        let end = block
+            .find(END)
+            .and_then(|end| {
+                let after = end + END.len();
+                block[after..].find(DASHES).map(|dashes| after + dashes + DASHES.len())
+            })
+            .ok_or_else(|| Error::BadPem("missing end of document".to_owned()))?;
+        documents.push(unwrap(&block.as_bytes()[..end])?);
+        rest = &block[end..];
+    }
+    Ok(documents)
+}
+
+/// Read a certificate chain file, as PEM or a single DER certificate
+pub fn read_certs(cert_file: &str) -> Result<Vec<Vec<u8>>> {
+    let bytes = std::fs::read(cert_file).map_err(Error::ReadFileError)?;
+    if bytes.first() == Some(&0x30) {
+        return Ok(vec![bytes]);
+    }
+    let certs: Vec<Vec<u8>> = unwrap_all(&bytes)?
+        .into_iter()
+        .filter(|(label, _)| label == CERTIFICATE)
+        .map(|(_, der)| der)
+        .collect();
+    if certs.is_empty() {
+        bail!(Error::BadPem(format!("{}: no certificates", cert_file)));
+    }
+    Ok(certs)
+}
+
+/// A DER document as a single line of base64, with no label and no line
+/// breaks.  B64URL is unpadded, as JOSE writes it.
+pub fn to_base64_line(der: &[u8], encoding: Encoding) -> Result<String> {
+    match encoding {
+        Encoding::B64 => Ok(Base64::encode_string(der)),
+        Encoding::B64URL => Ok(Base64UrlUnpadded::encode_string(der)),
+        _ => bail!(Error::UnknownEncoding),
+    }
+}
+
+/// The DER document of a single line of base64, in either alphabet, padded
+/// or not.  Unless the encoding is given, only a document that starts as a
+/// DER SEQUENCE is taken, so that other text is not mistaken for base64.
+pub fn from_base64_line(bytes: &[u8], in_format: Option<Encoding>) -> Option<(Encoding, Vec<u8>)> {
+    let text = std::str::from_utf8(bytes).ok()?.trim();
+    if text.is_empty() || text.contains(char::is_whitespace) {
+        return None;
+    }
+    let decoded = Base64::decode_vec(text)
+        .or_else(|_| Base64Unpadded::decode_vec(text))
+        .map(|der| (Encoding::B64, der))
+        .or_else(|_| {
+            Base64Url::decode_vec(text)
+                .or_else(|_| Base64UrlUnpadded::decode_vec(text))
+                .map(|der| (Encoding::B64URL, der))
+        })
+        .ok()?;
+    match in_format {
+        Some(Encoding::B64 | Encoding::B64URL) => Some(decoded),
+        _ if decoded.1.first() == Some(&0x30) => Some(decoded),
+        _ => None,
+    }
+}
+
+/// PEM text with its common damage repaired, and what was repaired: a byte
+/// order mark or UTF-16 text, mixed line endings, stray whitespace, blank
+/// lines in the base64, base64 that was wrapped again at another width, and
+/// a missing final newline.  Text outside of the documents is kept as it
+/// is.  None if the bytes are not PEM, or there is nothing to repair.
+///
+/// ```rust
+/// use kt::pem::repair;
+///
+/// let text = b"\xef\xbb\xbf-----BEGIN PUBLIC KEY-----\r\n  MAMCAQE=\n-----END PUBLIC KEY-----";
+/// let (pem, fixes) = repair(text).unwrap();
+/// assert_eq!(pem, b"-----BEGIN PUBLIC KEY-----\nMAMCAQE=\n-----END PUBLIC KEY-----\n");
+/// assert_eq!(fixes.len(), 4);
+/// ```
+pub fn repair(bytes: &[u8]) -> Option<(Vec<u8>, Vec<&'static str>)> {
+    let mut fixes = Vec::new();
+    let text = match bytes {
+        [0xef, 0xbb, 0xbf, rest @ ..] => {
+            fixes.push("removed the byte order mark");
+            String::from_utf8(rest.to_vec()).ok()?
+        }
+        [0xff, 0xfe, rest @ ..] | [0xfe, 0xff, rest @ ..] if rest.len() % 2 == 0 => {
+            let units = rest.chunks(2).map(|unit| match bytes[0] {
+                0xff => u16::from_le_bytes([unit[0], unit[1]]),
+                _ => u16::from_be_bytes([unit[0], unit[1]]),
+            });
+            fixes.push("converted the UTF-16 text to UTF-8");
+            char::decode_utf16(units).collect::<Result<String, _>>().ok()?
+        }
+        _ => String::from_utf8(bytes.to_vec()).ok()?,
+    };
+    if !text.contains("-----BEGIN ") {
+        return None;
+    }
+
+    let crlf = text.matches("\r\n").count();
+    let cr = text.matches('\r').count() - crlf;
+    let lf = text.matches('\n').count() - crlf;
+    if cr > 0 || (crlf > 0 && lf > 0) {
+        fixes.push("made the line endings consistent");
+    }
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    /// A document being read: its headers, its base64 and the length of
+    /// each line of base64
+    #[derive(Default)]
+    struct Body<'a> {
+        headers: Vec<&'a str>,
+        separated: bool,
+        base64: String,
+        lines: Vec<usize>,
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut body: Option<Body> = None;
+    let (mut whitespace, mut blank, mut rewrapped, mut separator) = (false, false, false, false);
+    for line in text.lines() {
+        let trimmed = line.trim();
+        let armor = trimmed.starts_with("-----") && trimmed.ends_with("-----");
+        let current = match body.as_mut() {
+            Some(current) => current,
+            None => {
+                if armor && trimmed.starts_with("-----BEGIN ") {
+                    whitespace |= trimmed.len() != line.len();
+                    body = Some(Body::default());
+                    out.push_str(trimmed);
+                } else {
+                    out.push_str(line);
+                }
+                out.push('\n');
+                continue;
+            }
+        };
+        whitespace |= trimmed.len() != line.len();
+        if armor && trimmed.starts_with("-----END ") {
+            for header in &current.headers {
+                out.push_str(header);
+                out.push('\n');
+            }
+            if !current.headers.is_empty() {
+                separator |= !current.separated;
+                out.push('\n');
+            }
+            // Every line but the last is full
+            let (last, full) = current.lines.split_last().unwrap_or((&64, &[]));
+            rewrapped |= *last > 64 || full.iter().any(|len| *len != 64);
+            for chunk in current.base64.as_bytes().chunks(64) {
+                out.push_str(std::str::from_utf8(chunk).ok()?);
+                out.push('\n');
+            }
+            out.push_str(trimmed);
+            out.push('\n');
+            body = None;
+        } else if trimmed.is_empty() {
+            if current.headers.is_empty() || current.separated || !current.base64.is_empty() {
+                blank = true;
+            } else {
+                current.separated = true;
+            }
+        } else if current.base64.is_empty() && trimmed.contains(':') {
+            current.headers.push(trimmed);
+        } else {
+            let base64: String = trimmed.split_whitespace().collect();
+            whitespace |= base64.len() != trimmed.len();
+            current.lines.push(base64.len());
+            current.base64.push_str(&base64);
+        }
+    }
+    // A document with no END line is beyond repair
+    if body.is_some() {
+        return None;
+    }
+    if whitespace {
+        fixes.push("removed stray whitespace");
+    }
+    if blank {
+        fixes.push("removed blank lines from the base64");
+    }
+    if rewrapped {
+        fixes.push("wrapped the base64 again at 64 characters");
+    }
+    if separator {
+        fixes.push("added the blank line after the encryption headers");
+    }
+    if !text.ends_with('\n') {
+        fixes.push("added the final newline");
+    }
+    if fixes.is_empty() {
+        None
+    } else {
+        Some((out.into_bytes(), fixes))
+    }
+}
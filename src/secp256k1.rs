@@ -0,0 +1,84 @@
+//! The secp256k1 curve, for Ethereum and Bitcoin keys ([SEC 2](https://www.secg.org/sec2-v2.pdf))
+//!
+//! Only what is needed to read and write keys is here: the public point of a
+//! private key, and the uncompressed form of a compressed point.  The
+//! arithmetic is that of the [k256] crate, which is constant time.
+use anyhow::Result;
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use pkcs8::der::Encodable;
+use rand_core::CryptoRngCore;
+use sec1::{EcParameters, EcPrivateKey};
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+use crate::oids;
+
+/// Size in bytes of a field element and of a scalar
+pub const FIELD_SIZE: usize = 32;
+
+/// The secret key of a big endian scalar, which may leave out its leading
+/// zeros
+fn secret_key(scalar: &[u8]) -> Option<k256::SecretKey> {
+    let leading_zeros = scalar.iter().take_while(|byte| **byte == 0).count();
+    let scalar = &scalar[leading_zeros..];
+    if scalar.len() > FIELD_SIZE {
+        return None;
+    }
+    let mut bytes = Zeroizing::new([0u8; FIELD_SIZE]);
+    bytes[FIELD_SIZE - scalar.len()..].copy_from_slice(scalar);
+    k256::SecretKey::from_bytes(k256::FieldBytes::from_slice(&bytes[..])).ok()
+}
+
+/// Test whether the big endian scalar is a private key: from 1 to n - 1
+pub fn is_valid_scalar(scalar: &[u8]) -> bool {
+    secret_key(scalar).is_some()
+}
+
+/// The uncompressed public point of the big endian private scalar
+///
+/// ```rust
+/// use kt::secp256k1::public_point;
+///
+/// // The base point is the public key of 1
+/// let point = public_point(&[1]).unwrap();
+/// assert_eq!(
+///     hex::encode(&point[1..33]),
+///     "79be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798"
+/// );
+/// ```
+pub fn public_point(scalar: &[u8]) -> Result<Vec<u8>> {
+    let secret = secret_key(scalar).ok_or(Error::BadCrypto)?;
+    Ok(secret.public_key().to_encoded_point(false).as_bytes().to_vec())
+}
+
+/// The uncompressed form of a SEC1 point, which is checked to be on the
+/// curve
+pub fn uncompressed_point(point: &[u8]) -> Result<Vec<u8>> {
+    let public_key = k256::PublicKey::from_sec1_bytes(point).map_err(|_| Error::BadEcPoint)?;
+    Ok(public_key.to_encoded_point(false).as_bytes().to_vec())
+}
+
+/// A SEC1 ECPrivateKey document of the secret key, with the named curve and
+/// the public key
+fn secret_sec1_der(secret: &k256::SecretKey) -> Result<Zeroizing<Vec<u8>>> {
+    let scalar = Zeroizing::new(secret.to_bytes().to_vec());
+    let public_key = secret.public_key().to_encoded_point(false);
+    let private_key = EcPrivateKey {
+        private_key: &scalar,
+        parameters: Some(EcParameters::NamedCurve(oids::SECP256K1)),
+        public_key: Some(public_key.as_bytes()),
+    };
+    Ok(Zeroizing::new(private_key.to_vec()?))
+}
+
+/// A SEC1 ECPrivateKey document of the scalar, with the named curve and the
+/// public key
+pub fn sec1_der(scalar: &[u8]) -> Result<Zeroizing<Vec<u8>>> {
+    secret_sec1_der(&secret_key(scalar).ok_or(Error::BadCrypto)?)
+}
+
+/// A SEC1 ECPrivateKey document of a random private key, from the random
+/// number generator
+pub fn random_sec1_der(mut rng: &mut dyn CryptoRngCore) -> Result<Zeroizing<Vec<u8>>> {
+    secret_sec1_der(&k256::SecretKey::random(&mut rng))
+}
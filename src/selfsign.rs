@@ -0,0 +1,264 @@
+//! Self-signed X.509 certificates ([RFC 5280](https://www.rfc-editor.org/rfc/rfc5280))
+//!
+//! A v3 certificate is built from a private key, and signed with that key,
+//! for TLS testing.  The issuer is the subject, and the serial number is 16
+//! random bytes.  The validity starts now, and lasts for a number of days.
+//!
+//! The certificate has these extensions:
+//!
+//! * basicConstraints, critical - `CA:TRUE` only if the certificate is to
+//!   sign other certificates.
+//! * keyUsage, critical - by default `digitalSignature`, and
+//!   `keyEncipherment` for RSA keys.  CA certificates add `keyCertSign` and
+//!   `cRLSign`.
+//! * subjectKeyIdentifier - the SHA-1 of the public key, as openssl writes
+//!   it.
+//! * subjectAltName, if names are given.  It is critical if the subject is
+//!   empty.
+//!
+//! ```rust
+//! use kt::selfsign::{key_usage_der, KeyUsage};
+//!
+//! // digitalSignature and keyEncipherment: a BIT STRING of 5 unused bits
+//! let der = key_usage_der(&[KeyUsage::DigitalSignature, KeyUsage::KeyEncipherment]).unwrap();
+//! assert_eq!(der, [0x03, 0x02, 0x05, 0xa0]);
+//! ```
+use anyhow::{bail, Result};
+use chrono::{DateTime, Datelike, Duration, Utc};
+use der::asn1::{ObjectIdentifier, UIntBytes};
+use der::{Decodable, Encodable, Tag, TagNumber};
+use rand_core::{OsRng, RngCore};
+use sha1::{Digest, Sha1};
+use spki::SubjectPublicKeyInfo;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::app_state::AppState;
+use crate::csr::{
+    extension_der, general_names_der, name_der, parse_sans, parse_subject, signature_alg_id_der,
+    signed_der, tlv, SubjectAltName, SUBJECT_ALT_NAME,
+};
+use crate::discover::discover;
+use crate::document::spki_docs::public_spki_der;
+use crate::errors::Error;
+use crate::key_info::{Alg, Encoding, KeyInfo, KeyType};
+use crate::oids;
+use crate::pem;
+use crate::signature::SignatureScheme;
+
+/// X.509 subjectKeyIdentifier extension
+const SUBJECT_KEY_IDENTIFIER: ObjectIdentifier = ObjectIdentifier::new("2.5.29.14");
+/// X.509 keyUsage extension
+const KEY_USAGE: ObjectIdentifier = ObjectIdentifier::new("2.5.29.15");
+/// X.509 basicConstraints extension
+const BASIC_CONSTRAINTS: ObjectIdentifier = ObjectIdentifier::new("2.5.29.19");
+
+/// Days a certificate is valid for, if not given.  The same as openssl
+pub const DEFAULT_DAYS: u32 = 30;
+
+/// Bits of the keyUsage extension
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyUsage {
+    DigitalSignature,
+    NonRepudiation,
+    KeyEncipherment,
+    DataEncipherment,
+    KeyAgreement,
+    KeyCertSign,
+    CrlSign,
+    EncipherOnly,
+    DecipherOnly,
+}
+
+impl KeyUsage {
+    pub fn all() -> Vec<&'static str> {
+        vec![
+            "digitalSignature",
+            "nonRepudiation",
+            "keyEncipherment",
+            "dataEncipherment",
+            "keyAgreement",
+            "keyCertSign",
+            "cRLSign",
+            "encipherOnly",
+            "decipherOnly",
+        ]
+    }
+
+    /// The bit of the usage in the BIT STRING, from 0
+    fn bit(&self) -> usize {
+        *self as usize
+    }
+}
+
+impl FromStr for KeyUsage {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<KeyUsage> {
+        match s.to_lowercase().as_str() {
+            "digitalsignature" => Ok(KeyUsage::DigitalSignature),
+            "nonrepudiation" => Ok(KeyUsage::NonRepudiation),
+            "keyencipherment" => Ok(KeyUsage::KeyEncipherment),
+            "dataencipherment" => Ok(KeyUsage::DataEncipherment),
+            "keyagreement" => Ok(KeyUsage::KeyAgreement),
+            "keycertsign" => Ok(KeyUsage::KeyCertSign),
+            "crlsign" => Ok(KeyUsage::CrlSign),
+            "encipheronly" => Ok(KeyUsage::EncipherOnly),
+            "decipheronly" => Ok(KeyUsage::DecipherOnly),
+            _ => Err(Error::UnknownKeyUsage.into()),
+        }
+    }
+}
+
+impl fmt::Display for KeyUsage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", KeyUsage::all()[self.bit()])
+    }
+}
+
+/// The usages of a key, if none are given
+pub fn default_key_usage(key_info: &KeyInfo, ca: bool) -> Vec<KeyUsage> {
+    let mut usage = vec![KeyUsage::DigitalSignature];
+    if key_info.alg == Alg::Rsa && key_info.oid != Some(oids::RSASSA_PSS) {
+        usage.push(KeyUsage::KeyEncipherment);
+    }
+    if ca {
+        usage.extend([KeyUsage::KeyCertSign, KeyUsage::CrlSign]);
+    }
+    usage
+}
+
+/// The keyUsage BIT STRING.  DER drops the trailing zero bits, and counts
+/// them as unused.
+pub fn key_usage_der(usage: &[KeyUsage]) -> Result<Vec<u8>> {
+    let mut bits = [0u8; 2];
+    for key_usage in usage {
+        bits[key_usage.bit() / 8] |= 0x80 >> (key_usage.bit() % 8);
+    }
+    let len = bits.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    let unused = bits[..len].last().map_or(0, |b| b.trailing_zeros() as u8);
+    tlv(Tag::BitString, &[&[unused][..], &bits[..len]].concat())
+}
+
+/// A Time of the validity.  Dates before 2050 are UTCTime, and later dates
+/// are GeneralizedTime.
+fn time_der(time: DateTime<Utc>) -> Result<Vec<u8>> {
+    match time.year() < 2050 {
+        true => tlv(Tag::UtcTime, time.format("%y%m%d%H%M%SZ").to_string().as_bytes()),
+        false => tlv(
+            Tag::GeneralizedTime,
+            time.format("%Y%m%d%H%M%SZ").to_string().as_bytes(),
+        ),
+    }
+}
+
+/// A random, positive, serial number of 16 bytes
+fn serial_number_der() -> Result<Vec<u8>> {
+    let mut serial = [0u8; 16];
+    OsRng.fill_bytes(&mut serial);
+    serial[0] = (serial[0] & 0x7f) | 0x01;
+    Ok(UIntBytes::new(&serial)?.to_vec()?)
+}
+
+/// The extensions of the certificate, as the `[3]` EXPLICIT Extensions
+fn extensions_der(
+    spki_der: &[u8],
+    usage: &[KeyUsage],
+    ca: bool,
+    sans: &[SubjectAltName],
+    empty_subject: bool,
+) -> Result<Vec<u8>> {
+    let key_id = Sha1::digest(SubjectPublicKeyInfo::from_der(spki_der)?.subject_public_key);
+
+    let basic_constraints = match ca {
+        true => tlv(Tag::Sequence, &true.to_vec()?)?,
+        false => tlv(Tag::Sequence, &[])?,
+    };
+    let mut extensions = [
+        extension_der(BASIC_CONSTRAINTS, true, &basic_constraints)?,
+        extension_der(KEY_USAGE, true, &key_usage_der(usage)?)?,
+        extension_der(SUBJECT_KEY_IDENTIFIER, false, &tlv(Tag::OctetString, &key_id)?)?,
+    ]
+    .concat();
+    if !sans.is_empty() {
+        extensions.extend(extension_der(
+            SUBJECT_ALT_NAME,
+            empty_subject,
+            &general_names_der(sans)?,
+        )?);
+    }
+    tlv(
+        Tag::ContextSpecific {
+            constructed: true,
+            number: TagNumber::N3,
+        },
+        &tlv(Tag::Sequence, &extensions)?,
+    )
+}
+
+/// Build a DER self-signed certificate for a private key
+pub fn build_certificate(
+    key_info: &KeyInfo,
+    subject: &[(ObjectIdentifier, String)],
+    sans: &[SubjectAltName],
+    days: u32,
+    usage: &[KeyUsage],
+    ca: bool,
+) -> Result<Vec<u8>> {
+    if key_info.key_type != KeyType::Private {
+        bail!(Error::TypeMismatch);
+    }
+    if subject.is_empty() && sans.is_empty() {
+        bail!(Error::MissingInput("subject or subject alternative name".to_owned()));
+    }
+    let scheme = SignatureScheme::for_key(key_info)?;
+    let spki = public_spki_der(key_info)?;
+    let name = name_der(subject)?;
+    let not_before = Utc::now();
+    let not_after = not_before + Duration::days(days as i64);
+
+    // v3 is the INTEGER 2, in an EXPLICIT [0]
+    let version = tlv(
+        Tag::ContextSpecific {
+            constructed: true,
+            number: TagNumber::N0,
+        },
+        &UIntBytes::new(&[2])?.to_vec()?,
+    )?;
+    let validity = tlv(
+        Tag::Sequence,
+        &[time_der(not_before)?, time_der(not_after)?].concat(),
+    )?;
+    let tbs = tlv(
+        Tag::Sequence,
+        &[
+            version,
+            serial_number_der()?,
+            signature_alg_id_der(scheme)?,
+            name.clone(),
+            validity,
+            name,
+            spki.clone(),
+            extensions_der(&spki, usage, ca, sans, subject.is_empty())?,
+        ]
+        .concat(),
+    )?;
+    signed_der(key_info, scheme, tbs)
+}
+
+/// Write a self-signed certificate for the input private key, as PEM or DER
+pub fn selfsign(app_state: &mut AppState) -> Result<()> {
+    let subject = parse_subject(app_state.subject.as_deref().unwrap_or_default())?;
+    let sans = parse_sans(&app_state.sans)?;
+    let key_info = discover(app_state)?;
+    let usage = match app_state.key_usage.is_empty() {
+        true => default_key_usage(&key_info, app_state.ca),
+        false => app_state.key_usage.clone(),
+    };
+    let der = build_certificate(&key_info, &subject, &sans, app_state.days, &usage, app_state.ca)?;
+    match app_state.encoding {
+        Encoding::DER => app_state.write_stream(&der),
+        Encoding::PEM => app_state.write_stream(pem::wrap(pem::CERTIFICATE, &der)?.as_bytes()),
+        _ => bail!(Error::NotSupported),
+    }
+}
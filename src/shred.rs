@@ -0,0 +1,109 @@
+//! Overwrite and remove key files
+//!
+//! Each file is overwritten with random bytes, synced to disk after every
+//! pass, truncated, and then removed.  This is best effort: SSDs remap
+//! writes to other flash cells, and copy-on-write or journaling file systems
+//! (btrfs, ZFS, APFS), snapshots and backups keep old copies of the data, so
+//! the key may still be recoverable from the device.  Full disk encryption
+//! is the only reliable protection on such storage.
+//!
+//! Only files that are recognized as keys are shredded, unless forced, so
+//! that a mistyped name doesn't destroy some other file.
+use anyhow::{bail, Result};
+use log::info;
+use rand_core::{OsRng, RngCore};
+use std::fs::{self, OpenOptions};
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+use zeroize::Zeroizing;
+
+use crate::app_state::AppState;
+use crate::discover::discover;
+use crate::errors::Error;
+use crate::events::{self, Event};
+
+/// Passes of random bytes written over a file, if not provided
+pub const DEFAULT_PASSES: u32 = 3;
+
+/// Test whether the file can be read as a key.  Encrypted keys are keys,
+/// even without the password.
+fn is_key_file(app_state: &AppState, path: &Path) -> Result<bool> {
+    let mut file_state = AppState {
+        in_file: Some(path.display().to_string()),
+        in_stream: Box::new(fs::File::open(path).map_err(Error::ReadFileError)?),
+        curve: app_state.curve,
+        ..Default::default()
+    };
+    let key = match discover(&mut file_state) {
+        Ok(_) => true,
+        Err(e) => match e.downcast_ref::<Error>() {
+            Some(Error::BadPassword) => true,
+            Some(Error::MissingInput(what)) => what == "password",
+            _ => false,
+        },
+    };
+    Ok(key)
+}
+
+/// Overwrite the file with `passes` passes of random bytes, then truncate
+/// and remove it.
+///
+/// Symbolic links and special files are refused, rather than overwriting
+/// whatever they point to.
+pub fn shred_file(path: &Path, passes: u32) -> Result<()> {
+    let metadata = fs::symlink_metadata(path).map_err(Error::ReadFileError)?;
+    if !metadata.file_type().is_file() {
+        bail!(Error::NotAFile(path.display().to_string()));
+    }
+    let len = metadata.len();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(path)
+        .map_err(Error::WriteFileError)?;
+    let mut block = Zeroizing::new(vec![0u8; 4096]);
+    for _ in 0..passes {
+        file.seek(SeekFrom::Start(0)).map_err(Error::WriteFileError)?;
+        let mut remaining = len;
+        while remaining > 0 {
+            let size = remaining.min(block.len() as u64) as usize;
+            OsRng.fill_bytes(&mut block[..size]);
+            file.write_all(&block[..size]).map_err(Error::WriteFileError)?;
+            remaining -= size as u64;
+        }
+        file.sync_all().map_err(Error::WriteFileError)?;
+    }
+    file.set_len(0).map_err(Error::WriteFileError)?;
+    file.sync_all().map_err(Error::WriteFileError)?;
+    drop(file);
+    fs::remove_file(path).map_err(Error::WriteFileError)?;
+    info!("Shredded {} with {} passes", path.display(), passes);
+    Ok(())
+}
+
+/// Shred the input files.
+///
+/// Every file is checked before any is shredded, so that one file that is
+/// not a key stops the command without removing the others.
+pub fn shred(app_state: &mut AppState) -> Result<()> {
+    if app_state.in_files.is_empty() {
+        bail!(Error::MissingInput("file to shred".to_owned()));
+    }
+    if !app_state.force {
+        for name in &app_state.in_files {
+            if !is_key_file(app_state, Path::new(name))? {
+                let message = format!("{} is not a key file. Use --force to shred it anyway", name);
+                events::emit(
+                    Event::warning("not-a-key", &message)
+                        .with("file", name.as_str())
+                        .with_text(&message),
+                );
+                bail!(Error::UnknownKeyType);
+            }
+        }
+    }
+    for name in &app_state.in_files {
+        shred_file(Path::new(name), app_state.passes)?;
+        events::emit(Event::progress(&format!("Shredded {}", name)).with("file", name.as_str()));
+    }
+    Ok(())
+}
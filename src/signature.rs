@@ -0,0 +1,320 @@
+//! Digital signatures
+//!
+//! Maps a [KeyInfo] to the signature scheme it naturally produces, and signs
+//! messages with it.  The key bytes are handed to the RustCrypto algorithm
+//! crates as DER, so that any format kt can discover can be used to sign.
+use anyhow::{bail, Result};
+use pkcs8::der::{asn1::OctetString, Decodable};
+use rand_core::OsRng;
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::signature::{RandomizedSigner, SignatureEncoding, Signer, Verifier};
+use sec1::EcPrivateKey;
+use sha2::Sha256;
+
+use crate::errors::Error;
+use crate::key_info::{Alg, Curve, KeyInfo, KeyType};
+use crate::oids;
+use crate::secp256k1;
+use crate::sm2;
+
+/// Signature schemes that kt can produce
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureScheme {
+    /// RSASSA-PKCS1-v1_5 with SHA-256
+    RsaPkcs1Sha256,
+    /// RSASSA-PSS with SHA-256, MGF1 with SHA-256, and a 32 byte salt
+    RsaPssSha256,
+    /// ECDSA on P-256 with SHA-256
+    EcdsaP256Sha256,
+    /// ECDSA on P-384 with SHA-384
+    EcdsaP384Sha384,
+    /// ECDSA on P-521 with SHA-512
+    EcdsaP521Sha512,
+    /// ECDSA on secp256k1 with SHA-256
+    EcdsaSecp256k1Sha256,
+    /// Pure Ed25519
+    Ed25519,
+}
+
+impl SignatureScheme {
+    /// Select the scheme for a key.
+    ///
+    /// RSA keys use PKCS1 v1.5 padding, and RSASSA-PSS keys, identified by
+    /// either the alg or the document OID, use PSS.  EC keys use the hash
+    /// that matches the curve size.  SM2 and Ed448 keys are not signed with.
+    ///
+    /// ```rust
+    /// use kt::key_info::{Alg, KeyInfo, KeyType};
+    /// use kt::signature::SignatureScheme;
+    ///
+    /// let key_info = KeyInfo::new().with_alg(Alg::EdDsa25519).with_key_type(KeyType::Private);
+    /// assert_eq!(SignatureScheme::for_key(&key_info).unwrap(), SignatureScheme::Ed25519);
+    ///
+    /// let key_info = KeyInfo::new().with_alg(Alg::EdDsa448).with_key_type(KeyType::Private);
+    /// let err = SignatureScheme::for_key(&key_info).unwrap_err();
+    /// assert_eq!(err.to_string(), "Signing with Ed448 keys is not supported");
+    /// ```
+    pub fn for_key(key_info: &KeyInfo) -> Result<Self> {
+        match key_info.alg {
+            Alg::Rsa if key_info.oid == Some(oids::RSASSA_PSS) => Ok(Self::RsaPssSha256),
+            Alg::Rsa => Ok(Self::RsaPkcs1Sha256),
+            Alg::RsaSsaPss => Ok(Self::RsaPssSha256),
+            Alg::Ecdsa => match key_info.curve().ok_or(Error::UnknownCurve)? {
+                Curve::P256 => Ok(Self::EcdsaP256Sha256),
+                Curve::P384 => Ok(Self::EcdsaP384Sha384),
+                Curve::P521 => Ok(Self::EcdsaP521Sha512),
+                Curve::Secp256k1 => Ok(Self::EcdsaSecp256k1Sha256),
+                Curve::Sm2 => bail!(Error::SigningNotSupported("SM2")),
+            },
+            Alg::EdDsa25519 => Ok(Self::Ed25519),
+            Alg::EdDsa448 | Alg::EdDsa448Ph => bail!(Error::SigningNotSupported("Ed448")),
+            _ => bail!(Error::NotSupported),
+        }
+    }
+
+    /// Select the scheme named by a JOSE `alg` header value
+    pub fn from_jose_alg(alg: &str) -> Result<Self> {
+        match alg {
+            "RS256" => Ok(Self::RsaPkcs1Sha256),
+            "PS256" => Ok(Self::RsaPssSha256),
+            "ES256" => Ok(Self::EcdsaP256Sha256),
+            "ES384" => Ok(Self::EcdsaP384Sha384),
+            "ES512" => Ok(Self::EcdsaP521Sha512),
+            "ES256K" => Ok(Self::EcdsaSecp256k1Sha256),
+            "EdDSA" => Ok(Self::Ed25519),
+            _ => bail!(Error::UnknownAlg),
+        }
+    }
+
+    /// Test whether a key can be used with this scheme.
+    ///
+    /// Any RSA key can be used with either padding.
+    pub fn accepts_key(&self, key_info: &KeyInfo) -> bool {
+        match self {
+            Self::RsaPkcs1Sha256 | Self::RsaPssSha256 => {
+                matches!(key_info.alg, Alg::Rsa | Alg::RsaSsaPss)
+            }
+            Self::Ed25519 => key_info.alg == Alg::EdDsa25519,
+            _ => key_info.alg == Alg::Ecdsa && Self::for_key(key_info).ok() == Some(*self),
+        }
+    }
+
+    /// The JOSE `alg` header value, per RFC 7518, RFC 8037 and RFC 8812
+    pub fn jose_alg(&self) -> &'static str {
+        match self {
+            Self::RsaPkcs1Sha256 => "RS256",
+            Self::RsaPssSha256 => "PS256",
+            Self::EcdsaP256Sha256 => "ES256",
+            Self::EcdsaP384Sha384 => "ES384",
+            Self::EcdsaP521Sha512 => "ES512",
+            Self::EcdsaSecp256k1Sha256 => "ES256K",
+            Self::Ed25519 => "EdDSA",
+        }
+    }
+}
+
+fn private_bytes(key_info: &KeyInfo) -> Result<&[u8]> {
+    if key_info.key_type != KeyType::Private {
+        bail!(Error::TypeMismatch);
+    }
+    key_info
+        .bytes
+        .as_ref()
+        .map(|bytes| bytes.as_slice())
+        .ok_or_else(|| Error::MissingInput("key".to_owned()).into())
+}
+
+fn public_bytes(key_info: &KeyInfo) -> Result<&[u8]> {
+    key_info
+        .bytes
+        .as_ref()
+        .map(|bytes| bytes.as_slice())
+        .ok_or_else(|| Error::MissingInput("key".to_owned()).into())
+}
+
+/// Load an RSA private key from its RSAPrivateKey DER
+///
+/// The rsa crate does not decode multi-prime keys, so their primes are
+/// given to it one by one.
+pub fn rsa_private_key(key_info: &KeyInfo) -> Result<rsa::RsaPrivateKey> {
+    let der = private_bytes(key_info)?;
+    let pk1 = pkcs1::RsaPrivateKey::from_der(der).map_err(|_| Error::BadCrypto)?;
+    let other_prime_infos = match pk1.other_prime_infos {
+        Some(other_prime_infos) => other_prime_infos,
+        None => {
+            return rsa::RsaPrivateKey::from_pkcs1_der(der).map_err(|_| Error::BadCrypto.into());
+        }
+    };
+    let uint = |bytes: &[u8]| rsa::BigUint::from_bytes_be(bytes);
+    let primes = [pk1.prime1, pk1.prime2]
+        .iter()
+        .chain(other_prime_infos.iter().map(|info| &info.prime))
+        .map(|prime| uint(prime.as_bytes()))
+        .collect();
+    rsa::RsaPrivateKey::from_components(
+        uint(pk1.modulus.as_bytes()),
+        uint(pk1.public_exponent.as_bytes()),
+        uint(pk1.private_exponent.as_bytes()),
+        primes,
+    )
+    .map_err(|_| Error::BadCrypto.into())
+}
+
+/// Load an RSA public key, from either a public or a private key
+pub fn rsa_public_key(key_info: &KeyInfo) -> Result<rsa::RsaPublicKey> {
+    match key_info.key_type {
+        KeyType::Private => Ok(rsa_private_key(key_info)?.to_public_key()),
+        _ => rsa::RsaPublicKey::from_pkcs1_der(public_bytes(key_info)?)
+            .map_err(|_| Error::BadCrypto.into()),
+    }
+}
+
+/// The SEC1 encoded public point of an EC key, from either a public or a
+/// private key
+pub fn ec_public_point(key_info: &KeyInfo) -> Result<Vec<u8>> {
+    if key_info.key_type != KeyType::Private {
+        return Ok(public_bytes(key_info)?.to_vec());
+    }
+    let der = private_bytes(key_info)?;
+    let point = match key_info.curve().ok_or(Error::UnknownCurve)? {
+        Curve::P256 => p256::SecretKey::from_sec1_der(der)
+            .map(|secret| secret.public_key().to_sec1_bytes().to_vec()),
+        Curve::P384 => p384::SecretKey::from_sec1_der(der)
+            .map(|secret| secret.public_key().to_sec1_bytes().to_vec()),
+        Curve::P521 => p521::SecretKey::from_sec1_der(der)
+            .map(|secret| secret.public_key().to_sec1_bytes().to_vec()),
+        Curve::Secp256k1 => {
+            let ec_private_key = EcPrivateKey::from_der(der).map_err(|_| Error::BadCrypto)?;
+            return secp256k1::public_point(ec_private_key.private_key);
+        }
+        Curve::Sm2 => {
+            let ec_private_key = EcPrivateKey::from_der(der).map_err(|_| Error::BadCrypto)?;
+            return sm2::public_point(ec_private_key.private_key);
+        }
+    };
+    point.map_err(|_| Error::BadCrypto.into())
+}
+
+/// Load an Ed25519 public key, from either a public or a private key
+pub fn ed25519_verifying_key(key_info: &KeyInfo) -> Result<ed25519_dalek::VerifyingKey> {
+    if key_info.key_type == KeyType::Private {
+        return Ok(ed25519_signing_key(key_info)?.verifying_key());
+    }
+    let bytes: [u8; 32] = public_bytes(key_info)?
+        .try_into()
+        .map_err(|_| Error::BadCrypto)?;
+    ed25519_dalek::VerifyingKey::from_bytes(&bytes).map_err(|_| Error::BadCrypto.into())
+}
+
+/// Load the 32 byte seed of an Ed25519 private key from its CurvePrivateKey
+/// OCTET STRING
+pub fn ed25519_signing_key(key_info: &KeyInfo) -> Result<ed25519_dalek::SigningKey> {
+    let octets = OctetString::from_der(private_bytes(key_info)?).map_err(Error::BadPKCS8DER)?;
+    let seed: [u8; 32] = octets.as_bytes().try_into().map_err(|_| Error::BadCrypto)?;
+    Ok(ed25519_dalek::SigningKey::from_bytes(&seed))
+}
+
+/// Sign a message with a private key.
+///
+/// ECDSA signatures are returned in the fixed size `r || s` form used by
+/// JOSE, rather than as a DER SEQUENCE.
+pub fn sign(key_info: &KeyInfo, scheme: SignatureScheme, msg: &[u8]) -> Result<Vec<u8>> {
+    let signature = match scheme {
+        SignatureScheme::RsaPkcs1Sha256 => {
+            let key = rsa::pkcs1v15::SigningKey::<Sha256>::new(rsa_private_key(key_info)?);
+            key.try_sign(msg).map_err(|_| Error::BadCrypto)?.to_vec()
+        }
+        SignatureScheme::RsaPssSha256 => {
+            let key = rsa::pss::BlindedSigningKey::<Sha256>::new(rsa_private_key(key_info)?);
+            key.try_sign_with_rng(&mut OsRng, msg)
+                .map_err(|_| Error::BadCrypto)?
+                .to_vec()
+        }
+        SignatureScheme::EcdsaP256Sha256 => {
+            let secret = p256::SecretKey::from_sec1_der(private_bytes(key_info)?)
+                .map_err(|_| Error::BadCrypto)?;
+            let signature: p256::ecdsa::Signature = p256::ecdsa::SigningKey::from(secret)
+                .try_sign(msg)
+                .map_err(|_| Error::BadCrypto)?;
+            signature.to_vec()
+        }
+        SignatureScheme::EcdsaP384Sha384 => {
+            let secret = p384::SecretKey::from_sec1_der(private_bytes(key_info)?)
+                .map_err(|_| Error::BadCrypto)?;
+            let signature: p384::ecdsa::Signature = p384::ecdsa::SigningKey::from(secret)
+                .try_sign(msg)
+                .map_err(|_| Error::BadCrypto)?;
+            signature.to_vec()
+        }
+        SignatureScheme::EcdsaP521Sha512 => {
+            let secret = p521::SecretKey::from_sec1_der(private_bytes(key_info)?)
+                .map_err(|_| Error::BadCrypto)?;
+            let key = p521::ecdsa::SigningKey::from_bytes(&secret.to_bytes())
+                .map_err(|_| Error::BadCrypto)?;
+            let signature: p521::ecdsa::Signature =
+                key.try_sign(msg).map_err(|_| Error::BadCrypto)?;
+            signature.to_vec()
+        }
+        SignatureScheme::EcdsaSecp256k1Sha256 => {
+            let secret = k256::SecretKey::from_sec1_der(private_bytes(key_info)?)
+                .map_err(|_| Error::BadCrypto)?;
+            let signature: k256::ecdsa::Signature = k256::ecdsa::SigningKey::from(secret)
+                .try_sign(msg)
+                .map_err(|_| Error::BadCrypto)?;
+            signature.to_vec()
+        }
+        SignatureScheme::Ed25519 => ed25519_signing_key(key_info)?
+            .try_sign(msg)
+            .map_err(|_| Error::BadCrypto)?
+            .to_vec(),
+    };
+    Ok(signature)
+}
+
+/// Verify a signature made by [sign], with either a public or a private key
+pub fn verify(
+    key_info: &KeyInfo,
+    scheme: SignatureScheme,
+    msg: &[u8],
+    signature: &[u8],
+) -> Result<()> {
+    let verified = match scheme {
+        SignatureScheme::RsaPkcs1Sha256 => {
+            let key = rsa::pkcs1v15::VerifyingKey::<Sha256>::new(rsa_public_key(key_info)?);
+            rsa::pkcs1v15::Signature::try_from(signature).and_then(|sig| key.verify(msg, &sig))
+        }
+        SignatureScheme::RsaPssSha256 => {
+            let key = rsa::pss::VerifyingKey::<Sha256>::new(rsa_public_key(key_info)?);
+            rsa::pss::Signature::try_from(signature).and_then(|sig| key.verify(msg, &sig))
+        }
+        SignatureScheme::EcdsaP256Sha256 => {
+            let key = p256::ecdsa::VerifyingKey::from_sec1_bytes(&ec_public_point(key_info)?)
+                .map_err(|_| Error::BadEcPoint)?;
+            p256::ecdsa::Signature::from_slice(signature).and_then(|sig| key.verify(msg, &sig))
+        }
+        SignatureScheme::EcdsaP384Sha384 => {
+            let key = p384::ecdsa::VerifyingKey::from_sec1_bytes(&ec_public_point(key_info)?)
+                .map_err(|_| Error::BadEcPoint)?;
+            p384::ecdsa::Signature::from_slice(signature).and_then(|sig| key.verify(msg, &sig))
+        }
+        SignatureScheme::EcdsaP521Sha512 => {
+            let key = p521::ecdsa::VerifyingKey::from_sec1_bytes(&ec_public_point(key_info)?)
+                .map_err(|_| Error::BadEcPoint)?;
+            p521::ecdsa::Signature::from_slice(signature).and_then(|sig| key.verify(msg, &sig))
+        }
+        SignatureScheme::EcdsaSecp256k1Sha256 => {
+            let key = k256::ecdsa::VerifyingKey::from_sec1_bytes(&ec_public_point(key_info)?)
+                .map_err(|_| Error::BadEcPoint)?;
+            // k256 only verifies low-S signatures, which other signers need
+            // not produce
+            k256::ecdsa::Signature::from_slice(signature)
+                .map(|sig| sig.normalize_s().unwrap_or(sig))
+                .and_then(|sig| key.verify(msg, &sig))
+        }
+        SignatureScheme::Ed25519 => {
+            let key = ed25519_verifying_key(key_info)?;
+            ed25519_dalek::Signature::from_slice(signature).and_then(|sig| key.verify(msg, &sig))
+        }
+    };
+    verified.map_err(|_| Error::BadSignature.into())
+}
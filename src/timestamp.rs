@@ -0,0 +1,52 @@
+//! Timestamps in kt output
+//!
+//! Every timestamp kt writes, in reports, dumps and log lines, is
+//! [RFC 3339](https://www.rfc-editor.org/rfc/rfc3339), to the second.  They
+//! are in UTC, so that output doesn't depend on where it was produced, unless
+//! `--local-time` is given.
+//!
+//! ```rust
+//! use std::time::{Duration, UNIX_EPOCH};
+//! use kt::timestamp::format;
+//!
+//! let time = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+//! assert_eq!(format(time, false), "2023-11-14T22:13:20Z");
+//! ```
+use chrono::{DateTime, Local, SecondsFormat, Utc};
+use std::io::Write;
+use std::time::SystemTime;
+
+/// Format a time as RFC 3339, in UTC or with the local offset
+pub fn format(time: SystemTime, local: bool) -> String {
+    match local {
+        true => DateTime::<Local>::from(time).to_rfc3339_opts(SecondsFormat::Secs, false),
+        false => DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Secs, true),
+    }
+}
+
+/// The current time as RFC 3339
+pub fn now(local: bool) -> String {
+    format(SystemTime::now(), local)
+}
+
+/// Start logging, with the same timestamps as the rest of the output.
+///
+/// As with `env_logger::init`, the level is set by `RUST_LOG`.  Once events
+/// are enabled, log lines are written as events.
+pub fn init_logger(local: bool) {
+    env_logger::Builder::from_default_env()
+        .format(move |buf, record| {
+            if let Some(line) = crate::events::log_line(record) {
+                return writeln!(buf, "{}", line);
+            }
+            writeln!(
+                buf,
+                "[{} {:<5} {}] {}",
+                now(local),
+                record.level(),
+                record.target(),
+                record.args()
+            )
+        })
+        .init();
+}
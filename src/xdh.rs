@@ -0,0 +1,140 @@
+//! The X25519 and X448 functions ([RFC 7748](https://www.rfc-editor.org/rfc/rfc7748)),
+//! for the public key of an X25519 or X448 private key
+//!
+//! Only what is needed to write keys is here: the public key of a private
+//! key, which is the private key multiplied by the base point.  X25519 is
+//! that of [curve25519_dalek], which is constant time.  The X448 ladder is
+//! not constant time, so kt does not agree keys with X448 keys.
+use anyhow::{bail, Result};
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use rsa::BigUint;
+use zeroize::Zeroizing;
+
+use crate::errors::Error;
+
+/// Size in bytes of an X25519 private key, and of a public key
+pub const X25519_KEY_SIZE: usize = 32;
+/// Size in bytes of an X448 private key, and of a public key
+pub const X448_KEY_SIZE: usize = 56;
+
+/// A curve v^2 = u^3 + A u^2 + u
+struct Curve {
+    /// The field prime
+    p: BigUint,
+    /// (A - 2) / 4
+    a24: u32,
+    /// The u coordinate of the base point
+    base_u: u32,
+    /// Size in bytes of keys
+    size: usize,
+    /// Size in bits of scalars
+    bits: usize,
+}
+
+impl Curve {
+    /// p = 2^448 - 2^224 - 1, A = 156326
+    fn x448() -> Self {
+        let one = BigUint::from(1u32);
+        Curve {
+            p: (&one << 448) - (&one << 224) - one,
+            a24: 39081,
+            base_u: 5,
+            size: X448_KEY_SIZE,
+            bits: 448,
+        }
+    }
+
+    /// The u coordinate of the clamped little endian scalar multiplied by
+    /// the base point, with the Montgomery ladder
+    fn multiply(&self, scalar: &[u8]) -> Vec<u8> {
+        let p = &self.p;
+        let sub = |a: &BigUint, b: &BigUint| (a + p - b) % p;
+        let u = BigUint::from(self.base_u);
+        let (mut x2, mut z2) = (BigUint::from(1u32), BigUint::default());
+        let (mut x3, mut z3) = (u.clone(), BigUint::from(1u32));
+        let mut swap = false;
+        for bit in (0..self.bits).rev() {
+            let k = scalar[bit / 8] >> (bit % 8) & 1 == 1;
+            if swap != k {
+                std::mem::swap(&mut x2, &mut x3);
+                std::mem::swap(&mut z2, &mut z3);
+            }
+            swap = k;
+
+            let a = (&x2 + &z2) % p;
+            let aa = &a * &a % p;
+            let b = sub(&x2, &z2);
+            let bb = &b * &b % p;
+            let e = sub(&aa, &bb);
+            let c = (&x3 + &z3) % p;
+            let d = sub(&x3, &z3);
+            let da = d * &a % p;
+            let cb = c * &b % p;
+            let sum = (&da + &cb) % p;
+            let difference = sub(&da, &cb);
+            x3 = &sum * &sum % p;
+            z3 = &u * (&difference * &difference % p) % p;
+            x2 = &aa * &bb % p;
+            z2 = &e * ((&aa + self.a24 * &e) % p) % p;
+        }
+        if swap {
+            std::mem::swap(&mut x2, &mut x3);
+            std::mem::swap(&mut z2, &mut z3);
+        }
+
+        let u = x2 * z2.modpow(&(p - 2u32), p) % p;
+        let mut bytes = u.to_bytes_le();
+        bytes.resize(self.size, 0);
+        bytes
+    }
+}
+
+/// The public key of the 32 byte X25519 private key
+///
+/// ```rust
+/// use kt::xdh::x25519_public_key;
+///
+/// // The key of Alice of RFC 7748, section 6.1
+/// let private_key = hex::decode(
+///     "77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a",
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     hex::encode(x25519_public_key(&private_key).unwrap()),
+///     "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"
+/// );
+/// ```
+pub fn x25519_public_key(private_key: &[u8]) -> Result<Vec<u8>> {
+    let scalar: Zeroizing<[u8; X25519_KEY_SIZE]> =
+        Zeroizing::new(private_key.try_into().map_err(|_| Error::BadCrypto)?);
+    Ok(MontgomeryPoint::mul_base_clamped(*scalar).to_bytes().to_vec())
+}
+
+/// The public key of the 56 byte X448 private key
+///
+/// ```rust
+/// use kt::xdh::x448_public_key;
+///
+/// // The key of Alice of RFC 7748, section 6.2
+/// let private_key = hex::decode(
+///     "9a8f4925d1519f5775cf46b04b5800d4ee9ee8bae8bc5565d498c28d\
+///      d9c9baf574a9419744897391006382a6f127ab1d9ac2d8c0a598726b",
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     hex::encode(x448_public_key(&private_key).unwrap()),
+///     "9b08f7cc31b7e3e67d22d5aea121074a273bd2b83de09c63faa73d2c\
+///      22c5d9bbc836647241d953d40c5b12da88120d53177f80e532c41fa0"
+/// );
+/// ```
+pub fn x448_public_key(private_key: &[u8]) -> Result<Vec<u8>> {
+    if private_key.len() != X448_KEY_SIZE {
+        bail!(Error::BadCrypto);
+    }
+    // The private key is clamped to a multiple of the cofactor, with the
+    // top bit set
+    let mut scalar = Zeroizing::new(private_key.to_vec());
+    scalar[0] &= 0xfc;
+    scalar[X448_KEY_SIZE - 1] |= 0x80;
+    Ok(Curve::x448().multiply(&scalar))
+}